@@ -0,0 +1,131 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `BigBuffer`, like mojo_base::BigBuffer in
+//! mojo/public/cpp/base/big_buffer.h. It's what `mojo_base.mojom.BigBuffer` is
+//! typemapped onto.
+
+use system::mojo_types::MojoError;
+use system::shared_buffer::{SharedBufferHandle, SharedBufferMapping};
+
+/// Buffers of up to this many bytes are stored inline, and bigger ones in
+/// shared memory, like kMaxInlineBytes in the C++ `BigBuffer`.
+pub const MAX_INLINE_BYTES: usize = 64 * 1024;
+
+/// A buffer of bytes which may be too big to send efficiently in a message.
+/// Big ones are stored in shared memory, so only their handle is sent.
+#[derive(Debug)]
+pub enum BigBuffer {
+    Bytes(Vec<u8>),
+    SharedMemory(BigBufferSharedMemoryRegion),
+}
+
+impl BigBuffer {
+    /// Copy `data` into a new buffer, which is stored in shared memory if
+    /// it's bigger than `MAX_INLINE_BYTES`. If the shared memory can't be
+    /// created, it's stored inline instead, like in C++.
+    pub fn new(data: &[u8]) -> Self {
+        let region = (data.len() > MAX_INLINE_BYTES)
+            .then(|| BigBufferSharedMemoryRegion::create(data.len()));
+        match region {
+            Some(Ok(mut region)) => {
+                region.as_mut_slice().copy_from_slice(data);
+                BigBuffer::SharedMemory(region)
+            }
+            _ => BigBuffer::Bytes(data.to_vec()),
+        }
+    }
+
+    /// The contents of the buffer. If it's stored in shared memory, other
+    /// processes may change them meanwhile, so they should be treated as
+    /// untrusted.
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            BigBuffer::Bytes(bytes) => bytes,
+            BigBuffer::SharedMemory(region) => region.as_slice(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for BigBuffer {
+    fn default() -> Self {
+        BigBuffer::Bytes(vec![])
+    }
+}
+
+impl From<Vec<u8>> for BigBuffer {
+    /// Like `BigBuffer::new`, but small buffers keep `bytes` without copying
+    /// them.
+    fn from(bytes: Vec<u8>) -> Self {
+        if bytes.len() > MAX_INLINE_BYTES {
+            BigBuffer::new(&bytes)
+        } else {
+            BigBuffer::Bytes(bytes)
+        }
+    }
+}
+
+impl AsRef<[u8]> for BigBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+/// The storage of a `BigBuffer` in shared memory: a handle to the shared
+/// buffer, and a mapping of the part of it which holds the contents.
+#[derive(Debug)]
+pub struct BigBufferSharedMemoryRegion {
+    buffer: SharedBufferHandle,
+    mapping: SharedBufferMapping,
+}
+
+impl BigBufferSharedMemoryRegion {
+    /// Create a new shared buffer of `size` bytes, which are initially zero.
+    /// Fails with `MojoError::InvalidArgument` if `size` is over `u32::MAX`,
+    /// since `mojo_base.mojom.BigBuffer` can't say so.
+    pub fn create(size: usize) -> Result<Self, MojoError> {
+        let num_bytes = u32::try_from(size).map_err(|_| MojoError::InvalidArgument)?;
+        Self::from_buffer(SharedBufferHandle::create(num_bytes.into())?, size)
+    }
+
+    /// Use the first `size` bytes of `buffer`, e.g. one which was received in
+    /// a message. Fails with `MojoError::InvalidArgument` if the buffer is
+    /// smaller than that or `size` is over `u32::MAX`, or if the buffer can't
+    /// be mapped.
+    pub fn from_buffer(buffer: SharedBufferHandle, size: usize) -> Result<Self, MojoError> {
+        let num_bytes = u32::try_from(size).map_err(|_| MojoError::InvalidArgument)?;
+        if u64::from(num_bytes) > buffer.size()? {
+            return Err(MojoError::InvalidArgument);
+        }
+        let mapping = buffer.map(0, size)?;
+        Ok(BigBufferSharedMemoryRegion { buffer, mapping })
+    }
+
+    /// Give up the mapping, and return the buffer and the number of bytes of
+    /// it which are used, e.g. to send them in a message.
+    pub fn into_buffer(self) -> (SharedBufferHandle, usize) {
+        let size = self.mapping.len();
+        (self.buffer, size)
+    }
+
+    pub fn size(&self) -> usize {
+        self.mapping.len()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.mapping.as_slice()
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.mapping.as_mut_slice()
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.BigBuffer` onto `mojo_base::BigBuffer`, with the same
+//! encoding as big_buffer_mojom_traits.cc.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::{bail, Context};
+
+/// Decoding fails on `invalid_buffer`, which C++ only sends if it couldn't
+/// create a buffer, and on shared memory regions which are bigger than their
+/// shared buffer or can't be mapped.
+impl MojomTraits<crate::BigBuffer> for ::mojo_base::BigBuffer {
+    fn to_mojom(self) -> crate::BigBuffer {
+        match self {
+            ::mojo_base::BigBuffer::Bytes(bytes) => crate::BigBuffer::Bytes(bytes),
+            ::mojo_base::BigBuffer::SharedMemory(region) => {
+                let (buffer_handle, size) = region.into_buffer();
+                crate::BigBuffer::SharedMemory(crate::BigBufferSharedMemoryRegion {
+                    buffer_handle,
+                    size: size.try_into().expect("Regions are at most u32::MAX bytes"),
+                })
+            }
+        }
+    }
+
+    fn from_mojom(mojom: crate::BigBuffer) -> MojomResult<Self> {
+        match mojom {
+            crate::BigBuffer::Bytes(bytes) => Ok(::mojo_base::BigBuffer::Bytes(bytes)),
+            crate::BigBuffer::SharedMemory(region) => {
+                let size = usize::try_from(region.size).context("BigBuffer is too big")?;
+                let region = ::mojo_base::BigBufferSharedMemoryRegion::from_buffer(
+                    region.buffer_handle,
+                    size,
+                )
+                .context("Invalid BigBuffer shared memory region")?;
+                Ok(::mojo_base::BigBuffer::SharedMemory(region))
+            }
+            crate::BigBuffer::InvalidBuffer(_) => bail!("Invalid BigBuffer"),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.ByteString` onto `Vec<u8>`, like
+//! byte_string_mojom_traits.h does for `std::string`.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+impl MojomTraits<crate::ByteString> for Vec<u8> {
+    fn to_mojom(self) -> crate::ByteString {
+        crate::ByteString { data: self }
+    }
+
+    fn from_mojom(mojom: crate::ByteString) -> MojomResult<Self> {
+        Ok(mojom.data)
+    }
+}
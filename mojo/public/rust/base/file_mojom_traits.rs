@@ -0,0 +1,57 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.File` and `mojo_base.mojom.ReadOnlyFile` onto
+//! `std::fs::File`, like file_mojom_traits.cc and
+//! read_only_file_mojom_traits.cc.
+//!
+//! `std::fs::File` doesn't know whether it was opened for asynchronous IO, so
+//! `async` is always encoded as false, and ignored when decoding.
+
+use mojo_bindings::PlatformHandle;
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::Context;
+use std::fs::File;
+
+/// Whether `file` can only be read, like `IsReadOnlyFile()` in C++. We can
+/// only tell on POSIX, so elsewhere this is always true.
+fn is_read_only_file(file: &File) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd;
+        // SAFETY: F_GETFL only reads the file descriptor's flags.
+        let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+        flags != -1 && flags & libc::O_ACCMODE == libc::O_RDONLY
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = file;
+        true
+    }
+}
+
+impl MojomTraits<crate::File> for File {
+    fn to_mojom(self) -> crate::File {
+        let fd = PlatformHandle::from_file(self).expect("Files can always be wrapped");
+        crate::File { fd, async_: false }
+    }
+
+    fn from_mojom(mojom: crate::File) -> MojomResult<Self> {
+        mojom.fd.into_file().context("File is not a file handle")
+    }
+}
+
+/// Encoding a file which can be written panics, like it crashes in C++.
+impl MojomTraits<crate::ReadOnlyFile> for File {
+    fn to_mojom(self) -> crate::ReadOnlyFile {
+        assert!(is_read_only_file(&self), "ReadOnlyFile can be written");
+        let fd = PlatformHandle::from_file(self).expect("Files can always be wrapped");
+        crate::ReadOnlyFile { fd, async_: false }
+    }
+
+    fn from_mojom(mojom: crate::ReadOnlyFile) -> MojomResult<Self> {
+        mojom.fd.into_file().context("ReadOnlyFile is not a file handle")
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.FilePath` onto `PathBuf`, with the same encoding as
+//! file_path_mojom_traits.cc: a UTF-16 array on Windows, and a string
+//! elsewhere.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use std::path::PathBuf;
+
+/// Mojom strings must be UTF-8, so outside Windows, paths which aren't are
+/// encoded lossily.
+impl MojomTraits<crate::FilePath> for PathBuf {
+    #[cfg(not(windows))]
+    fn to_mojom(self) -> crate::FilePath {
+        crate::FilePath { path: self.to_string_lossy().into_owned() }
+    }
+
+    #[cfg(windows)]
+    fn to_mojom(self) -> crate::FilePath {
+        use std::os::windows::ffi::OsStrExt;
+        crate::FilePath { path: self.as_os_str().encode_wide().collect() }
+    }
+
+    #[cfg(not(windows))]
+    fn from_mojom(mojom: crate::FilePath) -> MojomResult<Self> {
+        Ok(PathBuf::from(mojom.path))
+    }
+
+    #[cfg(windows)]
+    fn from_mojom(mojom: crate::FilePath) -> MojomResult<Self> {
+        use std::os::windows::ffi::OsStringExt;
+        Ok(PathBuf::from(std::ffi::OsString::from_wide(&mojom.path)))
+    }
+}
@@ -0,0 +1,21 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.GenericPendingReceiver` onto
+//! `mojo_bindings::GenericPendingReceiver`, like
+//! generic_pending_receiver_mojom_traits.h does for
+//! mojo::GenericPendingReceiver.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+impl MojomTraits<crate::GenericPendingReceiver> for ::mojo_bindings::GenericPendingReceiver {
+    fn to_mojom(self) -> crate::GenericPendingReceiver {
+        let (interface_name, receiving_pipe) = self.into_parts();
+        crate::GenericPendingReceiver { interface_name, receiving_pipe }
+    }
+
+    fn from_mojom(mojom: crate::GenericPendingReceiver) -> MojomResult<Self> {
+        Ok(::mojo_bindings::GenericPendingReceiver::new(mojom.interface_name, mojom.receiving_pipe))
+    }
+}
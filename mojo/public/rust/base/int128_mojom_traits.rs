@@ -0,0 +1,28 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.Int128` onto `i128` and `mojo_base.mojom.Uint128`
+//! onto `u128`, with the same encoding as int128_mojom_traits.cc.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+impl MojomTraits<crate::Int128> for i128 {
+    fn to_mojom(self) -> crate::Int128 {
+        crate::Int128 { high: (self >> 64) as i64, low: self as u64 }
+    }
+
+    fn from_mojom(mojom: crate::Int128) -> MojomResult<Self> {
+        Ok((i128::from(mojom.high) << 64) | i128::from(mojom.low))
+    }
+}
+
+impl MojomTraits<crate::Uint128> for u128 {
+    fn to_mojom(self) -> crate::Uint128 {
+        crate::Uint128 { high: (self >> 64) as u64, low: self as u64 }
+    }
+
+    fn from_mojom(mojom: crate::Uint128) -> MojomResult<Self> {
+        Ok((u128::from(mojom.high) << 64) | u128::from(mojom.low))
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Rust types which //mojo/public/mojom/base types are typemapped onto, when
+//! the standard library has nothing suitable. These correspond to the types
+//! in //mojo/public/cpp/base.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_rust_system_api" as system;
+}
+
+mod big_buffer;
+mod shared_memory;
+mod time_delta;
+mod time_ticks;
+mod token;
+mod uuid;
+mod version;
+
+pub use crate::big_buffer::*;
+pub use crate::shared_memory::*;
+pub use crate::time_delta::*;
+pub use crate::time_ticks::*;
+pub use crate::token::*;
+pub use crate::uuid::*;
+pub use crate::version::*;
@@ -0,0 +1,172 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines the shared memory regions that `mojo_base.mojom`'s
+//! `ReadOnlySharedMemoryRegion`, `WritableSharedMemoryRegion` and
+//! `UnsafeSharedMemoryRegion` are typemapped onto, like the regions in
+//! base/memory/. Each one wraps a shared buffer, whose access mode Mojo keeps
+//! track of, and which must match the region type.
+
+use system::mojo_types::MojoError;
+use system::shared_buffer::{SharedBufferAccessMode, SharedBufferHandle, SharedBufferMapping};
+
+/// Create a shared buffer of `size` bytes. Mojo starts it off writable, and
+/// it becomes read-only or unsafe when it's first duplicated.
+fn create_buffer(size: usize) -> Result<SharedBufferHandle, MojoError> {
+    SharedBufferHandle::create(size.try_into().map_err(|_| MojoError::InvalidArgument)?)
+}
+
+/// Check that `buffer` has the access mode `expected`, like
+/// `PlatformSharedMemoryRegion::ConvertToReadOnly()` and friends check it in
+/// C++. Fails with `MojoError::InvalidArgument` if it has another mode.
+fn check_access_mode(
+    buffer: SharedBufferHandle,
+    expected: SharedBufferAccessMode,
+) -> Result<SharedBufferHandle, MojoError> {
+    let (buffer, mode) = buffer.query_access_mode()?;
+    if mode != expected {
+        return Err(MojoError::InvalidArgument);
+    }
+    Ok(buffer)
+}
+
+/// Map the whole of `buffer` into memory.
+fn map_buffer(buffer: &SharedBufferHandle) -> Result<SharedBufferMapping, MojoError> {
+    let size = buffer.size()?.try_into().map_err(|_| MojoError::ResourceExhausted)?;
+    buffer.map(0, size)
+}
+
+/// Shared memory which can only be read, by anyone with a handle to it.
+/// Whoever creates it gets the one writable mapping of it.
+#[derive(Debug)]
+pub struct ReadOnlySharedMemoryRegion {
+    buffer: SharedBufferHandle,
+}
+
+impl ReadOnlySharedMemoryRegion {
+    /// Create a region of `size` bytes, which are initially zero, along with
+    /// a writable mapping of it, like `ReadOnlySharedMemoryRegion::Create()`
+    /// in C++.
+    pub fn create(size: usize) -> Result<(Self, SharedBufferMapping), MojoError> {
+        let region = WritableSharedMemoryRegion::create(size)?;
+        let mapping = region.map()?;
+        Ok((region.convert_to_read_only()?, mapping))
+    }
+
+    /// Wrap a read-only shared buffer, e.g. one that was passed from C++.
+    /// Fails with `MojoError::InvalidArgument` if the buffer isn't read-only.
+    pub fn from_buffer(buffer: SharedBufferHandle) -> Result<Self, MojoError> {
+        Ok(ReadOnlySharedMemoryRegion {
+            buffer: check_access_mode(buffer, SharedBufferAccessMode::ReadOnly)?,
+        })
+    }
+
+    pub fn into_buffer(self) -> SharedBufferHandle {
+        self.buffer
+    }
+
+    pub fn duplicate(&self) -> Result<Self, MojoError> {
+        Ok(ReadOnlySharedMemoryRegion { buffer: self.buffer.duplicate_read_only()? })
+    }
+
+    pub fn map(&self) -> Result<ReadOnlySharedMemoryMapping, MojoError> {
+        Ok(ReadOnlySharedMemoryMapping { mapping: map_buffer(&self.buffer)? })
+    }
+}
+
+/// A mapping of a `ReadOnlySharedMemoryRegion`, which is unmapped when this is
+/// dropped.
+#[derive(Debug)]
+pub struct ReadOnlySharedMemoryMapping {
+    mapping: SharedBufferMapping,
+}
+
+impl ReadOnlySharedMemoryMapping {
+    pub fn len(&self) -> usize {
+        self.mapping.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mapping.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.mapping.as_slice()
+    }
+}
+
+/// Shared memory which has a single owner, who can write to it and later
+/// convert it into a `ReadOnlySharedMemoryRegion` to share it.
+#[derive(Debug)]
+pub struct WritableSharedMemoryRegion {
+    buffer: SharedBufferHandle,
+}
+
+impl WritableSharedMemoryRegion {
+    /// Create a region of `size` bytes, which are initially zero.
+    pub fn create(size: usize) -> Result<Self, MojoError> {
+        Ok(WritableSharedMemoryRegion { buffer: create_buffer(size)? })
+    }
+
+    /// Wrap a writable shared buffer, e.g. one that was passed from C++.
+    /// Fails with `MojoError::InvalidArgument` if the buffer isn't writable.
+    pub fn from_buffer(buffer: SharedBufferHandle) -> Result<Self, MojoError> {
+        Ok(WritableSharedMemoryRegion {
+            buffer: check_access_mode(buffer, SharedBufferAccessMode::Writable)?,
+        })
+    }
+
+    pub fn into_buffer(self) -> SharedBufferHandle {
+        self.buffer
+    }
+
+    pub fn map(&self) -> Result<SharedBufferMapping, MojoError> {
+        map_buffer(&self.buffer)
+    }
+
+    /// Make the region read-only. Mappings which already exist stay
+    /// writable.
+    pub fn convert_to_read_only(self) -> Result<ReadOnlySharedMemoryRegion, MojoError> {
+        Ok(ReadOnlySharedMemoryRegion { buffer: self.buffer.duplicate_read_only()? })
+    }
+
+    /// Make the region unsafe, so that it can be shared with write access.
+    pub fn convert_to_unsafe(self) -> Result<UnsafeSharedMemoryRegion, MojoError> {
+        Ok(UnsafeSharedMemoryRegion { buffer: self.buffer.duplicate()? })
+    }
+}
+
+/// Shared memory which anyone with a handle to it can write to. Prefer a
+/// `ReadOnlySharedMemoryRegion` where possible, like in C++.
+#[derive(Debug)]
+pub struct UnsafeSharedMemoryRegion {
+    buffer: SharedBufferHandle,
+}
+
+impl UnsafeSharedMemoryRegion {
+    /// Create a region of `size` bytes, which are initially zero.
+    pub fn create(size: usize) -> Result<Self, MojoError> {
+        WritableSharedMemoryRegion::create(size)?.convert_to_unsafe()
+    }
+
+    /// Wrap an unsafe shared buffer, e.g. one that was passed from C++.
+    /// Fails with `MojoError::InvalidArgument` if the buffer isn't unsafe.
+    pub fn from_buffer(buffer: SharedBufferHandle) -> Result<Self, MojoError> {
+        Ok(UnsafeSharedMemoryRegion {
+            buffer: check_access_mode(buffer, SharedBufferAccessMode::Unsafe)?,
+        })
+    }
+
+    pub fn into_buffer(self) -> SharedBufferHandle {
+        self.buffer
+    }
+
+    pub fn duplicate(&self) -> Result<Self, MojoError> {
+        Ok(UnsafeSharedMemoryRegion { buffer: self.buffer.duplicate()? })
+    }
+
+    pub fn map(&self) -> Result<SharedBufferMapping, MojoError> {
+        map_buffer(&self.buffer)
+    }
+}
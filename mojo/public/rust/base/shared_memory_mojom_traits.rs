@@ -0,0 +1,45 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom`'s shared memory regions onto the region types in
+//! `mojo_base`, like shared_memory_mojom_traits.cc. As in C++, decoding fails
+//! if the buffer's access mode doesn't match the region type, e.g. a writable
+//! buffer can't be decoded as a read-only region.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::Context;
+
+impl MojomTraits<crate::ReadOnlySharedMemoryRegion> for ::mojo_base::ReadOnlySharedMemoryRegion {
+    fn to_mojom(self) -> crate::ReadOnlySharedMemoryRegion {
+        crate::ReadOnlySharedMemoryRegion { buffer: self.into_buffer() }
+    }
+
+    fn from_mojom(mojom: crate::ReadOnlySharedMemoryRegion) -> MojomResult<Self> {
+        ::mojo_base::ReadOnlySharedMemoryRegion::from_buffer(mojom.buffer)
+            .context("Expected a read-only shared memory region")
+    }
+}
+
+impl MojomTraits<crate::WritableSharedMemoryRegion> for ::mojo_base::WritableSharedMemoryRegion {
+    fn to_mojom(self) -> crate::WritableSharedMemoryRegion {
+        crate::WritableSharedMemoryRegion { buffer: self.into_buffer() }
+    }
+
+    fn from_mojom(mojom: crate::WritableSharedMemoryRegion) -> MojomResult<Self> {
+        ::mojo_base::WritableSharedMemoryRegion::from_buffer(mojom.buffer)
+            .context("Expected a writable shared memory region")
+    }
+}
+
+impl MojomTraits<crate::UnsafeSharedMemoryRegion> for ::mojo_base::UnsafeSharedMemoryRegion {
+    fn to_mojom(self) -> crate::UnsafeSharedMemoryRegion {
+        crate::UnsafeSharedMemoryRegion { buffer: self.into_buffer() }
+    }
+
+    fn from_mojom(mojom: crate::UnsafeSharedMemoryRegion) -> MojomResult<Self> {
+        ::mojo_base::UnsafeSharedMemoryRegion::from_buffer(mojom.buffer)
+            .context("Expected an unsafe shared memory region")
+    }
+}
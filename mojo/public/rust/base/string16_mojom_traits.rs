@@ -0,0 +1,23 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.String16` onto `String`, with the same encoding as
+//! string16_mojom_traits.cc.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::Context;
+
+/// C++ allows any UTF-16 code units, but a `String` must be valid, so
+/// decoding fails on unpaired surrogates. Use the generated `String16` to
+/// receive those.
+impl MojomTraits<crate::String16> for String {
+    fn to_mojom(self) -> crate::String16 {
+        crate::String16 { data: self.encode_utf16().collect() }
+    }
+
+    fn from_mojom(mojom: crate::String16) -> MojomResult<Self> {
+        String::from_utf16(&mojom.data).context("String16 isn't valid UTF-16")
+    }
+}
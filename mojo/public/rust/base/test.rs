@@ -0,0 +1,337 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Tests for the Rust typemaps of //mojo/public/mojom/base. The expected
+//! values are what the C++ traits produce, so that both can talk to each
+//! other.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_base";
+    "//mojo/public/rust:mojo_bindings";
+    "//mojo/public/rust/mojom_parser";
+}
+
+use mojo_bindings::{create_message_pipe, Message, SharedBufferHandle};
+use mojo_public_mojom_base_base_rust::*;
+use mojom_parser::{decode_mojom, encode_mojom, MessageHeader, MojomTraits};
+use rust_gtest_interop::prelude::*;
+
+use std::num::NonZeroU128;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[gtest(MojoBaseTestSuite, Time)]
+fn test_time() {
+    let delta = mojo_base::TimeDelta::from(Duration::from_millis(1500));
+    expect_eq!(delta.to_mojom(), TimeDelta { microseconds: 1_500_000 });
+    expect_eq!(
+        mojo_base::TimeDelta::from_mojom(TimeDelta { microseconds: 1_500_000 }).unwrap(),
+        delta
+    );
+    expect_eq!(Duration::try_from(delta), Ok(Duration::from_millis(1500)));
+    // Negative deltas are valid, like in C++, but aren't `Duration`s.
+    let negative = mojo_base::TimeDelta::from_mojom(TimeDelta { microseconds: -1 }).unwrap();
+    expect_true!(negative.is_negative());
+    expect_eq!(negative.magnitude(), Duration::from_micros(1));
+    expect_true!(Duration::try_from(negative).is_err());
+    // Longer than TimeDelta::Max(), which is i64::MAX microseconds.
+    expect_eq!(mojo_base::TimeDelta::from(Duration::MAX), mojo_base::TimeDelta::MAX);
+
+    // Ticks are passed through unchanged, since their epoch is unspecified.
+    let ticks = mojo_base::TimeTicks::from_mojom(TimeTicks { internal_value: 1_000 }).unwrap();
+    expect_eq!(ticks.to_mojom(), TimeTicks { internal_value: 1_000 });
+    expect_eq!(ticks + delta - ticks, delta);
+    expect_eq!(ticks - delta, mojo_base::TimeTicks::from_internal_value(-1_499_000));
+    expect_true!(mojo_base::TimeTicks::default().is_null());
+
+    // base::Time counts from 1601, not 1970.
+    let unix_epoch = Time { internal_value: 11_644_473_600_000_000 };
+    expect_eq!(UNIX_EPOCH.to_mojom(), unix_epoch);
+    expect_eq!(SystemTime::from_mojom(unix_epoch).unwrap(), UNIX_EPOCH);
+    let later = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    expect_eq!(SystemTime::from_mojom(later.to_mojom()).unwrap(), later);
+    let null = SystemTime::from_mojom(Time { internal_value: 0 }).unwrap();
+    expect_eq!(null.to_mojom(), Time { internal_value: 0 });
+
+    // Typemapped fields of other mojo_base types use the mapped type.
+    let info = FileInfo {
+        size: 10,
+        is_directory: false,
+        is_symbolic_link: false,
+        last_modified: later,
+        last_accessed: later + Duration::from_micros(1),
+        creation_time: UNIX_EPOCH,
+    };
+    expect_eq!(decode_mojom::<FileInfo>(&encode_mojom(info.clone()).unwrap()).unwrap(), info);
+}
+
+#[gtest(MojoBaseTestSuite, File)]
+fn test_file() {
+    use std::io::{Read, Seek, Write};
+
+    let path = std::env::temp_dir().join(format!("mojo_base_test_file_{}", std::process::id()));
+    let mut file = std::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(b"mojo").unwrap();
+    let mojom: File = file.to_mojom();
+    expect_false!(mojom.async_);
+    let mut file: std::fs::File = MojomTraits::<File>::from_mojom(mojom).unwrap();
+    file.rewind().unwrap();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    expect_eq!(contents, "mojo");
+
+    let read_only = std::fs::File::open(&path).unwrap();
+    let mojom: ReadOnlyFile = read_only.to_mojom();
+    let mut read_only: std::fs::File = MojomTraits::<ReadOnlyFile>::from_mojom(mojom).unwrap();
+    let mut contents = String::new();
+    read_only.read_to_string(&mut contents).unwrap();
+    expect_eq!(contents, "mojo");
+
+    // Like in C++, a file which can be written can't be sent as read-only.
+    #[cfg(unix)]
+    expect_true!(std::panic::catch_unwind(|| MojomTraits::<ReadOnlyFile>::to_mojom(file)).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[gtest(MojoBaseTestSuite, FilePath)]
+fn test_file_path() {
+    let path = PathBuf::from("dir/file.txt");
+    let mojom = path.clone().to_mojom();
+    #[cfg(not(windows))]
+    expect_eq!(mojom.path, "dir/file.txt");
+    #[cfg(windows)]
+    expect_eq!(mojom.path, "dir/file.txt".encode_utf16().collect::<Vec<u16>>());
+    expect_eq!(PathBuf::from_mojom(mojom).unwrap(), path);
+
+    let name = SafeBaseName { path: PathBuf::from("file.txt") };
+    expect_eq!(decode_mojom::<SafeBaseName>(&encode_mojom(name.clone()).unwrap()).unwrap(), name);
+}
+
+#[gtest(MojoBaseTestSuite, String16)]
+fn test_string16() {
+    let mojom = "h\u{e9}\u{1f600}".to_string().to_mojom();
+    expect_eq!(mojom.data, vec![0x68, 0xe9, 0xd83d, 0xde00]);
+    expect_eq!(String::from_mojom(mojom).unwrap(), "h\u{e9}\u{1f600}");
+    // An unpaired surrogate.
+    expect_true!(String::from_mojom(String16 { data: vec![0xd83d] }).is_err());
+}
+
+#[gtest(MojoBaseTestSuite, ByteString)]
+fn test_byte_string() {
+    let bytes = vec![0, 0xff, 0x80];
+    expect_eq!(bytes.clone().to_mojom(), ByteString { data: bytes.clone() });
+    expect_eq!(Vec::<u8>::from_mojom(ByteString { data: bytes.clone() }).unwrap(), bytes);
+}
+
+#[gtest(MojoBaseTestSuite, Int128)]
+fn test_int128() {
+    expect_eq!(u128::MAX.to_mojom(), Uint128 { high: u64::MAX, low: u64::MAX });
+    expect_eq!((1u128 << 64 | 2).to_mojom(), Uint128 { high: 1, low: 2 });
+    expect_eq!(u128::from_mojom(Uint128 { high: 1, low: 2 }).unwrap(), 1u128 << 64 | 2);
+
+    expect_eq!((-1i128).to_mojom(), Int128 { high: -1, low: u64::MAX });
+    expect_eq!(i128::MIN.to_mojom(), Int128 { high: i64::MIN, low: 0 });
+    for value in [0, 1, -1, i128::MIN, i128::MAX, -(1 << 64) - 5] {
+        expect_eq!(i128::from_mojom(value.to_mojom()).unwrap(), value);
+    }
+}
+
+#[gtest(MojoBaseTestSuite, Token)]
+fn test_token() {
+    let token = mojo_base::Token::from(1u128 << 64 | 0xab);
+    expect_eq!(token.to_mojom(), Token { high: 1, low: 0xab });
+    expect_eq!(mojo_base::Token::from_mojom(Token { high: 1, low: 0xab }).unwrap(), token);
+    expect_eq!(token.to_string(), "000000000000000100000000000000AB");
+    expect_eq!(u128::from(token), 1u128 << 64 | 0xab);
+    // Tokens may be zero, but unguessable tokens may not.
+    expect_true!(mojo_base::Token::from_mojom(Token { high: 0, low: 0 }).unwrap().is_zero());
+    let token = NonZeroU128::new(u128::MAX - 1).unwrap();
+    expect_eq!(token.to_mojom(), UnguessableToken { high: u64::MAX, low: u64::MAX - 1 });
+    expect_eq!(NonZeroU128::from_mojom(token.to_mojom()).unwrap(), token);
+    expect_true!(NonZeroU128::from_mojom(UnguessableToken { high: 0, low: 0 }).is_err());
+}
+
+#[gtest(MojoBaseTestSuite, Uuid)]
+fn test_uuid() {
+    let uuid = mojo_base::Uuid::from_u128(0x0123_4567_89ab_cdef_0011_2233_4455_6677);
+    let mojom = uuid.to_mojom();
+    expect_eq!(mojom.value, "01234567-89ab-cdef-0011-223344556677");
+    expect_eq!(mojo_base::Uuid::from_mojom(mojom).unwrap(), uuid);
+
+    // C++ only accepts the lowercase form.
+    for invalid in [
+        "01234567-89AB-CDEF-0011-223344556677",
+        "0123456789abcdef0011223344556677",
+        "01234567-89ab-cdef-0011-22334455667",
+        "01234567-89ab-cdef-0011+223344556677",
+        "",
+    ] {
+        expect_true!(
+            mojo_base::Uuid::from_mojom(Uuid { value: invalid.to_string() }).is_err(),
+            "{}",
+            invalid
+        );
+    }
+}
+
+#[gtest(MojoBaseTestSuite, Version)]
+fn test_version() {
+    let version: mojo_base::Version = "1.2.03".parse().unwrap();
+    expect_eq!(version.components(), &[1, 2, 3]);
+    expect_eq!(version.to_string(), "1.2.3");
+    expect_eq!(version.clone().to_mojom(), Version { components: vec![1, 2, 3] });
+    expect_eq!(
+        mojo_base::Version::from_mojom(Version { components: vec![1, 2, 3] }).unwrap(),
+        version
+    );
+
+    // Trailing zeros don't matter when comparing, like in C++.
+    expect_eq!(mojo_base::Version::new(vec![1, 0]), mojo_base::Version::new(vec![1]));
+    expect_true!(mojo_base::Version::new(vec![1, 10]) > mojo_base::Version::new(vec![1, 9, 5]));
+
+    // Versions without components are sent too, and are invalid.
+    let invalid = mojo_base::Version::from_mojom(Version { components: vec![] }).unwrap();
+    expect_false!(invalid.is_valid());
+    expect_eq!(invalid.to_string(), "invalid");
+    for invalid in ["", "01.2", "1..2", "1.+2", "1.-2", "1.2.*", "4294967296"] {
+        expect_true!(invalid.parse::<mojo_base::Version>().is_err(), "{}", invalid);
+    }
+}
+
+#[gtest(MojoBaseTestSuite, Values)]
+fn test_values() {
+    use serde_json::json;
+
+    let value = json!({
+        "null": null,
+        "bool": true,
+        "int": -5,
+        "double": 0.5,
+        "string": "text",
+        "list": [1, [2, {"nested": []}]],
+        "dict": {},
+    });
+    let serde_json::Value::Object(dict) = value.clone() else { unreachable!() };
+    let mojom = dict.clone().to_mojom();
+    expect_eq!(mojom.storage["null"].clone().to_mojom(), Value::NullValue(0));
+    expect_eq!(mojom.storage["int"].clone().to_mojom(), Value::IntValue(-5));
+    expect_eq!(mojom.storage["double"].clone().to_mojom(), Value::DoubleValue(0.5));
+    let decoded = decode_mojom::<DictionaryValue>(&encode_mojom(mojom).unwrap()).unwrap();
+    expect_eq!(serde_json::Map::from_mojom(decoded).unwrap(), dict);
+    expect_eq!(serde_json::Value::from_mojom(value.clone().to_mojom()).unwrap(), value);
+
+    // Numbers which don't fit in an int32 are sent as doubles.
+    expect_eq!(json!(1u64 << 40).to_mojom(), Value::DoubleValue((1u64 << 40) as f64));
+    expect_eq!(json!(-1.5).to_mojom(), Value::DoubleValue(-1.5));
+
+    // JSON has no binary values or non-finite doubles.
+    expect_eq!(
+        serde_json::Value::from_mojom(Value::BinaryValue(vec![1, 0xff])).unwrap(),
+        json!([1, 255])
+    );
+    expect_true!(serde_json::Value::from_mojom(Value::DoubleValue(f64::NAN)).is_err());
+}
+
+/// Send `data` in a `BigBuffer` over a message pipe, and return what arrives
+/// along with the number of handles the message carried.
+fn send_big_buffer(data: mojo_base::BigBuffer) -> (mojo_base::BigBuffer, usize) {
+    let (sender, receiver) = create_message_pipe().unwrap();
+    let mut message = Message::new(MessageHeader::default(), BigString { data }).unwrap();
+    let handles = std::mem::take(&mut message.handles);
+    let num_handles = handles.len();
+    sender
+        .write_with_handles(message.serialized_size(), handles, |buffer| message.write_to(buffer))
+        .unwrap();
+    let (bytes, handles) = receiver.read_with_handles().unwrap();
+    let mut message = Message::from_bytes(&bytes, handles).unwrap();
+    (message.decode_payload::<BigString>().unwrap().data, num_handles)
+}
+
+#[gtest(MojoBaseTestSuite, BigBuffer)]
+fn test_big_buffer() {
+    // Buffers of up to 64 KiB are sent inline, like in C++.
+    let small: Vec<u8> = (0..mojo_base::MAX_INLINE_BYTES).map(|i| i as u8).collect();
+    let buffer = mojo_base::BigBuffer::new(&small);
+    expect_true!(matches!(buffer, mojo_base::BigBuffer::Bytes(_)));
+    let (received, num_handles) = send_big_buffer(buffer);
+    expect_eq!(num_handles, 0);
+    expect_true!(matches!(received, mojo_base::BigBuffer::Bytes(_)));
+    expect_eq!(received.as_slice(), &small[..]);
+
+    // Bigger ones are sent in shared memory.
+    let big: Vec<u8> = (0..mojo_base::MAX_INLINE_BYTES + 1).map(|i| (i % 251) as u8).collect();
+    let buffer = mojo_base::BigBuffer::from(big.clone());
+    expect_true!(matches!(buffer, mojo_base::BigBuffer::SharedMemory(_)));
+    expect_eq!(buffer.len(), big.len());
+    let (received, num_handles) = send_big_buffer(buffer);
+    expect_eq!(num_handles, 1);
+    expect_true!(matches!(received, mojo_base::BigBuffer::SharedMemory(_)));
+    expect_eq!(received.as_slice(), &big[..]);
+
+    expect_true!(mojo_base::BigBuffer::default().is_empty());
+}
+
+#[gtest(MojoBaseTestSuite, BigBufferValidation)]
+fn test_big_buffer_validation() {
+    expect_true!(mojo_base::BigBuffer::from_mojom(BigBuffer::InvalidBuffer(true)).is_err());
+
+    // The region can't be bigger than its shared buffer.
+    let region = |size| {
+        BigBuffer::SharedMemory(BigBufferSharedMemoryRegion {
+            buffer_handle: SharedBufferHandle::create(16).unwrap(),
+            size,
+        })
+    };
+    expect_true!(mojo_base::BigBuffer::from_mojom(region(17)).is_err());
+    let buffer = mojo_base::BigBuffer::from_mojom(region(16)).unwrap();
+    expect_eq!(buffer.as_slice(), &[0; 16]);
+}
+
+#[gtest(MojoBaseTestSuite, SharedMemoryRegions)]
+fn test_shared_memory_regions() {
+    // Only the creator of a read-only region can write to it.
+    let (region, mut mapping) = mojo_base::ReadOnlySharedMemoryRegion::create(16).unwrap();
+    mapping.as_mut_slice().copy_from_slice(&[7; 16]);
+    let region = mojo_base::ReadOnlySharedMemoryRegion::from_mojom(region.to_mojom()).unwrap();
+    let duplicate = region.duplicate().unwrap();
+    expect_eq!(duplicate.map().unwrap().as_slice(), &[7; 16]);
+
+    let writable = mojo_base::WritableSharedMemoryRegion::create(8).unwrap();
+    let writable = mojo_base::WritableSharedMemoryRegion::from_mojom(writable.to_mojom()).unwrap();
+    writable.map().unwrap().as_mut_slice().copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    let read_only = writable.convert_to_read_only().unwrap();
+    expect_eq!(read_only.map().unwrap().as_slice(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+    // Every duplicate of an unsafe region can write to it.
+    let unsafe_region = mojo_base::UnsafeSharedMemoryRegion::create(4).unwrap();
+    let unsafe_region =
+        mojo_base::UnsafeSharedMemoryRegion::from_mojom(unsafe_region.to_mojom()).unwrap();
+    let duplicate = unsafe_region.duplicate().unwrap();
+    duplicate.map().unwrap().as_mut_slice().copy_from_slice(&[9; 4]);
+    expect_eq!(unsafe_region.map().unwrap().as_slice(), &[9; 4]);
+
+    // Like in C++, a buffer only decodes as the region type matching its
+    // access mode.
+    let writable = mojo_base::WritableSharedMemoryRegion::create(8).unwrap().to_mojom();
+    expect_true!(mojo_base::ReadOnlySharedMemoryRegion::from_mojom(ReadOnlySharedMemoryRegion {
+        buffer: writable.buffer
+    })
+    .is_err());
+    let (read_only, _mapping) = mojo_base::ReadOnlySharedMemoryRegion::create(8).unwrap();
+    expect_true!(mojo_base::WritableSharedMemoryRegion::from_mojom(WritableSharedMemoryRegion {
+        buffer: read_only.into_buffer()
+    })
+    .is_err());
+    let unsafe_region = mojo_base::UnsafeSharedMemoryRegion::create(8).unwrap();
+    expect_true!(mojo_base::WritableSharedMemoryRegion::from_mojom(WritableSharedMemoryRegion {
+        buffer: unsafe_region.into_buffer()
+    })
+    .is_err());
+}
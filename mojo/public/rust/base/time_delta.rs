@@ -0,0 +1,57 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `TimeDelta`, a signed span of time, like base::TimeDelta in
+//! base/time/time.h. It's what `mojo_base.mojom.TimeDelta` is typemapped
+//! onto, since `std::time::Duration` can't be negative.
+
+use std::time::Duration;
+
+/// A span of time in microseconds, which may be negative, e.g. the
+/// difference between two times.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeDelta {
+    microseconds: i64,
+}
+
+impl TimeDelta {
+    /// The longest span, which C++ treats as infinitely long.
+    pub const MAX: TimeDelta = TimeDelta { microseconds: i64::MAX };
+    /// The longest negative span, which C++ treats as infinitely long.
+    pub const MIN: TimeDelta = TimeDelta { microseconds: i64::MIN };
+
+    pub const fn from_microseconds(microseconds: i64) -> Self {
+        TimeDelta { microseconds }
+    }
+
+    pub const fn in_microseconds(self) -> i64 {
+        self.microseconds
+    }
+
+    pub const fn is_negative(self) -> bool {
+        self.microseconds < 0
+    }
+
+    /// The length of the span, ignoring its sign.
+    pub fn magnitude(self) -> Duration {
+        Duration::from_micros(self.microseconds.unsigned_abs())
+    }
+}
+
+/// Durations which are too long saturate to `TimeDelta::MAX`, and are
+/// rounded down to microseconds.
+impl From<Duration> for TimeDelta {
+    fn from(duration: Duration) -> Self {
+        TimeDelta { microseconds: duration.as_micros().try_into().unwrap_or(i64::MAX) }
+    }
+}
+
+/// Fails if the span is negative.
+impl TryFrom<TimeDelta> for Duration {
+    type Error = std::num::TryFromIntError;
+
+    fn try_from(delta: TimeDelta) -> Result<Self, Self::Error> {
+        Ok(Duration::from_micros(delta.microseconds.try_into()?))
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.TimeDelta` onto `mojo_base::TimeDelta`,
+//! `mojo_base.mojom.TimeTicks` onto `mojo_base::TimeTicks` and
+//! `mojo_base.mojom.Time` onto `SystemTime`, with the same encoding as
+//! time_mojom_traits.cc.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::Context;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The number of microseconds between the Windows epoch (1601-01-01), which
+/// `base::Time` counts from, and the Unix epoch, which `SystemTime` does.
+const WINDOWS_TO_UNIX_EPOCH_MICROSECONDS: i64 = 11_644_473_600_000_000;
+
+/// Like in C++, every number of microseconds is valid, including negative
+/// ones.
+impl MojomTraits<crate::TimeDelta> for ::mojo_base::TimeDelta {
+    fn to_mojom(self) -> crate::TimeDelta {
+        crate::TimeDelta { microseconds: self.in_microseconds() }
+    }
+
+    fn from_mojom(mojom: crate::TimeDelta) -> MojomResult<Self> {
+        Ok(::mojo_base::TimeDelta::from_microseconds(mojom.microseconds))
+    }
+}
+
+impl MojomTraits<crate::TimeTicks> for ::mojo_base::TimeTicks {
+    fn to_mojom(self) -> crate::TimeTicks {
+        crate::TimeTicks { internal_value: self.internal_value() }
+    }
+
+    fn from_mojom(mojom: crate::TimeTicks) -> MojomResult<Self> {
+        Ok(::mojo_base::TimeTicks::from_internal_value(mojom.internal_value))
+    }
+}
+
+/// Times are rounded down to microseconds, and saturate if they're too far
+/// from the Windows epoch.
+impl MojomTraits<crate::Time> for SystemTime {
+    fn to_mojom(self) -> crate::Time {
+        let since_unix_epoch = match self.duration_since(UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_micros()).unwrap_or(i64::MAX),
+            Err(before) => {
+                i64::try_from(before.duration().as_micros()).map_or(i64::MIN, |micros| -micros)
+            }
+        };
+        crate::Time {
+            internal_value: since_unix_epoch.saturating_add(WINDOWS_TO_UNIX_EPOCH_MICROSECONDS),
+        }
+    }
+
+    fn from_mojom(mojom: crate::Time) -> MojomResult<Self> {
+        let since_unix_epoch =
+            i128::from(mojom.internal_value) - i128::from(WINDOWS_TO_UNIX_EPOCH_MICROSECONDS);
+        let offset = Duration::from_micros(since_unix_epoch.unsigned_abs() as u64);
+        if since_unix_epoch >= 0 {
+            UNIX_EPOCH.checked_add(offset)
+        } else {
+            UNIX_EPOCH.checked_sub(offset)
+        }
+        .with_context(|| format!("Time {} is out of range", mojom.internal_value))
+    }
+}
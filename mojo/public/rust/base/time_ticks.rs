@@ -0,0 +1,59 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `TimeTicks`, a point on a monotonic clock, like base::TimeTicks in
+//! base/time/time.h. It's what `mojo_base.mojom.TimeTicks` is typemapped onto,
+//! since `std::time::Instant` can't be created from a number.
+
+use crate::TimeDelta;
+use std::ops::{Add, Sub};
+
+/// A number of microseconds since a fixed but unspecified epoch, which is
+/// only meaningful within the process that created it, and the processes it
+/// sends it to. The default value is null, like in C++.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeTicks {
+    internal_value: i64,
+}
+
+impl TimeTicks {
+    pub const fn from_internal_value(internal_value: i64) -> Self {
+        TimeTicks { internal_value }
+    }
+
+    pub const fn internal_value(self) -> i64 {
+        self.internal_value
+    }
+
+    pub const fn is_null(self) -> bool {
+        self.internal_value == 0
+    }
+}
+
+/// Saturates, like in C++.
+impl Add<TimeDelta> for TimeTicks {
+    type Output = TimeTicks;
+
+    fn add(self, delta: TimeDelta) -> TimeTicks {
+        TimeTicks { internal_value: self.internal_value.saturating_add(delta.in_microseconds()) }
+    }
+}
+
+/// Saturates, like in C++.
+impl Sub<TimeDelta> for TimeTicks {
+    type Output = TimeTicks;
+
+    fn sub(self, delta: TimeDelta) -> TimeTicks {
+        TimeTicks { internal_value: self.internal_value.saturating_sub(delta.in_microseconds()) }
+    }
+}
+
+/// The time between two ticks, which saturates like in C++.
+impl Sub for TimeTicks {
+    type Output = TimeDelta;
+
+    fn sub(self, other: TimeTicks) -> TimeDelta {
+        TimeDelta::from_microseconds(self.internal_value.saturating_sub(other.internal_value))
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `Token`, a 128-bit value, like base::Token in base/token.h. It's
+//! what `mojo_base.mojom.Token` is typemapped onto.
+
+use std::fmt;
+
+/// A 128-bit value made of two 64-bit words, e.g. an identifier. Tokens may
+/// be zero, unlike unguessable tokens, which are typemapped onto
+/// `NonZeroU128`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Token {
+    high: u64,
+    low: u64,
+}
+
+impl Token {
+    pub const fn new(high: u64, low: u64) -> Self {
+        Token { high, low }
+    }
+
+    pub const fn high(self) -> u64 {
+        self.high
+    }
+
+    pub const fn low(self) -> u64 {
+        self.low
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.high == 0 && self.low == 0
+    }
+}
+
+impl From<u128> for Token {
+    fn from(value: u128) -> Self {
+        Token { high: (value >> 64) as u64, low: value as u64 }
+    }
+}
+
+impl From<Token> for u128 {
+    fn from(token: Token) -> Self {
+        (u128::from(token.high) << 64) | u128::from(token.low)
+    }
+}
+
+/// Writes 32 uppercase hex digits, like `Token::ToString()` in C++.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016X}{:016X}", self.high, self.low)
+    }
+}
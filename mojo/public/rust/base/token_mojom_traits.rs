@@ -0,0 +1,35 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.Token` onto `mojo_base::Token` and
+//! `mojo_base.mojom.UnguessableToken` onto `NonZeroU128`, with the same
+//! encoding as token_mojom_traits.cc and unguessable_token_mojom_traits.cc.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::Context;
+use std::num::NonZeroU128;
+
+impl MojomTraits<crate::Token> for ::mojo_base::Token {
+    fn to_mojom(self) -> crate::Token {
+        crate::Token { high: self.high(), low: self.low() }
+    }
+
+    fn from_mojom(mojom: crate::Token) -> MojomResult<Self> {
+        Ok(::mojo_base::Token::new(mojom.high, mojom.low))
+    }
+}
+
+/// Decoding fails on an empty token, like in C++.
+impl MojomTraits<crate::UnguessableToken> for NonZeroU128 {
+    fn to_mojom(self) -> crate::UnguessableToken {
+        let value = self.get();
+        crate::UnguessableToken { high: (value >> 64) as u64, low: value as u64 }
+    }
+
+    fn from_mojom(mojom: crate::UnguessableToken) -> MojomResult<Self> {
+        NonZeroU128::new((u128::from(mojom.high) << 64) | u128::from(mojom.low))
+            .context("UnguessableToken is empty")
+    }
+}
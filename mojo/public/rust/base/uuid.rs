@@ -0,0 +1,77 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `Uuid`, like base::Uuid in base/uuid.h. It's what
+//! `mojo_base.mojom.Uuid` is typemapped onto.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A 128-bit UUID, which is written like
+/// "01234567-89ab-cdef-0123-456789abcdef".
+///
+/// Unlike the C++ `Uuid`, this is always valid, since decoding rejects the
+/// strings that C++ would parse as invalid UUIDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid {
+    value: u128,
+}
+
+impl Uuid {
+    pub const fn from_u128(value: u128) -> Self {
+        Uuid { value }
+    }
+
+    pub const fn as_u128(self) -> u128 {
+        self.value
+    }
+}
+
+/// Writes the lowercase form, which is what's sent to C++.
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.value);
+        write!(f, "{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+/// Returned when parsing a string which isn't a lowercase UUID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseUuidError;
+
+impl fmt::Display for ParseUuidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid UUID string")
+    }
+}
+
+impl std::error::Error for ParseUuidError {}
+
+/// Only accepts the lowercase form, like `Uuid::ParseLowercase()` in C++.
+impl FromStr for Uuid {
+    type Err = ParseUuidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const HYPHEN_POSITIONS: [usize; 4] = [8, 13, 18, 23];
+        if s.len() != 36 {
+            return Err(ParseUuidError);
+        }
+        let mut value: u128 = 0;
+        for (idx, c) in s.bytes().enumerate() {
+            if HYPHEN_POSITIONS.contains(&idx) {
+                if c != b'-' {
+                    return Err(ParseUuidError);
+                }
+                continue;
+            }
+            let digit = match c {
+                b'0'..=b'9' => c - b'0',
+                b'a'..=b'f' => c - b'a' + 10,
+                _ => return Err(ParseUuidError),
+            };
+            value = (value << 4) | u128::from(digit);
+        }
+        Ok(Uuid { value })
+    }
+}
@@ -0,0 +1,21 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.Uuid` onto `mojo_base::Uuid`, with the same encoding
+//! as uuid_mojom_traits.cc.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::Context;
+
+/// Decoding fails unless the string is a lowercase UUID, like in C++.
+impl MojomTraits<crate::Uuid> for ::mojo_base::Uuid {
+    fn to_mojom(self) -> crate::Uuid {
+        crate::Uuid { value: self.to_string() }
+    }
+
+    fn from_mojom(mojom: crate::Uuid) -> MojomResult<Self> {
+        mojom.value.parse().context("Uuid isn't a lowercase UUID")
+    }
+}
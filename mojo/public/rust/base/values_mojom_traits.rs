@@ -0,0 +1,72 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.Value`, `DictionaryValue` and `ListValue` onto
+//! `serde_json::Value`, `serde_json::Map` and `Vec<serde_json::Value>`, like
+//! values_mojom_traits.cc maps them onto `base::Value`.
+//!
+//! JSON numbers which fit in an `int32` are encoded as `int_value`, and the
+//! rest as `double_value`. JSON has no binary values, so `binary_value`
+//! decodes to an array of its bytes.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+use anyhow::Context;
+use serde_json::{Map, Number};
+
+/// Decoding fails if a double isn't finite, since JSON can't represent it.
+impl MojomTraits<crate::Value> for serde_json::Value {
+    fn to_mojom(self) -> crate::Value {
+        match self {
+            serde_json::Value::Null => crate::Value::NullValue(0),
+            serde_json::Value::Bool(value) => crate::Value::BoolValue(value),
+            serde_json::Value::Number(value) => {
+                match value.as_i64().and_then(|value| i32::try_from(value).ok()) {
+                    Some(value) => crate::Value::IntValue(value),
+                    None => crate::Value::DoubleValue(
+                        value.as_f64().expect("JSON numbers can always be doubles"),
+                    ),
+                }
+            }
+            serde_json::Value::String(value) => crate::Value::StringValue(value),
+            serde_json::Value::Array(values) => crate::Value::ListValue(values),
+            serde_json::Value::Object(values) => crate::Value::DictionaryValue(values),
+        }
+    }
+
+    fn from_mojom(mojom: crate::Value) -> MojomResult<Self> {
+        Ok(match mojom {
+            crate::Value::NullValue(_) => serde_json::Value::Null,
+            crate::Value::BoolValue(value) => value.into(),
+            crate::Value::IntValue(value) => value.into(),
+            crate::Value::DoubleValue(value) => {
+                Number::from_f64(value).context("Value is not a finite double")?.into()
+            }
+            crate::Value::StringValue(value) => value.into(),
+            crate::Value::BinaryValue(value) => value.into(),
+            crate::Value::DictionaryValue(values) => values.into(),
+            crate::Value::ListValue(values) => values.into(),
+        })
+    }
+}
+
+impl MojomTraits<crate::DictionaryValue> for Map<String, serde_json::Value> {
+    fn to_mojom(self) -> crate::DictionaryValue {
+        crate::DictionaryValue { storage: self.into_iter().collect() }
+    }
+
+    fn from_mojom(mojom: crate::DictionaryValue) -> MojomResult<Self> {
+        Ok(mojom.storage.into_iter().collect())
+    }
+}
+
+impl MojomTraits<crate::ListValue> for Vec<serde_json::Value> {
+    fn to_mojom(self) -> crate::ListValue {
+        crate::ListValue { storage: self }
+    }
+
+    fn from_mojom(mojom: crate::ListValue) -> MojomResult<Self> {
+        Ok(mojom.storage)
+    }
+}
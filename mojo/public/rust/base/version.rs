@@ -0,0 +1,114 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `Version`, a dotted version number, like base::Version in
+//! base/version.h. It's what `mojo_base.mojom.Version` is typemapped onto.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A version number like "1.2.3", made of any number of components. A version
+/// without components is invalid, like a default-constructed C++ `Version`.
+///
+/// Missing components count as zero when comparing versions, so "1.0" equals
+/// "1".
+#[derive(Debug, Default, Clone)]
+pub struct Version {
+    components: Vec<u32>,
+}
+
+impl Version {
+    pub fn new(components: Vec<u32>) -> Self {
+        Version { components }
+    }
+
+    pub fn components(&self) -> &[u32] {
+        &self.components
+    }
+
+    pub fn into_components(self) -> Vec<u32> {
+        self.components
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.components.is_empty()
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.components.len().max(other.components.len());
+        let component = |components: &[u32], idx| components.get(idx).copied().unwrap_or(0);
+        (0..len)
+            .map(|idx| component(&self.components, idx).cmp(&component(&other.components, idx)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Invalid versions are written as "invalid", like in C++.
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_valid() {
+            return write!(f, "invalid");
+        }
+        for (idx, component) in self.components.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{component}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returned when parsing a string which isn't a version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError;
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid version string")
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+/// Parses dot-separated decimal components, with the same rules as the C++
+/// `Version` constructor: the first component may not have leading zeros.
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let components = s
+            .split('.')
+            .enumerate()
+            .map(|(idx, component)| {
+                let is_number =
+                    !component.is_empty() && component.bytes().all(|b| b.is_ascii_digit());
+                let has_leading_zero = component.len() > 1 && component.starts_with('0');
+                if !is_number || (idx == 0 && has_leading_zero) {
+                    return Err(ParseVersionError);
+                }
+                component.parse().map_err(|_| ParseVersionError)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Version { components })
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Maps `mojo_base.mojom.Version` onto `mojo_base::Version`, with the same
+//! encoding as version_mojom_traits.cc.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+/// Like in C++, versions without components are sent as they are, and decode
+/// as invalid versions.
+impl MojomTraits<crate::Version> for ::mojo_base::Version {
+    fn to_mojom(self) -> crate::Version {
+        crate::Version { components: self.into_components() }
+    }
+
+    fn from_mojom(mojom: crate::Version) -> MojomResult<Self> {
+        Ok(::mojo_base::Version::new(mojom.components))
+    }
+}
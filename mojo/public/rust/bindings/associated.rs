@@ -0,0 +1,313 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `PendingAssociatedRemote` and `PendingAssociatedReceiver`, the ends
+//! of an associated interface, which shares its message pipe with the
+//! interface that it's passed over. These correspond to the mojom types
+//! `pending_associated_remote<I>` and `pending_associated_receiver<I>`. Once
+//! they reach the end of the pipe where they'll be used, they're bound to an
+//! ordinary `Remote` or `Receiver`.
+//!
+//! New associated interfaces are created by an `AssociatedGroup`, which
+//! allocates their IDs from the router of the pipe that one of their ends
+//! will be sent over. See router.rs.
+//!
+//! Encoding and decoding values doesn't know which pipe they'll be sent over
+//! or were read from, so the endpoint that's doing so says while it does it,
+//! like mojo::internal::SerializationContext in the C++ bindings.
+
+use crate::interface::Interface;
+use crate::message::Message;
+use crate::router::{Endpoint, Router};
+use mojom_parser::{Mojom, MojomResult, MojomType, MojomValue};
+
+use anyhow::{bail, Context};
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// One end of an associated interface, which hasn't been bound yet.
+enum PendingEndpoint {
+    /// Our end, which will be bound here.
+    Ours(Endpoint),
+    /// The end which we'll send to the peer, over the pipe it was created
+    /// for.
+    Peers(PeerEndpoint),
+}
+
+impl PendingEndpoint {
+    fn into_ours(self, what: &str) -> Endpoint {
+        match self {
+            PendingEndpoint::Ours(endpoint) => endpoint,
+            PendingEndpoint::Peers(_) => {
+                panic!("An associated {} must be sent to the peer before it can be bound", what)
+            }
+        }
+    }
+
+    /// Encode the endpoint, as the index of its interface ID in the message
+    /// being encoded.
+    fn into_index(self, what: &str) -> u32 {
+        match self {
+            PendingEndpoint::Peers(peer) => add_outgoing_endpoint(peer),
+            // FOR_RELEASE: The C++ bindings can also pass on an associated
+            // endpoint that they were passed.
+            PendingEndpoint::Ours(_) => {
+                panic!("Only new associated {}s can be sent for now", what)
+            }
+        }
+    }
+}
+
+/// The end of an associated interface that will be sent to the peer. If it's
+/// dropped instead, our end is disconnected.
+struct PeerEndpoint {
+    /// The router, or None once the endpoint has been sent.
+    router: Option<Router>,
+    id: u32,
+}
+
+impl Drop for PeerEndpoint {
+    fn drop(&mut self) {
+        if let Some(router) = self.router.take() {
+            router.borrow_mut().on_peer_endpoint_dropped(self.id);
+        }
+    }
+}
+
+/// The remote for an associated interface `I`, which can be passed to
+/// wherever the remote will be used, and bound with `Remote::new_associated`.
+pub struct PendingAssociatedRemote<I: ?Sized + Interface> {
+    endpoint: PendingEndpoint,
+    version: u32,
+    _interface: PhantomData<Box<I>>,
+}
+
+impl<I: ?Sized + Interface> PendingAssociatedRemote<I> {
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub(crate) fn into_endpoint(self) -> Endpoint {
+        self.endpoint.into_ours("remote")
+    }
+}
+
+impl<I: ?Sized + Interface> fmt::Debug for PendingAssociatedRemote<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingAssociatedRemote")
+            .field("interface", &I::NAME)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// The receiver for an associated interface `I`, which can be passed to
+/// wherever the implementation of `I` lives, and bound with
+/// `Receiver::new_associated`.
+pub struct PendingAssociatedReceiver<I: ?Sized + Interface> {
+    endpoint: PendingEndpoint,
+    _interface: PhantomData<Box<I>>,
+}
+
+impl<I: ?Sized + Interface> PendingAssociatedReceiver<I> {
+    pub(crate) fn into_endpoint(self) -> Endpoint {
+        self.endpoint.into_ours("receiver")
+    }
+}
+
+impl<I: ?Sized + Interface> fmt::Debug for PendingAssociatedReceiver<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingAssociatedReceiver").field("interface", &I::NAME).finish()
+    }
+}
+
+/// Creates associated interfaces on the pipe of a `Remote` or `Receiver`,
+/// whose `associated_group()` returns it. This corresponds to
+/// mojo::AssociatedGroup in the C++ bindings.
+///
+/// FOR_RELEASE: The C++ bindings can also create associated interfaces
+/// without a group, and associate them with a pipe when they're sent.
+pub struct AssociatedGroup {
+    /// The router, or None if the pipe is already closed.
+    router: Option<Router>,
+}
+
+impl AssociatedGroup {
+    pub(crate) fn new(router: Option<Router>) -> Self {
+        AssociatedGroup { router }
+    }
+
+    /// Create an associated interface whose receiver will be sent over the
+    /// pipe, e.g. as the parameter of a method like
+    /// `GetFoo(pending_associated_receiver<Foo> receiver)`. Returns the
+    /// remote, which stays here, and the receiver to send.
+    pub fn new_endpoint_and_pass_receiver<I: ?Sized + Interface>(
+        &self,
+    ) -> (PendingAssociatedRemote<I>, PendingAssociatedReceiver<I>) {
+        let (ours, peers) = self.new_endpoints();
+        (
+            PendingAssociatedRemote {
+                endpoint: PendingEndpoint::Ours(ours),
+                version: 0,
+                _interface: PhantomData,
+            },
+            PendingAssociatedReceiver {
+                endpoint: PendingEndpoint::Peers(peers),
+                _interface: PhantomData,
+            },
+        )
+    }
+
+    /// Create an associated interface whose remote will be sent over the pipe.
+    /// Returns the receiver, which stays here, and the remote to send.
+    pub fn new_endpoint_and_pass_remote<I: ?Sized + Interface>(
+        &self,
+    ) -> (PendingAssociatedReceiver<I>, PendingAssociatedRemote<I>) {
+        let (ours, peers) = self.new_endpoints();
+        (
+            PendingAssociatedReceiver {
+                endpoint: PendingEndpoint::Ours(ours),
+                _interface: PhantomData,
+            },
+            PendingAssociatedRemote {
+                endpoint: PendingEndpoint::Peers(peers),
+                version: I::VERSION,
+                _interface: PhantomData,
+            },
+        )
+    }
+
+    fn new_endpoints(&self) -> (Endpoint, PeerEndpoint) {
+        let Some(router) = &self.router else {
+            // Both ends are disconnected from the start.
+            return (Endpoint::closed(), PeerEndpoint { router: None, id: 0 });
+        };
+        let id = router.borrow_mut().allocate_interface_id();
+        (
+            Endpoint::from_router(router.clone(), id),
+            PeerEndpoint { router: Some(router.clone()), id },
+        )
+    }
+}
+
+thread_local! {
+    /// The peer endpoints in each message being encoded, innermost last.
+    static OUTGOING_ENDPOINTS: RefCell<Vec<Vec<PeerEndpoint>>> = const { RefCell::new(vec![]) };
+    /// The router and interface IDs of each message being decoded, innermost
+    /// last.
+    static INCOMING_ENDPOINTS: RefCell<Vec<(Router, Vec<u32>)>> = const { RefCell::new(vec![]) };
+}
+
+/// Encode a message with `encode`, to be sent over `router`'s pipe, and add
+/// the IDs of the associated interfaces that it passes.
+///
+/// Panics if it passes associated interfaces which were created for another
+/// pipe.
+pub(crate) fn encode_outgoing_endpoints(
+    router: &Router,
+    encode: impl FnOnce() -> MojomResult<Message>,
+) -> MojomResult<Message> {
+    OUTGOING_ENDPOINTS.with_borrow_mut(|stack| stack.push(vec![]));
+    let message = encode();
+    let peers = OUTGOING_ENDPOINTS.with_borrow_mut(|stack| stack.pop()).unwrap_or_default();
+    let mut message = message?;
+    message.interface_ids = peers
+        .into_iter()
+        .map(|mut peer| {
+            let sent_over = peer.router.take();
+            assert!(
+                sent_over.is_some_and(|sent_over| Rc::ptr_eq(&sent_over, router)),
+                "Associated interfaces can only be sent over the pipe they were created for"
+            );
+            peer.id
+        })
+        .collect();
+    Ok(message)
+}
+
+fn add_outgoing_endpoint(peer: PeerEndpoint) -> u32 {
+    OUTGOING_ENDPOINTS.with_borrow_mut(|stack| {
+        let endpoints = stack
+            .last_mut()
+            .expect("Associated interfaces can only be sent by a remote or receiver");
+        endpoints.push(peer);
+        u32::try_from(endpoints.len() - 1).expect("Too many associated interfaces")
+    })
+}
+
+/// Decode a message with `decode`, which was read from `router`'s pipe, and
+/// passes the associated interfaces in `interface_ids`.
+pub(crate) fn decode_incoming_endpoints<T>(
+    router: &Router,
+    interface_ids: &[u32],
+    decode: impl FnOnce() -> T,
+) -> T {
+    INCOMING_ENDPOINTS
+        .with_borrow_mut(|stack| stack.push((router.clone(), interface_ids.to_vec())));
+    let result = decode();
+    INCOMING_ENDPOINTS.with_borrow_mut(|stack| stack.pop());
+    result
+}
+
+/// Decode an endpoint, which is encoded as the index of its interface ID in
+/// the message being decoded.
+fn take_incoming_endpoint(index: u32) -> MojomResult<Endpoint> {
+    INCOMING_ENDPOINTS.with_borrow(|stack| {
+        let Some((router, interface_ids)) = stack.last() else {
+            bail!("Associated interfaces can only be decoded by a remote or receiver")
+        };
+        let id = *usize::try_from(index)
+            .ok()
+            .and_then(|index| interface_ids.get(index))
+            .with_context(|| format!("The message has no interface ID with index {}", index))?;
+        router.borrow_mut().claim(id)?;
+        Ok(Endpoint::from_router(router.clone(), id))
+    })
+}
+
+impl<I: ?Sized + Interface> Mojom for PendingAssociatedRemote<I> {
+    fn mojom_type() -> MojomType {
+        MojomType::PendingAssociatedRemote
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        MojomValue::PendingAssociatedRemote {
+            index: self.endpoint.into_index("remote"),
+            version: self.version,
+        }
+    }
+
+    fn from_mojom_value(value: MojomValue) -> MojomResult<Self> {
+        let MojomValue::PendingAssociatedRemote { index, version } = value else {
+            bail!("Expected a pending_associated_remote<{}>, but got {:?}", I::NAME, value)
+        };
+        Ok(PendingAssociatedRemote {
+            endpoint: PendingEndpoint::Ours(take_incoming_endpoint(index)?),
+            version,
+            _interface: PhantomData,
+        })
+    }
+}
+
+impl<I: ?Sized + Interface> Mojom for PendingAssociatedReceiver<I> {
+    fn mojom_type() -> MojomType {
+        MojomType::PendingAssociatedReceiver
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        MojomValue::PendingAssociatedReceiver { index: self.endpoint.into_index("receiver") }
+    }
+
+    fn from_mojom_value(value: MojomValue) -> MojomResult<Self> {
+        let MojomValue::PendingAssociatedReceiver { index } = value else {
+            bail!("Expected a pending_associated_receiver<{}>, but got {:?}", I::NAME, value)
+        };
+        Ok(PendingAssociatedReceiver {
+            endpoint: PendingEndpoint::Ours(take_incoming_endpoint(index)?),
+            _interface: PhantomData,
+        })
+    }
+}
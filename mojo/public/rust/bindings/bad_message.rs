@@ -0,0 +1,66 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Reporting bad messages to the system, which corresponds to
+//! mojo::ReportBadMessage and mojo::internal::MessageDispatchContext in the
+//! C++ bindings.
+//!
+//! Remotes and receivers report messages that fail validation by themselves,
+//! then close the pipe. The system passes the report on to whatever handles
+//! bad messages from the process that sent it, which may kill that process.
+//! Implementations can also report requests which are valid but make no
+//! sense, with `report_bad_message()`.
+
+use mojom_parser::MojomResult;
+use system::message_pipe::MessageHandle;
+
+use anyhow::anyhow;
+use std::cell::RefCell;
+
+thread_local! {
+    /// For each message being dispatched on this thread, innermost last, the
+    /// error that was reported for it, if any. There's more than one while a
+    /// sync call dispatches other messages.
+    static REPORTED_ERRORS: RefCell<Vec<Option<String>>> = const { RefCell::new(vec![]) };
+}
+
+/// Report that the request or response being dispatched on this thread is
+/// bad, with `error` saying why. Once the method or response handler returns,
+/// the remote or receiver which read it tells the system, like it does for
+/// invalid messages, and closes the pipe. This is like calling
+/// mojo::Receiver::ReportBadMessage in C++.
+///
+/// Panics if no message is being dispatched.
+pub fn report_bad_message(error: &str) {
+    REPORTED_ERRORS.with_borrow_mut(|errors| {
+        let reported =
+            errors.last_mut().expect("Bad messages can only be reported during dispatch");
+        // Like in C++, only the first report counts.
+        reported.get_or_insert_with(|| error.to_string());
+    });
+}
+
+/// Call `dispatch`, which dispatches a message to an implementation or a
+/// response handler, and fail if the message was reported as bad.
+pub(crate) fn dispatch_reporting_bad_messages(
+    dispatch: impl FnOnce() -> MojomResult<()>,
+) -> MojomResult<()> {
+    REPORTED_ERRORS.with_borrow_mut(|errors| errors.push(None));
+    let result = dispatch();
+    match REPORTED_ERRORS.with_borrow_mut(|errors| errors.pop().flatten()) {
+        Some(error) => Err(anyhow!(error)),
+        None => result,
+    }
+}
+
+/// Tell the system that the message which was read as `source` is bad,
+/// because of `error`. Messages which weren't read from a pipe, e.g. in tests,
+/// have no source, so there's nobody to tell.
+pub(crate) fn notify_bad_message(source: Option<&MessageHandle>, error: &anyhow::Error) {
+    if let Some(source) = source {
+        // Like the C++ bindings, ignore failures, since the pipe is closed
+        // either way.
+        let _ = source.notify_bad_message(&format!("{:#}", error));
+    }
+}
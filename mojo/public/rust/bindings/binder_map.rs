@@ -0,0 +1,87 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `BinderMap`, which binds each `GenericPendingReceiver` with the
+//! binder registered for its interface, e.g. to implement an interface broker
+//! such as `mojo_base.mojom.Binder`. This corresponds to mojo::BinderMap in
+//! the C++ bindings.
+
+use crate::generic_pending_receiver::GenericPendingReceiver;
+use crate::interface::Interface;
+use crate::receiver::PendingReceiver;
+use system::message_pipe::MessagePipeHandle;
+
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+
+/// Binds a receiver's message pipe, which is known to be for the binder's
+/// interface.
+type Binder = Box<dyn FnMut(MessagePipeHandle)>;
+
+/// Binders for receivers of different interfaces, by interface name.
+#[derive(Default)]
+pub struct BinderMap {
+    binders: HashMap<&'static str, Binder>,
+}
+
+impl BinderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind receivers for `I` by calling `binder`, which typically binds a
+    /// `Receiver` or adds one to a `ReceiverSet`.
+    ///
+    /// Panics if there's already a binder for `I`.
+    pub fn add<I: ?Sized + Interface>(
+        &mut self,
+        mut binder: impl FnMut(PendingReceiver<I>) + 'static,
+    ) {
+        self.insert(I::NAME, Box::new(move |pipe| binder(PendingReceiver::new(pipe))));
+    }
+
+    /// Bind receivers for `I` on another thread, by sending them over
+    /// `sender` to the thread which owns the other end of the channel, which
+    /// can then `take()` them. This is like passing a task runner to
+    /// mojo::BinderMap::Add in C++, which Rust doesn't have. If the other end
+    /// of the channel is gone, receivers are closed instead.
+    ///
+    /// Panics if there's already a binder for `I`.
+    pub fn add_with_sender<I: ?Sized + Interface>(
+        &mut self,
+        sender: Sender<GenericPendingReceiver>,
+    ) {
+        self.insert(
+            I::NAME,
+            Box::new(move |pipe| {
+                // Like a task posted to a task runner which has shut down,
+                // the receiver is dropped if nobody is listening.
+                let _ = sender.send(GenericPendingReceiver::new(I::NAME, pipe));
+            }),
+        );
+    }
+
+    fn insert(&mut self, interface_name: &'static str, binder: Binder) {
+        let previous = self.binders.insert(interface_name, binder);
+        assert!(previous.is_none(), "{interface_name} already has a binder");
+    }
+
+    /// Whether there's a binder for the interface named `interface_name`.
+    pub fn contains(&self, interface_name: &str) -> bool {
+        self.binders.contains_key(interface_name)
+    }
+
+    /// Bind `receiver` with the binder for its interface. If there isn't one,
+    /// give it back, e.g. so the caller can try elsewhere or report it.
+    pub fn try_bind(
+        &mut self,
+        receiver: GenericPendingReceiver,
+    ) -> Result<(), GenericPendingReceiver> {
+        let Some(binder) = self.binders.get_mut(receiver.interface_name()) else {
+            return Err(receiver);
+        };
+        binder(receiver.into_parts().1);
+        Ok(())
+    }
+}
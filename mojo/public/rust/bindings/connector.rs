@@ -0,0 +1,101 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `Connector`, which reads and writes whole messages on a message
+//! pipe, and notices when the pipe is disconnected. Each router (see
+//! router.rs) owns one. This corresponds to mojo::Connector in the C++
+//! bindings.
+
+use crate::bad_message::notify_bad_message;
+use crate::message::Message;
+use system::message_pipe::MessagePipeHandle;
+use system::mojo_types::MojoError;
+
+use anyhow::Context;
+
+/// The result of reading from a `Connector`, or from an `Endpoint`.
+pub(crate) enum ReadResult<T> {
+    Message(T),
+    /// There are no messages to read yet.
+    Empty,
+    /// The pipe was closed, at either end, so there never will be.
+    Disconnected,
+}
+
+pub(crate) struct Connector {
+    /// The pipe, or None if we've closed it.
+    pipe: Option<MessagePipeHandle>,
+}
+
+impl Connector {
+    pub(crate) fn new(pipe: MessagePipeHandle) -> Self {
+        Connector { pipe: Some(pipe) }
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        self.pipe.is_some()
+    }
+
+    /// The pipe, e.g. to wait for it to be readable, or None if we've closed
+    /// it.
+    pub(crate) fn pipe(&self) -> Option<&MessagePipeHandle> {
+        self.pipe.as_ref()
+    }
+
+    /// Write `message` to the pipe.
+    ///
+    /// Like the C++ bindings, we ignore failures to write: either we're
+    /// already disconnected, or we soon will be, which the owner will notice
+    /// when it next reads.
+    pub(crate) fn send(&self, mut message: Message) {
+        if let Some(pipe) = &self.pipe {
+            let handles = std::mem::take(&mut message.handles);
+            // Encode the message straight into the pipe's buffer.
+            let _ = pipe.write_with_handles(message.serialized_size(), handles, |buffer| {
+                message.write_to(buffer)
+            });
+        }
+    }
+
+    /// Read the next message, and parse its header. If that fails, this
+    /// reports the message as bad and closes the pipe, like the C++ connector.
+    pub(crate) fn read(&mut self) -> ReadResult<Message> {
+        let Some(pipe) = &self.pipe else {
+            return ReadResult::Disconnected;
+        };
+        let source = match pipe.read_message() {
+            Ok(source) => source,
+            Err(MojoError::ShouldWait) => return ReadResult::Empty,
+            Err(_) => {
+                self.close();
+                return ReadResult::Disconnected;
+            }
+        };
+        let message = source
+            .take_data_with_handles()
+            .context("The message's handles were invalid")
+            .and_then(|(bytes, handles)| Message::from_bytes(&bytes, handles));
+        match message {
+            Ok(mut message) => {
+                message.source = Some(source);
+                ReadResult::Message(message)
+            }
+            Err(error) => {
+                notify_bad_message(Some(&source), &error);
+                self.close();
+                ReadResult::Disconnected
+            }
+        }
+    }
+
+    /// Close our end of the pipe, which disconnects the other end.
+    pub(crate) fn close(&mut self) {
+        self.pipe = None;
+    }
+
+    /// Give up the pipe, if it's still open.
+    pub(crate) fn take_pipe(&mut self) -> Option<MessagePipeHandle> {
+        self.pipe.take()
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! The control messages which remotes and receivers send each other, e.g. to
+//! query the receiver's version, and `ControlMessageHandler`, which answers
+//! them. This corresponds to mojo::internal::ControlMessageHandler in the C++
+//! bindings; the requests that correspond to its ControlMessageProxy are sent
+//! by `Remote` and `Receiver` themselves.
+//!
+//! The types here are written out by hand from
+//! //mojo/public/interfaces/bindings/interface_control_messages.mojom, since
+//! the generated crate for it would depend on this one.
+
+use crate::interface::Request;
+use crate::message::Message;
+use crate::router::Endpoint;
+use mojom_parser::{MessageHeader, Mojom, MojomResult};
+
+use anyhow::{bail, Context};
+use std::time::Duration;
+
+/// The name of a control message which expects a response.
+pub(crate) const RUN_MESSAGE_ID: u32 = 0xFFFFFFFF;
+/// The name of a control message which has no response, and which closes the
+/// pipe if the receiver can't handle it.
+pub(crate) const RUN_OR_CLOSE_PIPE_MESSAGE_ID: u32 = 0xFFFFFFFE;
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct RunMessageParams {
+    pub input: RunInput,
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) enum RunInput {
+    QueryVersion(QueryVersion),
+    FlushForTesting(FlushForTesting),
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct RunResponseMessageParams {
+    #[mojom(nullable)]
+    pub output: Option<RunOutput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) enum RunOutput {
+    QueryVersionResult(QueryVersionResult),
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct QueryVersion {}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct QueryVersionResult {
+    pub version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct FlushForTesting {}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct RunOrClosePipeMessageParams {
+    pub input: RunOrClosePipeInput,
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) enum RunOrClosePipeInput {
+    RequireVersion(RequireVersion),
+    EnableIdleTracking(EnableIdleTracking),
+    MessageAck(MessageAck),
+    NotifyIdle(NotifyIdle),
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct RequireVersion {
+    pub version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct EnableIdleTracking {
+    pub timeout_in_microseconds: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct MessageAck {}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct NotifyIdle {}
+
+/// Whether a request is a control message, rather than a call of one of the
+/// interface's methods.
+pub(crate) fn is_control_message(header: &MessageHeader) -> bool {
+    header.name == RUN_MESSAGE_ID || header.name == RUN_OR_CLOSE_PIPE_MESSAGE_ID
+}
+
+/// Send a control message which has no response.
+pub(crate) fn send_run_or_close_pipe(endpoint: &Endpoint, input: RunOrClosePipeInput) {
+    let header = MessageHeader { name: RUN_OR_CLOSE_PIPE_MESSAGE_ID, ..Default::default() };
+    endpoint
+        .send(header, RunOrClosePipeMessageParams { input })
+        .expect("Control messages must be encodable");
+}
+
+/// The idle tracking state of one end of a pipe. Either end may track
+/// whether the other is idle, but in practice it's the remote that asks the
+/// receiver to tell it (see `Remote::set_idle_handler`).
+#[derive(Default)]
+pub(crate) struct ControlMessageHandler {
+    /// How long the receiver should be idle before it sends NotifyIdle, once
+    /// the remote has enabled idle tracking.
+    pub idle_timeout: Option<Duration>,
+    /// Called when the peer says that it's idle, if we've no unacknowledged
+    /// messages.
+    pub idle_handler: Option<Box<dyn FnMut()>>,
+    /// The number of messages we've sent since enabling idle tracking, which
+    /// the peer hasn't acknowledged yet.
+    pub num_unacked_messages: usize,
+}
+
+impl ControlMessageHandler {
+    /// Note that we sent a request with `header`, which the peer will
+    /// acknowledge if we're tracking whether it's idle.
+    pub(crate) fn on_request_sent(&mut self, header: &MessageHeader) {
+        if self.idle_handler.is_some() && !is_control_message(header) {
+            self.num_unacked_messages += 1;
+        }
+    }
+
+    /// Handle a control request from the peer, where `interface_version` is
+    /// the version of the interface that we implement. Fails if the request
+    /// is invalid, in which case the pipe should be closed.
+    ///
+    /// If the peer requires a version that we don't implement, this closes
+    /// the pipe itself. Like in C++, that doesn't make the request bad.
+    pub(crate) fn accept(
+        &mut self,
+        message: Message,
+        interface_version: u32,
+        endpoint: &Endpoint,
+    ) -> MojomResult<()> {
+        let request = Request::new(message, endpoint.downgrade());
+        if request.name() == RUN_MESSAGE_ID {
+            let (params, responder): (RunMessageParams, _) = request.decode_with_responder()?;
+            let output = match params.input {
+                RunInput::QueryVersion(_) => {
+                    Some(RunOutput::QueryVersionResult(QueryVersionResult {
+                        version: interface_version,
+                    }))
+                }
+                // We've already dispatched every request that was sent before
+                // this one, so just say so.
+                RunInput::FlushForTesting(_) => None,
+            };
+            responder.respond(RunResponseMessageParams { output });
+            return Ok(());
+        }
+
+        let params: RunOrClosePipeMessageParams = request.decode()?;
+        match params.input {
+            RunOrClosePipeInput::RequireVersion(RequireVersion { version }) => {
+                if version > interface_version {
+                    endpoint.close_pipe();
+                }
+            }
+            RunOrClosePipeInput::EnableIdleTracking(EnableIdleTracking {
+                timeout_in_microseconds,
+            }) => {
+                let timeout: u64 = timeout_in_microseconds
+                    .try_into()
+                    .context("The idle timeout must not be negative")?;
+                self.idle_timeout = Some(Duration::from_micros(timeout));
+            }
+            RunOrClosePipeInput::MessageAck(_) => {
+                if self.idle_handler.is_none() || self.num_unacked_messages == 0 {
+                    bail!("Got an unexpected MessageAck")
+                }
+                self.num_unacked_messages -= 1;
+            }
+            RunOrClosePipeInput::NotifyIdle(_) => {
+                let Some(idle_handler) = &mut self.idle_handler else {
+                    bail!("Got NotifyIdle, but we didn't enable idle tracking")
+                };
+                // If the peer hasn't acknowledged all our messages yet, it
+                // won't be idle once it reads them.
+                if self.num_unacked_messages == 0 {
+                    idle_handler();
+                }
+            }
+        }
+        Ok(())
+    }
+}
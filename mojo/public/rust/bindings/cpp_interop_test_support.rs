@@ -0,0 +1,541 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Rust endpoints for cpp_interop_unittest.cc, which binds them to C++
+//! endpoints to check that the two can talk to each other.
+//!
+//! Rust endpoints only read messages when they're told to, so the C++ tests
+//! call `dispatch_pending_messages()` on them whenever they expect the Rust
+//! side to act.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_base";
+    "//mojo/public/rust:mojo_bindings";
+}
+
+use mojo_bindings::*;
+use mojo_public_mojom_base_base_rust::Binder;
+use mojo_public_rust_bindings_test_interfaces_rust::*;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[cxx::bridge(namespace = "mojo_bindings_test")]
+mod ffi {
+    extern "Rust" {
+        type RustCalculatorReceiver;
+        fn bind_rust_calculator_receiver(pipe: usize) -> Box<RustCalculatorReceiver>;
+        fn dispatch_pending_messages(self: &mut RustCalculatorReceiver);
+        fn is_connected(self: &RustCalculatorReceiver) -> bool;
+        fn total(self: &RustCalculatorReceiver) -> i32;
+        fn notify_idle(self: &RustCalculatorReceiver);
+        fn reset_with_reason(
+            self: &mut RustCalculatorReceiver,
+            custom_reason: u32,
+            description: &str,
+        );
+
+        type RustCalculatorRemote;
+        fn bind_rust_calculator_remote(pipe: usize) -> Box<RustCalculatorRemote>;
+        fn dispatch_pending_messages(self: &mut RustCalculatorRemote);
+        fn is_connected(self: &RustCalculatorRemote) -> bool;
+        fn version(self: &RustCalculatorRemote) -> u32;
+        fn query_version(self: &mut RustCalculatorRemote);
+        fn require_version(self: &mut RustCalculatorRemote, version: u32);
+        fn disconnect_custom_reason(self: &RustCalculatorRemote) -> u32;
+        fn disconnect_description(self: &RustCalculatorRemote) -> String;
+
+        type RustBufferReverserReceiver;
+        fn bind_rust_buffer_reverser_receiver(pipe: usize) -> Box<RustBufferReverserReceiver>;
+        fn dispatch_pending_messages(self: &mut RustBufferReverserReceiver);
+        fn is_connected(self: &RustBufferReverserReceiver) -> bool;
+
+        type RustBroker;
+        fn bind_rust_broker(pipe: usize) -> Box<RustBroker>;
+        fn dispatch_pending_messages(self: &mut RustBroker);
+        fn is_connected(self: &RustBroker) -> bool;
+        fn num_calculators(self: &RustBroker) -> usize;
+
+        type RustCalculatorFactoryReceiver;
+        fn bind_rust_calculator_factory_receiver(pipe: usize)
+            -> Box<RustCalculatorFactoryReceiver>;
+        fn dispatch_pending_messages(self: &mut RustCalculatorFactoryReceiver);
+        fn is_connected(self: &RustCalculatorFactoryReceiver) -> bool;
+        fn num_calculators(self: &RustCalculatorFactoryReceiver) -> usize;
+        fn calculator_total(self: &RustCalculatorFactoryReceiver, index: usize) -> i32;
+        fn calculator_is_connected(self: &RustCalculatorFactoryReceiver, index: usize) -> bool;
+        fn close_calculator(self: &mut RustCalculatorFactoryReceiver, index: usize);
+        fn remote_is_connected(self: &RustCalculatorFactoryReceiver, index: usize) -> bool;
+        fn close_remote(self: &mut RustCalculatorFactoryReceiver, index: usize);
+
+        type RustCalculatorFactoryRemote;
+        fn bind_rust_calculator_factory_remote(pipe: usize) -> Box<RustCalculatorFactoryRemote>;
+        fn dispatch_pending_messages(self: &mut RustCalculatorFactoryRemote);
+        fn is_connected(self: &RustCalculatorFactoryRemote) -> bool;
+        fn create_calculator(self: &mut RustCalculatorFactoryRemote);
+        fn add(self: &mut RustCalculatorFactoryRemote, index: usize, value: i32);
+        fn calculator_is_connected(self: &RustCalculatorFactoryRemote, index: usize) -> bool;
+        fn close_calculator(self: &mut RustCalculatorFactoryRemote, index: usize);
+        fn use_calculator(self: &mut RustCalculatorFactoryRemote, value: i32);
+        fn receiver_total(self: &RustCalculatorFactoryRemote, index: usize) -> i32;
+        fn receiver_is_connected(self: &RustCalculatorFactoryRemote, index: usize) -> bool;
+        fn close_receiver(self: &mut RustCalculatorFactoryRemote, index: usize);
+
+        type RustEchoRemote;
+        fn bind_rust_echo_remote(pipe: usize) -> Box<RustEchoRemote>;
+        fn echo(self: &mut RustEchoRemote, value: i32) -> i32;
+        fn echo_no_interrupt(self: &mut RustEchoRemote, value: i32) -> i32;
+
+        type RustEchoReceiver;
+        fn bind_rust_echo_receiver(pipe: usize) -> Box<RustEchoReceiver>;
+        fn num_echoes(self: &RustEchoReceiver) -> i32;
+
+        fn disallow_sync_call();
+    }
+}
+
+struct CalculatorImpl {
+    total: Rc<Cell<i32>>,
+}
+
+impl Calculator for CalculatorImpl {
+    fn add(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        self.total.set(self.total.get() + value);
+        callback(self.total.get());
+    }
+
+    fn clear(&mut self) {
+        self.total.set(0);
+    }
+
+    fn translate(&mut self, point: Point, delta: Point, callback: Box<dyn FnOnce(Point, String)>) {
+        let result = Point { x: point.x + delta.x, y: point.y + delta.y };
+        let description = format!("({}, {})", result.x, result.y);
+        callback(result, description);
+    }
+
+    fn subtract(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        self.total.set(self.total.get() - value);
+        callback(self.total.get());
+    }
+}
+
+/// Take ownership of a message pipe endpoint which C++ has released.
+fn pipe_from_cpp(pipe: usize) -> MessagePipeHandle {
+    // SAFETY: The C++ tests release the endpoint before passing it here, so
+    // nothing else will use it.
+    unsafe { MessagePipeHandle::from_raw(pipe as _) }
+}
+
+pub struct RustCalculatorReceiver {
+    receiver: Receiver<dyn Calculator>,
+    total: Rc<Cell<i32>>,
+}
+
+fn bind_rust_calculator_receiver(pipe: usize) -> Box<RustCalculatorReceiver> {
+    let total = Rc::new(Cell::new(0));
+    let receiver = Receiver::new(
+        Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+        PendingReceiver::new(pipe_from_cpp(pipe)),
+    );
+    Box::new(RustCalculatorReceiver { receiver, total })
+}
+
+impl RustCalculatorReceiver {
+    fn dispatch_pending_messages(&mut self) {
+        self.receiver.dispatch_pending_messages();
+    }
+
+    fn is_connected(&self) -> bool {
+        self.receiver.is_connected()
+    }
+
+    fn total(&self) -> i32 {
+        self.total.get()
+    }
+
+    fn notify_idle(&self) {
+        self.receiver.notify_idle();
+    }
+
+    fn reset_with_reason(&mut self, custom_reason: u32, description: &str) {
+        self.receiver.reset_with_reason(custom_reason, description);
+    }
+}
+
+pub struct RustCalculatorRemote {
+    remote: Remote<dyn Calculator>,
+    /// Why the receiver closed the pipe, once the remote notices, if it said.
+    disconnect_reason: Rc<RefCell<Option<DisconnectReason>>>,
+}
+
+fn bind_rust_calculator_remote(pipe: usize) -> Box<RustCalculatorRemote> {
+    let mut remote = Remote::new(PendingRemote::new(pipe_from_cpp(pipe), 0));
+    let disconnect_reason = Rc::new(RefCell::new(None));
+    let disconnect_reason_clone = disconnect_reason.clone();
+    remote.set_disconnect_with_reason_handler(move |reason| {
+        *disconnect_reason_clone.borrow_mut() = reason;
+    });
+    Box::new(RustCalculatorRemote { remote, disconnect_reason })
+}
+
+impl RustCalculatorRemote {
+    fn dispatch_pending_messages(&mut self) {
+        self.remote.dispatch_pending_messages();
+    }
+
+    fn is_connected(&self) -> bool {
+        self.remote.is_connected()
+    }
+
+    fn version(&self) -> u32 {
+        self.remote.version()
+    }
+
+    fn query_version(&mut self) {
+        // The answer updates version().
+        self.remote.query_version(|_| {});
+    }
+
+    fn require_version(&mut self, version: u32) {
+        self.remote.require_version(version);
+    }
+
+    /// Like in C++, the reason is 0 and "" if there isn't one.
+    fn disconnect_custom_reason(&self) -> u32 {
+        self.disconnect_reason.borrow().as_ref().map_or(0, |reason| reason.custom_reason)
+    }
+
+    fn disconnect_description(&self) -> String {
+        self.disconnect_reason
+            .borrow()
+            .as_ref()
+            .map_or_else(String::new, |reason| reason.description.clone())
+    }
+}
+
+struct BufferReverserImpl;
+
+impl BufferReverser for BufferReverserImpl {
+    fn reverse(
+        &mut self,
+        buffer: mojo_base::BigBuffer,
+        callback: Box<dyn FnOnce(mojo_base::BigBuffer)>,
+    ) {
+        let mut bytes = buffer.as_slice().to_vec();
+        bytes.reverse();
+        callback(bytes.into());
+    }
+}
+
+pub struct RustBufferReverserReceiver {
+    receiver: Receiver<dyn BufferReverser>,
+}
+
+fn bind_rust_buffer_reverser_receiver(pipe: usize) -> Box<RustBufferReverserReceiver> {
+    Box::new(RustBufferReverserReceiver {
+        receiver: Receiver::new(
+            Box::new(BufferReverserImpl) as Box<dyn BufferReverser>,
+            PendingReceiver::new(pipe_from_cpp(pipe)),
+        ),
+    })
+}
+
+impl RustBufferReverserReceiver {
+    fn dispatch_pending_messages(&mut self) {
+        self.receiver.dispatch_pending_messages();
+    }
+
+    fn is_connected(&self) -> bool {
+        self.receiver.is_connected()
+    }
+}
+
+/// An interface broker, which binds the receivers that clients send it with
+/// its binders.
+struct BrokerImpl {
+    binders: BinderMap,
+}
+
+impl Binder for BrokerImpl {
+    fn bind(&mut self, receiver: GenericPendingReceiver) {
+        if let Err(receiver) = self.binders.try_bind(receiver) {
+            report_bad_message(&format!("No binder for {}", receiver.interface_name()));
+        }
+    }
+}
+
+/// A broker which binds calculators, each with its own total.
+pub struct RustBroker {
+    receiver: Receiver<dyn Binder>,
+    calculators: Rc<RefCell<ReceiverSet<dyn Calculator>>>,
+}
+
+fn bind_rust_broker(pipe: usize) -> Box<RustBroker> {
+    let calculators = Rc::new(RefCell::new(ReceiverSet::<dyn Calculator>::new()));
+    let calculators_clone = calculators.clone();
+    let mut binders = BinderMap::new();
+    binders.add::<dyn Calculator>(move |receiver| {
+        let implementation = CalculatorImpl { total: Rc::new(Cell::new(0)) };
+        calculators_clone.borrow_mut().add(Box::new(implementation), receiver, ());
+    });
+    let receiver = Receiver::new(
+        Box::new(BrokerImpl { binders }) as Box<dyn Binder>,
+        PendingReceiver::new(pipe_from_cpp(pipe)),
+    );
+    Box::new(RustBroker { receiver, calculators })
+}
+
+impl RustBroker {
+    /// Dispatch the requests for the broker, then for the calculators that it
+    /// bound.
+    fn dispatch_pending_messages(&mut self) {
+        self.receiver.dispatch_pending_messages();
+        self.calculators.borrow_mut().dispatch_pending_messages();
+    }
+
+    fn is_connected(&self) -> bool {
+        self.receiver.is_connected()
+    }
+
+    fn num_calculators(&self) -> usize {
+        self.calculators.borrow().len()
+    }
+}
+
+/// Associated calculator receivers, and their running totals. Closed ones
+/// stay in the list, so that the C++ tests can refer to them by index.
+type CalculatorList = Rc<RefCell<Vec<(Receiver<dyn Calculator>, Rc<Cell<i32>>)>>>;
+
+/// Binds a calculator receiver for `receiver`, and adds it to `calculators`.
+fn add_associated_calculator(
+    calculators: &CalculatorList,
+    receiver: PendingAssociatedReceiver<dyn Calculator>,
+) {
+    let total = Rc::new(Cell::new(0));
+    let receiver = Receiver::new_associated(
+        Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+        receiver,
+    );
+    calculators.borrow_mut().push((receiver, total));
+}
+
+/// Binds the calculators that it's sent on its own pipe, and keeps the
+/// remotes that it's sent after adding to them.
+struct CalculatorFactoryImpl {
+    calculators: CalculatorList,
+    remotes: Rc<RefCell<Vec<Remote<dyn Calculator>>>>,
+}
+
+impl CalculatorFactory for CalculatorFactoryImpl {
+    fn create_calculator(&mut self, receiver: PendingAssociatedReceiver<dyn Calculator>) {
+        add_associated_calculator(&self.calculators, receiver);
+    }
+
+    fn use_calculator(&mut self, calculator: PendingAssociatedRemote<dyn Calculator>, value: i32) {
+        let mut remote = Remote::new_associated(calculator);
+        remote.add(value, Box::new(|_| {}));
+        self.remotes.borrow_mut().push(remote);
+    }
+}
+
+pub struct RustCalculatorFactoryReceiver {
+    receiver: Receiver<dyn CalculatorFactory>,
+    calculators: CalculatorList,
+    remotes: Rc<RefCell<Vec<Remote<dyn Calculator>>>>,
+}
+
+fn bind_rust_calculator_factory_receiver(pipe: usize) -> Box<RustCalculatorFactoryReceiver> {
+    let calculators = CalculatorList::default();
+    let remotes = Rc::new(RefCell::new(vec![]));
+    let receiver = Receiver::new(
+        Box::new(CalculatorFactoryImpl {
+            calculators: calculators.clone(),
+            remotes: remotes.clone(),
+        }) as Box<dyn CalculatorFactory>,
+        PendingReceiver::new(pipe_from_cpp(pipe)),
+    );
+    Box::new(RustCalculatorFactoryReceiver { receiver, calculators, remotes })
+}
+
+impl RustCalculatorFactoryReceiver {
+    /// Dispatch the requests for the factory, then for its calculators, then
+    /// the responses for its remotes.
+    fn dispatch_pending_messages(&mut self) {
+        self.receiver.dispatch_pending_messages();
+        for (receiver, _) in self.calculators.borrow_mut().iter_mut() {
+            receiver.dispatch_pending_messages();
+        }
+        for remote in self.remotes.borrow_mut().iter_mut() {
+            remote.dispatch_pending_messages();
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.receiver.is_connected()
+    }
+
+    fn num_calculators(&self) -> usize {
+        self.calculators.borrow().len()
+    }
+
+    fn calculator_total(&self, index: usize) -> i32 {
+        self.calculators.borrow()[index].1.get()
+    }
+
+    fn calculator_is_connected(&self, index: usize) -> bool {
+        self.calculators.borrow()[index].0.is_connected()
+    }
+
+    fn close_calculator(&mut self, index: usize) {
+        self.calculators.borrow_mut()[index].0.reset();
+    }
+
+    fn remote_is_connected(&self, index: usize) -> bool {
+        self.remotes.borrow()[index].is_connected()
+    }
+
+    fn close_remote(&mut self, index: usize) {
+        self.remotes.borrow_mut()[index].reset();
+    }
+}
+
+/// Sends calculator receivers and remotes to a C++ factory. Like
+/// `CalculatorList`, closed ones stay in the lists.
+pub struct RustCalculatorFactoryRemote {
+    remote: Remote<dyn CalculatorFactory>,
+    /// The remotes for the receivers sent by `create_calculator()`.
+    calculators: Vec<Remote<dyn Calculator>>,
+    /// The receivers for the remotes sent by `use_calculator()`.
+    receivers: CalculatorList,
+}
+
+fn bind_rust_calculator_factory_remote(pipe: usize) -> Box<RustCalculatorFactoryRemote> {
+    Box::new(RustCalculatorFactoryRemote {
+        remote: Remote::new(PendingRemote::new(pipe_from_cpp(pipe), 0)),
+        calculators: vec![],
+        receivers: CalculatorList::default(),
+    })
+}
+
+impl RustCalculatorFactoryRemote {
+    /// Dispatch the responses for the factory and its calculators, then the
+    /// requests for the receivers.
+    fn dispatch_pending_messages(&mut self) {
+        self.remote.dispatch_pending_messages();
+        for calculator in &mut self.calculators {
+            calculator.dispatch_pending_messages();
+        }
+        for (receiver, _) in self.receivers.borrow_mut().iter_mut() {
+            receiver.dispatch_pending_messages();
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.remote.is_connected()
+    }
+
+    fn create_calculator(&mut self) {
+        let (calculator, receiver) =
+            self.remote.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+        self.remote.create_calculator(receiver);
+        self.calculators.push(Remote::new_associated(calculator));
+    }
+
+    fn add(&mut self, index: usize, value: i32) {
+        self.calculators[index].add(value, Box::new(|_| {}));
+    }
+
+    fn calculator_is_connected(&self, index: usize) -> bool {
+        self.calculators[index].is_connected()
+    }
+
+    fn close_calculator(&mut self, index: usize) {
+        self.calculators[index].reset();
+    }
+
+    fn use_calculator(&mut self, value: i32) {
+        let (receiver, calculator) =
+            self.remote.associated_group().new_endpoint_and_pass_remote::<dyn Calculator>();
+        add_associated_calculator(&self.receivers, receiver);
+        self.remote.use_calculator(calculator, value);
+    }
+
+    fn receiver_total(&self, index: usize) -> i32 {
+        self.receivers.borrow()[index].1.get()
+    }
+
+    fn receiver_is_connected(&self, index: usize) -> bool {
+        self.receivers.borrow()[index].0.is_connected()
+    }
+
+    fn close_receiver(&mut self, index: usize) {
+        self.receivers.borrow_mut()[index].0.reset();
+    }
+}
+
+/// Makes sync calls to an Echo receiver, which must be on another thread so
+/// that it can answer while this one waits.
+pub struct RustEchoRemote {
+    remote: Remote<dyn Echo>,
+}
+
+fn bind_rust_echo_remote(pipe: usize) -> Box<RustEchoRemote> {
+    Box::new(RustEchoRemote { remote: Remote::new(PendingRemote::new(pipe_from_cpp(pipe), 0)) })
+}
+
+impl RustEchoRemote {
+    fn echo(&mut self, value: i32) -> i32 {
+        self.remote.echo_sync(value).expect("Echo failed")
+    }
+
+    fn echo_no_interrupt(&mut self, value: i32) -> i32 {
+        self.remote.echo_no_interrupt_sync(value).expect("EchoNoInterrupt failed")
+    }
+}
+
+/// Echoes values, counting the requests.
+struct EchoImpl {
+    num_echoes: Rc<Cell<i32>>,
+}
+
+impl Echo for EchoImpl {
+    fn echo(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        self.num_echoes.set(self.num_echoes.get() + 1);
+        callback(value);
+    }
+
+    fn echo_no_interrupt(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        self.num_echoes.set(self.num_echoes.get() + 1);
+        callback(value);
+    }
+}
+
+/// An Echo receiver, which answers sync requests while a sync call on the
+/// same thread waits, without the C++ tests dispatching its messages.
+pub struct RustEchoReceiver {
+    _receiver: Receiver<dyn Echo>,
+    num_echoes: Rc<Cell<i32>>,
+}
+
+fn bind_rust_echo_receiver(pipe: usize) -> Box<RustEchoReceiver> {
+    let num_echoes = Rc::new(Cell::new(0));
+    let receiver = Receiver::new(
+        Box::new(EchoImpl { num_echoes: num_echoes.clone() }) as Box<dyn Echo>,
+        PendingReceiver::new(pipe_from_cpp(pipe)),
+    );
+    Box::new(RustEchoReceiver { _receiver: receiver, num_echoes })
+}
+
+impl RustEchoReceiver {
+    fn num_echoes(&self) -> i32 {
+        self.num_echoes.get()
+    }
+}
+
+/// Disallow sync calls in this process, which can't be undone, so the C++
+/// tests only call this in death tests.
+fn disallow_sync_call() {
+    SyncCallRestrictions::disallow_sync_call();
+}
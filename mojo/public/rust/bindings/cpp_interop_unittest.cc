@@ -0,0 +1,526 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+// Tests that Rust endpoints (see cpp_interop_test_support.rs) work with C++
+// endpoints on the other end of the pipe, in particular for the interface
+// control messages which C++ sends by itself.
+
+#include <stdint.h>
+
+#include <algorithm>
+#include <memory>
+#include <string>
+#include <utility>
+#include <vector>
+
+#include "base/functional/bind.h"
+#include "base/functional/callback_helpers.h"
+#include "base/run_loop.h"
+#include "base/strings/string_number_conversions.h"
+#include "base/task/single_thread_task_runner.h"
+#include "base/test/bind.h"
+#include "base/test/task_environment.h"
+#include "base/test/test_future.h"
+#include "base/threading/sequence_bound.h"
+#include "base/threading/thread.h"
+#include "base/time/time.h"
+#include "mojo/public/cpp/base/big_buffer.h"
+#include "mojo/public/cpp/bindings/associated_receiver.h"
+#include "mojo/public/cpp/bindings/associated_remote.h"
+#include "mojo/public/cpp/bindings/generic_pending_receiver.h"
+#include "mojo/public/cpp/bindings/receiver.h"
+#include "mojo/public/cpp/bindings/message.h"
+#include "mojo/public/cpp/bindings/pending_associated_receiver.h"
+#include "mojo/public/cpp/bindings/pending_associated_remote.h"
+#include "mojo/public/cpp/bindings/pending_receiver.h"
+#include "mojo/public/cpp/bindings/pending_remote.h"
+#include "mojo/public/cpp/bindings/remote.h"
+#include "mojo/public/cpp/system/functions.h"
+#include "mojo/public/cpp/system/message_pipe.h"
+#include "mojo/public/mojom/base/binder.mojom.h"
+#include "mojo/public/rust/bindings/cpp_interop_test_support.rs.h"
+#include "mojo/public/rust/bindings/test_interfaces.test-mojom.h"
+#include "testing/gtest/include/gtest/gtest.h"
+
+namespace mojo_bindings_test {
+namespace {
+
+using rust_bindings::test::mojom::BufferReverser;
+using rust_bindings::test::mojom::Calculator;
+using rust_bindings::test::mojom::CalculatorFactory;
+using rust_bindings::test::mojom::Echo;
+using rust_bindings::test::mojom::Point;
+using rust_bindings::test::mojom::PointPtr;
+
+class CppCalculator : public Calculator {
+ public:
+  // Calculator:
+  void Add(int32_t value, AddCallback callback) override {
+    total_ += value;
+    std::move(callback).Run(total_);
+  }
+  void Clear() override { total_ = 0; }
+  void Translate(PointPtr point,
+                 PointPtr delta,
+                 TranslateCallback callback) override {
+    auto result = Point::New(point->x + delta->x, point->y + delta->y);
+    std::string description = "(" + base::NumberToString(result->x) + ", " +
+                              base::NumberToString(result->y) + ")";
+    std::move(callback).Run(std::move(result), description);
+  }
+  void Subtract(int32_t value, SubtractCallback callback) override {
+    total_ -= value;
+    std::move(callback).Run(total_);
+  }
+
+  int32_t total() const { return total_; }
+
+ private:
+  int32_t total_ = 0;
+};
+
+// Binds the calculators that it's sent on its own pipe, and keeps the
+// remotes that it's sent after adding to them. Closed ones stay in the lists.
+class CppCalculatorFactory : public CalculatorFactory {
+ public:
+  // CalculatorFactory:
+  void CreateCalculator(
+      mojo::PendingAssociatedReceiver<Calculator> receiver) override {
+    calculators_.push_back(std::make_unique<CppCalculator>());
+    receivers_.push_back(std::make_unique<mojo::AssociatedReceiver<Calculator>>(
+        calculators_.back().get(), std::move(receiver)));
+    receivers_.back()->set_disconnect_handler(base::BindOnce(
+        &CppCalculatorFactory::OnDisconnect, base::Unretained(this)));
+  }
+  void UseCalculator(mojo::PendingAssociatedRemote<Calculator> calculator,
+                     int32_t value) override {
+    remotes_.emplace_back(std::move(calculator));
+    remotes_.back().set_disconnect_handler(base::BindOnce(
+        &CppCalculatorFactory::OnDisconnect, base::Unretained(this)));
+    remotes_.back()->Add(value, base::DoNothing());
+  }
+
+  size_t num_calculators() const { return calculators_.size(); }
+  int32_t calculator_total(size_t index) const {
+    return calculators_[index]->total();
+  }
+  void CloseCalculator(size_t index) { receivers_[index]->reset(); }
+  void CloseRemote(size_t index) { remotes_[index].reset(); }
+  int num_disconnects() const { return num_disconnects_; }
+
+ private:
+  void OnDisconnect() { ++num_disconnects_; }
+
+  std::vector<std::unique_ptr<CppCalculator>> calculators_;
+  std::vector<std::unique_ptr<mojo::AssociatedReceiver<Calculator>>>
+      receivers_;
+  std::vector<mojo::AssociatedRemote<Calculator>> remotes_;
+  int num_disconnects_ = 0;
+};
+
+// An Echo receiver for Rust sync calls, which must be on another thread so
+// that it can answer while the Rust remote blocks this one. If it has a
+// remote to forward to, it makes a sync call to that too, and adds 1.
+class CppEcho : public Echo {
+ public:
+  CppEcho(mojo::PendingReceiver<rust_bindings::test::mojom::Echo> receiver,
+          mojo::PendingRemote<rust_bindings::test::mojom::Echo> forward_to)
+      : receiver_(this, std::move(receiver)) {
+    if (forward_to) {
+      forward_to_.Bind(std::move(forward_to));
+    }
+  }
+
+  // Echo:
+  void Echo(int32_t value, EchoCallback callback) override {
+    if (!forward_to_) {
+      std::move(callback).Run(value);
+      return;
+    }
+    int32_t result = 0;
+    ASSERT_TRUE(forward_to_->Echo(value, &result));
+    std::move(callback).Run(result + 1);
+  }
+  void EchoNoInterrupt(int32_t value,
+                       EchoNoInterruptCallback callback) override {
+    std::move(callback).Run(value);
+  }
+
+ private:
+  mojo::Receiver<rust_bindings::test::mojom::Echo> receiver_;
+  mojo::Remote<rust_bindings::test::mojom::Echo> forward_to_;
+};
+
+class RustInteropTest : public testing::Test {
+ protected:
+  // Bind `remote` to a new Rust receiver.
+  rust::Box<RustCalculatorReceiver> BindRustReceiver(
+      mojo::Remote<Calculator>& remote) {
+    return bind_rust_calculator_receiver(
+        remote.BindNewPipeAndPassReceiver().PassPipe().release().value());
+  }
+
+  // Bind `receiver` to a new Rust remote.
+  rust::Box<RustCalculatorRemote> BindRustRemote(
+      mojo::Receiver<Calculator>& receiver) {
+    return bind_rust_calculator_remote(
+        receiver.BindNewPipeAndPassRemote().PassPipe().release().value());
+  }
+
+  base::test::SingleThreadTaskEnvironment task_environment_;
+};
+
+TEST_F(RustInteropTest, CallMethods) {
+  mojo::Remote<Calculator> remote;
+  auto receiver = BindRustReceiver(remote);
+
+  base::test::TestFuture<int32_t> total;
+  remote->Add(5, total.GetCallback());
+  receiver->dispatch_pending_messages();
+  EXPECT_EQ(5, total.Get());
+  EXPECT_EQ(5, receiver->total());
+}
+
+TEST_F(RustInteropTest, QueryVersion) {
+  mojo::Remote<Calculator> remote;
+  auto receiver = BindRustReceiver(remote);
+  EXPECT_EQ(0u, remote.version());
+
+  base::test::TestFuture<uint32_t> version;
+  remote.QueryVersion(version.GetCallback());
+  receiver->dispatch_pending_messages();
+  EXPECT_EQ(1u, version.Get());
+  EXPECT_EQ(1u, remote.version());
+}
+
+TEST_F(RustInteropTest, RequireVersion) {
+  mojo::Remote<Calculator> remote;
+  auto receiver = BindRustReceiver(remote);
+
+  remote.RequireVersion(1u);
+  base::test::TestFuture<int32_t> total;
+  remote->Subtract(2, total.GetCallback());
+  receiver->dispatch_pending_messages();
+  EXPECT_EQ(-2, total.Get());
+  EXPECT_TRUE(receiver->is_connected());
+
+  // The Rust receiver only implements version 1, so it closes the pipe.
+  base::RunLoop run_loop;
+  remote.set_disconnect_handler(run_loop.QuitClosure());
+  remote.RequireVersion(2u);
+  remote->Add(1, base::DoNothing());
+  receiver->dispatch_pending_messages();
+  EXPECT_FALSE(receiver->is_connected());
+  run_loop.Run();
+  EXPECT_FALSE(remote.is_connected());
+  EXPECT_EQ(-2, receiver->total());
+}
+
+TEST_F(RustInteropTest, FlushForTesting) {
+  mojo::Remote<Calculator> remote;
+  auto receiver = BindRustReceiver(remote);
+
+  // FlushForTesting() would block until the Rust receiver answers, so flush
+  // asynchronously instead.
+  base::test::TestFuture<void> flushed;
+  remote->Add(3, base::DoNothing());
+  remote.FlushAsyncForTesting(flushed.GetCallback());
+  receiver->dispatch_pending_messages();
+  EXPECT_TRUE(flushed.Wait());
+  EXPECT_EQ(3, receiver->total());
+}
+
+TEST_F(RustInteropTest, IdleTracking) {
+  mojo::Remote<Calculator> remote;
+  auto receiver = BindRustReceiver(remote);
+
+  base::RunLoop idle_loop;
+  remote.set_idle_handler(base::Milliseconds(5), idle_loop.QuitClosure());
+  base::test::TestFuture<int32_t> total;
+  remote->Add(2, total.GetCallback());
+  EXPECT_EQ(1u, remote.GetNumUnackedMessagesForTesting());
+
+  // The Rust receiver acknowledges the request, then says that it's idle.
+  receiver->dispatch_pending_messages();
+  receiver->notify_idle();
+  idle_loop.Run();
+  EXPECT_EQ(0u, remote.GetNumUnackedMessagesForTesting());
+  EXPECT_EQ(2, total.Get());
+}
+
+TEST_F(RustInteropTest, RustRemoteQueryVersion) {
+  CppCalculator impl;
+  mojo::Receiver<Calculator> receiver(&impl);
+  auto remote = BindRustRemote(receiver);
+  EXPECT_EQ(0u, remote->version());
+
+  remote->query_version();
+  base::RunLoop().RunUntilIdle();
+  remote->dispatch_pending_messages();
+  EXPECT_EQ(1u, remote->version());
+}
+
+TEST_F(RustInteropTest, RustRemoteRequireVersion) {
+  CppCalculator impl;
+  mojo::Receiver<Calculator> receiver(&impl);
+  auto remote = BindRustRemote(receiver);
+
+  remote->require_version(1u);
+  base::RunLoop().RunUntilIdle();
+  remote->dispatch_pending_messages();
+  EXPECT_TRUE(remote->is_connected());
+
+  // The C++ receiver only implements version 1, so it closes the pipe.
+  base::RunLoop run_loop;
+  receiver.set_disconnect_handler(run_loop.QuitClosure());
+  remote->require_version(2u);
+  run_loop.Run();
+  remote->dispatch_pending_messages();
+  EXPECT_FALSE(remote->is_connected());
+}
+
+TEST_F(RustInteropTest, CppReceiverFlushForTesting) {
+  CppCalculator impl;
+  mojo::Receiver<Calculator> receiver(&impl);
+  auto remote = BindRustRemote(receiver);
+
+  // The C++ receiver flushes the pipe by asking the Rust remote to answer,
+  // and waits in a nested run loop, so let the remote answer from there.
+  base::SingleThreadTaskRunner::GetCurrentDefault()->PostTask(
+      FROM_HERE,
+      base::BindLambdaForTesting([&] { remote->dispatch_pending_messages(); }));
+  receiver.FlushForTesting();
+  EXPECT_TRUE(remote->is_connected());
+}
+
+TEST_F(RustInteropTest, BigBuffer) {
+  mojo::Remote<BufferReverser> remote;
+  auto receiver = bind_rust_buffer_reverser_receiver(
+      remote.BindNewPipeAndPassReceiver().PassPipe().release().value());
+
+  // Big buffers are passed in shared memory in each direction, which the Rust
+  // side maps and creates with the same threshold as C++.
+  for (size_t size : {size_t{16}, mojo_base::BigBuffer::kMaxInlineBytes + 1}) {
+    std::vector<uint8_t> data(size);
+    for (size_t i = 0; i < size; ++i) {
+      data[i] = static_cast<uint8_t>(i % 251);
+    }
+    base::test::TestFuture<mojo_base::BigBuffer> reversed;
+    remote->Reverse(mojo_base::BigBuffer(data), reversed.GetCallback());
+    receiver->dispatch_pending_messages();
+
+    mojo_base::BigBuffer result = reversed.Take();
+    EXPECT_EQ(size > mojo_base::BigBuffer::kMaxInlineBytes
+                  ? mojo_base::BigBuffer::StorageType::kSharedMemory
+                  : mojo_base::BigBuffer::StorageType::kBytes,
+              result.storage_type());
+    std::ranges::reverse(data);
+    EXPECT_TRUE(std::ranges::equal(data, result.byte_span()));
+  }
+  EXPECT_TRUE(receiver->is_connected());
+}
+
+TEST_F(RustInteropTest, BadMessage) {
+  std::string bad_message_error;
+  mojo::SetDefaultProcessErrorHandler(base::BindLambdaForTesting(
+      [&](const std::string& error) { bad_message_error = error; }));
+  mojo::MessagePipe pipe;
+  auto receiver = bind_rust_calculator_receiver(pipe.handle1.release().value());
+
+  // Calculator has no method 7, so the Rust receiver reports the request as a
+  // bad message and closes the pipe.
+  mojo::Message message(/*name=*/7, /*flags=*/0, /*payload_size=*/0,
+                        /*payload_interface_id_count=*/0, /*handles=*/nullptr);
+  ASSERT_EQ(MOJO_RESULT_OK,
+            mojo::WriteMessageNew(pipe.handle0.get(), message.TakeMojoMessage(),
+                                  MOJO_WRITE_MESSAGE_FLAG_NONE));
+  receiver->dispatch_pending_messages();
+  EXPECT_FALSE(receiver->is_connected());
+  EXPECT_EQ(
+      "In a request to rust_bindings.test.mojom.Calculator: Unknown method 7",
+      bad_message_error);
+  mojo::SetDefaultProcessErrorHandler(base::NullCallback());
+}
+
+TEST_F(RustInteropTest, DisconnectReasons) {
+  // A Rust receiver can tell a C++ remote why it closed the pipe.
+  mojo::Remote<Calculator> remote;
+  auto receiver = BindRustReceiver(remote);
+  base::test::TestFuture<uint32_t, const std::string&> reason;
+  remote.set_disconnect_with_reason_handler(reason.GetCallback());
+  receiver->reset_with_reason(42u, "Go away");
+  EXPECT_EQ(42u, reason.Get<0>());
+  EXPECT_EQ("Go away", reason.Get<1>());
+
+  // And a C++ receiver can tell a Rust remote.
+  CppCalculator impl;
+  mojo::Receiver<Calculator> cpp_receiver(&impl);
+  auto rust_remote = BindRustRemote(cpp_receiver);
+  cpp_receiver.ResetWithReason(7u, "Done");
+  base::RunLoop().RunUntilIdle();
+  rust_remote->dispatch_pending_messages();
+  EXPECT_FALSE(rust_remote->is_connected());
+  EXPECT_EQ(7u, rust_remote->disconnect_custom_reason());
+  EXPECT_EQ("Done", std::string(rust_remote->disconnect_description()));
+}
+
+TEST_F(RustInteropTest, BinderMap) {
+  // A C++ client can bind interfaces by name through a Rust broker.
+  mojo::Remote<mojo_base::mojom::Binder> binder;
+  auto broker = bind_rust_broker(
+      binder.BindNewPipeAndPassReceiver().PassPipe().release().value());
+  mojo::Remote<Calculator> calculator;
+  binder->Bind(
+      mojo::GenericPendingReceiver(calculator.BindNewPipeAndPassReceiver()));
+  base::test::TestFuture<int32_t> total;
+  calculator->Add(5, total.GetCallback());
+  broker->dispatch_pending_messages();
+  EXPECT_EQ(1u, broker->num_calculators());
+  EXPECT_EQ(5, total.Get());
+
+  // The broker rejects interfaces that it has no binder for.
+  std::string bad_message_error;
+  mojo::SetDefaultProcessErrorHandler(base::BindLambdaForTesting(
+      [&](const std::string& error) { bad_message_error = error; }));
+  mojo::Remote<BufferReverser> reverser;
+  binder->Bind(
+      mojo::GenericPendingReceiver(reverser.BindNewPipeAndPassReceiver()));
+  broker->dispatch_pending_messages();
+  EXPECT_FALSE(broker->is_connected());
+  EXPECT_EQ(
+      "In a request to mojo_base.mojom.Binder: No binder for "
+      "rust_bindings.test.mojom.BufferReverser",
+      bad_message_error);
+  mojo::SetDefaultProcessErrorHandler(base::NullCallback());
+}
+
+TEST_F(RustInteropTest, AssociatedInterfaces) {
+  mojo::Remote<CalculatorFactory> factory;
+  auto rust_factory = bind_rust_calculator_factory_receiver(
+      factory.BindNewPipeAndPassReceiver().PassPipe().release().value());
+
+  // A C++ remote can send a receiver to Rust, and call it.
+  mojo::AssociatedRemote<Calculator> calculator;
+  factory->CreateCalculator(calculator.BindNewEndpointAndPassReceiver());
+  base::test::TestFuture<int32_t> total;
+  calculator->Add(5, total.GetCallback());
+  rust_factory->dispatch_pending_messages();
+  ASSERT_EQ(1u, rust_factory->num_calculators());
+  EXPECT_EQ(5, rust_factory->calculator_total(0));
+  EXPECT_EQ(5, total.Get());
+
+  // And it can send a remote to Rust, which calls it back.
+  CppCalculator impl;
+  mojo::AssociatedReceiver<Calculator> receiver(&impl);
+  factory->UseCalculator(receiver.BindNewEndpointAndPassRemote(), 3);
+  rust_factory->dispatch_pending_messages();
+  base::RunLoop().RunUntilIdle();
+  EXPECT_EQ(3, impl.total());
+
+  // Closing the C++ ends disconnects the Rust ends, but not the factory.
+  calculator.reset();
+  receiver.reset();
+  rust_factory->dispatch_pending_messages();
+  EXPECT_FALSE(rust_factory->calculator_is_connected(0));
+  EXPECT_FALSE(rust_factory->remote_is_connected(0));
+  EXPECT_TRUE(rust_factory->is_connected());
+
+  // And closing the Rust ends disconnects the C++ ends.
+  factory->CreateCalculator(calculator.BindNewEndpointAndPassReceiver());
+  factory->UseCalculator(receiver.BindNewEndpointAndPassRemote(), 1);
+  rust_factory->dispatch_pending_messages();
+  base::test::TestFuture<void> calculator_disconnected;
+  calculator.set_disconnect_handler(calculator_disconnected.GetCallback());
+  base::test::TestFuture<void> receiver_disconnected;
+  receiver.set_disconnect_handler(receiver_disconnected.GetCallback());
+  rust_factory->close_calculator(1);
+  rust_factory->close_remote(1);
+  EXPECT_TRUE(calculator_disconnected.Wait());
+  EXPECT_TRUE(receiver_disconnected.Wait());
+  EXPECT_EQ(4, impl.total());
+  EXPECT_TRUE(factory.is_connected());
+}
+
+TEST_F(RustInteropTest, RustAssociatedInterfaces) {
+  CppCalculatorFactory impl;
+  mojo::Receiver<CalculatorFactory> receiver(&impl);
+  auto factory = bind_rust_calculator_factory_remote(
+      receiver.BindNewPipeAndPassRemote().PassPipe().release().value());
+
+  // A Rust remote can send a receiver to C++, and call it.
+  factory->create_calculator();
+  factory->add(0, 5);
+  base::RunLoop().RunUntilIdle();
+  ASSERT_EQ(1u, impl.num_calculators());
+  EXPECT_EQ(5, impl.calculator_total(0));
+
+  // And it can send a remote to C++, which calls it back.
+  factory->use_calculator(3);
+  base::RunLoop().RunUntilIdle();
+  factory->dispatch_pending_messages();
+  EXPECT_EQ(3, factory->receiver_total(0));
+
+  // Closing the Rust ends disconnects the C++ ends, but not the factory.
+  factory->close_calculator(0);
+  factory->close_receiver(0);
+  base::RunLoop().RunUntilIdle();
+  EXPECT_EQ(2, impl.num_disconnects());
+  EXPECT_TRUE(receiver.is_bound());
+
+  // And closing the C++ ends disconnects the Rust ends.
+  factory->create_calculator();
+  factory->use_calculator(1);
+  base::RunLoop().RunUntilIdle();
+  impl.CloseCalculator(1);
+  impl.CloseRemote(1);
+  factory->dispatch_pending_messages();
+  EXPECT_FALSE(factory->calculator_is_connected(1));
+  EXPECT_FALSE(factory->receiver_is_connected(1));
+  EXPECT_EQ(1, factory->receiver_total(1));
+  EXPECT_TRUE(factory->is_connected());
+}
+
+TEST_F(RustInteropTest, RustSyncCalls) {
+  base::Thread echo_thread("Echo");
+  ASSERT_TRUE(echo_thread.Start());
+
+  // The C++ receiver forwards each Echo to a Rust receiver on this thread,
+  // which answers while the Rust remote waits.
+  mojo::PendingRemote<Echo> forward_to;
+  auto rust_receiver = bind_rust_echo_receiver(
+      forward_to.InitWithNewPipeAndPassReceiver().PassPipe().release().value());
+  mojo::PendingRemote<Echo> pending_remote;
+  base::SequenceBound<CppEcho> cpp_receiver(
+      echo_thread.task_runner(),
+      pending_remote.InitWithNewPipeAndPassReceiver(), std::move(forward_to));
+  auto remote =
+      bind_rust_echo_remote(pending_remote.PassPipe().release().value());
+
+  EXPECT_EQ(6, remote->echo(5));
+  EXPECT_EQ(1, rust_receiver->num_echoes());
+
+  // A [NoInterrupt] call doesn't need to dispatch anything while it waits.
+  EXPECT_EQ(7, remote->echo_no_interrupt(7));
+  EXPECT_EQ(1, rust_receiver->num_echoes());
+}
+
+TEST_F(RustInteropTest, SyncCallRestrictionsDeathTest) {
+  mojo::PendingRemote<Echo> pending_remote;
+  auto receiver = pending_remote.InitWithNewPipeAndPassReceiver();
+  auto remote =
+      bind_rust_echo_remote(pending_remote.PassPipe().release().value());
+
+  // Once sync calls are disallowed, a Rust sync call panics before it sends
+  // anything. The death test runs in a child process, so this doesn't
+  // disallow sync calls for the other tests.
+  EXPECT_DEATH_IF_SUPPORTED(
+      {
+        disallow_sync_call();
+        remote->echo(1);
+      },
+      "Mojo sync calls are not allowed");
+}
+
+}  // namespace
+}  // namespace mojo_bindings_test
@@ -0,0 +1,29 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! The Rust typemap for `Size`, for the tests in test.rs. It's compiled into
+//! the bindings_test_interfaces crate; see `rust_typemaps` in BUILD.gn.
+
+use mojom_parser::{MojomResult, MojomTraits};
+
+/// A size which can't be negative, unlike the mojom `Size` it's mapped onto.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MojomTraits<crate::Size> for Dimensions {
+    fn to_mojom(self) -> crate::Size {
+        // Sizes larger than i32::MAX can't be sent.
+        crate::Size {
+            width: self.width.try_into().unwrap_or(i32::MAX),
+            height: self.height.try_into().unwrap_or(i32::MAX),
+        }
+    }
+
+    fn from_mojom(mojom: crate::Size) -> MojomResult<Self> {
+        Ok(Dimensions { width: mojom.width.try_into()?, height: mojom.height.try_into()? })
+    }
+}
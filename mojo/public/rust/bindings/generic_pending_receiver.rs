@@ -0,0 +1,57 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `GenericPendingReceiver`, a pending receiver for an interface that
+//! is only known by name until something binds it. This corresponds to
+//! mojo::GenericPendingReceiver in the C++ bindings, and
+//! `mojo_base.mojom.GenericPendingReceiver` is typemapped onto it.
+
+use crate::interface::Interface;
+use crate::receiver::PendingReceiver;
+use system::message_pipe::MessagePipeHandle;
+
+/// The message pipe for a receiver of the interface named `interface_name`,
+/// e.g. one that a client sent to an interface broker to be bound.
+///
+/// Unlike `PendingReceiver`, this can be sent to other threads.
+#[derive(Debug)]
+pub struct GenericPendingReceiver {
+    interface_name: String,
+    pipe: MessagePipeHandle,
+}
+
+impl GenericPendingReceiver {
+    /// Wrap a message pipe whose other end is a remote for the interface named
+    /// `interface_name`, e.g. one that was passed from C++.
+    pub fn new(interface_name: impl Into<String>, pipe: MessagePipeHandle) -> Self {
+        GenericPendingReceiver { interface_name: interface_name.into(), pipe }
+    }
+
+    /// The fully qualified mojom name of the interface, e.g.
+    /// "mojo_base.mojom.Binder".
+    pub fn interface_name(&self) -> &str {
+        &self.interface_name
+    }
+
+    /// Return the typed receiver, if this is a receiver for `I`. Otherwise,
+    /// give this back so the caller can try another interface.
+    pub fn take<I: ?Sized + Interface>(self) -> Result<PendingReceiver<I>, Self> {
+        if self.interface_name == I::NAME {
+            Ok(PendingReceiver::new(self.pipe))
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Give up the name and the message pipe, e.g. to pass them to C++.
+    pub fn into_parts(self) -> (String, MessagePipeHandle) {
+        (self.interface_name, self.pipe)
+    }
+}
+
+impl<I: ?Sized + Interface> From<PendingReceiver<I>> for GenericPendingReceiver {
+    fn from(receiver: PendingReceiver<I>) -> Self {
+        GenericPendingReceiver::new(I::NAME, receiver.into_pipe())
+    }
+}
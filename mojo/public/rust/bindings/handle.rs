@@ -0,0 +1,199 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Passing handles in messages, e.g. for the mojom types `handle`,
+//! `handle<shared_buffer>` and `handle<platform>`. A message's handles are sent alongside its bytes,
+//! which refer to each one by its index in the list.
+//!
+//! The handle types are defined by //mojo/public/rust:mojo_rust_system_api, so
+//! Rust's orphan rule doesn't let us implement `Mojom` for them here. Instead,
+//! they're typemapped onto `EncodedHandle`, which the generated code does with
+//! `#[mojom(typemap = mojo_bindings::EncodedHandle)]`. Pending remotes and
+//! receivers are passed as their message pipes, so they're encoded here too.
+//!
+//! Like associated endpoints (see associated.rs), handles are added to and
+//! taken from the message being encoded or decoded on this thread, like
+//! mojo::internal::SerializationContext in the C++ bindings.
+//!
+//! FOR_RELEASE: Data pipes aren't supported yet.
+
+use crate::interface::Interface;
+use crate::message::Message;
+use crate::receiver::PendingReceiver;
+use crate::remote::PendingRemote;
+use mojom_parser::{
+    prepare_mojom_with_handles, Mojom, MojomResult, MojomTraits, MojomType, MojomValue,
+    PreparedStruct,
+};
+use system::handle::UntypedHandle;
+use system::message_pipe::MessagePipeHandle;
+use system::platform_handle::PlatformHandle;
+use system::shared_buffer::SharedBufferHandle;
+
+use anyhow::{bail, Context};
+use std::cell::RefCell;
+
+/// A handle as it's encoded, i.e. its index in the message's handles. Handle
+/// types are typemapped onto this.
+#[derive(Debug)]
+pub struct EncodedHandle {
+    index: u32,
+}
+
+impl Mojom for EncodedHandle {
+    fn mojom_type() -> MojomType {
+        MojomType::Handle
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        MojomValue::Handle { index: self.index }
+    }
+
+    fn from_mojom_value(value: MojomValue) -> MojomResult<Self> {
+        match value {
+            MojomValue::Handle { index } => Ok(EncodedHandle { index }),
+            _ => bail!("Expected a handle, but got {:?}", value),
+        }
+    }
+}
+
+thread_local! {
+    /// The handles in each message being encoded, innermost last.
+    static OUTGOING_HANDLES: RefCell<Vec<Vec<UntypedHandle>>> = const { RefCell::new(vec![]) };
+    /// The handles in each message being decoded, innermost last. Each is
+    /// taken when it's decoded.
+    static INCOMING_HANDLES: RefCell<Vec<Vec<Option<UntypedHandle>>>> =
+        const { RefCell::new(vec![]) };
+}
+
+/// Prepare `params`, which must be a struct, to be encoded as the payload of
+/// a message, and return it along with the handles it passes, in the order
+/// the payload refers to them.
+pub(crate) fn prepare_with_handles<P: Mojom>(
+    params: P,
+) -> MojomResult<(PreparedStruct, Vec<UntypedHandle>)> {
+    OUTGOING_HANDLES.with_borrow_mut(|stack| stack.push(vec![]));
+    let result = prepare_mojom_with_handles(params);
+    let handles = OUTGOING_HANDLES.with_borrow_mut(|stack| stack.pop()).unwrap_or_default();
+    let (payload, handle_order) = result?;
+    let mut handles: Vec<Option<UntypedHandle>> = handles.into_iter().map(Some).collect();
+    let handles = handle_order
+        .into_iter()
+        .map(|index| handles[index as usize].take().expect("Each handle is encoded exactly once"))
+        .collect();
+    Ok((payload, handles))
+}
+
+/// Decode the payload of `message` with `decode`, which may take the handles
+/// that `message` passes. Any that it doesn't take are closed.
+pub(crate) fn decode_incoming_handles<T>(
+    message: &mut Message,
+    decode: impl FnOnce(&Message) -> T,
+) -> T {
+    let handles = std::mem::take(&mut message.handles).into_iter().map(Some).collect();
+    INCOMING_HANDLES.with_borrow_mut(|stack| stack.push(handles));
+    let result = decode(message);
+    INCOMING_HANDLES.with_borrow_mut(|stack| stack.pop());
+    result
+}
+
+fn add_outgoing_handle(handle: UntypedHandle) -> u32 {
+    OUTGOING_HANDLES.with_borrow_mut(|stack| {
+        let handles = stack.last_mut().expect("Handles can only be encoded in a message");
+        handles.push(handle);
+        u32::try_from(handles.len() - 1).expect("Too many handles")
+    })
+}
+
+fn take_incoming_handle(index: u32) -> MojomResult<UntypedHandle> {
+    INCOMING_HANDLES.with_borrow_mut(|stack| {
+        let Some(handles) = stack.last_mut() else {
+            bail!("Handles can only be decoded from a message")
+        };
+        let slot = usize::try_from(index)
+            .ok()
+            .and_then(|index| handles.get_mut(index))
+            .with_context(|| format!("The message has no handle with index {}", index))?;
+        slot.take().with_context(|| format!("The handle with index {} was used twice", index))
+    })
+}
+
+impl MojomTraits<EncodedHandle> for UntypedHandle {
+    fn to_mojom(self) -> EncodedHandle {
+        EncodedHandle { index: add_outgoing_handle(self) }
+    }
+
+    fn from_mojom(mojom: EncodedHandle) -> MojomResult<Self> {
+        take_incoming_handle(mojom.index)
+    }
+}
+
+impl MojomTraits<EncodedHandle> for SharedBufferHandle {
+    fn to_mojom(self) -> EncodedHandle {
+        UntypedHandle::from(self).to_mojom()
+    }
+
+    fn from_mojom(mojom: EncodedHandle) -> MojomResult<Self> {
+        UntypedHandle::from_mojom(mojom).map(SharedBufferHandle::from)
+    }
+}
+
+impl MojomTraits<EncodedHandle> for MessagePipeHandle {
+    fn to_mojom(self) -> EncodedHandle {
+        UntypedHandle::from(self).to_mojom()
+    }
+
+    fn from_mojom(mojom: EncodedHandle) -> MojomResult<Self> {
+        UntypedHandle::from_mojom(mojom).map(MessagePipeHandle::from)
+    }
+}
+
+impl MojomTraits<EncodedHandle> for PlatformHandle {
+    fn to_mojom(self) -> EncodedHandle {
+        UntypedHandle::from(self).to_mojom()
+    }
+
+    fn from_mojom(mojom: EncodedHandle) -> MojomResult<Self> {
+        UntypedHandle::from_mojom(mojom).map(PlatformHandle::from)
+    }
+}
+
+impl<I: ?Sized + Interface> Mojom for PendingRemote<I> {
+    fn mojom_type() -> MojomType {
+        MojomType::PendingRemote
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        let version = self.version();
+        MojomValue::PendingRemote {
+            index: add_outgoing_handle(UntypedHandle::from(self.into_pipe())),
+            version,
+        }
+    }
+
+    fn from_mojom_value(value: MojomValue) -> MojomResult<Self> {
+        let MojomValue::PendingRemote { index, version } = value else {
+            bail!("Expected a pending_remote<{}>, but got {:?}", I::NAME, value)
+        };
+        let pipe = MessagePipeHandle::from(take_incoming_handle(index)?);
+        Ok(PendingRemote::new(pipe, version))
+    }
+}
+
+impl<I: ?Sized + Interface> Mojom for PendingReceiver<I> {
+    fn mojom_type() -> MojomType {
+        MojomType::Handle
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        MojomValue::Handle { index: add_outgoing_handle(UntypedHandle::from(self.into_pipe())) }
+    }
+
+    fn from_mojom_value(value: MojomValue) -> MojomResult<Self> {
+        let MojomValue::Handle { index } = value else {
+            bail!("Expected a pending_receiver<{}>, but got {:?}", I::NAME, value)
+        };
+        Ok(PendingReceiver::new(MessagePipeHandle::from(take_incoming_handle(index)?)))
+    }
+}
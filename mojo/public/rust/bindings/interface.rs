@@ -0,0 +1,104 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines the `Interface` trait, which connects the trait generated for a
+//! mojom interface to the messages that call its methods.
+
+use crate::message::Message;
+use crate::router::WeakEndpoint;
+use mojom_parser::{
+    MessageHeader, Mojom, MojomResult, MESSAGE_FLAG_IS_RESPONSE, MESSAGE_FLAG_IS_SYNC,
+};
+
+use anyhow::{bail, Context};
+
+/// A mojom interface. This is implemented by generated code for `dyn Foo`,
+/// where `Foo` is the trait generated for the interface.
+pub trait Interface: 'static {
+    /// The fully qualified mojom name of the interface.
+    const NAME: &'static str;
+    /// The interface's version, which is the largest `[MinVersion]` of any of
+    /// its methods.
+    const VERSION: u32;
+
+    /// Decode a request and call the corresponding method of `implementation`.
+    /// Fails if the request isn't a valid call of one of the interface's
+    /// methods.
+    fn dispatch_request(implementation: &mut Self, request: Request) -> MojomResult<()>;
+}
+
+/// A request that a receiver got, to call one of its interface's methods.
+pub struct Request {
+    message: Message,
+    /// The endpoint that the request was read from.
+    endpoint: WeakEndpoint,
+    responder: Option<Responder>,
+}
+
+impl Request {
+    pub(crate) fn new(message: Message, endpoint: WeakEndpoint) -> Self {
+        let responder = message.header.expects_response().then(|| Responder {
+            endpoint: endpoint.clone(),
+            header: MessageHeader {
+                interface_id: message.header.interface_id,
+                name: message.header.name,
+                // Responses to sync requests are sync too.
+                flags: MESSAGE_FLAG_IS_RESPONSE | (message.header.flags & MESSAGE_FLAG_IS_SYNC),
+                trace_nonce: message.header.trace_nonce,
+                request_id: message.header.request_id,
+            },
+        });
+        Request { message, endpoint, responder }
+    }
+
+    /// The ordinal of the method that the request calls.
+    pub fn name(&self) -> u32 {
+        self.message.header.name
+    }
+
+    /// Decode the parameters of a method which has no response.
+    pub fn decode<P: Mojom>(mut self) -> MojomResult<P> {
+        if self.responder.is_some() {
+            bail!("Method {} has no response, but the caller expects one", self.name())
+        }
+        self.decode_params()
+    }
+
+    /// Decode the parameters of a method which has a response, and return a
+    /// `Responder` to send it with.
+    pub fn decode_with_responder<P: Mojom>(mut self) -> MojomResult<(P, Responder)> {
+        let Some(responder) = self.responder.take() else {
+            bail!("Method {} has a response, but the caller doesn't expect one", self.name())
+        };
+        Ok((self.decode_params()?, responder))
+    }
+
+    /// Fail, because the request doesn't call any method that we know about.
+    pub fn reject_unknown_method(self) -> MojomResult<()> {
+        bail!("Unknown method {}", self.name())
+    }
+
+    fn decode_params<P: Mojom>(&mut self) -> MojomResult<P> {
+        self.endpoint
+            .decode(&mut self.message)
+            .with_context(|| format!("In the parameters of method {}", self.name()))
+    }
+}
+
+/// Sends the response to a request. If the receiver that got the request has
+/// been closed by the time the response is sent, it's dropped.
+pub struct Responder {
+    endpoint: WeakEndpoint,
+    header: MessageHeader,
+}
+
+impl Responder {
+    /// Send `params` as the response.
+    ///
+    /// Panics if `params` can't be encoded, which means that its `Mojom`
+    /// implementation is wrong.
+    pub fn respond<R: Mojom>(self, params: R) {
+        self.endpoint.send(self.header, params).expect("Response parameters must be encodable");
+    }
+}
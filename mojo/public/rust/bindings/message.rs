@@ -0,0 +1,127 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `Message`, a mojom message whose header has been parsed but whose
+//! payload hasn't.
+
+use crate::handle::{decode_incoming_handles, prepare_with_handles};
+use mojom_parser::{
+    decode_mojom, deparse_message_with, message_serialized_size, parse_message_parts,
+    MessageHeader, Mojom, MojomResult, PreparedStruct,
+};
+use system::handle::UntypedHandle;
+use system::message_pipe::MessageHandle;
+
+/// A mojom message, split into its header and its encoded payload.
+#[derive(Debug)]
+pub struct Message {
+    pub header: MessageHeader,
+    payload: Payload,
+    /// The IDs of the associated interfaces that the message passes, which
+    /// the payload refers to by their index in this list.
+    pub interface_ids: Vec<u32>,
+    /// The handles that the message passes, which the payload refers to by
+    /// their index in this list. They're sent alongside the message's bytes.
+    pub handles: Vec<UntypedHandle>,
+    /// The message object that the message was read from, if it was read
+    /// from a pipe, to report it with if it's bad.
+    pub(crate) source: Option<MessageHandle>,
+}
+
+/// A message's payload. The payloads of messages that we send aren't encoded
+/// until they're written, so that they're encoded straight into the buffer
+/// of the message on the pipe.
+#[derive(Debug)]
+enum Payload {
+    Encoded(Vec<u8>),
+    Prepared(PreparedStruct),
+}
+
+impl Payload {
+    fn len(&self) -> usize {
+        match self {
+            Payload::Encoded(bytes) => bytes.len(),
+            Payload::Prepared(prepared) => prepared.serialized_size(),
+        }
+    }
+}
+
+impl Message {
+    /// Create a message whose payload is `params`, which must be a struct.
+    ///
+    /// Panics if `params` contains associated endpoints, which can only be
+    /// sent by remotes and receivers.
+    pub fn new<P: Mojom>(header: MessageHeader, params: P) -> MojomResult<Self> {
+        let (payload, handles) = prepare_with_handles(params)?;
+        Ok(Message {
+            header,
+            payload: Payload::Prepared(payload),
+            interface_ids: vec![],
+            handles,
+            source: None,
+        })
+    }
+
+    /// Parse a message that we read from a message pipe, along with the
+    /// handles that it carried.
+    pub fn from_bytes(bytes: &[u8], handles: Vec<UntypedHandle>) -> MojomResult<Self> {
+        let (header, payload, interface_ids) = parse_message_parts(bytes)?;
+        Ok(Message {
+            header,
+            payload: Payload::Encoded(payload.to_vec()),
+            interface_ids,
+            handles,
+            source: None,
+        })
+    }
+
+    /// Encode the message, ready to be written to a message pipe.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.serialized_size()];
+        self.write_to(&mut bytes);
+        bytes
+    }
+
+    /// The number of bytes that the message is encoded as.
+    pub fn serialized_size(&self) -> usize {
+        message_serialized_size(self.payload.len(), &self.interface_ids)
+            .expect("Messages must have a valid number of interface IDs")
+    }
+
+    /// Encode the message into `buffer`, which must be exactly
+    /// `serialized_size()` bytes, e.g. the buffer of a new message on a
+    /// message pipe.
+    ///
+    /// Panics if the payload doesn't match its type, which the `Mojom`
+    /// implementation of the params that it was created from must ensure.
+    pub fn write_to(&self, buffer: &mut [u8]) {
+        let written = deparse_message_with(
+            buffer,
+            &self.header,
+            self.payload.len(),
+            &self.interface_ids,
+            |payload_buffer| match &self.payload {
+                Payload::Encoded(bytes) => {
+                    payload_buffer.copy_from_slice(bytes);
+                    Ok(())
+                }
+                Payload::Prepared(prepared) => prepared.deparse_into(payload_buffer),
+            },
+        )
+        .unwrap_or_else(|error| panic!("Failed to encode a message: {:#}", error));
+        assert_eq!(written, buffer.len(), "The buffer must be serialized_size() bytes");
+    }
+
+    /// Decode the payload as a `P`, which must be a struct. This takes the
+    /// message's handles, and closes any that `P` doesn't have.
+    ///
+    /// Fails if the payload contains associated endpoints, which can only be
+    /// decoded by remotes and receivers.
+    pub fn decode_payload<P: Mojom>(&mut self) -> MojomResult<P> {
+        decode_incoming_handles(self, |message| match &message.payload {
+            Payload::Encoded(bytes) => decode_mojom(bytes),
+            Payload::Prepared(prepared) => decode_mojom(&prepared.to_bytes()?),
+        })
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! The control messages which the routers at either end of a message pipe
+//! send each other, rather than to any particular interface. This corresponds
+//! to mojo::PipeControlMessageHandler and mojo::PipeControlMessageProxy in the
+//! C++ bindings.
+//!
+//! The types here are written out by hand from
+//! //mojo/public/interfaces/bindings/pipe_control_messages.mojom, like those
+//! in control_message.rs.
+
+use crate::connector::Connector;
+use crate::message::Message;
+use crate::router::INVALID_INTERFACE_ID;
+use mojom_parser::{MessageHeader, Mojom};
+
+/// The name of every pipe control message.
+pub(crate) const RUN_OR_CLOSE_PIPE_MESSAGE_ID: u32 = 0xFFFFFFFE;
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct RunOrClosePipeMessageParams {
+    pub input: RunOrClosePipeInput,
+}
+
+// FOR_RELEASE: The other fields, PauseUntilFlushCompletes and FlushAsync, pass
+// message pipes, which mojom_parser doesn't support yet. A router which gets
+// one of them closes the pipe, as if it were invalid.
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) enum RunOrClosePipeInput {
+    PeerAssociatedEndpointClosedEvent(PeerAssociatedEndpointClosedEvent),
+}
+
+/// Why the peer closed its end of an interface, which it may say when it does,
+/// like mojo::DisconnectReason in the C++ bindings. What the values mean is up
+/// to the interface.
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub struct DisconnectReason {
+    pub custom_reason: u32,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+pub(crate) struct PeerAssociatedEndpointClosedEvent {
+    pub id: u32,
+    #[mojom(nullable)]
+    pub disconnect_reason: Option<DisconnectReason>,
+}
+
+/// Whether a message is for the router, rather than for one of the
+/// interfaces sharing the pipe.
+pub(crate) fn is_pipe_control_message(header: &MessageHeader) -> bool {
+    header.interface_id == INVALID_INTERFACE_ID
+}
+
+/// Tell the peer that we closed our end of the interface `id`, and why, if we
+/// said.
+pub(crate) fn send_peer_endpoint_closed(
+    connector: &Connector,
+    id: u32,
+    disconnect_reason: Option<DisconnectReason>,
+) {
+    let header = MessageHeader {
+        interface_id: INVALID_INTERFACE_ID,
+        name: RUN_OR_CLOSE_PIPE_MESSAGE_ID,
+        ..Default::default()
+    };
+    let input =
+        RunOrClosePipeInput::PeerAssociatedEndpointClosedEvent(PeerAssociatedEndpointClosedEvent {
+            id,
+            disconnect_reason,
+        });
+    let message = Message::new(header, RunOrClosePipeMessageParams { input })
+        .expect("Pipe control messages must be encodable");
+    connector.send(message);
+}
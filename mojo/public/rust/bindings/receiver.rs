@@ -0,0 +1,290 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `Receiver`, which dispatches the requests that arrive on a message
+//! pipe to an implementation of an interface, and `PendingReceiver`, which is
+//! a receiver that hasn't been bound to an implementation yet.
+
+use crate::associated::{AssociatedGroup, PendingAssociatedReceiver};
+use crate::bad_message::{dispatch_reporting_bad_messages, notify_bad_message};
+use crate::connector::ReadResult;
+use crate::control_message::*;
+use crate::interface::{Interface, Request};
+use crate::message::Message;
+use crate::pipe_control_message::DisconnectReason;
+use crate::router::{Endpoint, Router};
+use crate::sync_handle_registry::{register_receiver, SyncReceiver};
+use mojom_parser::MojomResult;
+use system::message_pipe::MessagePipeHandle;
+
+use anyhow::{bail, Context};
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// The message pipe for a `Receiver<I>`, which can be passed to wherever the
+/// implementation of `I` lives.
+pub struct PendingReceiver<I: ?Sized + Interface> {
+    pipe: MessagePipeHandle,
+    _interface: PhantomData<Box<I>>,
+}
+
+impl<I: ?Sized + Interface> PendingReceiver<I> {
+    /// Wrap a message pipe whose other end is a remote for `I`, e.g. one that
+    /// was passed from C++.
+    pub fn new(pipe: MessagePipeHandle) -> Self {
+        PendingReceiver { pipe, _interface: PhantomData }
+    }
+
+    /// Give up the message pipe, e.g. to pass it to C++.
+    pub fn into_pipe(self) -> MessagePipeHandle {
+        self.pipe
+    }
+}
+
+impl<I: ?Sized + Interface> fmt::Debug for PendingReceiver<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingReceiver")
+            .field("interface", &I::NAME)
+            .field("pipe", &self.pipe)
+            .finish()
+    }
+}
+
+/// Receives requests for `I` from a message pipe, and dispatches them to an
+/// implementation of it.
+///
+/// A receiver for an associated interface is bound with `new_associated`, and
+/// otherwise works the same way, except that it shares its pipe with other
+/// interfaces.
+///
+/// While a sync call on the same thread waits for its response, the
+/// receiver's sync requests are dispatched too (see
+/// sync_handle_registry.rs), so its state is shared with the registry.
+pub struct Receiver<I: ?Sized + Interface> {
+    state: Rc<RefCell<ReceiverState<I>>>,
+}
+
+/// Wraps the dispatch of each request, e.g. to say which receiver of a
+/// `ReceiverSet` is dispatching it. It must call the function it's passed.
+type DispatchScope = Box<dyn Fn(&mut dyn FnMut())>;
+
+struct ReceiverState<I: ?Sized + Interface> {
+    implementation: Box<I>,
+    endpoint: Endpoint,
+    control: ControlMessageHandler,
+    disconnect_handler: Option<Box<dyn FnOnce(Option<DisconnectReason>)>>,
+    dispatch_scope: Option<DispatchScope>,
+}
+
+impl<I: ?Sized + Interface> Receiver<I> {
+    pub fn new(implementation: Box<I>, pending: PendingReceiver<I>) -> Self {
+        Self::with_endpoint(implementation, Endpoint::new_primary(pending.pipe, false))
+    }
+
+    /// Bind a receiver for an associated interface, which was either passed
+    /// to us or created by an `AssociatedGroup`.
+    ///
+    /// Panics if `pending` is the end of a new associated interface which is
+    /// meant to be sent to the peer.
+    pub fn new_associated(implementation: Box<I>, pending: PendingAssociatedReceiver<I>) -> Self {
+        Self::with_endpoint(implementation, pending.into_endpoint())
+    }
+
+    fn with_endpoint(implementation: Box<I>, endpoint: Endpoint) -> Self {
+        let state = Rc::new(RefCell::new(ReceiverState {
+            implementation,
+            endpoint,
+            control: ControlMessageHandler::default(),
+            disconnect_handler: None,
+            dispatch_scope: None,
+        }));
+        let sync_receiver: Rc<RefCell<dyn SyncReceiver>> = state.clone();
+        register_receiver(Rc::downgrade(&sync_receiver));
+        Receiver { state }
+    }
+
+    /// Panics if the implementation is already borrowed mutably, i.e. it's
+    /// dispatching a request.
+    pub fn implementation(&self) -> Ref<'_, I> {
+        Ref::map(self.state.borrow(), |state| &*state.implementation)
+    }
+
+    /// Panics if the implementation is already borrowed, e.g. it's
+    /// dispatching a request.
+    pub fn implementation_mut(&mut self) -> RefMut<'_, I> {
+        RefMut::map(self.state.borrow_mut(), |state| &mut *state.implementation)
+    }
+
+    /// Whether the message pipe is still open. See `Remote::is_connected`.
+    pub fn is_connected(&self) -> bool {
+        self.state.borrow().endpoint.is_connected()
+    }
+
+    /// Return a group which creates associated interfaces on the receiver's
+    /// pipe, to be passed to the remote. See `Remote::associated_group`.
+    pub fn associated_group(&self) -> AssociatedGroup {
+        AssociatedGroup::new(self.state.borrow().endpoint.router().cloned())
+    }
+
+    /// Set a function to call once the receiver is disconnected, either
+    /// because the remote was closed or because it sent us an invalid message.
+    pub fn set_disconnect_handler(&mut self, handler: impl FnOnce() + 'static) {
+        self.set_disconnect_with_reason_handler(move |_| handler());
+    }
+
+    /// Set a function to call once the receiver is disconnected, like
+    /// `set_disconnect_handler()`, which is also passed the reason that the
+    /// remote gave, if it was closed with `reset_with_reason()`.
+    pub fn set_disconnect_with_reason_handler(
+        &mut self,
+        handler: impl FnOnce(Option<DisconnectReason>) + 'static,
+    ) {
+        self.state.borrow_mut().disconnect_handler = Some(Box::new(handler));
+    }
+
+    /// Call `scope` around the dispatch of each request, whether it's
+    /// dispatched by `dispatch_pending_messages()` or while a sync call on
+    /// this thread waits.
+    pub(crate) fn set_dispatch_scope(&mut self, scope: impl Fn(&mut dyn FnMut()) + 'static) {
+        self.state.borrow_mut().dispatch_scope = Some(Box::new(scope));
+    }
+
+    /// How long the receiver should be idle before calling `notify_idle()`,
+    /// if the remote has asked to know (see `Remote::set_idle_handler`).
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.state.borrow().control.idle_timeout
+    }
+
+    /// Tell the remote that the receiver is idle, if it asked to know.
+    ///
+    /// FOR_RELEASE: The C++ bindings do this by themselves once a receiver
+    /// has no pending responses and has been idle for `idle_timeout()`, but
+    /// there is no Rust timer to do that with yet, so for now the owner of
+    /// the receiver must decide when to call this.
+    pub fn notify_idle(&self) {
+        let state = self.state.borrow();
+        if state.control.idle_timeout.is_some() {
+            send_run_or_close_pipe(&state.endpoint, RunOrClosePipeInput::NotifyIdle(NotifyIdle {}));
+        }
+    }
+
+    /// Close the message pipe, without calling the disconnect handler.
+    /// Responses to requests that we already dispatched will be dropped.
+    pub fn reset(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.endpoint.close();
+        state.disconnect_handler = None;
+    }
+
+    /// Close the message pipe like `reset()`, and tell the remote why, which
+    /// it passes to its disconnect handler.
+    pub fn reset_with_reason(&mut self, custom_reason: u32, description: &str) {
+        let disconnect_reason =
+            DisconnectReason { custom_reason, description: description.to_string() };
+        self.state.borrow_mut().endpoint.close_with_reason(disconnect_reason);
+        self.reset();
+    }
+
+    /// Unbind the receiver, returning the implementation and, if it's still
+    /// open, the pipe.
+    ///
+    /// Panics if the receiver is for an associated interface, or if any
+    /// associated interfaces share its pipe.
+    pub fn unbind(self) -> (Box<I>, Option<PendingReceiver<I>>) {
+        let Ok(state) = Rc::try_unwrap(self.state) else {
+            panic!("A receiver can't be unbound while it's dispatching a request")
+        };
+        let state = state.into_inner();
+        let pending = state.endpoint.take_pipe().map(PendingReceiver::new);
+        (state.implementation, pending)
+    }
+
+    /// Read all the requests which have arrived, and dispatch them to the
+    /// implementation.
+    ///
+    /// If a request is invalid, or the remote was closed, this closes the
+    /// receiver and calls the disconnect handler. Invalid requests are also
+    /// reported to the system as bad messages, as are requests that the
+    /// implementation passes to `report_bad_message()`.
+    pub fn dispatch_pending_messages(&mut self) {
+        self.state.borrow_mut().dispatch_pending_messages();
+    }
+}
+
+impl<I: ?Sized + Interface> ReceiverState<I> {
+    fn dispatch_pending_messages(&mut self) {
+        loop {
+            match self.endpoint.read() {
+                ReadResult::Message(message) => self.accept_or_close(message),
+                ReadResult::Empty => return,
+                ReadResult::Disconnected => {
+                    if let Some(handler) = self.disconnect_handler.take() {
+                        handler(self.endpoint.take_disconnect_reason());
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    fn accept_or_close(&mut self, mut message: Message) {
+        let source = message.source.take();
+        if let Err(error) = self.accept(message) {
+            notify_bad_message(source.as_ref(), &error);
+            self.endpoint.close_pipe();
+        }
+    }
+
+    fn accept(&mut self, message: Message) -> MojomResult<()> {
+        if message.header.is_response() {
+            bail!("Receivers can only receive requests, but got a response for {}", I::NAME)
+        }
+        if is_control_message(&message.header) {
+            return self
+                .control
+                .accept(message, I::VERSION, &self.endpoint)
+                .with_context(|| format!("In a control message to {}", I::NAME));
+        }
+        let mut request = Some(Request::new(message, self.endpoint.downgrade()));
+        let implementation = &mut self.implementation;
+        let mut result = Ok(());
+        let mut dispatch = || {
+            let request = request.take().expect("A request can only be dispatched once");
+            result =
+                dispatch_reporting_bad_messages(|| I::dispatch_request(implementation, request));
+        };
+        match &self.dispatch_scope {
+            Some(scope) => scope(&mut dispatch),
+            None => dispatch(),
+        }
+        result.with_context(|| format!("In a request to {}", I::NAME))?;
+        // If the remote is tracking whether we're idle, it waits for us to
+        // acknowledge each request.
+        if self.control.idle_timeout.is_some() {
+            send_run_or_close_pipe(&self.endpoint, RunOrClosePipeInput::MessageAck(MessageAck {}));
+        }
+        Ok(())
+    }
+}
+
+impl<I: ?Sized + Interface> SyncReceiver for ReceiverState<I> {
+    fn dispatch_sync_request(&mut self) -> bool {
+        match self.endpoint.read_sync() {
+            ReadResult::Message(message) => {
+                self.accept_or_close(message);
+                true
+            }
+            // We'll notice that we're disconnected when our owner next
+            // dispatches our messages.
+            ReadResult::Empty | ReadResult::Disconnected => false,
+        }
+    }
+
+    fn router(&self) -> Option<Router> {
+        self.endpoint.router().cloned()
+    }
+}
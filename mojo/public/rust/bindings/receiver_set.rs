@@ -0,0 +1,211 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `ReceiverSet`, which owns many receivers for the same interface,
+//! e.g. one for each client of a service, along with a context for each. This
+//! corresponds to mojo::ReceiverSet in the C++ bindings.
+
+use crate::interface::Interface;
+use crate::pipe_control_message::DisconnectReason;
+use crate::receiver::{PendingReceiver, Receiver};
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Identifies a receiver in a `ReceiverSet`. IDs are never reused by the same
+/// set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReceiverId(u64);
+
+/// Says which receiver of a `ReceiverSet` is dispatching the current request,
+/// and its context.
+///
+/// The implementations can't use the set while it's dispatching requests to
+/// them, so they should keep one of these instead, from
+/// `ReceiverSet::current()`, where a C++ implementation would call
+/// current_context() on its receiver set.
+///
+/// This is also right for sync requests, which are dispatched by sync calls on
+/// the same thread rather than by the set, and may interrupt the dispatch of
+/// another request.
+pub struct CurrentReceiver<Ctx> {
+    current: Rc<RefCell<Option<ReceiverAndContext<Ctx>>>>,
+}
+
+type ReceiverAndContext<Ctx> = (ReceiverId, Rc<Ctx>);
+
+impl<Ctx> Clone for CurrentReceiver<Ctx> {
+    fn clone(&self) -> Self {
+        CurrentReceiver { current: self.current.clone() }
+    }
+}
+
+impl<Ctx> CurrentReceiver<Ctx> {
+    /// The ID of the receiver which is dispatching the current request.
+    ///
+    /// Panics if the set isn't dispatching a request.
+    pub fn current_receiver(&self) -> ReceiverId {
+        self.get().0
+    }
+
+    /// The context that the receiver which is dispatching the current request
+    /// was added with.
+    ///
+    /// Panics if the set isn't dispatching a request.
+    pub fn current_context(&self) -> Rc<Ctx> {
+        self.get().1
+    }
+
+    fn get(&self) -> ReceiverAndContext<Ctx> {
+        self.current.borrow().clone().expect("The receiver set isn't dispatching a request")
+    }
+}
+
+type ReceiverAndReason = (ReceiverId, Option<DisconnectReason>);
+
+/// Called with the ID and context of each receiver that's disconnected, and
+/// the reason that its remote gave, if any.
+type DisconnectHandler<Ctx> = Box<dyn FnMut(ReceiverId, &Ctx, Option<DisconnectReason>)>;
+
+/// Owns receivers for `I`, each with a context of type `Ctx`, and removes
+/// them once they're disconnected.
+///
+/// Like a `Receiver`, the set only dispatches requests when its owner calls
+/// `dispatch_pending_messages()`.
+pub struct ReceiverSet<I: ?Sized + Interface, Ctx: 'static = ()> {
+    receivers: BTreeMap<ReceiverId, (Receiver<I>, Rc<Ctx>)>,
+    next_id: u64,
+    current: CurrentReceiver<Ctx>,
+    /// The receivers which have been disconnected since we last removed
+    /// them, and why, which their disconnect handlers add.
+    disconnected: Rc<RefCell<Vec<ReceiverAndReason>>>,
+    disconnect_handler: Option<DisconnectHandler<Ctx>>,
+}
+
+impl<I: ?Sized + Interface, Ctx: 'static> Default for ReceiverSet<I, Ctx> {
+    fn default() -> Self {
+        ReceiverSet {
+            receivers: BTreeMap::new(),
+            next_id: 0,
+            current: CurrentReceiver { current: Rc::new(RefCell::new(None)) },
+            disconnected: Rc::default(),
+            disconnect_handler: None,
+        }
+    }
+}
+
+impl<I: ?Sized + Interface, Ctx: 'static> ReceiverSet<I, Ctx> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a receiver which dispatches the requests from `pending` to
+    /// `implementation`, and add it to the set with `context`.
+    pub fn add(
+        &mut self,
+        implementation: Box<I>,
+        pending: PendingReceiver<I>,
+        context: Ctx,
+    ) -> ReceiverId {
+        let id = ReceiverId(self.next_id);
+        self.next_id += 1;
+        let mut receiver = Receiver::new(implementation, pending);
+        let disconnected = self.disconnected.clone();
+        receiver.set_disconnect_with_reason_handler(move |reason| {
+            disconnected.borrow_mut().push((id, reason));
+        });
+        let context = Rc::new(context);
+        let current = self.current.current.clone();
+        let receiver_context = context.clone();
+        receiver.set_dispatch_scope(move |dispatch| {
+            let previous = current.replace(Some((id, receiver_context.clone())));
+            dispatch();
+            *current.borrow_mut() = previous;
+        });
+        self.receivers.insert(id, (receiver, context));
+        id
+    }
+
+    /// Close and remove the receiver `id`, without calling the disconnect
+    /// handler. Returns whether it was in the set.
+    pub fn remove(&mut self, id: ReceiverId) -> bool {
+        self.receivers.remove(&id).is_some()
+    }
+
+    /// Close and remove the receiver `id` like `remove()`, and tell its
+    /// remote why.
+    pub fn remove_with_reason(
+        &mut self,
+        id: ReceiverId,
+        custom_reason: u32,
+        description: &str,
+    ) -> bool {
+        match self.receivers.remove(&id) {
+            Some((mut receiver, _)) => {
+                receiver.reset_with_reason(custom_reason, description);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Close and remove every receiver, without calling the disconnect
+    /// handler.
+    pub fn clear(&mut self) {
+        self.receivers.clear();
+    }
+
+    pub fn contains(&self, id: ReceiverId) -> bool {
+        self.receivers.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.receivers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receivers.is_empty()
+    }
+
+    /// Return a handle which says which receiver is dispatching the current
+    /// request, for the implementations to keep.
+    pub fn current(&self) -> CurrentReceiver<Ctx> {
+        self.current.clone()
+    }
+
+    /// Set a function to call with the ID and context of each receiver which
+    /// is disconnected, once it's been removed from the set.
+    pub fn set_disconnect_handler(&mut self, mut handler: impl FnMut(ReceiverId, &Ctx) + 'static) {
+        self.set_disconnect_with_reason_handler(move |id, context, _| handler(id, context));
+    }
+
+    /// Set a function to call with each receiver which is disconnected, like
+    /// `set_disconnect_handler()`, which is also passed the reason that its
+    /// remote gave, if any.
+    pub fn set_disconnect_with_reason_handler(
+        &mut self,
+        handler: impl FnMut(ReceiverId, &Ctx, Option<DisconnectReason>) + 'static,
+    ) {
+        self.disconnect_handler = Some(Box::new(handler));
+    }
+
+    /// Dispatch the requests which have arrived for each receiver, in order
+    /// of their IDs, then remove the receivers which were disconnected and
+    /// call the disconnect handler for each of them.
+    pub fn dispatch_pending_messages(&mut self) {
+        for (receiver, _) in self.receivers.values_mut() {
+            receiver.dispatch_pending_messages();
+        }
+        let disconnected = std::mem::take(&mut *self.disconnected.borrow_mut());
+        for (id, reason) in disconnected {
+            let Some((_, context)) = self.receivers.remove(&id) else {
+                continue;
+            };
+            if let Some(handler) = &mut self.disconnect_handler {
+                handler(id, &context, reason);
+            }
+        }
+    }
+}
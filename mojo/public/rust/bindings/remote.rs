@@ -0,0 +1,454 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `Remote`, which calls the methods of an interface over a message
+//! pipe, and `PendingRemote`, which is a remote that hasn't been bound yet.
+
+use crate::associated::{AssociatedGroup, PendingAssociatedRemote};
+use crate::bad_message::{dispatch_reporting_bad_messages, notify_bad_message};
+use crate::connector::ReadResult;
+use crate::control_message::*;
+use crate::interface::Interface;
+use crate::message::Message;
+use crate::pipe_control_message::DisconnectReason;
+use crate::response::{response_channel, ResponseFuture};
+use crate::router::Endpoint;
+use crate::sync_call_restrictions::SyncCallRestrictions;
+use crate::sync_handle_registry::wait_for_sync_message;
+use mojom_parser::{
+    MessageHeader, Mojom, MojomResult, MESSAGE_FLAG_EXPECTS_RESPONSE, MESSAGE_FLAG_IS_SYNC,
+    MESSAGE_FLAG_NO_INTERRUPT,
+};
+use system::message_pipe::MessagePipeHandle;
+
+use anyhow::{bail, Context};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// The message pipe for a `Remote<I>`, which can be passed to wherever the
+/// remote will be used.
+pub struct PendingRemote<I: ?Sized + Interface> {
+    pipe: MessagePipeHandle,
+    version: u32,
+    _interface: PhantomData<Box<I>>,
+}
+
+impl<I: ?Sized + Interface> PendingRemote<I> {
+    /// Wrap a message pipe whose other end is bound to an implementation of
+    /// version `version` of `I`, e.g. one that was passed from C++.
+    pub fn new(pipe: MessagePipeHandle, version: u32) -> Self {
+        PendingRemote { pipe, version, _interface: PhantomData }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Give up the message pipe, e.g. to pass it to C++.
+    pub fn into_pipe(self) -> MessagePipeHandle {
+        self.pipe
+    }
+}
+
+impl<I: ?Sized + Interface> fmt::Debug for PendingRemote<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingRemote")
+            .field("interface", &I::NAME)
+            .field("pipe", &self.pipe)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// Called with the response to a request, and the endpoint it was read from,
+/// to decode it and pass it on.
+type ResponseHandler = Box<dyn FnOnce(Message, &Endpoint) -> MojomResult<()>>;
+
+/// Sends requests to an implementation of `I` on the other end of a message
+/// pipe, and dispatches their responses.
+///
+/// The generated code for each interface `Foo` implements `Foo` for
+/// `Remote<dyn Foo>`, so usually you'll call its methods instead of sending
+/// requests yourself.
+///
+/// A remote for an associated interface is bound with `new_associated`, and
+/// otherwise works the same way, except that it shares its pipe with other
+/// interfaces.
+pub struct Remote<I: ?Sized + Interface> {
+    endpoint: Endpoint,
+    /// Shared with the response handler of `query_version()`, which updates
+    /// it.
+    version: Rc<Cell<u32>>,
+    next_request_id: u64,
+    response_handlers: HashMap<u64, ResponseHandler>,
+    control: ControlMessageHandler,
+    disconnect_handler: Option<Box<dyn FnOnce(Option<DisconnectReason>)>>,
+    _interface: PhantomData<Box<I>>,
+}
+
+impl<I: ?Sized + Interface> Remote<I> {
+    pub fn new(pending: PendingRemote<I>) -> Self {
+        Self::with_endpoint(Endpoint::new_primary(pending.pipe, true), pending.version)
+    }
+
+    /// Bind a remote for an associated interface, which was either passed to
+    /// us or created by an `AssociatedGroup`.
+    ///
+    /// Panics if `pending` is the end of a new associated interface which is
+    /// meant to be sent to the peer.
+    pub fn new_associated(pending: PendingAssociatedRemote<I>) -> Self {
+        let version = pending.version();
+        Self::with_endpoint(pending.into_endpoint(), version)
+    }
+
+    fn with_endpoint(endpoint: Endpoint, version: u32) -> Self {
+        Remote {
+            endpoint,
+            version: Rc::new(Cell::new(version)),
+            next_request_id: 0,
+            response_handlers: HashMap::new(),
+            control: ControlMessageHandler::default(),
+            disconnect_handler: None,
+            _interface: PhantomData,
+        }
+    }
+
+    /// The version of `I` that the receiver is known to implement. This is
+    /// the version that the remote was created with, until it's updated by
+    /// `query_version()` or `require_version()`.
+    pub fn version(&self) -> u32 {
+        self.version.get()
+    }
+
+    /// Ask the receiver which version of `I` it implements, and pass it to
+    /// `callback` once `dispatch_pending_messages()` reads the answer. This
+    /// also updates `version()`.
+    pub fn query_version(&mut self, callback: impl FnOnce(u32) + 'static) {
+        let version = self.version.clone();
+        self.send_request_with_response(
+            RUN_MESSAGE_ID,
+            RunMessageParams { input: RunInput::QueryVersion(QueryVersion {}) },
+            move |response: RunResponseMessageParams| {
+                let queried = match response.output {
+                    Some(RunOutput::QueryVersionResult(result)) => result.version,
+                    None => 0,
+                };
+                version.set(queried);
+                callback(queried);
+            },
+        );
+    }
+
+    /// Tell the receiver to close the pipe if it doesn't implement at least
+    /// `version` of `I`. Does nothing if the receiver is already known to
+    /// implement it.
+    pub fn require_version(&mut self, version: u32) {
+        if version <= self.version() {
+            return;
+        }
+        self.version.set(version);
+        send_run_or_close_pipe(
+            &self.endpoint,
+            RunOrClosePipeInput::RequireVersion(RequireVersion { version }),
+        );
+    }
+
+    /// Return a future which is ready once the receiver has dispatched every
+    /// request that was sent before this, and `dispatch_pending_messages()`
+    /// has read its answer. Fails if the remote is disconnected first.
+    pub fn flush_for_testing(&mut self) -> ResponseFuture<()> {
+        let (sender, future) = response_channel();
+        self.send_request_with_response(
+            RUN_MESSAGE_ID,
+            RunMessageParams { input: RunInput::FlushForTesting(FlushForTesting {}) },
+            move |_: RunResponseMessageParams| sender.send(Ok(())),
+        );
+        future
+    }
+
+    /// Ask the receiver to say when it's idle, and call `handler` whenever it
+    /// does, as long as it has dispatched every request we've sent.
+    ///
+    /// `timeout` is how long the receiver must be idle for before it tells us.
+    /// See `Receiver::notify_idle()`.
+    pub fn set_idle_handler(&mut self, timeout: Duration, handler: impl FnMut() + 'static) {
+        let timeout_in_microseconds = timeout.as_micros().try_into().unwrap_or(i64::MAX);
+        send_run_or_close_pipe(
+            &self.endpoint,
+            RunOrClosePipeInput::EnableIdleTracking(EnableIdleTracking { timeout_in_microseconds }),
+        );
+        self.control.idle_handler = Some(Box::new(handler));
+    }
+
+    /// The number of requests which the receiver hasn't acknowledged yet.
+    /// This is only counted once `set_idle_handler()` has been called.
+    pub fn num_unacked_messages(&self) -> usize {
+        self.control.num_unacked_messages
+    }
+
+    /// Whether the message pipe is still open. This only changes when we
+    /// close it, or when `dispatch_pending_messages()` notices that the other
+    /// end closed it.
+    pub fn is_connected(&self) -> bool {
+        self.endpoint.is_connected()
+    }
+
+    /// Return a group which creates associated interfaces on the remote's
+    /// pipe, to be passed to the receiver. If the remote is already
+    /// disconnected, so are they.
+    pub fn associated_group(&self) -> AssociatedGroup {
+        AssociatedGroup::new(self.endpoint.router().cloned())
+    }
+
+    /// Set a function to call once the remote is disconnected, either because
+    /// the receiver was closed or because it sent us an invalid message.
+    pub fn set_disconnect_handler(&mut self, handler: impl FnOnce() + 'static) {
+        self.set_disconnect_with_reason_handler(move |_| handler());
+    }
+
+    /// Set a function to call once the remote is disconnected, like
+    /// `set_disconnect_handler()`, which is also passed the reason that the
+    /// receiver gave, if it was closed with `reset_with_reason()`.
+    pub fn set_disconnect_with_reason_handler(
+        &mut self,
+        handler: impl FnOnce(Option<DisconnectReason>) + 'static,
+    ) {
+        self.disconnect_handler = Some(Box::new(handler));
+    }
+
+    /// Close the message pipe. This drops all pending response handlers
+    /// without calling them, and doesn't call the disconnect handler.
+    pub fn reset(&mut self) {
+        self.endpoint.close();
+        self.response_handlers.clear();
+        self.control = ControlMessageHandler::default();
+        self.disconnect_handler = None;
+    }
+
+    /// Close the message pipe like `reset()`, and tell the receiver why,
+    /// which it passes to its disconnect handler.
+    pub fn reset_with_reason(&mut self, custom_reason: u32, description: &str) {
+        let disconnect_reason =
+            DisconnectReason { custom_reason, description: description.to_string() };
+        self.endpoint.close_with_reason(disconnect_reason);
+        self.reset();
+    }
+
+    /// Unbind the remote, so that the pipe can be passed elsewhere. Any
+    /// pending response handlers are dropped without being called.
+    ///
+    /// Panics if the remote is for an associated interface, or if any
+    /// associated interfaces share its pipe.
+    pub fn unbind(self) -> Option<PendingRemote<I>> {
+        let version = self.version();
+        let pipe = self.endpoint.take_pipe()?;
+        Some(PendingRemote::new(pipe, version))
+    }
+
+    /// Call the method `name` without expecting a response.
+    ///
+    /// Panics if `params` can't be encoded, which means that its `Mojom`
+    /// implementation is wrong.
+    pub fn send_request<P: Mojom>(&mut self, name: u32, params: P) {
+        let header = MessageHeader { name, ..Default::default() };
+        self.send(header, params);
+    }
+
+    /// Call the method `name`, and pass its response to `on_response` once
+    /// `dispatch_pending_messages()` reads it. If the remote is disconnected
+    /// first, `on_response` is dropped without being called.
+    pub fn send_request_with_response<P: Mojom, R: Mojom>(
+        &mut self,
+        name: u32,
+        params: P,
+        on_response: impl FnOnce(R) + 'static,
+    ) {
+        let request_id = self.next_request_id;
+        // Request IDs only need to be unique among pending requests.
+        self.next_request_id = self.next_request_id.wrapping_add(1);
+        let header = MessageHeader {
+            name,
+            flags: MESSAGE_FLAG_EXPECTS_RESPONSE,
+            request_id,
+            ..Default::default()
+        };
+        if self.send(header, params) {
+            self.response_handlers.insert(
+                request_id,
+                Box::new(move |mut message, endpoint| {
+                    if message.header.name != name {
+                        bail!("Got response {} to a call of {}", message.header.name, name)
+                    }
+                    on_response(
+                        endpoint
+                            .decode(&mut message)
+                            .with_context(|| format!("In the response to method {}", name))?,
+                    );
+                    Ok(())
+                }),
+            );
+        }
+    }
+
+    /// Call the method `name`, and return a future for its response.
+    pub fn call<P: Mojom, R: Mojom + 'static>(
+        &mut self,
+        name: u32,
+        params: P,
+    ) -> ResponseFuture<R> {
+        let (sender, future) = response_channel();
+        self.send_request_with_response(name, params, move |response| sender.send(Ok(response)));
+        future
+    }
+
+    /// Call the sync method `name`, and block until its response arrives.
+    /// While we wait, sync requests for the receivers on this thread are
+    /// dispatched, so that e.g. a receiver on this thread can answer us, or
+    /// make a sync call back to the receiver that's calling it. Other
+    /// messages wait until they're dispatched as usual.
+    ///
+    /// Fails if the remote is disconnected before the response arrives, or
+    /// if the response is invalid, which also closes the remote and reports
+    /// it as a bad message. The disconnect handler is called once
+    /// `dispatch_pending_messages()` notices.
+    ///
+    /// Panics if sync calls aren't allowed (see `SyncCallRestrictions`).
+    pub fn call_sync<P: Mojom, R: Mojom>(&mut self, name: u32, params: P) -> MojomResult<R> {
+        self.call_sync_with_flags(name, params, 0)
+    }
+
+    /// Call the sync method `name` like `call_sync()`, for methods with the
+    /// `[NoInterrupt]` attribute, which don't dispatch any other messages
+    /// while they wait.
+    pub fn call_sync_no_interrupt<P: Mojom, R: Mojom>(
+        &mut self,
+        name: u32,
+        params: P,
+    ) -> MojomResult<R> {
+        self.call_sync_with_flags(name, params, MESSAGE_FLAG_NO_INTERRUPT)
+    }
+
+    fn call_sync_with_flags<P: Mojom, R: Mojom>(
+        &mut self,
+        name: u32,
+        params: P,
+        flags: u32,
+    ) -> MojomResult<R> {
+        SyncCallRestrictions::assert_sync_call_allowed();
+        let request_id = self.next_request_id;
+        self.next_request_id = self.next_request_id.wrapping_add(1);
+        let header = MessageHeader {
+            name,
+            flags: MESSAGE_FLAG_EXPECTS_RESPONSE | MESSAGE_FLAG_IS_SYNC | flags,
+            request_id,
+            ..Default::default()
+        };
+        if !self.send(header, params) {
+            bail!("Can't call method {} of {}, because the remote is disconnected", name, I::NAME)
+        }
+        let allow_interrupts = flags & MESSAGE_FLAG_NO_INTERRUPT == 0
+            && SyncCallRestrictions::are_sync_call_interrupts_enabled();
+        loop {
+            let mut message = match wait_for_sync_message(&self.endpoint, allow_interrupts) {
+                ReadResult::Message(message) => message,
+                ReadResult::Empty => unreachable!("Waiting only returns once there's a message"),
+                ReadResult::Disconnected => {
+                    bail!("{} was disconnected during a call of method {}", I::NAME, name)
+                }
+            };
+            let source = message.source.take();
+            let result = if message.header.is_response() && message.header.request_id == request_id
+            {
+                self.decode_sync_response(name, &mut message)
+            } else {
+                self.accept(message).map(|()| None)
+            };
+            match result {
+                Ok(Some(response)) => return Ok(response),
+                Ok(None) => {}
+                Err(error) => {
+                    notify_bad_message(source.as_ref(), &error);
+                    self.endpoint.close_pipe();
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    fn decode_sync_response<R: Mojom>(
+        &self,
+        name: u32,
+        message: &mut Message,
+    ) -> MojomResult<Option<R>> {
+        if message.header.name != name {
+            bail!("Got response {} to a call of {}", message.header.name, name)
+        }
+        self.endpoint
+            .decode(message)
+            .map(Some)
+            .with_context(|| format!("In the response from {} to method {}", I::NAME, name))
+    }
+
+    /// Returns whether the message was sent.
+    fn send<P: Mojom>(&mut self, header: MessageHeader, params: P) -> bool {
+        if !self.is_connected() {
+            return false;
+        }
+        self.endpoint.send(header, params).expect("Request parameters must be encodable");
+        self.control.on_request_sent(&header);
+        true
+    }
+
+    /// Read all the responses which have arrived, and call their handlers.
+    ///
+    /// If a response is invalid, or the receiver was closed, this closes the
+    /// remote and calls the disconnect handler. Invalid responses are also
+    /// reported to the system as bad messages, as are responses that their
+    /// handler passes to `report_bad_message()`.
+    pub fn dispatch_pending_messages(&mut self) {
+        loop {
+            match self.endpoint.read() {
+                ReadResult::Message(mut message) => {
+                    let source = message.source.take();
+                    if let Err(error) = self.accept(message) {
+                        notify_bad_message(source.as_ref(), &error);
+                        self.endpoint.close_pipe();
+                    }
+                }
+                ReadResult::Empty => return,
+                ReadResult::Disconnected => {
+                    self.response_handlers.clear();
+                    if let Some(handler) = self.disconnect_handler.take() {
+                        handler(self.endpoint.take_disconnect_reason());
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self, message: Message) -> MojomResult<()> {
+        if !message.header.is_response() {
+            // The receiver may send us control messages, e.g. to flush our
+            // end of the pipe or to acknowledge our requests, but nothing
+            // else.
+            if is_control_message(&message.header) {
+                return self
+                    .control
+                    .accept(message, I::VERSION, &self.endpoint)
+                    .with_context(|| format!("In a control message from {}", I::NAME));
+            }
+            bail!("Remotes can only receive responses, but got a request for {}", I::NAME)
+        }
+        let Some(handler) = self.response_handlers.remove(&message.header.request_id) else {
+            bail!("Got a response to unknown request {}", message.header.request_id)
+        };
+        dispatch_reporting_bad_messages(|| handler(message, &self.endpoint))
+            .with_context(|| format!("In a response from {}", I::NAME))
+    }
+}
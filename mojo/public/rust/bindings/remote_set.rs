@@ -0,0 +1,142 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `RemoteSet`, which owns many remotes for the same interface, e.g.
+//! to send notifications to every client of a service. This corresponds to
+//! mojo::RemoteSet in the C++ bindings.
+
+use crate::interface::Interface;
+use crate::remote::{PendingRemote, Remote};
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Identifies a remote in a `RemoteSet`. IDs are never reused by the same
+/// set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RemoteSetElementId(u64);
+
+/// Owns remotes for `I`, and removes them once they're disconnected.
+///
+/// To call a method of every remote, e.g. to broadcast a notification, use
+/// `iter_mut()`. Like a `Remote`, the set only notices that remotes are
+/// disconnected, and dispatches their responses, when its owner calls
+/// `dispatch_pending_messages()`.
+pub struct RemoteSet<I: ?Sized + Interface> {
+    remotes: BTreeMap<RemoteSetElementId, Remote<I>>,
+    next_id: u64,
+    /// The remotes which have been disconnected since we last removed them,
+    /// which their disconnect handlers add.
+    disconnected: Rc<RefCell<Vec<RemoteSetElementId>>>,
+    disconnect_handler: Option<Box<dyn FnMut(RemoteSetElementId)>>,
+}
+
+impl<I: ?Sized + Interface> Default for RemoteSet<I> {
+    fn default() -> Self {
+        RemoteSet {
+            remotes: BTreeMap::new(),
+            next_id: 0,
+            disconnected: Rc::default(),
+            disconnect_handler: None,
+        }
+    }
+}
+
+impl<I: ?Sized + Interface> RemoteSet<I> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a remote to `pending`, and add it to the set.
+    pub fn add(&mut self, pending: PendingRemote<I>) -> RemoteSetElementId {
+        self.add_remote(Remote::new(pending))
+    }
+
+    /// Add a remote which is already bound, e.g. one for an associated
+    /// interface. This replaces its disconnect handler.
+    pub fn add_remote(&mut self, mut remote: Remote<I>) -> RemoteSetElementId {
+        let id = RemoteSetElementId(self.next_id);
+        self.next_id += 1;
+        let disconnected = self.disconnected.clone();
+        remote.set_disconnect_handler(move || disconnected.borrow_mut().push(id));
+        self.remotes.insert(id, remote);
+        id
+    }
+
+    /// Close and remove the remote `id`, without calling the disconnect
+    /// handler. Returns whether it was in the set.
+    pub fn remove(&mut self, id: RemoteSetElementId) -> bool {
+        self.remotes.remove(&id).is_some()
+    }
+
+    /// Close and remove the remote `id` like `remove()`, and tell its
+    /// receiver why.
+    pub fn remove_with_reason(
+        &mut self,
+        id: RemoteSetElementId,
+        custom_reason: u32,
+        description: &str,
+    ) -> bool {
+        match self.remotes.remove(&id) {
+            Some(mut remote) => {
+                remote.reset_with_reason(custom_reason, description);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Close and remove every remote, without calling the disconnect handler.
+    pub fn clear(&mut self) {
+        self.remotes.clear();
+    }
+
+    /// The remote `id`, if it's still in the set.
+    pub fn get(&mut self, id: RemoteSetElementId) -> Option<&mut Remote<I>> {
+        self.remotes.get_mut(&id)
+    }
+
+    pub fn contains(&self, id: RemoteSetElementId) -> bool {
+        self.remotes.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.remotes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remotes.is_empty()
+    }
+
+    /// Iterate over the remotes, in the order they were added, e.g. to call a
+    /// method of each of them.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Remote<I>> {
+        self.remotes.values_mut()
+    }
+
+    /// Set a function to call with the ID of each remote which is
+    /// disconnected, once it's been removed from the set.
+    pub fn set_disconnect_handler(&mut self, handler: impl FnMut(RemoteSetElementId) + 'static) {
+        self.disconnect_handler = Some(Box::new(handler));
+    }
+
+    /// Dispatch the responses which have arrived for each remote, then
+    /// remove the remotes which were disconnected and call the disconnect
+    /// handler for each of them.
+    pub fn dispatch_pending_messages(&mut self) {
+        for remote in self.remotes.values_mut() {
+            remote.dispatch_pending_messages();
+        }
+        let disconnected = std::mem::take(&mut *self.disconnected.borrow_mut());
+        for id in disconnected {
+            if self.remotes.remove(&id).is_none() {
+                continue;
+            }
+            if let Some(handler) = &mut self.disconnect_handler {
+                handler(id);
+            }
+        }
+    }
+}
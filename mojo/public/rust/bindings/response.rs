@@ -0,0 +1,78 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `ResponseFuture`, which lets callers await the response to a
+//! request instead of passing a callback.
+
+use mojom_parser::MojomResult;
+
+use anyhow::anyhow;
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+struct ResponseState<R> {
+    /// Whether the result has been set. It can only be set once.
+    sent: bool,
+    /// The result, until the future returns it.
+    result: Option<MojomResult<R>>,
+    waker: Option<Waker>,
+}
+
+/// The response to a request, which will be ready once the remote that sent
+/// the request has dispatched it. Fails if the remote is disconnected first.
+pub struct ResponseFuture<R> {
+    state: Rc<RefCell<ResponseState<R>>>,
+}
+
+/// The other end of a `ResponseFuture`. If this is dropped without sending a
+/// response, e.g. because the remote was disconnected, the future fails.
+pub(crate) struct ResponseSender<R> {
+    state: Rc<RefCell<ResponseState<R>>>,
+}
+
+pub(crate) fn response_channel<R>() -> (ResponseSender<R>, ResponseFuture<R>) {
+    let state = Rc::new(RefCell::new(ResponseState { sent: false, result: None, waker: None }));
+    (ResponseSender { state: state.clone() }, ResponseFuture { state })
+}
+
+impl<R> ResponseSender<R> {
+    pub(crate) fn send(self, result: MojomResult<R>) {
+        self.set_result(result);
+    }
+
+    fn set_result(&self, result: MojomResult<R>) {
+        let mut state = self.state.borrow_mut();
+        if !state.sent {
+            state.sent = true;
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<R> Drop for ResponseSender<R> {
+    fn drop(&mut self) {
+        self.set_result(Err(anyhow!("The remote was disconnected before the response arrived")));
+    }
+}
+
+impl<R> Future for ResponseFuture<R> {
+    type Output = MojomResult<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
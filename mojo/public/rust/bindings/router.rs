@@ -0,0 +1,513 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines the router, which lets several interfaces share one message pipe,
+//! and `Endpoint`, which is one interface's end of it. Remotes and receivers
+//! each own an endpoint. This corresponds to mojo::internal::MultiplexRouter
+//! in the C++ bindings.
+//!
+//! Every message says which interface it's for in its header's
+//! `interface_id`. The interface that the pipe was created for is the primary
+//! interface, with ID 0, and any others are associated with it. Either end of
+//! the pipe may create associated interfaces (see associated.rs); the end
+//! with the primary remote sets the namespace bit in the IDs it allocates, so
+//! that the two ends never pick the same ID.
+//!
+//! Like the C++ router, we dispatch the messages for all the interfaces in the
+//! order they arrived in, so that e.g. a request which passes an associated
+//! receiver is always dispatched before the requests for that receiver. Since
+//! endpoints only read messages when their owners tell them to (see lib.rs),
+//! this means that an endpoint can't read its messages while an earlier
+//! message for another endpoint is still waiting to be read. The exception is
+//! sync messages, which are read ahead of the others while a sync call is
+//! waiting for its response (see sync_handle_registry.rs).
+
+use crate::associated::{decode_incoming_endpoints, encode_outgoing_endpoints};
+use crate::bad_message::notify_bad_message;
+use crate::connector::{Connector, ReadResult};
+use crate::message::Message;
+use crate::pipe_control_message::*;
+use mojom_parser::{MessageHeader, Mojom, MojomResult};
+use system::message_pipe::MessagePipeHandle;
+
+use anyhow::{bail, Context};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::{Rc, Weak};
+
+/// The ID of the interface that a message pipe was created for.
+pub(crate) const PRIMARY_INTERFACE_ID: u32 = 0;
+/// Not the ID of any interface. Pipe control messages have it instead.
+pub(crate) const INVALID_INTERFACE_ID: u32 = 0xFFFFFFFF;
+/// Set in the IDs allocated by the end of the pipe with the primary remote.
+const INTERFACE_ID_NAMESPACE_MASK: u32 = 0x80000000;
+
+/// What holds our end of an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    /// Nothing yet. The peer told us that it closed its end before the
+    /// message which passes ours to us arrived.
+    Nothing,
+    /// A message which passes our end to us, which hasn't been decoded yet.
+    Message,
+    /// An `Endpoint`.
+    Endpoint,
+    /// Nothing, because we closed it.
+    Closed,
+}
+
+struct EndpointState {
+    owner: Owner,
+    /// Whether the peer has closed its end, so that no more messages for it
+    /// will arrive.
+    peer_closed: bool,
+    /// Why the peer closed its end, if it said.
+    disconnect_reason: Option<DisconnectReason>,
+}
+
+impl EndpointState {
+    fn new(owner: Owner, peer_closed: bool) -> Self {
+        EndpointState { owner, peer_closed, disconnect_reason: None }
+    }
+}
+
+pub(crate) struct RouterState {
+    connector: Connector,
+    /// Whether to set the namespace bit in the IDs we allocate.
+    set_interface_id_namespace_bit: bool,
+    next_interface_id: u32,
+    /// Every interface which either end might still use. An interface is
+    /// forgotten once both ends have closed it.
+    endpoints: HashMap<u32, EndpointState>,
+    /// The messages which we've read from the pipe, but which the endpoints
+    /// they're for haven't read yet, in the order they arrived in.
+    incoming: VecDeque<Message>,
+}
+
+/// The router is shared by the endpoints on its pipe, and the pipe stays open
+/// as long as any of them do.
+pub(crate) type Router = Rc<RefCell<RouterState>>;
+
+impl RouterState {
+    /// Whether our end of `id` is still open.
+    pub(crate) fn is_open(&self, id: u32) -> bool {
+        self.endpoints.get(&id).is_some_and(|state| state.owner != Owner::Closed)
+    }
+
+    /// Allocate the ID of a new associated interface, whose end will be held
+    /// by an `Endpoint`.
+    pub(crate) fn allocate_interface_id(&mut self) -> u32 {
+        loop {
+            if self.next_interface_id >= INTERFACE_ID_NAMESPACE_MASK {
+                self.next_interface_id = 1;
+            }
+            let mut id = self.next_interface_id;
+            self.next_interface_id += 1;
+            if self.set_interface_id_namespace_bit {
+                id |= INTERFACE_ID_NAMESPACE_MASK;
+            }
+            if !self.endpoints.contains_key(&id) {
+                // If the pipe is already disconnected, so is the peer's end.
+                let peer_closed = !self.connector.is_connected();
+                self.endpoints.insert(id, EndpointState::new(Owner::Endpoint, peer_closed));
+                return id;
+            }
+        }
+    }
+
+    /// Note that the end of `id` which we were going to send to the peer was
+    /// dropped instead, so the peer will never have it.
+    pub(crate) fn on_peer_endpoint_dropped(&mut self, id: u32) {
+        self.update(id, |state| state.peer_closed = true);
+    }
+
+    /// Close our end of `id`, and tell the peer about it, and why, if we say.
+    pub(crate) fn close(&mut self, id: u32, disconnect_reason: Option<DisconnectReason>) {
+        let Some(state) = self.endpoints.get(&id) else {
+            return;
+        };
+        // The peer finds out that we closed the primary interface once we
+        // close the pipe, unless we have a reason to tell it, like in C++.
+        if (id != PRIMARY_INTERFACE_ID || disconnect_reason.is_some()) && !state.peer_closed {
+            send_peer_endpoint_closed(&self.connector, id, disconnect_reason);
+        }
+        self.update(id, |state| state.owner = Owner::Closed);
+    }
+
+    /// Give the `Endpoint` being decoded from a message our end of `id`,
+    /// which the message passes to us.
+    pub(crate) fn claim(&mut self, id: u32) -> MojomResult<()> {
+        match self.endpoints.get_mut(&id) {
+            Some(state) if state.owner == Owner::Message => {
+                state.owner = Owner::Endpoint;
+                Ok(())
+            }
+            _ => bail!("Interface {} was passed more than once", id),
+        }
+    }
+
+    /// Close our ends of the interfaces in `ids` which a message passed to us,
+    /// but which no `Endpoint` claimed when it was decoded.
+    pub(crate) fn close_unclaimed(&mut self, ids: &[u32]) {
+        for id in ids {
+            if self.endpoints.get(id).is_some_and(|state| state.owner == Owner::Message) {
+                self.close(*id, None);
+            }
+        }
+    }
+
+    /// Close the pipe, which disconnects every interface on it.
+    pub(crate) fn close_pipe(&mut self) {
+        self.connector.close();
+        for id in self.endpoints.keys().copied().collect::<Vec<_>>() {
+            self.update(id, |state| state.peer_closed = true);
+        }
+    }
+
+    /// Why the peer closed its end of `id`, if it said.
+    pub(crate) fn take_disconnect_reason(&mut self, id: u32) -> Option<DisconnectReason> {
+        self.endpoints.get_mut(&id).and_then(|state| state.disconnect_reason.take())
+    }
+
+    /// The pipe, or None if it's been closed.
+    pub(crate) fn pipe(&self) -> Option<&MessagePipeHandle> {
+        self.connector.pipe()
+    }
+
+    /// Apply `f` to the state of `id`, then forget `id` if both ends have
+    /// been closed.
+    fn update(&mut self, id: u32, f: impl FnOnce(&mut EndpointState)) {
+        if let Some(state) = self.endpoints.get_mut(&id) {
+            f(state);
+            if state.owner == Owner::Closed && state.peer_closed {
+                self.endpoints.remove(&id);
+            }
+        }
+    }
+
+    /// Read the next message for `id`.
+    fn read(&mut self, id: u32) -> ReadResult<Message> {
+        loop {
+            if let Some(front) = self.incoming.front() {
+                let front_id = front.header.interface_id;
+                if front_id == id {
+                    return ReadResult::Message(self.incoming.pop_front().unwrap());
+                }
+                if !self.is_open(front_id) {
+                    self.drop_front();
+                    continue;
+                }
+                if self.connector.is_connected() {
+                    // The front message's endpoint must read it first.
+                    return ReadResult::Empty;
+                }
+                // No more messages will arrive, so the order no longer
+                // matters.
+                return match self.incoming.iter().position(|m| m.header.interface_id == id) {
+                    Some(idx) => ReadResult::Message(self.incoming.remove(idx).unwrap()),
+                    None => ReadResult::Disconnected,
+                };
+            }
+            if self.endpoints.get(&id).is_none_or(|state| state.peer_closed) {
+                return ReadResult::Disconnected;
+            }
+            match self.connector.read() {
+                ReadResult::Message(message) => self.accept(message),
+                ReadResult::Empty => return ReadResult::Empty,
+                ReadResult::Disconnected => self.close_pipe(),
+            }
+        }
+    }
+
+    /// Read the next sync message for `id`, i.e. a sync request or the
+    /// response to one, ahead of any other messages that arrived before it.
+    /// This is what lets a sync call return while earlier messages are still
+    /// waiting to be dispatched, like the C++ router does.
+    fn read_sync(&mut self, id: u32) -> ReadResult<Message> {
+        // Queue everything that has arrived, so that we can look past it.
+        loop {
+            match self.connector.read() {
+                ReadResult::Message(message) => self.accept(message),
+                ReadResult::Empty => break,
+                ReadResult::Disconnected => {
+                    self.close_pipe();
+                    break;
+                }
+            }
+        }
+        let position = self
+            .incoming
+            .iter()
+            .position(|message| message.header.interface_id == id && message.header.is_sync());
+        if let Some(position) = position {
+            return ReadResult::Message(self.incoming.remove(position).unwrap());
+        }
+        if self.endpoints.get(&id).is_none_or(|state| state.peer_closed) {
+            return ReadResult::Disconnected;
+        }
+        ReadResult::Empty
+    }
+
+    /// Drop the front message, whose endpoint was closed, along with any
+    /// interfaces that it passes.
+    fn drop_front(&mut self) {
+        if let Some(message) = self.incoming.pop_front() {
+            self.close_unclaimed(&message.interface_ids);
+        }
+    }
+
+    /// Handle a message that we read from the pipe, either by queueing it or,
+    /// for pipe control messages, by acting on it. If the message is invalid,
+    /// this reports it and closes the pipe.
+    fn accept(&mut self, mut message: Message) {
+        let is_pipe_control_message = is_pipe_control_message(&message.header);
+        let result = if is_pipe_control_message {
+            self.accept_pipe_control_message(&mut message).context("In a pipe control message")
+        } else {
+            message.interface_ids.iter().try_for_each(|id| self.add_passed_interface(*id))
+        };
+        if let Err(error) = result {
+            notify_bad_message(message.source.as_ref(), &error);
+            self.close_pipe();
+        } else if !is_pipe_control_message {
+            self.incoming.push_back(message);
+        }
+    }
+
+    fn accept_pipe_control_message(&mut self, message: &mut Message) -> MojomResult<()> {
+        if message.header.name != RUN_OR_CLOSE_PIPE_MESSAGE_ID {
+            bail!("Unknown pipe control message {}", message.header.name)
+        }
+        let params: RunOrClosePipeMessageParams = message.decode_payload()?;
+        match params.input {
+            RunOrClosePipeInput::PeerAssociatedEndpointClosedEvent(event) => {
+                // The peer only says that it closed the primary interface to
+                // say why, and then closes the pipe.
+                if event.id == PRIMARY_INTERFACE_ID && event.disconnect_reason.is_none() {
+                    bail!("The primary interface can only be closed by closing the pipe")
+                }
+                // The peer may close its end before we get the message which
+                // passes ours to us.
+                self.endpoints.entry(event.id).or_insert(EndpointState::new(Owner::Nothing, false));
+                self.update(event.id, |state| {
+                    state.peer_closed = true;
+                    state.disconnect_reason = event.disconnect_reason;
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Note that a message passes our end of the interface `id` to us.
+    fn add_passed_interface(&mut self, id: u32) -> MojomResult<()> {
+        if id == PRIMARY_INTERFACE_ID || id == INVALID_INTERFACE_ID {
+            bail!("Message passed interface {}, which can't be associated", id)
+        }
+        // The peer allocated the ID, so it must have the other namespace.
+        if (id & INTERFACE_ID_NAMESPACE_MASK != 0) == self.set_interface_id_namespace_bit {
+            bail!("Message passed interface {}, which isn't from the peer's namespace", id)
+        }
+        let state = self.endpoints.entry(id).or_insert(EndpointState::new(Owner::Nothing, false));
+        if state.owner != Owner::Nothing {
+            bail!("Message passed interface {}, which is already in use", id)
+        }
+        state.owner = Owner::Message;
+        Ok(())
+    }
+}
+
+/// Our end of one of the interfaces on a router's pipe. It's closed when it's
+/// dropped, which tells the peer, if it's associated.
+pub(crate) struct Endpoint {
+    /// The router, or None once the endpoint is closed.
+    router: Option<Router>,
+    id: u32,
+    /// Why the peer closed its end, if it said, once `read()` has noticed
+    /// that it did.
+    disconnect_reason: Option<DisconnectReason>,
+}
+
+impl Endpoint {
+    /// Create a router for `pipe`, and return the primary interface's
+    /// endpoint. `is_remote` says whether the endpoint is for a remote or a
+    /// receiver.
+    pub(crate) fn new_primary(pipe: MessagePipeHandle, is_remote: bool) -> Self {
+        let endpoints =
+            HashMap::from([(PRIMARY_INTERFACE_ID, EndpointState::new(Owner::Endpoint, false))]);
+        let router = RouterState {
+            connector: Connector::new(pipe),
+            set_interface_id_namespace_bit: is_remote,
+            next_interface_id: 1,
+            endpoints,
+            incoming: VecDeque::new(),
+        };
+        Endpoint {
+            router: Some(Rc::new(RefCell::new(router))),
+            id: PRIMARY_INTERFACE_ID,
+            disconnect_reason: None,
+        }
+    }
+
+    /// Wrap our end of `id`, which `router` has already given us.
+    pub(crate) fn from_router(router: Router, id: u32) -> Self {
+        Endpoint { router: Some(router), id, disconnect_reason: None }
+    }
+
+    /// An endpoint which is already closed.
+    pub(crate) fn closed() -> Self {
+        Endpoint { router: None, id: INVALID_INTERFACE_ID, disconnect_reason: None }
+    }
+
+    pub(crate) fn router(&self) -> Option<&Router> {
+        self.router.as_ref()
+    }
+
+    /// Whether the endpoint is still open. This only changes when it's closed,
+    /// either by `close()` or by `read()` noticing that it's disconnected.
+    pub(crate) fn is_connected(&self) -> bool {
+        self.router.is_some()
+    }
+
+    /// A reference to the endpoint which doesn't keep the pipe open.
+    pub(crate) fn downgrade(&self) -> WeakEndpoint {
+        WeakEndpoint {
+            router: self.router.as_ref().map_or_else(Weak::new, Rc::downgrade),
+            id: self.id,
+        }
+    }
+
+    /// Send a message for this endpoint's interface, whose payload is
+    /// `params`, which must be a struct. Fails if `params` can't be encoded.
+    /// See `Connector::send` for what happens if the pipe is disconnected.
+    pub(crate) fn send<P: Mojom>(&self, header: MessageHeader, params: P) -> MojomResult<()> {
+        match &self.router {
+            Some(router) => send_on(router, self.id, header, params),
+            None => Ok(()),
+        }
+    }
+
+    /// Read the next message for this endpoint. If the endpoint is
+    /// disconnected, this closes it.
+    pub(crate) fn read(&mut self) -> ReadResult<Message> {
+        let Some(router) = &self.router else {
+            return ReadResult::Disconnected;
+        };
+        let result = router.borrow_mut().read(self.id);
+        if let ReadResult::Disconnected = result {
+            self.disconnect_reason = router.borrow_mut().take_disconnect_reason(self.id);
+            self.close();
+        }
+        result
+    }
+
+    /// Why the peer closed its end, if it said, once `read()` has returned
+    /// `ReadResult::Disconnected`.
+    pub(crate) fn take_disconnect_reason(&mut self) -> Option<DisconnectReason> {
+        self.disconnect_reason.take()
+    }
+
+    /// Read the next sync message for this endpoint, like `read()`, but
+    /// ahead of any other messages. Unlike `read()`, this doesn't close the
+    /// endpoint if it's disconnected, since other messages may still be
+    /// waiting to be read.
+    pub(crate) fn read_sync(&self) -> ReadResult<Message> {
+        match &self.router {
+            Some(router) => router.borrow_mut().read_sync(self.id),
+            None => ReadResult::Disconnected,
+        }
+    }
+
+    /// Decode the payload of `message`, which was read from this endpoint, as
+    /// a `P`, including any associated interfaces and handles it passes.
+    pub(crate) fn decode<P: Mojom>(&self, message: &mut Message) -> MojomResult<P> {
+        let router = self.router.as_ref().context("The endpoint was closed")?;
+        decode_on(router, message)
+    }
+
+    /// Close the pipe, e.g. because the peer sent us an invalid message. This
+    /// disconnects every endpoint on it, including this one, once they notice.
+    pub(crate) fn close_pipe(&self) {
+        if let Some(router) = &self.router {
+            router.borrow_mut().close_pipe();
+        }
+    }
+
+    /// Close the endpoint.
+    pub(crate) fn close(&mut self) {
+        if let Some(router) = self.router.take() {
+            router.borrow_mut().close(self.id, None);
+        }
+    }
+
+    /// Close the endpoint, and tell the peer why.
+    pub(crate) fn close_with_reason(&mut self, disconnect_reason: DisconnectReason) {
+        if let Some(router) = self.router.take() {
+            router.borrow_mut().close(self.id, Some(disconnect_reason));
+        }
+    }
+
+    /// Give up the pipe, if the endpoint is still open. Panics if the
+    /// endpoint isn't for the primary interface, or if the pipe has any
+    /// associated interfaces, which would be disconnected.
+    pub(crate) fn take_pipe(mut self) -> Option<MessagePipeHandle> {
+        assert_eq!(self.id, PRIMARY_INTERFACE_ID, "Associated endpoints have no pipe of their own");
+        let router = self.router.take()?;
+        let Ok(router) = Rc::try_unwrap(router) else {
+            panic!("A pipe can't be unbound while it has associated interfaces")
+        };
+        router.into_inner().connector.take_pipe()
+    }
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// A reference to an endpoint which doesn't keep it open, e.g. to send a
+/// response once the request has been dispatched.
+#[derive(Clone)]
+pub(crate) struct WeakEndpoint {
+    router: Weak<RefCell<RouterState>>,
+    id: u32,
+}
+
+impl WeakEndpoint {
+    /// Send a message like `Endpoint::send`, if the endpoint is still open.
+    /// Otherwise, the message is dropped.
+    pub(crate) fn send<P: Mojom>(&self, header: MessageHeader, params: P) -> MojomResult<()> {
+        match self.router.upgrade() {
+            Some(router) if router.borrow().is_open(self.id) => {
+                send_on(&router, self.id, header, params)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Decode the payload of `message` like `Endpoint::decode`.
+    pub(crate) fn decode<P: Mojom>(&self, message: &mut Message) -> MojomResult<P> {
+        let router = self.router.upgrade().context("The endpoint was closed")?;
+        decode_on(&router, message)
+    }
+}
+
+fn send_on<P: Mojom>(
+    router: &Router,
+    id: u32,
+    header: MessageHeader,
+    params: P,
+) -> MojomResult<()> {
+    let header = MessageHeader { interface_id: id, ..header };
+    let message = encode_outgoing_endpoints(router, || Message::new(header, params))?;
+    router.borrow().connector.send(message);
+    Ok(())
+}
+
+fn decode_on<P: Mojom>(router: &Router, message: &mut Message) -> MojomResult<P> {
+    let interface_ids = std::mem::take(&mut message.interface_ids);
+    let result = decode_incoming_endpoints(router, &interface_ids, || message.decode_payload());
+    router.borrow_mut().close_unclaimed(&interface_ids);
+    result
+}
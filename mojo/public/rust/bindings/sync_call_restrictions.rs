@@ -0,0 +1,112 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `SyncCallRestrictions`, which controls whether sync calls are
+//! allowed, and whether they dispatch incoming sync requests while they wait.
+//! This corresponds to mojo::SyncCallRestrictions in the C++ bindings, and
+//! shares its process-wide policy only with other Rust code.
+//!
+//! Sync calls block the calling thread, so processes which mustn't block
+//! (e.g. the browser) call `SyncCallRestrictions::disallow_sync_call()` at
+//! startup. After that, a thread may only make sync calls while it holds a
+//! `ScopedAllowSyncCall`.
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether `disallow_sync_call()` has been called.
+static SYNC_CALL_DISALLOWED: AtomicBool = AtomicBool::new(false);
+/// Whether sync calls may dispatch other sync messages while they wait.
+static SYNC_CALL_INTERRUPTS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+thread_local! {
+    /// The number of `ScopedAllowSyncCall`s on this thread.
+    static SCOPED_ALLOW_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The process-wide policy for sync calls. See the module documentation.
+pub enum SyncCallRestrictions {}
+
+impl SyncCallRestrictions {
+    /// Whether the current thread is allowed to make sync calls.
+    pub fn is_sync_call_allowed() -> bool {
+        !SYNC_CALL_DISALLOWED.load(Ordering::Relaxed) || SCOPED_ALLOW_COUNT.get() > 0
+    }
+
+    /// Panic if the current thread isn't allowed to make sync calls.
+    pub fn assert_sync_call_allowed() {
+        if !Self::is_sync_call_allowed() {
+            panic!(
+                "Mojo sync calls are not allowed in this process, because they can lead to \
+                 jank and deadlock. If you must make an exception, hold a ScopedAllowSyncCall."
+            )
+        }
+    }
+
+    /// Disallow sync calls in this process, except on threads which hold a
+    /// `ScopedAllowSyncCall`. This can't be undone, except by tests.
+    pub fn disallow_sync_call() {
+        SYNC_CALL_DISALLOWED.store(true, Ordering::Relaxed);
+    }
+
+    /// Undo `disallow_sync_call()`, for tests, which share a process with
+    /// other tests.
+    pub fn allow_sync_call_for_testing() {
+        SYNC_CALL_DISALLOWED.store(false, Ordering::Relaxed);
+    }
+
+    /// Stop sync calls from dispatching incoming sync requests while they
+    /// wait, as if every method were `[NoInterrupt]`. Incoming sync requests
+    /// wait until the caller dispatches them itself.
+    pub fn disable_sync_call_interrupts() {
+        SYNC_CALL_INTERRUPTS_ENABLED.store(false, Ordering::Relaxed);
+    }
+
+    /// Undo `disable_sync_call_interrupts()`, for tests.
+    pub fn enable_sync_call_interrupts_for_testing() {
+        SYNC_CALL_INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn are_sync_call_interrupts_enabled() -> bool {
+        SYNC_CALL_INTERRUPTS_ENABLED.load(Ordering::Relaxed)
+    }
+}
+
+/// Allows the current thread to make sync calls for as long as it's held, even
+/// if `SyncCallRestrictions::disallow_sync_call()` has been called.
+///
+/// Every use of this must be approved by mojo/OWNERS, like the friends of
+/// mojo::ScopedAllowSyncCall in the C++ bindings. Tests should use
+/// `ScopedAllowSyncCallForTesting` instead.
+pub struct ScopedAllowSyncCall {
+    /// The count is per thread, so the scope must stay on its thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ScopedAllowSyncCall {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        SCOPED_ALLOW_COUNT.set(SCOPED_ALLOW_COUNT.get() + 1);
+        ScopedAllowSyncCall { _not_send: PhantomData }
+    }
+}
+
+impl Drop for ScopedAllowSyncCall {
+    fn drop(&mut self) {
+        SCOPED_ALLOW_COUNT.set(SCOPED_ALLOW_COUNT.get() - 1);
+    }
+}
+
+/// Like `ScopedAllowSyncCall`, for tests, which don't need approval.
+pub struct ScopedAllowSyncCallForTesting {
+    _scope: ScopedAllowSyncCall,
+}
+
+impl ScopedAllowSyncCallForTesting {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        ScopedAllowSyncCallForTesting { _scope: ScopedAllowSyncCall::new() }
+    }
+}
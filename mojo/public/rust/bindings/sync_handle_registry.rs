@@ -0,0 +1,105 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Waits for the response to a sync call, while dispatching the sync requests
+//! that arrive for the receivers on the same thread. This corresponds to
+//! mojo::SyncHandleRegistry in the C++ bindings.
+//!
+//! Without this, a sync call to a receiver on the same thread would never be
+//! dispatched, and neither would a sync call back to us from the receiver
+//! we're calling, so both would deadlock. Only sync messages are dispatched
+//! while we wait; any others stay queued until their owners next call
+//! `dispatch_pending_messages()`.
+
+use crate::connector::ReadResult;
+use crate::message::Message;
+use crate::router::{Endpoint, Router, RouterState};
+use system::wait::wait_for_readable;
+
+use std::cell::{Ref, RefCell};
+use std::rc::{Rc, Weak};
+
+/// A receiver which can dispatch its sync requests while another endpoint on
+/// its thread waits for the response to a sync call.
+pub(crate) trait SyncReceiver {
+    /// Dispatch the next sync request, if one has arrived. Returns whether
+    /// one was dispatched.
+    fn dispatch_sync_request(&mut self) -> bool;
+
+    /// The router of the receiver's pipe, or None if it's closed.
+    fn router(&self) -> Option<Router>;
+}
+
+thread_local! {
+    /// The receivers on this thread, which are forgotten once they're dropped.
+    static RECEIVERS: RefCell<Vec<Weak<RefCell<dyn SyncReceiver>>>> = const { RefCell::new(vec![]) };
+}
+
+/// Let sync calls on this thread dispatch `receiver`'s sync requests while
+/// they wait.
+pub(crate) fn register_receiver(receiver: Weak<RefCell<dyn SyncReceiver>>) {
+    RECEIVERS.with_borrow_mut(|receivers| {
+        receivers.retain(|receiver| receiver.strong_count() > 0);
+        receivers.push(receiver);
+    });
+}
+
+/// The receivers on this thread which aren't already dispatching a message,
+/// further up the stack.
+fn available_receivers() -> Vec<Rc<RefCell<dyn SyncReceiver>>> {
+    RECEIVERS.with_borrow(|receivers| {
+        receivers
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|receiver| receiver.try_borrow_mut().is_ok())
+            .collect()
+    })
+}
+
+/// Block until the next sync message for `endpoint` arrives, and return it.
+/// If `allow_interrupts` is set, dispatch the sync requests for the receivers
+/// on this thread in the meantime.
+///
+/// Returns `ReadResult::Disconnected` if the endpoint is disconnected first,
+/// but doesn't close it.
+pub(crate) fn wait_for_sync_message(
+    endpoint: &Endpoint,
+    allow_interrupts: bool,
+) -> ReadResult<Message> {
+    loop {
+        match endpoint.read_sync() {
+            ReadResult::Empty => {}
+            result => return result,
+        }
+        let receivers = if allow_interrupts { available_receivers() } else { vec![] };
+        let mut dispatched = false;
+        for receiver in &receivers {
+            // A receiver may be borrowed by a sync call that an earlier one
+            // dispatched, so check again.
+            if let Ok(mut receiver) = receiver.try_borrow_mut() {
+                dispatched |= receiver.dispatch_sync_request();
+            }
+        }
+        if dispatched {
+            // Our response may have arrived in the meantime.
+            continue;
+        }
+
+        // Nothing is ready, so wait for one of the pipes to be readable.
+        let mut routers: Vec<Router> = endpoint.router().cloned().into_iter().collect();
+        for receiver in &receivers {
+            if let Some(router) = receiver.try_borrow().ok().and_then(|r| r.router())
+                && !routers.iter().any(|known| Rc::ptr_eq(known, &router))
+            {
+                routers.push(router);
+            }
+        }
+        let states: Vec<Ref<RouterState>> =
+            routers.iter().filter_map(|router| router.try_borrow().ok()).collect();
+        let pipes: Vec<_> = states.iter().filter_map(|state| state.pipe()).collect();
+        if wait_for_readable(&pipes).is_err() {
+            return ReadResult::Disconnected;
+        }
+    }
+}
@@ -0,0 +1,1215 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Tests for remotes and receivers.
+//!
+//! Each test binds a remote and a receiver to the two ends of a message pipe,
+//! then moves messages along by calling `dispatch_pending_messages()` on each
+//! of them in turn.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_base";
+    "//mojo/public/rust:mojo_bindings";
+    "//mojo/public/rust/mojom_parser";
+}
+
+use mojo_bindings::*;
+use mojo_public_mojom_base_base_rust::Binder;
+use mojo_public_rust_bindings_test_interfaces_rust::*;
+use rust_gtest_interop::prelude::*;
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Keeps a running total, which the test can see even after the receiver
+/// owns the implementation.
+struct CalculatorImpl {
+    total: Rc<Cell<i32>>,
+}
+
+impl Calculator for CalculatorImpl {
+    fn add(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        self.total.set(self.total.get() + value);
+        callback(self.total.get());
+    }
+
+    fn clear(&mut self) {
+        self.total.set(0);
+    }
+
+    fn translate(&mut self, point: Point, delta: Point, callback: Box<dyn FnOnce(Point, String)>) {
+        let result = Point { x: point.x + delta.x, y: point.y + delta.y };
+        let description = format!("({}, {})", result.x, result.y);
+        callback(result, description);
+    }
+
+    fn subtract(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        self.total.set(self.total.get() - value);
+        callback(self.total.get());
+    }
+}
+
+/// A bound remote and receiver, and the receiver's running total.
+type BoundCalculator = (Remote<dyn Calculator>, Receiver<dyn Calculator>, Rc<Cell<i32>>);
+
+fn bind_calculator() -> BoundCalculator {
+    let (pending_remote, pending_receiver) = create_interface_pipe::<dyn Calculator>().unwrap();
+    let total = Rc::new(Cell::new(0));
+    let receiver = Receiver::new(
+        Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+        pending_receiver,
+    );
+    (Remote::new(pending_remote), receiver, total)
+}
+
+#[gtest(MojoBindingsTestSuite, CallMethods)]
+fn test_call_methods() {
+    let (mut remote, mut receiver, total) = bind_calculator();
+
+    let responses = Rc::new(RefCell::new(vec![]));
+    let add_responses = responses.clone();
+    remote.add(5, Box::new(move |total| add_responses.borrow_mut().push(total)));
+    let add_responses = responses.clone();
+    remote.add(3, Box::new(move |total| add_responses.borrow_mut().push(total)));
+    // Nothing happens until the receiver dispatches the requests.
+    expect_eq!(total.get(), 0);
+    receiver.dispatch_pending_messages();
+    expect_eq!(total.get(), 8);
+    // The callbacks aren't called until the remote dispatches the responses.
+    expect_eq!(*responses.borrow(), Vec::<i32>::new());
+    remote.dispatch_pending_messages();
+    expect_eq!(*responses.borrow(), vec![5, 8]);
+
+    remote.clear();
+    receiver.dispatch_pending_messages();
+    expect_eq!(total.get(), 0);
+
+    let translated = Rc::new(RefCell::new(None));
+    let translated_clone = translated.clone();
+    remote.translate(
+        Point { x: 1, y: 2 },
+        Point { x: 10, y: -20 },
+        Box::new(move |point, description| {
+            *translated_clone.borrow_mut() = Some((point, description))
+        }),
+    );
+    receiver.dispatch_pending_messages();
+    remote.dispatch_pending_messages();
+    expect_eq!(*translated.borrow(), Some((Point { x: 11, y: -18 }, "(11, -18)".to_string())));
+    expect_true!(remote.is_connected());
+    expect_true!(receiver.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, ResponseFuture)]
+fn test_response_future() {
+    let (mut remote, mut receiver, _) = bind_calculator();
+    let mut context = Context::from_waker(Waker::noop());
+
+    let mut response = pin!(remote.call::<_, calculator::AddResponseParams>(
+        calculator::ADD_ORDINAL,
+        calculator::AddParams { value: 7 },
+    ));
+    expect_true!(response.as_mut().poll(&mut context).is_pending());
+    receiver.dispatch_pending_messages();
+    remote.dispatch_pending_messages();
+    expect_true!(matches!(
+        response.as_mut().poll(&mut context),
+        Poll::Ready(Ok(calculator::AddResponseParams { total: 7 }))
+    ));
+
+    // If the receiver goes away first, the future fails.
+    let mut response = pin!(remote.call::<_, calculator::AddResponseParams>(
+        calculator::ADD_ORDINAL,
+        calculator::AddParams { value: 7 },
+    ));
+    drop(receiver);
+    remote.dispatch_pending_messages();
+    expect_true!(matches!(response.as_mut().poll(&mut context), Poll::Ready(Err(_))));
+}
+
+#[gtest(MojoBindingsTestSuite, Disconnect)]
+fn test_disconnect() {
+    let (mut remote, mut receiver, _) = bind_calculator();
+    let remote_disconnected = Rc::new(Cell::new(false));
+    let remote_disconnected_clone = remote_disconnected.clone();
+    remote.set_disconnect_handler(move || remote_disconnected_clone.set(true));
+    let receiver_disconnected = Rc::new(Cell::new(false));
+    let receiver_disconnected_clone = receiver_disconnected.clone();
+    receiver.set_disconnect_handler(move || receiver_disconnected_clone.set(true));
+
+    // Closing the receiver disconnects the remote, and drops its pending
+    // callbacks.
+    let called = Rc::new(Cell::new(false));
+    let called_clone = called.clone();
+    remote.add(1, Box::new(move |_| called_clone.set(true)));
+    receiver.reset();
+    expect_false!(receiver.is_connected());
+    expect_true!(remote.is_connected());
+    remote.dispatch_pending_messages();
+    expect_false!(remote.is_connected());
+    expect_true!(remote_disconnected.get());
+    expect_false!(called.get());
+    // reset() doesn't call the disconnect handler.
+    expect_false!(receiver_disconnected.get());
+
+    // Calls on a disconnected remote are ignored.
+    remote.clear();
+
+    // Dropping the remote disconnects the receiver.
+    let (remote, mut receiver, _) = bind_calculator();
+    let receiver_disconnected = Rc::new(Cell::new(false));
+    let receiver_disconnected_clone = receiver_disconnected.clone();
+    receiver.set_disconnect_handler(move || receiver_disconnected_clone.set(true));
+    drop(remote);
+    receiver.dispatch_pending_messages();
+    expect_true!(receiver_disconnected.get());
+}
+
+#[gtest(MojoBindingsTestSuite, BadMessages)]
+fn test_bad_messages() {
+    // Returns a receiver, the raw pipe of its remote, and whether the receiver
+    // has been disconnected.
+    let bind_raw = || {
+        let (remote_pipe, receiver_pipe) = create_message_pipe().unwrap();
+        let mut receiver = Receiver::new(
+            Box::new(CalculatorImpl { total: Rc::new(Cell::new(0)) }) as Box<dyn Calculator>,
+            PendingReceiver::new(receiver_pipe),
+        );
+        let disconnected = Rc::new(Cell::new(false));
+        let disconnected_clone = disconnected.clone();
+        receiver.set_disconnect_handler(move || disconnected_clone.set(true));
+        (receiver, remote_pipe, disconnected)
+    };
+    let message = |name, flags, params: calculator::AddParams| {
+        let header = mojom_parser::MessageHeader { name, flags, ..Default::default() };
+        Message::new(header, params).unwrap().to_bytes()
+    };
+    let params = calculator::AddParams { value: 1 };
+
+    let bad_messages = [
+        // Not a message at all.
+        vec![1, 2, 3],
+        // An unknown method.
+        message(7, mojom_parser::MESSAGE_FLAG_EXPECTS_RESPONSE, params.clone()),
+        // Add has a response, so the caller must expect one.
+        message(calculator::ADD_ORDINAL, 0, params.clone()),
+        // Clear has no response, so the caller mustn't expect one.
+        message(
+            calculator::CLEAR_ORDINAL,
+            mojom_parser::MESSAGE_FLAG_EXPECTS_RESPONSE,
+            params.clone(),
+        ),
+        // Receivers don't get responses.
+        message(calculator::ADD_ORDINAL, mojom_parser::MESSAGE_FLAG_IS_RESPONSE, params.clone()),
+    ];
+    for bad_message in bad_messages {
+        let (mut receiver, remote_pipe, disconnected) = bind_raw();
+        remote_pipe.write(&bad_message).unwrap();
+        receiver.dispatch_pending_messages();
+        expect_false!(receiver.is_connected());
+        expect_true!(disconnected.get());
+        // The remote's end sees that the receiver closed the pipe.
+        expect_eq!(remote_pipe.read(), Err(MojoError::FailedPrecondition));
+    }
+
+    // The good version of the message works.
+    let (mut receiver, remote_pipe, disconnected) = bind_raw();
+    remote_pipe
+        .write(&message(
+            calculator::ADD_ORDINAL,
+            mojom_parser::MESSAGE_FLAG_EXPECTS_RESPONSE,
+            params,
+        ))
+        .unwrap();
+    receiver.dispatch_pending_messages();
+    expect_true!(receiver.is_connected());
+    expect_false!(disconnected.get());
+}
+
+/// Reports requests to subtract as bad, as if they made no sense.
+struct NoSubtractCalculator;
+
+impl Calculator for NoSubtractCalculator {
+    fn add(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        callback(value);
+    }
+
+    fn clear(&mut self) {}
+
+    fn translate(&mut self, point: Point, _delta: Point, callback: Box<dyn FnOnce(Point, String)>) {
+        callback(point, String::new());
+    }
+
+    fn subtract(&mut self, _value: i32, _callback: Box<dyn FnOnce(i32)>) {
+        report_bad_message("Can't subtract");
+    }
+}
+
+#[gtest(MojoBindingsTestSuite, ReportBadMessage)]
+fn test_report_bad_message() {
+    let (pending_remote, pending_receiver) = create_interface_pipe::<dyn Calculator>().unwrap();
+    let mut remote = Remote::new(pending_remote);
+    let mut receiver =
+        Receiver::new(Box::new(NoSubtractCalculator) as Box<dyn Calculator>, pending_receiver);
+    let disconnected = Rc::new(Cell::new(false));
+    let disconnected_clone = disconnected.clone();
+    receiver.set_disconnect_handler(move || disconnected_clone.set(true));
+
+    // Reporting a request closes the receiver once the method returns, but
+    // the requests before it were dispatched as usual.
+    let responses = Rc::new(RefCell::new(vec![]));
+    let add_responses = responses.clone();
+    remote.add(1, Box::new(move |total| add_responses.borrow_mut().push(total)));
+    remote.subtract(1, Box::new(|_| panic!("Bad requests get no response")));
+    remote.add(2, Box::new(|_| panic!("Requests after a bad one aren't dispatched")));
+    receiver.dispatch_pending_messages();
+    expect_false!(receiver.is_connected());
+    expect_true!(disconnected.get());
+    remote.dispatch_pending_messages();
+    expect_eq!(*responses.borrow(), vec![1]);
+    expect_false!(remote.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, DisconnectReasons)]
+fn test_disconnect_reasons() {
+    // Returns where a disconnect handler records the reason it's passed, and
+    // the handler.
+    let record = || {
+        let reason = Rc::new(RefCell::new(None));
+        let reason_clone = reason.clone();
+        (reason, move |r: Option<DisconnectReason>| *reason_clone.borrow_mut() = Some(r))
+    };
+    let go_away = DisconnectReason { custom_reason: 42, description: "Go away".to_string() };
+
+    // A receiver can say why it closed the pipe.
+    let (mut remote, mut receiver, _) = bind_calculator();
+    let (reason, handler) = record();
+    remote.set_disconnect_with_reason_handler(handler);
+    receiver.reset_with_reason(42, "Go away");
+    remote.dispatch_pending_messages();
+    expect_false!(remote.is_connected());
+    expect_eq!(*reason.borrow(), Some(Some(go_away.clone())));
+
+    // So can a remote.
+    let (mut remote, mut receiver, _) = bind_calculator();
+    let (reason, handler) = record();
+    receiver.set_disconnect_with_reason_handler(handler);
+    remote.reset_with_reason(42, "Go away");
+    receiver.dispatch_pending_messages();
+    expect_eq!(*reason.borrow(), Some(Some(go_away.clone())));
+
+    // Otherwise, there's no reason.
+    let (remote, mut receiver, _) = bind_calculator();
+    let (reason, handler) = record();
+    receiver.set_disconnect_with_reason_handler(handler);
+    drop(remote);
+    receiver.dispatch_pending_messages();
+    expect_eq!(*reason.borrow(), Some(None));
+
+    // Associated interfaces can say why too, without disconnecting the rest
+    // of the pipe.
+    let (mut factory, mut factory_receiver, calculators) = bind_calculator_factory();
+    let (calculator, receiver) =
+        factory.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+    factory.create_calculator(receiver);
+    factory_receiver.dispatch_pending_messages();
+    let (reason, handler) = record();
+    calculators.borrow_mut()[0].0.set_disconnect_with_reason_handler(handler);
+    Remote::new_associated(calculator).reset_with_reason(42, "Go away");
+    calculators.borrow_mut()[0].0.dispatch_pending_messages();
+    expect_eq!(*reason.borrow(), Some(Some(go_away)));
+    factory_receiver.dispatch_pending_messages();
+    expect_true!(factory_receiver.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, WireFormat)]
+fn test_wire_format() {
+    // Check the exact bytes of a request and its response, which must match
+    // what the C++ bindings send and expect.
+    let (remote_pipe, receiver_pipe) = create_message_pipe().unwrap();
+    let mut remote = Remote::<dyn Calculator>::new(PendingRemote::new(remote_pipe, 0));
+    let total = Rc::new(Cell::new(0));
+    let total_clone = total.clone();
+    // The first request has ID 0, so send two to check that the ID is
+    // encoded.
+    remote.add(0, Box::new(|_| {}));
+    remote.add(0x1234, Box::new(move |value| total_clone.set(value)));
+    let request: [u8; 48] = [
+        0x20, 0x00, 0x00, 0x00, // Header: Size in bytes (32)
+        0x01, 0x00, 0x00, 0x00, // Header: Version number (1)
+        0x00, 0x00, 0x00, 0x00, // Header: Interface ID
+        0x00, 0x00, 0x00, 0x00, // Header: Message name (Add)
+        0x01, 0x00, 0x00, 0x00, // Header: Flags (expects response)
+        0x00, 0x00, 0x00, 0x00, // Header: Trace nonce
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Header: Request ID (1)
+        0x10, 0x00, 0x00, 0x00, // Payload: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Payload: Version number (0)
+        0x34, 0x12, 0x00, 0x00, // Payload: value
+        0x00, 0x00, 0x00, 0x00, // Payload: Padding
+    ];
+    let _ = receiver_pipe.read().unwrap();
+    expect_eq!(receiver_pipe.read().unwrap(), request);
+
+    // A response has the same layout, with a different flag.
+    let mut response = request;
+    response[16] = 0x02; // Header: Flags (is response)
+    receiver_pipe.write(&response).unwrap();
+    remote.dispatch_pending_messages();
+    expect_eq!(total.get(), 0x1234);
+    expect_true!(remote.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, QueryVersion)]
+fn test_query_version() {
+    let (mut remote, mut receiver, _) = bind_calculator();
+    expect_eq!(remote.version(), 0);
+
+    let queried = Rc::new(Cell::new(None));
+    let queried_clone = queried.clone();
+    remote.query_version(move |version| queried_clone.set(Some(version)));
+    receiver.dispatch_pending_messages();
+    remote.dispatch_pending_messages();
+    expect_eq!(queried.get(), Some(calculator::VERSION));
+    expect_eq!(remote.version(), 1);
+
+    // Requiring a version which the receiver has keeps it connected.
+    remote.require_version(1);
+    receiver.dispatch_pending_messages();
+    expect_true!(receiver.is_connected());
+
+    // Requiring a later version makes the receiver close the pipe.
+    let disconnected = Rc::new(Cell::new(false));
+    let disconnected_clone = disconnected.clone();
+    remote.set_disconnect_handler(move || disconnected_clone.set(true));
+    remote.require_version(2);
+    expect_eq!(remote.version(), 2);
+    receiver.dispatch_pending_messages();
+    expect_false!(receiver.is_connected());
+    remote.dispatch_pending_messages();
+    expect_true!(disconnected.get());
+}
+
+#[gtest(MojoBindingsTestSuite, FlushForTesting)]
+fn test_flush_for_testing() {
+    let (mut remote, mut receiver, total) = bind_calculator();
+    let mut context = Context::from_waker(Waker::noop());
+
+    remote.add(3, Box::new(|_| {}));
+    let mut flushed = pin!(remote.flush_for_testing());
+    expect_true!(flushed.as_mut().poll(&mut context).is_pending());
+    receiver.dispatch_pending_messages();
+    expect_eq!(total.get(), 3);
+    remote.dispatch_pending_messages();
+    expect_true!(matches!(flushed.as_mut().poll(&mut context), Poll::Ready(Ok(()))));
+
+    // Flushing a disconnected remote fails straight away.
+    drop(receiver);
+    remote.dispatch_pending_messages();
+    let mut flushed = pin!(remote.flush_for_testing());
+    expect_true!(matches!(flushed.as_mut().poll(&mut context), Poll::Ready(Err(_))));
+}
+
+#[gtest(MojoBindingsTestSuite, IdleTracking)]
+fn test_idle_tracking() {
+    let (mut remote, mut receiver, _) = bind_calculator();
+    let idle_count = Rc::new(Cell::new(0));
+    let idle_count_clone = idle_count.clone();
+    expect_eq!(receiver.idle_timeout(), None);
+    remote.set_idle_handler(Duration::from_millis(5), move || {
+        idle_count_clone.set(idle_count_clone.get() + 1)
+    });
+    receiver.dispatch_pending_messages();
+    expect_eq!(receiver.idle_timeout(), Some(Duration::from_millis(5)));
+
+    remote.clear();
+    remote.add(1, Box::new(|_| {}));
+    expect_eq!(remote.num_unacked_messages(), 2);
+    receiver.dispatch_pending_messages();
+    // The receiver says it's idle before the remote has read the
+    // acknowledgements, so the remote only believes it once it has.
+    receiver.notify_idle();
+    remote.dispatch_pending_messages();
+    expect_eq!(remote.num_unacked_messages(), 0);
+    expect_eq!(idle_count.get(), 1);
+
+    // If the receiver says it's idle while a request is on its way, the remote
+    // ignores it.
+    remote.clear();
+    receiver.notify_idle();
+    remote.dispatch_pending_messages();
+    expect_eq!(idle_count.get(), 1);
+    receiver.dispatch_pending_messages();
+    receiver.notify_idle();
+    remote.dispatch_pending_messages();
+    expect_eq!(idle_count.get(), 2);
+    expect_true!(remote.is_connected());
+}
+
+/// The calculators bound by a `CalculatorFactoryImpl`, and their running
+/// totals.
+type CalculatorList = Rc<RefCell<Vec<(Receiver<dyn Calculator>, Rc<Cell<i32>>)>>>;
+
+/// Binds each calculator it's asked for, and hands them to the test.
+struct CalculatorFactoryImpl {
+    calculators: CalculatorList,
+    /// The calculators passed to `use_calculator`, which stay connected until
+    /// the factory is dropped.
+    remotes: Vec<Remote<dyn Calculator>>,
+}
+
+impl CalculatorFactory for CalculatorFactoryImpl {
+    fn create_calculator(&mut self, receiver: PendingAssociatedReceiver<dyn Calculator>) {
+        let total = Rc::new(Cell::new(0));
+        let receiver = Receiver::new_associated(
+            Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+            receiver,
+        );
+        self.calculators.borrow_mut().push((receiver, total));
+    }
+
+    fn use_calculator(&mut self, calculator: PendingAssociatedRemote<dyn Calculator>, value: i32) {
+        let mut remote = Remote::new_associated(calculator);
+        remote.add(value, Box::new(|_| {}));
+        self.remotes.push(remote);
+    }
+}
+
+fn bind_calculator_factory(
+) -> (Remote<dyn CalculatorFactory>, Receiver<dyn CalculatorFactory>, CalculatorList) {
+    let (pending_remote, pending_receiver) =
+        create_interface_pipe::<dyn CalculatorFactory>().unwrap();
+    let calculators = CalculatorList::default();
+    let receiver = Receiver::new(
+        Box::new(CalculatorFactoryImpl { calculators: calculators.clone(), remotes: vec![] })
+            as Box<dyn CalculatorFactory>,
+        pending_receiver,
+    );
+    (Remote::new(pending_remote), receiver, calculators)
+}
+
+#[gtest(MojoBindingsTestSuite, AssociatedInterfaces)]
+fn test_associated_interfaces() {
+    let (mut factory, mut factory_receiver, calculators) = bind_calculator_factory();
+
+    let (calculator, receiver) =
+        factory.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+    factory.create_calculator(receiver);
+    let mut calculator = Remote::new_associated(calculator);
+    // The calculator can be called straight away, and the request waits for
+    // the factory to bind its receiver.
+    let responses = Rc::new(RefCell::new(vec![]));
+    let add_responses = responses.clone();
+    calculator.add(5, Box::new(move |total| add_responses.borrow_mut().push(total)));
+
+    factory_receiver.dispatch_pending_messages();
+    expect_eq!(calculators.borrow().len(), 1);
+    {
+        let (receiver, total) = &mut calculators.borrow_mut()[0];
+        receiver.dispatch_pending_messages();
+        expect_eq!(total.get(), 5);
+    }
+    // The response only goes to the calculator, not the factory.
+    factory.dispatch_pending_messages();
+    expect_eq!(*responses.borrow(), Vec::<i32>::new());
+    calculator.dispatch_pending_messages();
+    expect_eq!(*responses.borrow(), vec![5]);
+    expect_true!(factory.is_connected());
+    expect_true!(calculator.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, AssociatedRemotes)]
+fn test_associated_remotes() {
+    // A remote can be sent the other way, for calling a receiver on this side
+    // of the pipe.
+    let (mut factory, mut factory_receiver, _) = bind_calculator_factory();
+    let (receiver, calculator) =
+        factory.associated_group().new_endpoint_and_pass_remote::<dyn Calculator>();
+    let total = Rc::new(Cell::new(0));
+    let mut receiver = Receiver::new_associated(
+        Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+        receiver,
+    );
+    factory.use_calculator(calculator, 3);
+    factory_receiver.dispatch_pending_messages();
+    receiver.dispatch_pending_messages();
+    expect_eq!(total.get(), 3);
+    expect_true!(receiver.is_connected());
+
+    // Dropping the factory drops its remote, which disconnects the receiver.
+    drop(factory_receiver);
+    factory.dispatch_pending_messages();
+    receiver.dispatch_pending_messages();
+    expect_false!(receiver.is_connected());
+}
+
+/// Binds each calculator it's asked for on its own pipe, and hands them to the
+/// test.
+struct CalculatorProviderImpl {
+    calculators: CalculatorList,
+    /// The calculators passed to `pass_calculator`, which stay connected until
+    /// the provider is dropped.
+    remotes: Vec<Remote<dyn Calculator>>,
+    /// The versions of the calculators passed to `pass_calculator`.
+    versions: Rc<RefCell<Vec<u32>>>,
+}
+
+impl CalculatorProvider for CalculatorProviderImpl {
+    fn bind_calculator(&mut self, receiver: PendingReceiver<dyn Calculator>) {
+        let total = Rc::new(Cell::new(0));
+        let receiver = Receiver::new(
+            Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+            receiver,
+        );
+        self.calculators.borrow_mut().push((receiver, total));
+    }
+
+    fn pass_calculator(&mut self, calculator: PendingRemote<dyn Calculator>, value: i32) {
+        let mut remote = Remote::new(calculator);
+        self.versions.borrow_mut().push(remote.version());
+        remote.add(value, Box::new(|_| {}));
+        self.remotes.push(remote);
+    }
+}
+
+#[gtest(MojoBindingsTestSuite, PassRemotesAndReceivers)]
+fn test_pass_remotes_and_receivers() {
+    let (pending_remote, pending_receiver) =
+        create_interface_pipe::<dyn CalculatorProvider>().unwrap();
+    let calculators = CalculatorList::default();
+    let versions = Rc::new(RefCell::new(vec![]));
+    let mut provider_receiver = Receiver::new(
+        Box::new(CalculatorProviderImpl {
+            calculators: calculators.clone(),
+            remotes: vec![],
+            versions: versions.clone(),
+        }) as Box<dyn CalculatorProvider>,
+        pending_receiver,
+    );
+    let mut provider = Remote::new(pending_remote);
+
+    // A receiver sent to the provider gets bound on its side.
+    let (calculator, receiver) = create_interface_pipe::<dyn Calculator>().unwrap();
+    provider.bind_calculator(receiver);
+    provider_receiver.dispatch_pending_messages();
+    expect_eq!(calculators.borrow().len(), 1);
+    let mut calculator = Remote::new(calculator);
+    calculator.add(5, Box::new(|_| {}));
+    {
+        let (receiver, total) = &mut calculators.borrow_mut()[0];
+        receiver.dispatch_pending_messages();
+        expect_eq!(total.get(), 5);
+    }
+
+    // A remote sent to the provider calls the receiver on this side, and keeps
+    // its version.
+    let (calculator, receiver) = create_interface_pipe::<dyn Calculator>().unwrap();
+    let calculator = PendingRemote::new(calculator.into_pipe(), 1);
+    let total = Rc::new(Cell::new(0));
+    let mut receiver = Receiver::new(
+        Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+        receiver,
+    );
+    provider.pass_calculator(calculator, 3);
+    provider_receiver.dispatch_pending_messages();
+    receiver.dispatch_pending_messages();
+    expect_eq!(total.get(), 3);
+    expect_eq!(*versions.borrow(), vec![1]);
+
+    // Dropping the provider drops its remote, which disconnects the receiver.
+    drop(provider_receiver);
+    receiver.dispatch_pending_messages();
+    expect_false!(receiver.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, AssociatedOrdering)]
+fn test_associated_ordering() {
+    let (mut factory, mut factory_receiver, calculators) = bind_calculator_factory();
+    let group = factory.associated_group();
+    let (first, receiver) = group.new_endpoint_and_pass_receiver::<dyn Calculator>();
+    factory.create_calculator(receiver);
+    let mut first = Remote::new_associated(first);
+    first.clear();
+    let (_second, receiver) = group.new_endpoint_and_pass_receiver::<dyn Calculator>();
+    factory.create_calculator(receiver);
+
+    // The factory can't read its second request until the first calculator
+    // has read the request sent before it.
+    factory_receiver.dispatch_pending_messages();
+    expect_eq!(calculators.borrow().len(), 1);
+    calculators.borrow_mut()[0].0.dispatch_pending_messages();
+    factory_receiver.dispatch_pending_messages();
+    expect_eq!(calculators.borrow().len(), 2);
+
+    // Once the first calculator is closed, the messages for it no longer
+    // hold up the others.
+    first.clear();
+    let (_third, receiver) = group.new_endpoint_and_pass_receiver::<dyn Calculator>();
+    factory.create_calculator(receiver);
+    calculators.borrow_mut().remove(0);
+    factory_receiver.dispatch_pending_messages();
+    expect_eq!(calculators.borrow().len(), 2);
+}
+
+#[gtest(MojoBindingsTestSuite, AssociatedDisconnect)]
+fn test_associated_disconnect() {
+    let (mut factory, mut factory_receiver, calculators) = bind_calculator_factory();
+    let factory_disconnected = Rc::new(Cell::new(false));
+    let factory_disconnected_clone = factory_disconnected.clone();
+    factory_receiver.set_disconnect_handler(move || factory_disconnected_clone.set(true));
+
+    // Dropping an associated remote disconnects its receiver, but not the
+    // rest of the pipe.
+    let (calculator, receiver) =
+        factory.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+    factory.create_calculator(receiver);
+    factory_receiver.dispatch_pending_messages();
+    let calculator_disconnected = Rc::new(Cell::new(false));
+    let calculator_disconnected_clone = calculator_disconnected.clone();
+    calculators.borrow_mut()[0]
+        .0
+        .set_disconnect_handler(move || calculator_disconnected_clone.set(true));
+    drop(calculator);
+    calculators.borrow_mut()[0].0.dispatch_pending_messages();
+    expect_true!(calculator_disconnected.get());
+    factory_receiver.dispatch_pending_messages();
+    expect_false!(factory_disconnected.get());
+    expect_true!(factory.is_connected());
+
+    // If the receiver is never sent, the remote is disconnected.
+    let (calculator, receiver) =
+        factory.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+    let mut calculator = Remote::new_associated(calculator);
+    drop(receiver);
+    calculator.dispatch_pending_messages();
+    expect_false!(calculator.is_connected());
+
+    // If the factory's receiver drops the calculator's receiver, the remote
+    // finds out.
+    let (calculator, receiver) =
+        factory.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+    let mut calculator = Remote::new_associated(calculator);
+    factory.create_calculator(receiver);
+    factory_receiver.dispatch_pending_messages();
+    calculators.borrow_mut().clear();
+    calculator.dispatch_pending_messages();
+    expect_false!(calculator.is_connected());
+
+    // Closing the pipe disconnects every interface on it.
+    let (calculator, receiver) =
+        factory.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+    let mut calculator = Remote::new_associated(calculator);
+    factory.create_calculator(receiver);
+    factory_receiver.dispatch_pending_messages();
+    drop(factory_receiver);
+    calculators.borrow_mut().clear();
+    factory.dispatch_pending_messages();
+    calculator.dispatch_pending_messages();
+    expect_false!(factory.is_connected());
+    expect_false!(calculator.is_connected());
+    // Associated interfaces created now start disconnected.
+    let (calculator, _) =
+        factory.associated_group().new_endpoint_and_pass_receiver::<dyn Calculator>();
+    expect_false!(Remote::new_associated(calculator).is_connected());
+}
+
+/// Echoes values back, or forwards them to another `Echo` with a sync call,
+/// and adds one to its answer.
+struct EchoImpl {
+    forward_to: Option<Remote<dyn Echo>>,
+}
+
+impl Echo for EchoImpl {
+    fn echo(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        match &mut self.forward_to {
+            Some(remote) => callback(remote.echo_sync(value).unwrap() + 1),
+            None => callback(value),
+        }
+    }
+
+    fn echo_no_interrupt(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        callback(value);
+    }
+}
+
+fn bind_echo(forward_to: Option<Remote<dyn Echo>>) -> (Remote<dyn Echo>, Receiver<dyn Echo>) {
+    let (pending_remote, pending_receiver) = create_interface_pipe::<dyn Echo>().unwrap();
+    let receiver =
+        Receiver::new(Box::new(EchoImpl { forward_to }) as Box<dyn Echo>, pending_receiver);
+    (Remote::new(pending_remote), receiver)
+}
+
+/// Logs each request, along with the context of the receiver that got it.
+struct LoggingCalculator {
+    current: CurrentReceiver<&'static str>,
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+impl Calculator for LoggingCalculator {
+    fn add(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        self.log.borrow_mut().push(format!("{} adds {}", self.current.current_context(), value));
+        callback(value);
+    }
+
+    fn clear(&mut self) {
+        self.log.borrow_mut().push(format!("{} clears", self.current.current_context()));
+    }
+
+    fn translate(&mut self, point: Point, _delta: Point, callback: Box<dyn FnOnce(Point, String)>) {
+        callback(point, String::new());
+    }
+
+    fn subtract(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        callback(-value);
+    }
+}
+
+/// Add a receiver named `name` to `receivers`, and return its remote and ID.
+fn add_logging_calculator(
+    receivers: &mut ReceiverSet<dyn Calculator, &'static str>,
+    name: &'static str,
+    log: &Rc<RefCell<Vec<String>>>,
+) -> (Remote<dyn Calculator>, ReceiverId) {
+    let (pending_remote, pending_receiver) = create_interface_pipe::<dyn Calculator>().unwrap();
+    let implementation = LoggingCalculator { current: receivers.current(), log: log.clone() };
+    let id = receivers.add(Box::new(implementation), pending_receiver, name);
+    (Remote::new(pending_remote), id)
+}
+
+#[gtest(MojoBindingsTestSuite, ReceiverSet)]
+fn test_receiver_set() {
+    let mut receivers = ReceiverSet::new();
+    let log = Rc::new(RefCell::new(vec![]));
+    let disconnected = Rc::new(RefCell::new(vec![]));
+    let disconnected_clone = disconnected.clone();
+    receivers.set_disconnect_handler(move |_, name| disconnected_clone.borrow_mut().push(*name));
+    let (mut alice, alice_id) = add_logging_calculator(&mut receivers, "alice", &log);
+    let (mut bob, bob_id) = add_logging_calculator(&mut receivers, "bob", &log);
+    expect_eq!(receivers.len(), 2);
+
+    // Each request is dispatched with the context of the receiver that got
+    // it.
+    bob.add(1, Box::new(|_| {}));
+    alice.clear();
+    bob.clear();
+    receivers.dispatch_pending_messages();
+    expect_eq!(*log.borrow(), vec!["alice clears", "bob adds 1", "bob clears"]);
+
+    // Receivers are removed once they're disconnected.
+    drop(alice);
+    receivers.dispatch_pending_messages();
+    expect_false!(receivers.contains(alice_id));
+    expect_eq!(*disconnected.borrow(), vec!["alice"]);
+
+    // Removing a receiver closes it, without calling the disconnect handler.
+    expect_true!(receivers.remove(bob_id));
+    expect_false!(receivers.remove(bob_id));
+    bob.dispatch_pending_messages();
+    expect_false!(bob.is_connected());
+    expect_true!(receivers.is_empty());
+    expect_eq!(*disconnected.borrow(), vec!["alice"]);
+
+    // IDs aren't reused.
+    let (_carol, carol_id) = add_logging_calculator(&mut receivers, "carol", &log);
+    expect_ne!(carol_id, alice_id);
+    expect_ne!(carol_id, bob_id);
+}
+
+/// Echoes values plus the context of its receiver in a `ReceiverSet`, after
+/// forwarding them to another `Echo` with a sync call, if it has one.
+struct ContextEcho {
+    current: CurrentReceiver<i32>,
+    forward_to: Option<Remote<dyn Echo>>,
+}
+
+impl Echo for ContextEcho {
+    fn echo(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        let value = match &mut self.forward_to {
+            Some(remote) => remote.echo_sync(value).unwrap(),
+            None => value,
+        };
+        callback(value + *self.current.current_context());
+    }
+
+    fn echo_no_interrupt(&mut self, value: i32, callback: Box<dyn FnOnce(i32)>) {
+        callback(value);
+    }
+}
+
+#[gtest(MojoBindingsTestSuite, ReceiverSetSyncCalls)]
+fn test_receiver_set_sync_calls() {
+    let _allow = ScopedAllowSyncCallForTesting::new();
+    let mut receivers = ReceiverSet::<dyn Echo, i32>::new();
+
+    // A sync call dispatches the set's sync requests while it waits, with the
+    // context of the receiver that got each one.
+    let (inner, pending_receiver) = create_interface_pipe::<dyn Echo>().unwrap();
+    let implementation = ContextEcho { current: receivers.current(), forward_to: None };
+    receivers.add(Box::new(implementation), pending_receiver, 10);
+    let mut inner = Remote::new(inner);
+    expect_eq!(inner.echo_sync(1).unwrap(), 11);
+
+    // Including when the sync call interrupts the set dispatching a request to
+    // another of its receivers, whose context is restored afterwards.
+    let (outer, pending_receiver) = create_interface_pipe::<dyn Echo>().unwrap();
+    let implementation = ContextEcho { current: receivers.current(), forward_to: Some(inner) };
+    receivers.add(Box::new(implementation), pending_receiver, 100);
+    let mut outer = Remote::new(outer);
+    let result = Rc::new(Cell::new(0));
+    let result_clone = result.clone();
+    outer.echo(1, Box::new(move |value| result_clone.set(value)));
+    receivers.dispatch_pending_messages();
+    outer.dispatch_pending_messages();
+    expect_eq!(result.get(), 111);
+}
+
+#[gtest(MojoBindingsTestSuite, RemoteSet)]
+fn test_remote_set() {
+    let mut remotes = RemoteSet::<dyn Calculator>::new();
+    let disconnected = Rc::new(RefCell::new(vec![]));
+    let disconnected_clone = disconnected.clone();
+    remotes.set_disconnect_handler(move |id| disconnected_clone.borrow_mut().push(id));
+    let mut receivers = vec![];
+    let mut ids = vec![];
+    for _ in 0..3 {
+        let (pending_remote, pending_receiver) = create_interface_pipe::<dyn Calculator>().unwrap();
+        let total = Rc::new(Cell::new(0));
+        receivers.push((
+            Receiver::new(
+                Box::new(CalculatorImpl { total: total.clone() }) as Box<dyn Calculator>,
+                pending_receiver,
+            ),
+            total,
+        ));
+        ids.push(remotes.add(pending_remote));
+    }
+
+    // Calls can be broadcast to every remote.
+    for remote in remotes.iter_mut() {
+        remote.add(2, Box::new(|_| {}));
+    }
+    for (receiver, total) in &mut receivers {
+        receiver.dispatch_pending_messages();
+        expect_eq!(total.get(), 2);
+    }
+
+    // Remotes are removed once they're disconnected.
+    receivers.remove(1);
+    remotes.dispatch_pending_messages();
+    expect_eq!(remotes.len(), 2);
+    expect_false!(remotes.contains(ids[1]));
+    expect_eq!(*disconnected.borrow(), vec![ids[1]]);
+
+    // Removing a remote closes it, and can say why.
+    let reason = Rc::new(RefCell::new(None));
+    let reason_clone = reason.clone();
+    receivers[1].0.set_disconnect_with_reason_handler(move |r| *reason_clone.borrow_mut() = r);
+    expect_true!(remotes.remove_with_reason(ids[2], 1, "Unsubscribed"));
+    receivers[1].0.dispatch_pending_messages();
+    expect_eq!(
+        *reason.borrow(),
+        Some(DisconnectReason { custom_reason: 1, description: "Unsubscribed".to_string() })
+    );
+    expect_true!(remotes.get(ids[0]).is_some_and(|remote| remote.is_connected()));
+    expect_eq!(remotes.len(), 1);
+}
+
+/// An interface broker, which binds the receivers that clients send it with
+/// its binders.
+struct BrokerImpl {
+    binders: BinderMap,
+}
+
+impl Binder for BrokerImpl {
+    fn bind(&mut self, receiver: GenericPendingReceiver) {
+        if let Err(receiver) = self.binders.try_bind(receiver) {
+            report_bad_message(&format!("No binder for {}", receiver.interface_name()));
+        }
+    }
+}
+
+#[gtest(MojoBindingsTestSuite, GenericPendingReceiver)]
+fn test_generic_pending_receiver() {
+    let (_, pending_receiver) = create_interface_pipe::<dyn Calculator>().unwrap();
+    let receiver = GenericPendingReceiver::from(pending_receiver);
+    expect_eq!(receiver.interface_name(), "rust_bindings.test.mojom.Calculator");
+
+    // Taking it as the wrong interface gives it back.
+    let Err(receiver) = receiver.take::<dyn Echo>() else {
+        panic!("A Calculator receiver was taken as an Echo receiver");
+    };
+    expect_true!(receiver.take::<dyn Calculator>().is_ok());
+}
+
+#[gtest(MojoBindingsTestSuite, BinderMap)]
+fn test_binder_map() {
+    // The broker binds calculators on this thread, and echoes on another.
+    let calculators = Rc::new(RefCell::new(ReceiverSet::<dyn Calculator>::new()));
+    let calculators_clone = calculators.clone();
+    let mut binders = BinderMap::new();
+    binders.add::<dyn Calculator>(move |receiver| {
+        let total = Rc::new(Cell::new(0));
+        calculators_clone.borrow_mut().add(Box::new(CalculatorImpl { total }), receiver, ());
+    });
+    let (echo_sender, echo_receivers) = std::sync::mpsc::channel();
+    binders.add_with_sender::<dyn Echo>(echo_sender);
+    expect_true!(binders.contains("rust_bindings.test.mojom.Echo"));
+    expect_false!(binders.contains("rust_bindings.test.mojom.CalculatorFactory"));
+
+    let (pending_broker, broker_receiver) = create_interface_pipe::<dyn Binder>().unwrap();
+    let mut broker =
+        Receiver::new(Box::new(BrokerImpl { binders }) as Box<dyn Binder>, broker_receiver);
+    let mut client = Remote::new(pending_broker);
+
+    let (pending_calculator, calculator_receiver) =
+        create_interface_pipe::<dyn Calculator>().unwrap();
+    client.bind(calculator_receiver.into());
+    let (pending_echo, echo_receiver) = create_interface_pipe::<dyn Echo>().unwrap();
+    client.bind(echo_receiver.into());
+    broker.dispatch_pending_messages();
+    expect_eq!(calculators.borrow().len(), 1);
+
+    let mut calculator = Remote::new(pending_calculator);
+    let total = Rc::new(Cell::new(0));
+    let total_clone = total.clone();
+    calculator.add(3, Box::new(move |result| total_clone.set(result)));
+    calculators.borrow_mut().dispatch_pending_messages();
+    calculator.dispatch_pending_messages();
+    expect_eq!(total.get(), 3);
+
+    // The other thread takes the echo receiver from the channel, and answers
+    // the request which is already waiting for it.
+    let mut echo = Remote::new(pending_echo);
+    let echoed = Rc::new(Cell::new(0));
+    let echoed_clone = echoed.clone();
+    echo.echo(5, Box::new(move |result| echoed_clone.set(result)));
+    std::thread::spawn(move || {
+        let receiver = echo_receivers.recv().unwrap().take::<dyn Echo>().unwrap();
+        Receiver::new(Box::new(EchoImpl { forward_to: None }) as Box<dyn Echo>, receiver)
+            .dispatch_pending_messages();
+    })
+    .join()
+    .unwrap();
+    echo.dispatch_pending_messages();
+    expect_eq!(echoed.get(), 5);
+
+    // The broker can't bind interfaces that it has no binder for.
+    let (_, factory_receiver) = create_interface_pipe::<dyn CalculatorFactory>().unwrap();
+    client.bind(factory_receiver.into());
+    broker.dispatch_pending_messages();
+    expect_false!(broker.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, SyncCalls)]
+fn test_sync_calls() {
+    let _allow = ScopedAllowSyncCallForTesting::new();
+
+    // The receiver is on this thread, so its sync requests are dispatched
+    // while we wait, ahead of the async request that was sent first.
+    let (mut remote, mut receiver) = bind_echo(None);
+    let responses = Rc::new(RefCell::new(vec![]));
+    let echo_responses = responses.clone();
+    remote.echo(1, Box::new(move |result| echo_responses.borrow_mut().push(result)));
+    expect_eq!(remote.echo_sync(2).unwrap(), 2);
+    expect_eq!(*responses.borrow(), Vec::<i32>::new());
+    receiver.dispatch_pending_messages();
+    remote.dispatch_pending_messages();
+    expect_eq!(*responses.borrow(), vec![1]);
+
+    // A receiver can make a sync call while it's dispatching one.
+    let (inner_remote, _inner_receiver) = bind_echo(None);
+    let (mut outer_remote, _outer_receiver) = bind_echo(Some(inner_remote));
+    expect_eq!(outer_remote.echo_sync(5).unwrap(), 6);
+
+    // [NoInterrupt] calls don't dispatch anything else while they wait, so
+    // the receiver must be on another thread.
+    let (remote_pipe, receiver_pipe) = create_message_pipe().unwrap();
+    let receiver_thread = std::thread::spawn(move || {
+        let mut receiver = Receiver::new(
+            Box::new(EchoImpl { forward_to: None }) as Box<dyn Echo>,
+            PendingReceiver::new(receiver_pipe),
+        );
+        while receiver.is_connected() {
+            receiver.dispatch_pending_messages();
+            std::thread::yield_now();
+        }
+    });
+    let mut threaded_remote = Remote::<dyn Echo>::new(PendingRemote::new(remote_pipe, 0));
+    expect_eq!(threaded_remote.echo_no_interrupt_sync(7).unwrap(), 7);
+    drop(threaded_remote);
+    receiver_thread.join().unwrap();
+
+    // A call fails if the receiver is disconnected while we wait, and the
+    // remote finds out when it next dispatches its messages.
+    let disconnected = Rc::new(Cell::new(false));
+    let disconnected_clone = disconnected.clone();
+    remote.set_disconnect_handler(move || disconnected_clone.set(true));
+    drop(receiver);
+    expect_true!(remote.echo_sync(3).is_err());
+    expect_false!(disconnected.get());
+    remote.dispatch_pending_messages();
+    expect_true!(disconnected.get());
+    expect_false!(remote.is_connected());
+}
+
+#[gtest(MojoBindingsTestSuite, SyncCallRestrictions)]
+fn test_sync_call_restrictions() {
+    let (mut remote, _receiver) = bind_echo(None);
+    expect_true!(SyncCallRestrictions::is_sync_call_allowed());
+    expect_eq!(remote.echo_sync(1).unwrap(), 1);
+
+    // Once sync calls are disallowed, only allowlisted scopes may make them.
+    // Other sync calls panic, which aborts the test binary, so
+    // cpp_interop_unittest.cc checks that in a death test.
+    SyncCallRestrictions::disallow_sync_call();
+    expect_false!(SyncCallRestrictions::is_sync_call_allowed());
+    {
+        let _allow = ScopedAllowSyncCallForTesting::new();
+        expect_true!(SyncCallRestrictions::is_sync_call_allowed());
+        expect_eq!(remote.echo_sync(2).unwrap(), 2);
+    }
+    expect_false!(SyncCallRestrictions::is_sync_call_allowed());
+
+    // Let the other tests in this binary make sync calls again.
+    SyncCallRestrictions::allow_sync_call_for_testing();
+    expect_true!(SyncCallRestrictions::is_sync_call_allowed());
+}
+
+#[gtest(MojoBindingsTestSuite, StructDefaults)]
+fn test_struct_defaults() {
+    use mojom_parser::{Mojom, MojomValue};
+
+    // Generated structs list the defaults that their fields declare.
+    let descriptor = Settings::type_descriptor();
+    expect_eq!(descriptor.field_default(0), Some(&MojomValue::Int32(DEFAULT_SCALE)));
+    expect_eq!(descriptor.field_default(1), Some(&MojomValue::Int64(2)));
+    expect_eq!(
+        descriptor.field_default(2),
+        Some(&MojomValue::String(Settings::DEFAULT_NAME.to_string()))
+    );
+    expect_eq!(descriptor.field_default(5), Some(&MojomValue::Int32(Mode::Scientific as i32)));
+    // `Point origin = default` isn't supported, since Point isn't `Default`.
+    expect_eq!(descriptor.field_default(6), None);
+
+    // Values from version 0 get the default of the field added in version 1.
+    let MojomValue::Struct(mut fields) =
+        MojomValue::default_with_descriptor(&Settings::mojom_type(), &descriptor)
+    else {
+        unreachable!()
+    };
+    fields.pop();
+    let settings = Settings::from_mojom_value(MojomValue::Struct(fields)).unwrap();
+    expect_eq!(
+        settings,
+        Settings {
+            scale: 2,
+            limit: 2,
+            name: "calculator".to_string(),
+            label: Some("none".to_string()),
+            rounding: true,
+            mode: Mode::Scientific,
+            origin: Point { x: 0, y: 0 },
+            precision: -2,
+        }
+    );
+}
+
+#[gtest(MojoBindingsTestSuite, Typemaps)]
+fn test_typemaps() {
+    use mojom_parser::{decode_mojom, encode_mojom, Mojom, MojomValue};
+    use std::collections::HashMap;
+
+    // Typemapped fields use the Rust type, but are encoded as the mojom type.
+    let frame = Frame {
+        size: Dimensions { width: 640, height: 480 },
+        max_size: None,
+        tiles: vec![Dimensions { width: 1, height: 2 }, Dimensions { width: 3, height: 4 }],
+        layers: [("background".to_string(), Dimensions { width: 5, height: 6 })].into(),
+    };
+    let MojomValue::Struct(fields) = frame.clone().into_mojom_value() else { unreachable!() };
+    expect_eq!(fields[0], Size { width: 640, height: 480 }.into_mojom_value());
+    expect_eq!(fields[1], MojomValue::Null);
+    expect_eq!(decode_mojom::<Frame>(&encode_mojom(frame.clone()).unwrap()).unwrap(), frame);
+
+    let frame = Frame { max_size: Some(Dimensions { width: 10, height: 20 }), ..frame };
+    expect_eq!(decode_mojom::<Frame>(&encode_mojom(frame.clone()).unwrap()).unwrap(), frame);
+
+    // Decoding fails if the traits reject the value.
+    let invalid = MojomValue::Struct(vec![
+        Size { width: 1, height: 1 }.into_mojom_value(),
+        MojomValue::Null,
+        vec![Size { width: 1, height: -1 }].into_mojom_value(),
+        HashMap::<String, Size>::new().into_mojom_value(),
+    ]);
+    expect_true!(Frame::from_mojom_value(invalid).is_err());
+    let invalid = MojomValue::Struct(vec![
+        Size { width: 1, height: 1 }.into_mojom_value(),
+        MojomValue::Null,
+        Vec::<Size>::new().into_mojom_value(),
+        HashMap::from([("border".to_string(), Size { width: -1, height: 1 })]).into_mojom_value(),
+    ]);
+    expect_true!(Frame::from_mojom_value(invalid).is_err());
+}
+
+#[gtest(MojoBindingsTestSuite, RecursiveTypes)]
+fn test_recursive_types() {
+    use mojom_parser::{decode_mojom, encode_mojom};
+
+    let leaf = |name: &str| Shape { name: name.to_string(), outline: None, parts: vec![] };
+    let shape = Shape {
+        name: "house".to_string(),
+        outline: Some(Box::new(Shape { outline: Some(Box::new(leaf("square"))), ..leaf("box") })),
+        parts: vec![leaf("door"), Shape { parts: vec![leaf("pane")], ..leaf("window") }],
+    };
+    expect_eq!(decode_mojom::<Shape>(&encode_mojom(shape.clone()).unwrap()).unwrap(), shape);
+}
+
+struct BufferReverserImpl;
+
+impl BufferReverser for BufferReverserImpl {
+    fn reverse(
+        &mut self,
+        buffer: mojo_base::BigBuffer,
+        callback: Box<dyn FnOnce(mojo_base::BigBuffer)>,
+    ) {
+        let mut bytes = buffer.as_slice().to_vec();
+        bytes.reverse();
+        callback(bytes.into());
+    }
+}
+
+#[gtest(MojoBindingsTestSuite, Handles)]
+fn test_handles() {
+    let (pending_remote, pending_receiver) = create_interface_pipe::<dyn BufferReverser>().unwrap();
+    let mut remote = Remote::new(pending_remote);
+    let mut receiver =
+        Receiver::new(Box::new(BufferReverserImpl) as Box<dyn BufferReverser>, pending_receiver);
+
+    // Big buffers pass a shared buffer handle in each direction.
+    for size in [16, mojo_base::MAX_INLINE_BYTES + 1] {
+        let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let reversed = Rc::new(RefCell::new(None));
+        let reversed_clone = reversed.clone();
+        remote.reverse(
+            mojo_base::BigBuffer::new(&data),
+            Box::new(move |buffer| *reversed_clone.borrow_mut() = Some(buffer)),
+        );
+        receiver.dispatch_pending_messages();
+        remote.dispatch_pending_messages();
+
+        let reversed = reversed.take().unwrap();
+        expect_eq!(
+            matches!(reversed, mojo_base::BigBuffer::SharedMemory(_)),
+            size > mojo_base::MAX_INLINE_BYTES
+        );
+        let mut expected = data;
+        expected.reverse();
+        expect_eq!(reversed.as_slice(), &expected[..]);
+    }
+    expect_true!(remote.is_connected());
+    expect_true!(receiver.is_connected());
+}
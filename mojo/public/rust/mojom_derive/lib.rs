@@ -0,0 +1,571 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Implements `#[derive(Mojom)]`, which implements the `mojom_parser::Mojom`
+//! trait for a Rust struct or enum.
+//!
+//! For a struct, the fields are the fields of the mojom struct, in ordinal
+//! order. Each field's type must implement `Mojom` itself. Fields accept the
+//! following attributes:
+//! - `#[mojom(nullable)]`: The field is nullable. Its Rust type must be
+//!   `Option<T>`, where `T` is a struct, array, string or union type.
+//! - `#[mojom(min_version = N)]`: The field was added in version N of the
+//!   struct. Versions must not decrease from one field to the next. If a
+//!   value from an older version doesn't have the field, it gets its default
+//!   value.
+//! - `#[mojom(default = EXPR)]`: The default value that the field declares in
+//!   the mojom file. `EXPR` must have the field's type. The
+//!   default is listed in the struct's `TypeDescriptor`, and fields without
+//!   one default to their type's `Default` value.
+//! - `#[mojom(typemap = T)]`: The field's type is mapped onto the mojom type of
+//!   `T`, which must implement `Mojom`, by implementing
+//!   `mojom_parser::MojomTraits<T>`. This is how the generator applies
+//!   typemaps. If the field is also nullable, `Option`'s argument implements
+//!   it instead.
+//! - `#[mojom(recursive)]`: The field's type contains the struct itself, so
+//!   its `MojomType` is only built when it's needed (see
+//!   `mojom_parser::MojomType::Recursive`). The field must be a struct, array
+//!   or map, and like in any recursive Rust type, a struct which holds itself
+//!   must be boxed. Every loop of types must have a field like this in it.
+//!
+//! An enum with only unit variants is a mojom enum, and is encoded as an int32
+//! using each variant's discriminant. It accepts the following attributes:
+//! - `#[mojom(extensible)]`, on the enum: Unknown values are mapped to the
+//!   default variant rather than rejected.
+//! - `#[mojom(default)]`, on a variant: The default variant. Extensible enums
+//!   must have one.
+//!
+//! An enum whose variants each hold a single unnamed field is a mojom union.
+//! The variants are the fields of the union, in ordinal (i.e. tag) order, and
+//! accept `#[mojom(nullable)]`, `#[mojom(typemap = T)]` and
+//! `#[mojom(recursive)]` just like struct fields, except that a recursive
+//! field may also be a union. Each field's name is its variant's name in
+//! snake_case.
+//!
+//! Rust unions aren't supported.
+//!
+//! The generated code refers to the `mojom_parser` crate by name, so it must be
+//! imported as `mojom_parser` at the root of the crate using the macro.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DataEnum, DataStruct, DeriveInput, Error, Expr, Fields,
+    GenericArgument, LitInt, PathArguments, Token, Type,
+};
+
+#[proc_macro_derive(Mojom, attributes(mojom))]
+pub fn derive_mojom(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let result = match &input.data {
+        Data::Struct(data) => derive_struct(&input, data),
+        Data::Enum(data) => derive_enum(&input, data),
+        Data::Union(_) => Err(Error::new(
+            input.ident.span(),
+            "#[derive(Mojom)] doesn't support Rust unions. Mojom unions are Rust enums",
+        )),
+    };
+    result.unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// The options given by the `#[mojom(...)]` attributes on a single item.
+#[derive(Default)]
+struct MojomAttrs {
+    nullable: bool,
+    min_version: Option<u32>,
+    extensible: bool,
+    default: bool,
+    default_value: Option<Expr>,
+    typemap: Option<Type>,
+    recursive: bool,
+}
+
+impl MojomAttrs {
+    /// Parse all the `#[mojom(...)]` attributes in `attrs`.
+    fn parse(attrs: &[Attribute]) -> Result<MojomAttrs, Error> {
+        let mut ret = MojomAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("mojom")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("nullable") {
+                    ret.nullable = true;
+                } else if meta.path.is_ident("min_version") {
+                    ret.min_version = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                } else if meta.path.is_ident("extensible") {
+                    ret.extensible = true;
+                } else if meta.path.is_ident("default") && meta.input.peek(Token![=]) {
+                    ret.default_value = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default") {
+                    ret.default = true;
+                } else if meta.path.is_ident("typemap") {
+                    ret.typemap = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("recursive") {
+                    ret.recursive = true;
+                } else {
+                    return Err(meta.error("Unknown mojom attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(ret)
+    }
+
+    /// Fail if any of the options which aren't in `allowed` were given.
+    /// `item_kind` describes the item the attributes are on.
+    fn check_allowed(
+        &self,
+        allowed: &[&str],
+        item_kind: &str,
+        span: proc_macro2::Span,
+    ) -> Result<(), Error> {
+        let given = [
+            ("nullable", self.nullable),
+            ("min_version", self.min_version.is_some()),
+            ("extensible", self.extensible),
+            ("default", self.default),
+            ("default = ...", self.default_value.is_some()),
+            ("typemap", self.typemap.is_some()),
+            ("recursive", self.recursive),
+        ];
+        for (name, is_given) in given {
+            if is_given && !allowed.contains(&name) {
+                return Err(Error::new(
+                    span,
+                    format!("#[mojom({})] isn't allowed on {}", name, item_kind),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// If `ty` is `Option<T>`, return `T`.
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    match args.args.first()? {
+        GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+/// Convert a CamelCase identifier to snake_case.
+fn to_snake_case(ident: &str) -> String {
+    let mut ret = String::new();
+    for (idx, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if idx != 0 {
+                ret.push('_');
+            }
+            ret.extend(c.to_lowercase());
+        } else {
+            ret.push(c);
+        }
+    }
+    ret
+}
+
+/// The generated code for a single field of a struct or union.
+struct FieldConversions {
+    /// The field's `MojomType`.
+    mojom_type: TokenStream,
+    /// The field's `TypeDescriptor`.
+    descriptor: TokenStream,
+    /// Converts the field's value, which is bound to `binding`, to a
+    /// `MojomValue`.
+    into_value: TokenStream,
+    /// Converts a `MojomValue` named `value` to the field's type, returning a
+    /// `MojomResult`.
+    from_value: TokenStream,
+}
+
+/// Generate the conversions for the field `field_name` of the struct or union
+/// `item_name`.
+fn field_conversions(
+    ty: &Type,
+    attrs: &MojomAttrs,
+    binding: &syn::Ident,
+    item_name: &str,
+    field_name: &str,
+) -> Result<FieldConversions, Error> {
+    let mut conversions = if let Some(mojom_ty) = &attrs.typemap {
+        typemapped_field_conversions(ty, mojom_ty, attrs.nullable, binding, item_name, field_name)?
+    } else if attrs.nullable {
+        let inner_ty = option_inner_type(ty)
+            .ok_or_else(|| Error::new(ty.span(), "Nullable fields must have type Option<T>"))?;
+        FieldConversions {
+            mojom_type: quote!(::mojom_parser::nullable_mojom_type::<#inner_ty>()),
+            descriptor: quote!(<#inner_ty as ::mojom_parser::Mojom>::type_descriptor()),
+            into_value: quote!(::mojom_parser::nullable_into_mojom_value(#binding)),
+            from_value: quote!(::mojom_parser::nullable_field_from_mojom_value::<#inner_ty>(
+                value, #item_name, #field_name
+            )),
+        }
+    } else {
+        FieldConversions {
+            mojom_type: quote!(<#ty as ::mojom_parser::Mojom>::mojom_type()),
+            descriptor: quote!(<#ty as ::mojom_parser::Mojom>::type_descriptor()),
+            into_value: quote!(::mojom_parser::Mojom::into_mojom_value(#binding)),
+            from_value: quote!(::mojom_parser::field_from_mojom_value::<#ty>(
+                value, #item_name, #field_name
+            )),
+        }
+    };
+    if attrs.recursive {
+        // The type whose definition the field's mojom type is.
+        let definition_ty = match (&attrs.typemap, attrs.nullable) {
+            (Some(mojom_ty), _) => mojom_ty,
+            (None, true) => option_inner_type(ty).expect("Checked above"),
+            (None, false) => ty,
+        };
+        let recursive_type = quote!(::mojom_parser::RecursiveType::of::<#definition_ty>());
+        let mojom_type = quote!(::mojom_parser::MojomType::Recursive(#recursive_type));
+        conversions.mojom_type = if attrs.nullable {
+            quote!(::mojom_parser::MojomType::Nullable(Box::new(#mojom_type)))
+        } else {
+            mojom_type
+        };
+        conversions.descriptor = quote!(::mojom_parser::TypeDescriptor::Recursive(#recursive_type));
+    }
+    Ok(conversions)
+}
+
+/// Generate the conversions for a field whose type `ty` is mapped onto the
+/// mojom type of `mojom_ty`.
+fn typemapped_field_conversions(
+    ty: &Type,
+    mojom_ty: &Type,
+    nullable: bool,
+    binding: &syn::Ident,
+    item_name: &str,
+    field_name: &str,
+) -> Result<FieldConversions, Error> {
+    let descriptor = quote!(<#mojom_ty as ::mojom_parser::Mojom>::type_descriptor());
+    Ok(if nullable {
+        let inner_ty = option_inner_type(ty)
+            .ok_or_else(|| Error::new(ty.span(), "Nullable fields must have type Option<T>"))?;
+        FieldConversions {
+            mojom_type: quote!(::mojom_parser::nullable_mojom_type::<#mojom_ty>()),
+            descriptor,
+            into_value: quote!(
+                ::mojom_parser::nullable_typemapped_into_mojom_value::<#mojom_ty, #inner_ty>(
+                    #binding
+                )
+            ),
+            from_value: quote!(
+                ::mojom_parser::nullable_typemapped_field_from_mojom_value::<#mojom_ty, #inner_ty>(
+                    value, #item_name, #field_name
+                )
+            ),
+        }
+    } else {
+        FieldConversions {
+            mojom_type: quote!(<#mojom_ty as ::mojom_parser::Mojom>::mojom_type()),
+            descriptor,
+            into_value: quote!(
+                ::mojom_parser::typemapped_into_mojom_value::<#mojom_ty, #ty>(#binding)
+            ),
+            from_value: quote!(::mojom_parser::typemapped_field_from_mojom_value::<#mojom_ty, #ty>(
+                value, #item_name, #field_name
+            )),
+        }
+    })
+}
+
+fn derive_struct(input: &DeriveInput, data: &DataStruct) -> Result<TokenStream, Error> {
+    MojomAttrs::parse(&input.attrs)?.check_allowed(&[], "structs", input.ident.span())?;
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut field_types = vec![];
+    let mut field_descriptors = vec![];
+    let mut into_values = vec![];
+    let mut from_values = vec![];
+    let mut field_defaults = vec![];
+    let mut has_defaults = false;
+    let mut min_versions = vec![];
+    let mut members = vec![];
+    let mut bindings = vec![];
+    let mut num_required_fields: usize = 0;
+    let mut last_version = 0;
+    for (idx, field) in data.fields.iter().enumerate() {
+        let attrs = MojomAttrs::parse(&field.attrs)?;
+        attrs.check_allowed(
+            &["nullable", "min_version", "default = ...", "typemap", "recursive"],
+            "struct fields",
+            field.span(),
+        )?;
+
+        let member = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let idx = syn::Index::from(idx);
+                quote!(#idx)
+            }
+        };
+        let field_name = match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => idx.to_string(),
+        };
+        let binding = format_ident!("field_{}", idx);
+
+        let min_version = attrs.min_version.unwrap_or(0);
+        if min_version < last_version {
+            return Err(Error::new(
+                field.span(),
+                format!(
+                    "Field {} has min_version {}, but an earlier field has min_version {}",
+                    field_name, min_version, last_version
+                ),
+            ));
+        }
+        last_version = min_version;
+        min_versions.push(min_version);
+        if min_version == 0 {
+            num_required_fields += 1;
+        }
+
+        let FieldConversions { mojom_type, descriptor, into_value, from_value } =
+            field_conversions(&field.ty, &attrs, &binding, &name_str, &field_name)?;
+        field_types.push(mojom_type);
+        field_descriptors.push(quote!((#field_name.to_string(), #descriptor)));
+        let field_ty = &field.ty;
+        let default = match &attrs.default_value {
+            Some(expr) => quote!({
+                let default: #field_ty = #expr;
+                default
+            }),
+            None => quote!(<#field_ty as ::core::default::Default>::default()),
+        };
+        has_defaults |= attrs.default_value.is_some();
+        field_defaults.push(match &attrs.default_value {
+            Some(_) => quote!(Some({
+                let #binding = #default;
+                #into_value
+            })),
+            None => quote!(None),
+        });
+        into_values.push(into_value);
+        // Fields which were added in later versions may be missing.
+        from_values.push(if min_version == 0 {
+            quote! {
+                let value = fields.next().expect("Checked by struct_fields_from_mojom_value");
+                #from_value?
+            }
+        } else {
+            quote! {
+                match fields.next() {
+                    Some(value) => #from_value?,
+                    None => #default,
+                }
+            }
+        });
+        members.push(member);
+        bindings.push(binding);
+    }
+    let num_fields = members.len();
+    // Leave the defaults empty if no field declares one.
+    if !has_defaults {
+        field_defaults.clear();
+    }
+    // Likewise the versions, if every field is in version 0.
+    if last_version == 0 {
+        min_versions.clear();
+    }
+    let construct = if num_fields == 0 {
+        quote!(Ok(Self {}))
+    } else {
+        quote! {
+            let mut fields = fields.into_iter();
+            Ok(Self { #(#members: { #from_values }),* })
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::mojom_parser::Mojom for #name #ty_generics #where_clause {
+            fn mojom_type() -> ::mojom_parser::MojomType {
+                ::mojom_parser::MojomType::Struct {
+                    fields: vec![#(#field_types),*],
+                    min_versions: vec![#(#min_versions),*],
+                }
+            }
+
+            fn type_descriptor() -> ::mojom_parser::TypeDescriptor {
+                ::mojom_parser::TypeDescriptor::Struct {
+                    name: #name_str.to_string(),
+                    fields: vec![#(#field_descriptors),*],
+                    defaults: vec![#(#field_defaults),*],
+                }
+            }
+
+            fn into_mojom_value(self) -> ::mojom_parser::MojomValue {
+                let Self { #(#members: #bindings),* } = self;
+                ::mojom_parser::MojomValue::Struct(vec![#(#into_values),*])
+            }
+
+            fn from_mojom_value(
+                value: ::mojom_parser::MojomValue,
+            ) -> ::mojom_parser::MojomResult<Self> {
+                let fields = ::mojom_parser::struct_fields_from_mojom_value(
+                    value,
+                    #name_str,
+                    #num_fields,
+                    #num_required_fields,
+                )?;
+                #construct
+            }
+        }
+    })
+}
+
+fn derive_enum(input: &DeriveInput, data: &DataEnum) -> Result<TokenStream, Error> {
+    let is_union_variant =
+        |fields: &Fields| matches!(fields, Fields::Unnamed(f) if f.unnamed.len() == 1);
+    if !data.variants.is_empty() && data.variants.iter().all(|v| is_union_variant(&v.fields)) {
+        return derive_union(input, data);
+    }
+    let enum_attrs = MojomAttrs::parse(&input.attrs)?;
+    enum_attrs.check_allowed(&["extensible"], "enums", input.ident.span())?;
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    if data.variants.is_empty() {
+        return Err(Error::new(name.span(), "Mojom enums must have at least one variant"));
+    }
+    let mut variants = vec![];
+    let mut default_variant = None;
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new(
+                variant.span(),
+                "Mojom enums must have only unit variants, and mojom unions must have only \
+                 variants with a single unnamed field",
+            ));
+        }
+        let attrs = MojomAttrs::parse(&variant.attrs)?;
+        attrs.check_allowed(&["default"], "enum variants", variant.span())?;
+        let ident = &variant.ident;
+        if attrs.default {
+            if default_variant.is_some() {
+                return Err(Error::new(variant.span(), "Enums can only have one default variant"));
+            }
+            default_variant = Some(ident);
+        }
+        variants.push(ident);
+    }
+
+    let default_value = match (enum_attrs.extensible, default_variant) {
+        (true, Some(default_variant)) => quote!(Some(Self::#default_variant as i32)),
+        (true, None) => {
+            return Err(Error::new(
+                name.span(),
+                "Extensible enums must have a variant marked #[mojom(default)]",
+            ));
+        }
+        (false, _) => quote!(None),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::mojom_parser::Mojom for #name #ty_generics #where_clause {
+            fn mojom_type() -> ::mojom_parser::MojomType {
+                ::mojom_parser::MojomType::Int32
+            }
+
+            fn into_mojom_value(self) -> ::mojom_parser::MojomValue {
+                ::mojom_parser::MojomValue::Int32(self as i32)
+            }
+
+            fn from_mojom_value(
+                value: ::mojom_parser::MojomValue,
+            ) -> ::mojom_parser::MojomResult<Self> {
+                let value = ::mojom_parser::enum_from_mojom_value(
+                    value,
+                    #name_str,
+                    &[#(Self::#variants as i32),*],
+                    #default_value,
+                )?;
+                #(
+                    if value == Self::#variants as i32 {
+                        return Ok(Self::#variants);
+                    }
+                )*
+                unreachable!("enum_from_mojom_value only returns known values")
+            }
+        }
+    })
+}
+
+fn derive_union(input: &DeriveInput, data: &DataEnum) -> Result<TokenStream, Error> {
+    MojomAttrs::parse(&input.attrs)?.check_allowed(&[], "unions", input.ident.span())?;
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut variant_types = vec![];
+    let mut variant_descriptors = vec![];
+    let mut into_values = vec![];
+    let mut from_values = vec![];
+    for (tag, variant) in data.variants.iter().enumerate() {
+        let attrs = MojomAttrs::parse(&variant.attrs)?;
+        attrs.check_allowed(
+            &["nullable", "typemap", "recursive"],
+            "union variants",
+            variant.span(),
+        )?;
+        let ident = &variant.ident;
+        let field_name = to_snake_case(&ident.to_string());
+        let field = variant.fields.iter().next().expect("Union variants have one field");
+        let binding = format_ident!("value");
+        let FieldConversions { mojom_type, descriptor, into_value, from_value } =
+            field_conversions(&field.ty, &attrs, &binding, &name_str, &field_name)?;
+        variant_types.push(mojom_type);
+        variant_descriptors.push(quote!((#field_name.to_string(), #descriptor)));
+        into_values.push(quote! {
+            Self::#ident(value) => ::mojom_parser::MojomValue::Union {
+                tag: #tag,
+                value: Box::new(#into_value),
+            }
+        });
+        from_values.push(quote!(#tag => Ok(Self::#ident(#from_value?))));
+    }
+    let num_variants = data.variants.len();
+
+    Ok(quote! {
+        impl #impl_generics ::mojom_parser::Mojom for #name #ty_generics #where_clause {
+            fn mojom_type() -> ::mojom_parser::MojomType {
+                ::mojom_parser::MojomType::Union { variants: vec![#(#variant_types),*] }
+            }
+
+            fn type_descriptor() -> ::mojom_parser::TypeDescriptor {
+                ::mojom_parser::TypeDescriptor::Union {
+                    name: #name_str.to_string(),
+                    variants: vec![#(#variant_descriptors),*],
+                }
+            }
+
+            fn into_mojom_value(self) -> ::mojom_parser::MojomValue {
+                match self {
+                    #(#into_values),*
+                }
+            }
+
+            fn from_mojom_value(
+                value: ::mojom_parser::MojomValue,
+            ) -> ::mojom_parser::MojomResult<Self> {
+                let (tag, value) =
+                    ::mojom_parser::union_variant_from_mojom_value(value, #name_str, #num_variants)?;
+                match tag {
+                    #(#from_values,)*
+                    _ => unreachable!("union_variant_from_mojom_value only returns known tags"),
+                }
+            }
+        }
+    })
+}
@@ -7,17 +7,21 @@
 //! This module provides the ability to represent Mojom types and values as
 //! rust enums.
 
+// The fields of a struct are identified only by their ordinal, which is their
+// index in the struct's definition. Field names aren't needed to encode or
+// decode anything, so they live separately in a TypeDescriptor, which is only
+// used for debugging and pretty-printing.
+//
 // FOR_RELEASE: The current AST is dead simple: standard recursive data
-// structures. We'll probably need an intermediate one as well to represent data
-// on the wire, since it doesn't quite match the MojomValue structure (e.g.
-// packed bitfields). For a more optimized version, we should look into:
+// structures. For a more optimized version, we should look into:
 // - Flat ASTs (using a single vector instead of a nested recursive structure
 // - Mapping between these and rust types that are created by the bindings
 //   generator
-// - Some way to mark fields that doesn't rely on using strings to tag them.
-// - For example, using fixed-length arrays indexed by by the field's ordinal.
-//   This is much less intuitive, though, so it should wait until we're certain
-//   things are already working.
+
+use crate::convert::Mojom;
+
+use std::any::TypeId;
+use std::borrow::Cow;
 
 /// Representation of a type that can appear in a .mojom file.
 ///
@@ -37,18 +41,143 @@ pub enum MojomType {
     UInt32,
     Int64,
     UInt64,
+    Float,
+    Double,
     String,
-    Struct { fields: Vec<(String, MojomType)> },
+    /// The types of the struct's fields, indexed by ordinal.
+    Struct {
+        fields: Vec<MojomType>,
+        /// The version of the struct which added each field, indexed by
+        /// ordinal. Empty if every field is in version 0.
+        min_versions: Vec<u32>,
+    },
     // Mojom has separate sized/unsized array types; we could have two variants here, but
     // rust's type system can't enforce that the length is correct so there's little point.
-    Array { element_type: Box<MojomType>, num_elements: Option<usize> },
+    Array {
+        element_type: Box<MojomType>,
+        num_elements: Option<usize>,
+    },
+    /// The types of the union's fields, indexed by tag (i.e. their ordinal).
+    // FOR_RELEASE: Extensible unions, which map unknown tags to a default
+    // field, aren't supported yet.
+    Union {
+        variants: Vec<MojomType>,
+    },
+    /// A `pending_remote<I>`, which is a message pipe and the version of the
+    /// interface that the receiver implements. A `pending_receiver<I>` is just
+    /// a message pipe, so it's a `Handle`.
+    PendingRemote,
+    /// A `pending_associated_remote<I>`, which shares its message pipe with
+    /// the interface it's sent over.
+    PendingAssociatedRemote,
+    /// A `pending_associated_receiver<I>`.
+    PendingAssociatedReceiver,
+    /// Any kind of handle, e.g. `handle` or `handle<shared_buffer>`, which
+    /// are all encoded the same way.
+    Handle,
+    /// A type whose values may also be null.
+    ///
+    /// Nullable value kinds (bools and numbers) are encoded as a has-value
+    /// flag followed by the value, so they can only be struct fields.
+    // FOR_RELEASE: Arrays of nullable value kinds, which C++ encodes as a
+    // bitfield of has-value flags followed by the values, aren't supported.
+    Nullable(Box<MojomType>),
+    /// A type which contains itself, e.g. a struct with an array of the same
+    /// struct. Its definition is only built when it's needed (see
+    /// `resolve`), so that the `MojomType` is finite.
+    ///
+    /// It's always encoded as a pointer, so it must be a struct, array or
+    /// string, or a union which is a field of another union.
+    Recursive(RecursiveType),
+}
+
+/// The definition of a recursive type, which is the `MojomType` and
+/// `TypeDescriptor` of some Rust type. See `MojomType::Recursive`.
+#[derive(Clone, Copy)]
+pub struct RecursiveType {
+    type_id: TypeId,
+    type_name: &'static str,
+    mojom_type: fn() -> MojomType,
+    type_descriptor: fn() -> TypeDescriptor,
+}
+
+impl RecursiveType {
+    /// The definition of `T`'s mojom type.
+    pub fn of<T: Mojom + 'static>() -> RecursiveType {
+        RecursiveType {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            mojom_type: T::mojom_type,
+            type_descriptor: T::type_descriptor,
+        }
+    }
+
+    pub fn mojom_type(&self) -> MojomType {
+        (self.mojom_type)()
+    }
+
+    pub fn type_descriptor(&self) -> TypeDescriptor {
+        (self.type_descriptor)()
+    }
+}
+
+/// Two recursive types are the same if they're defined by the same Rust type.
+impl PartialEq for RecursiveType {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id
+    }
+}
+
+impl std::fmt::Debug for RecursiveType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.type_name)
+    }
+}
+
+impl MojomType {
+    /// The type of a `map<K, V>`, which is encoded as a struct with two
+    /// arrays of the same length: the keys, and then the values.
+    pub fn map(key_type: MojomType, value_type: MojomType) -> MojomType {
+        let array = |element_type| MojomType::Array {
+            element_type: Box::new(element_type),
+            num_elements: None,
+        };
+        MojomType::Struct { fields: vec![array(key_type), array(value_type)], min_versions: vec![] }
+    }
+
+    /// Whether the type is a value kind, i.e. a bool or a number. Enums are
+    /// represented as `Int32`s, so they're value kinds too.
+    pub fn is_value_kind(&self) -> bool {
+        matches!(
+            self,
+            MojomType::Bool
+                | MojomType::Int8
+                | MojomType::UInt8
+                | MojomType::Int16
+                | MojomType::UInt16
+                | MojomType::Int32
+                | MojomType::UInt32
+                | MojomType::Int64
+                | MojomType::UInt64
+                | MojomType::Float
+                | MojomType::Double
+        )
+    }
+
+    /// If this is a `Recursive` type, return its definition, which is one
+    /// level deep. Otherwise, return the type as is.
+    pub fn resolve(&self) -> Cow<'_, MojomType> {
+        match self {
+            MojomType::Recursive(recursive_type) => Cow::Owned(recursive_type.mojom_type()),
+            _ => Cow::Borrowed(self),
+        }
+    }
 }
 
 /// Representation of a value of a MojomType. These are what get encoded/decoded
 /// into/from Mojom messages.
-// FOR_RELEASE: For the first iteration of the parser where we don't worry
-// about trying to be zero-copy, we just have this type own all its data.
-// We should migrate to a view type when we figure out how.
+// This type owns all its data. For a zero-copy alternative which borrows from
+// the encoded message, see MojomValueRef in view.rs.
 #[derive(Debug, Clone, PartialEq)]
 pub enum MojomValue {
     Bool(bool),
@@ -60,10 +189,142 @@ pub enum MojomValue {
     UInt32(u32),
     Int64(i64),
     UInt64(u64),
+    Float(f32),
+    Double(f64),
     String(String),
-    Struct(Vec<(String, MojomValue)>),
+    /// The values of the struct's fields, indexed by ordinal.
+    Struct(Vec<MojomValue>),
     // Invariant: all MojomValues in the array are the same type.
     Array(Vec<MojomValue>),
+    /// The tag of the union's active field, and that field's value.
+    Union {
+        tag: Ordinal,
+        value: Box<MojomValue>,
+    },
+    /// A remote, as the index of its message pipe in the message's list of
+    /// handles, and the version of the interface that the receiver
+    /// implements. See `INVALID_HANDLE_INDEX`.
+    PendingRemote {
+        index: u32,
+        version: u32,
+    },
+    /// An associated remote, as the index of its interface ID in the
+    /// message's list of interface IDs, and the version of the interface
+    /// that the receiver implements. See `INVALID_ENDPOINT_INDEX`.
+    PendingAssociatedRemote {
+        index: u32,
+        version: u32,
+    },
+    /// An associated receiver, as the index of its interface ID in the
+    /// message's list of interface IDs.
+    PendingAssociatedReceiver {
+        index: u32,
+    },
+    /// A handle, as its index in the message's list of handles, which are
+    /// sent alongside the message's bytes. See `INVALID_HANDLE_INDEX`.
+    Handle {
+        index: u32,
+    },
+    /// The value of a nullable type which is absent. Values of nullable types
+    /// which are present are represented the same way as non-nullable ones.
+    Null,
+}
+
+/// Human-readable names for a MojomType, used only for debugging and
+/// pretty-printing. Parsing and deparsing never look at it.
+///
+/// A descriptor mirrors the structure of the MojomType it describes, except
+/// that nullable types share the descriptor of the type they wrap.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum TypeDescriptor {
+    /// A type with no names of its own, such as a primitive. This is also a
+    /// reasonable placeholder for any type whose names are unknown.
+    #[default]
+    Unnamed,
+    Struct {
+        name: String,
+        /// The name and descriptor of each field, indexed by ordinal.
+        fields: Vec<(String, TypeDescriptor)>,
+        /// The default value that each field declares in the mojom file, if
+        /// any, indexed by ordinal. Empty if no field declares one.
+        defaults: Vec<Option<MojomValue>>,
+    },
+    Array {
+        element: Box<TypeDescriptor>,
+    },
+    Union {
+        name: String,
+        /// The name and descriptor of each field, indexed by tag.
+        variants: Vec<(String, TypeDescriptor)>,
+    },
+    /// An enum, which is an `Int32` on the wire.
+    Enum {
+        name: String,
+        /// The name and value of each enumerator.
+        values: Vec<(String, i32)>,
+    },
+    /// A recursive type, whose descriptor is only built when it's needed.
+    /// See `MojomType::Recursive`.
+    Recursive(RecursiveType),
+}
+
+impl TypeDescriptor {
+    /// Describe a `map<K, V>`, whose keys are described by `key` and values
+    /// by `value`. See `MojomType::map`.
+    pub fn map(key: TypeDescriptor, value: TypeDescriptor) -> TypeDescriptor {
+        let array = |element| TypeDescriptor::Array { element: Box::new(element) };
+        TypeDescriptor::Struct {
+            name: "map".to_string(),
+            fields: vec![("keys".to_string(), array(key)), ("values".to_string(), array(value))],
+            defaults: vec![],
+        }
+    }
+
+    /// If this describes a recursive type, return its descriptor, which is one
+    /// level deep. Otherwise, return the descriptor as is. The methods below
+    /// treat recursive types as unnamed, so resolve them first.
+    pub fn resolve(&self) -> Cow<'_, TypeDescriptor> {
+        match self {
+            TypeDescriptor::Recursive(recursive_type) => {
+                Cow::Owned(recursive_type.type_descriptor())
+            }
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// Return the name of the field with the given ordinal, if this describes
+    /// a struct or union with such a field. For unions, the ordinal is the
+    /// field's tag.
+    pub fn field_name(&self, ordinal: Ordinal) -> Option<&str> {
+        match self {
+            TypeDescriptor::Struct { fields, .. }
+            | TypeDescriptor::Union { variants: fields, .. } => {
+                fields.get(ordinal).map(|(name, _)| name.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the descriptor of the field with the given ordinal, or
+    /// `Unnamed` if there isn't one.
+    pub fn field(&self, ordinal: Ordinal) -> &TypeDescriptor {
+        match self {
+            TypeDescriptor::Struct { fields, .. }
+            | TypeDescriptor::Union { variants: fields, .. } => {
+                fields.get(ordinal).map_or(&TypeDescriptor::Unnamed, |(_, desc)| desc)
+            }
+            _ => &TypeDescriptor::Unnamed,
+        }
+    }
+
+    /// Return the default value that the struct field with the given ordinal
+    /// declares, if this describes a struct with such a field.
+    pub fn field_default(&self, ordinal: Ordinal) -> Option<&MojomValue> {
+        match self {
+            TypeDescriptor::Struct { defaults, .. } => defaults.get(ordinal)?.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 /******************************************************************************
@@ -95,12 +356,26 @@ pub enum MojomWireType {
         /// Bits are never skipped, so the array is a contiguous block of `Some`s,
         /// followed by zero or more `None`s.
         ordinals: [Option<Ordinal>; 8],
+        /// A mask of the bits which are the has-value flag of a nullable value
+        /// field, rather than the value of a bool field. The field's value is
+        /// stored separately, under the same ordinal.
+        has_value_bits: u8,
         // The associated data is always a single byte, so no need to store a
         // type here.
     },
     /// A 64-bit pointer to either an array or struct, which will appear at the
-    /// end of the containing struct.
-    Pointer { ordinal: Ordinal, nested_data_type: PackedStructuredType },
+    /// end of the containing struct. Null pointers are encoded as 0, and are
+    /// only allowed if `nullable` is set.
+    Pointer { ordinal: Ordinal, nested_data_type: PackedStructuredType, nullable: bool },
+    /// A union, which is stored inline as a 4-byte size, a 4-byte tag and an
+    /// 8-byte value. A null union has a size of 0, and is only allowed if
+    /// `nullable` is set.
+    ///
+    /// `variants` holds the wire type of each field, indexed by tag, as it's
+    /// stored in the 8-byte value. Unions can't be stored inline in other
+    /// unions, so any union-typed field is a pointer to a
+    /// `PackedStructuredType::Union` instead.
+    Union { ordinal: Ordinal, variants: Vec<MojomWireType>, nullable: bool },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -114,12 +389,57 @@ pub enum PackedLeafType {
     UInt32,
     Int64,
     UInt64,
+    Float,
+    Double,
+    /// A 4-byte index into the message's interface IDs, for an associated
+    /// receiver. A null endpoint has `INVALID_ENDPOINT_INDEX`.
+    AssociatedEndpoint {
+        nullable: bool,
+    },
+    /// A 4-byte index into the message's interface IDs, followed by a 4-byte
+    /// version, for an associated remote.
+    AssociatedInterface {
+        nullable: bool,
+    },
+    /// A 4-byte index into the message's handles, followed by a 4-byte
+    /// version, for a remote. A null remote has `INVALID_HANDLE_INDEX`.
+    Interface {
+        nullable: bool,
+    },
+    /// A 4-byte index into the message's handles. A null handle has
+    /// `INVALID_HANDLE_INDEX`.
+    Handle {
+        nullable: bool,
+    },
 }
 
+/// The index that a null associated endpoint is encoded as.
+pub const INVALID_ENDPOINT_INDEX: u32 = u32::MAX;
+
+/// The index that a null handle is encoded as.
+pub const INVALID_HANDLE_INDEX: u32 = u32::MAX;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PackedStructuredType {
-    Struct { packed_field_types: Vec<(String, MojomWireType)> },
-    Array { element_type: Box<MojomWireType>, array_type: PackedArrayType },
+    /// The struct's fields, in wire order.
+    Struct {
+        packed_field_types: Vec<MojomWireType>,
+        /// The version of the struct which added each field, indexed by
+        /// ordinal, as in `MojomType::Struct`. Empty if every field is in
+        /// version 0.
+        min_versions: Vec<u32>,
+    },
+    Array {
+        element_type: Box<MojomWireType>,
+        array_type: PackedArrayType,
+    },
+    /// A union which is pointed to by a field of another union.
+    Union {
+        variants: Vec<MojomWireType>,
+    },
+    /// A recursive type, which is only packed when it's needed (see
+    /// `resolve` in pack.rs).
+    Recursive(RecursiveType),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -139,17 +459,49 @@ impl MojomWireType {
             MojomWireType::Leaf { leaf_type, .. } => match leaf_type {
                 PackedLeafType::Int8 | PackedLeafType::UInt8 => 1,
                 PackedLeafType::Int16 | PackedLeafType::UInt16 => 2,
-                PackedLeafType::Int32 | PackedLeafType::UInt32 => 4,
-                PackedLeafType::Int64 | PackedLeafType::UInt64 => 8,
+                PackedLeafType::Int32
+                | PackedLeafType::UInt32
+                | PackedLeafType::Float
+                | PackedLeafType::AssociatedEndpoint { .. }
+                | PackedLeafType::Handle { .. } => 4,
+                PackedLeafType::Int64
+                | PackedLeafType::UInt64
+                | PackedLeafType::Double
+                | PackedLeafType::Interface { .. }
+                | PackedLeafType::AssociatedInterface { .. } => 8,
             },
             MojomWireType::Bitfield { .. } => 1,
             // Structs and arrays are stored as 64-bit pointers
             MojomWireType::Pointer { .. } => 8,
+            MojomWireType::Union { .. } => 16,
         }
     }
 
-    /// The alignment requirement for each type is equal to its size in bytes.
+    /// The ordinal of the field, or of the first bit of a bitfield, which is
+    /// the one from the earliest version.
+    pub fn first_ordinal(&self) -> Ordinal {
+        match self {
+            MojomWireType::Leaf { ordinal, .. }
+            | MojomWireType::Pointer { ordinal, .. }
+            | MojomWireType::Union { ordinal, .. } => *ordinal,
+            MojomWireType::Bitfield { ordinals, .. } => {
+                ordinals[0].expect("Bitfields always have at least one bit")
+            }
+        }
+    }
+
+    /// The alignment requirement for each type is equal to its size in bytes,
+    /// except for unions, which only need to be 8-byte aligned, and remotes
+    /// and associated remotes, which are a pair of 4-byte values.
     pub fn alignment(&self) -> usize {
-        return self.size();
+        match self {
+            MojomWireType::Union { .. } => 8,
+            MojomWireType::Leaf {
+                leaf_type:
+                    PackedLeafType::Interface { .. } | PackedLeafType::AssociatedInterface { .. },
+                ..
+            } => 4,
+            _ => self.size(),
+        }
     }
 }
@@ -0,0 +1,132 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Compares the owned parser (`parse_struct`) against the zero-copy views
+//! (`view_struct`).
+//!
+//! This is a plain executable rather than a test, since timings are only
+//! meaningful in optimized builds. Run it with e.g.
+//! `out/Release/mojom_parser_benchmarks`.
+
+chromium::import! {
+    "//mojo/public/rust/mojom_parser";
+}
+
+use mojom_parser::*;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 100_000;
+
+/// Build a struct type with `num_fields` fields of assorted leaf types,
+/// strings and byte arrays, plus `depth` levels of nested structs of the same
+/// shape. Strings and arrays are where the owned parser has to allocate.
+fn make_type(num_fields: usize, depth: usize) -> MojomType {
+    let mut fields: Vec<MojomType> = (0..num_fields)
+        .map(|idx| match idx % 6 {
+            0 => MojomType::Bool,
+            1 => MojomType::Int32,
+            2 => MojomType::String,
+            3 => MojomType::UInt16,
+            4 => MojomType::Array { element_type: Box::new(MojomType::UInt8), num_elements: None },
+            _ => MojomType::Int64,
+        })
+        .collect();
+    if depth > 0 {
+        fields.push(make_type(num_fields, depth - 1));
+    }
+    MojomType::Struct { fields, min_versions: vec![] }
+}
+
+/// Build a value of the type returned by `make_type`.
+fn make_value(num_fields: usize, depth: usize) -> MojomValue {
+    let mut fields: Vec<MojomValue> = (0..num_fields)
+        .map(|idx| match idx % 6 {
+            0 => MojomValue::Bool(idx % 3 == 0),
+            1 => MojomValue::Int32(-(idx as i32)),
+            2 => MojomValue::String(format!("field number {}", idx)),
+            3 => MojomValue::UInt16(idx as u16),
+            4 => MojomValue::Array((0..32).map(MojomValue::UInt8).collect()),
+            _ => MojomValue::Int64(idx as i64 * 1000),
+        })
+        .collect();
+    if depth > 0 {
+        fields.push(make_value(num_fields, depth - 1));
+    }
+    MojomValue::Struct(fields)
+}
+
+/// Visit every leaf of a view, and every byte of its strings and byte arrays,
+/// so the view does as much work as the parser.
+fn visit_all(view: StructRef) -> u64 {
+    let mut sum = 0u64;
+    for ordinal in 0..view.num_fields() {
+        sum = sum.wrapping_add(match view.field(ordinal) {
+            Some(MojomValueRef::Struct(nested)) => visit_all(nested),
+            Some(MojomValueRef::Bool(b)) => b as u64,
+            Some(MojomValueRef::Int32(n)) => n as u64,
+            Some(MojomValueRef::UInt16(n)) => n as u64,
+            Some(MojomValueRef::Int64(n)) => n as u64,
+            Some(MojomValueRef::String(s)) => s.bytes().map(u64::from).sum(),
+            Some(MojomValueRef::Array(array)) => {
+                array.as_bytes().unwrap_or_default().iter().copied().map(u64::from).sum()
+            }
+            _ => 0,
+        });
+    }
+    sum
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn run_benchmark(num_fields: usize, depth: usize) {
+    let packed_type = pack_mojom_type(&make_type(num_fields, depth), 0);
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = &packed_type
+    else {
+        unreachable!("Structs always pack to pointers")
+    };
+    let MojomValue::Struct(field_values) = make_value(num_fields, depth) else {
+        unreachable!("make_value always returns a struct")
+    };
+    let mut data: Vec<u8> = vec![];
+    deparse_struct(&mut data, &field_values, packed_field_types, &[]).unwrap();
+    let layout = StructLayout::new(packed_field_types, &[]);
+
+    let owned = time(|| {
+        let mut parser_data = ParserData::new(black_box(&data));
+        black_box(parse_struct(&mut parser_data, packed_field_types, &[]).unwrap());
+    });
+    let view_only = time(|| {
+        black_box(view_struct(black_box(&data), &layout).unwrap());
+    });
+    let view_and_visit = time(|| {
+        black_box(visit_all(view_struct(black_box(&data), &layout).unwrap()));
+    });
+
+    println!(
+        "{} fields x {} levels ({} bytes): owned {:?}, view {:?}, view + visit all {:?}",
+        num_fields,
+        depth + 1,
+        data.len(),
+        owned,
+        view_only,
+        view_and_visit
+    );
+}
+
+fn main() {
+    run_benchmark(8, 0);
+    run_benchmark(32, 0);
+    run_benchmark(8, 4);
+    run_benchmark(32, 4);
+}
@@ -0,0 +1,470 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! This file implements the mojom packing algorithm as a `const fn`, so that
+//! the wire layout of a struct can be computed at compile time.
+//!
+//! pack.rs packs a `MojomType` at runtime, which requires the type to be built
+//! on the heap first. Here, a struct is instead described by a fixed-size
+//! array of `ConstField`s, and the result is a `StructPacking` holding the
+//! offset and bit of every field, from which the size of each version can be
+//! computed. Since everything is a `const fn`, any problem with the layout
+//! (for example, an invalid `min_version`) is reported as a compile error.
+//!
+//! The algorithm follows mojo/public/tools/mojom/mojom/generate/pack.py as
+//! closely as possible: fields are visited in ordinal order, and each one is
+//! put in the first hole between already-packed fields that it fits in, or at
+//! the end if there is none. Unlike pack.rs, each bool is tracked individually
+//! (with its own bit), exactly as pack.py does.
+//!
+//! The `mojom_struct_layout!` macro ties this to a Rust struct definition.
+//! `pack_struct_dynamic` runs the same algorithm on a list of fields which is
+//! only known at runtime; the differential tests use it to compare against
+//! layouts exported from pack.py.
+
+/// Size of the struct header in bytes: num_bytes [4B] + version [4B].
+const HEADER_SIZE: usize = 8;
+
+/// The kinds of field that the packing algorithm distinguishes between. Only
+/// the size and alignment of a field, and whether it's a bool, affect its
+/// placement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackingKind {
+    Bool,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float,
+    Double,
+    /// Handles (including pending receivers), which are encoded as a 32-bit
+    /// index into the message's handle table.
+    Handle,
+    /// Interfaces (including pending remotes), which are encoded as a handle
+    /// followed by a 32-bit version number.
+    Interface,
+    /// Unions, which are encoded inline as a 16-byte tag and value.
+    Union,
+    /// Structs, arrays and strings, which are all encoded as 64-bit pointers.
+    Pointer,
+}
+
+impl PackingKind {
+    /// The size of a field of this kind, in bytes. Bools nominally take up a
+    /// whole byte, but several of them can share one.
+    pub const fn size(self) -> usize {
+        match self {
+            PackingKind::Bool | PackingKind::Int8 | PackingKind::UInt8 => 1,
+            PackingKind::Int16 | PackingKind::UInt16 => 2,
+            PackingKind::Int32 | PackingKind::UInt32 | PackingKind::Float | PackingKind::Handle => {
+                4
+            }
+            PackingKind::Int64
+            | PackingKind::UInt64
+            | PackingKind::Double
+            | PackingKind::Interface
+            | PackingKind::Pointer => 8,
+            PackingKind::Union => 16,
+        }
+    }
+
+    /// The alignment requirement of a field of this kind, in bytes. This is
+    /// equal to its size, except for interfaces, which are really two 4-byte
+    /// values, and unions, which only need 8-byte alignment.
+    pub const fn alignment(self) -> usize {
+        match self {
+            PackingKind::Interface => 4,
+            PackingKind::Union => 8,
+            _ => self.size(),
+        }
+    }
+
+    const fn is_bool(self) -> bool {
+        matches!(self, PackingKind::Bool)
+    }
+
+    /// Whether this kind refers to something outside the struct (i.e. it's
+    /// not a plain value), which means it can only be added to a later version
+    /// if it's nullable.
+    const fn is_reference(self) -> bool {
+        matches!(
+            self,
+            PackingKind::Handle
+                | PackingKind::Interface
+                | PackingKind::Union
+                | PackingKind::Pointer
+        )
+    }
+}
+
+/// A single field of a struct, as input to `pack_struct_const`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstField {
+    pub kind: PackingKind,
+    /// The version of the struct which introduced this field.
+    pub min_version: u32,
+    /// Whether the field is nullable. Nullable value kinds (e.g. `int32?`) are
+    /// represented by two fields, a bool flag and the value itself, so this
+    /// only makes a difference for reference kinds.
+    pub nullable: bool,
+}
+
+impl ConstField {
+    pub const fn new(kind: PackingKind) -> ConstField {
+        ConstField { kind, min_version: 0, nullable: false }
+    }
+
+    pub const fn with_min_version(self, min_version: u32) -> ConstField {
+        ConstField { min_version, ..self }
+    }
+
+    pub const fn with_nullable(self, nullable: bool) -> ConstField {
+        ConstField { nullable, ..self }
+    }
+}
+
+/// The wire layout of a struct with `N` fields, indexed by ordinal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StructPacking<const N: usize> {
+    pub fields: [ConstField; N],
+    /// The offset of each field in bytes, from the end of the struct header.
+    /// (This matches the offsets that pack.py computes.)
+    pub offsets: [usize; N],
+    /// The bit each bool is stored in, counting from the LSB. This is 0 for
+    /// every other field.
+    pub bits: [u8; N],
+}
+
+/// The same as `StructPacking`, for structs whose number of fields is only
+/// known at runtime. Created by `pack_struct_dynamic`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicStructPacking {
+    pub fields: Vec<ConstField>,
+    pub offsets: Vec<usize>,
+    pub bits: Vec<u8>,
+}
+
+/// Returns the offset and bit at which `field` would be placed if it came
+/// right after `last`, where `last` is at `last_offset`/`last_bit`.
+/// This is GetFieldOffset in pack.py.
+const fn offset_after(
+    field: PackingKind,
+    last: PackingKind,
+    last_offset: usize,
+    last_bit: u8,
+) -> (usize, u8) {
+    if field.is_bool() && last.is_bool() && last_bit < 7 {
+        return (last_offset, last_bit + 1);
+    }
+    let offset = last_offset + last.size();
+    let alignment = field.alignment();
+    (offset + (alignment - offset % alignment) % alignment, 0)
+}
+
+/// Check that the fields' versions are valid, as pack.py does.
+const fn validate_versions(fields: &[ConstField]) -> Result<(), &'static str> {
+    let mut ordinal = 0;
+    let mut last_min_version = 0;
+    while ordinal < fields.len() {
+        let field = fields[ordinal];
+        if field.min_version < last_min_version {
+            return Err("Fields must be in order of increasing min_version");
+        }
+        if field.min_version != 0 && field.kind.is_reference() && !field.nullable {
+            return Err("Non-nullable reference fields are only allowed in version 0 of a struct");
+        }
+        last_min_version = field.min_version;
+        ordinal += 1;
+    }
+    Ok(())
+}
+
+/// The packing algorithm itself, shared by `pack_struct_const` and
+/// `pack_struct_dynamic`. The fields must already have been validated.
+///
+/// `offsets`, `bits` and `packed` must all be the same length as `fields`.
+/// `packed` is scratch space.
+const fn pack_into(
+    fields: &[ConstField],
+    offsets: &mut [usize],
+    bits: &mut [u8],
+    packed: &mut [usize],
+) {
+    if fields.is_empty() {
+        return;
+    }
+
+    // `packed` holds the ordinals of the fields we've packed so far, in
+    // increasing offset order. Only the first `num_packed` entries are
+    // meaningful. The first field always goes at the start.
+    packed[0] = 0;
+    let mut num_packed = 1;
+
+    let mut ordinal = 1;
+    while ordinal < fields.len() {
+        let kind = fields[ordinal].kind;
+
+        // Find the first hole which is big enough for this field.
+        let mut insert_at = num_packed;
+        let mut idx = 1;
+        while idx < num_packed {
+            let last = packed[idx - 1];
+            let next = packed[idx];
+            let (offset, bit) = offset_after(kind, fields[last].kind, offsets[last], bits[last]);
+            if offset + kind.size() <= offsets[next] {
+                offsets[ordinal] = offset;
+                bits[ordinal] = bit;
+                insert_at = idx;
+                break;
+            }
+            idx += 1;
+        }
+
+        // If there was no hole, add it to the end.
+        if insert_at == num_packed {
+            let last = packed[num_packed - 1];
+            let (offset, bit) = offset_after(kind, fields[last].kind, offsets[last], bits[last]);
+            offsets[ordinal] = offset;
+            bits[ordinal] = bit;
+        }
+
+        // Shift everything after the insertion point over by one.
+        let mut shift = num_packed;
+        while shift > insert_at {
+            packed[shift] = packed[shift - 1];
+            shift -= 1;
+        }
+        packed[insert_at] = ordinal;
+        num_packed += 1;
+        ordinal += 1;
+    }
+}
+
+/// The size in bytes (including the header) of the given version of a struct
+/// packed by `pack_into`.
+const fn size_for_version(fields: &[ConstField], offsets: &[usize], version: u32) -> usize {
+    // The size of a version is determined by whichever of its fields ends
+    // last, which isn't necessarily the one with the highest ordinal.
+    let mut payload_size = 0;
+    let mut ordinal = 0;
+    while ordinal < fields.len() {
+        if fields[ordinal].min_version <= version {
+            let end = offsets[ordinal] + fields[ordinal].kind.size();
+            let padded_end = end + (8 - end % 8) % 8;
+            if padded_end > payload_size {
+                payload_size = padded_end;
+            }
+        }
+        ordinal += 1;
+    }
+    HEADER_SIZE + payload_size
+}
+
+const fn latest_version(fields: &[ConstField]) -> u32 {
+    match fields.last() {
+        Some(field) => field.min_version,
+        None => 0,
+    }
+}
+
+/// Compute the wire layout of a struct whose fields (in ordinal order) are
+/// `fields`.
+///
+/// This panics, and therefore fails to compile when evaluated in a const
+/// context, if the fields' versions are invalid.
+pub const fn pack_struct_const<const N: usize>(fields: [ConstField; N]) -> StructPacking<N> {
+    if let Err(msg) = validate_versions(&fields) {
+        panic!("{}", msg);
+    }
+    let mut offsets = [0; N];
+    let mut bits = [0; N];
+    pack_into(&fields, &mut offsets, &mut bits, &mut [0; N]);
+    StructPacking { fields, offsets, bits }
+}
+
+/// Compute the wire layout of a struct whose fields (in ordinal order) are
+/// `fields`, returning an error if the fields' versions are invalid.
+pub fn pack_struct_dynamic(fields: Vec<ConstField>) -> anyhow::Result<DynamicStructPacking> {
+    validate_versions(&fields).map_err(anyhow::Error::msg)?;
+    let mut offsets = vec![0; fields.len()];
+    let mut bits = vec![0; fields.len()];
+    pack_into(&fields, &mut offsets, &mut bits, &mut vec![0; fields.len()]);
+    Ok(DynamicStructPacking { fields, offsets, bits })
+}
+
+impl<const N: usize> StructPacking<N> {
+    /// The offset of the field with the given ordinal, in bytes from the
+    /// start of the struct (including the header).
+    pub const fn offset_in_struct(&self, ordinal: usize) -> usize {
+        HEADER_SIZE + self.offsets[ordinal]
+    }
+
+    /// The bit which holds the field with the given ordinal, if it's a bool.
+    pub const fn bit(&self, ordinal: usize) -> Option<u8> {
+        if self.fields[ordinal].kind.is_bool() {
+            Some(self.bits[ordinal])
+        } else {
+            None
+        }
+    }
+
+    /// The most recent version of the struct.
+    pub const fn latest_version(&self) -> u32 {
+        latest_version(&self.fields)
+    }
+
+    /// The size in bytes (including the header) of the given version of the
+    /// struct. This is the num_bytes value that appears in its header.
+    pub const fn size_for_version(&self, version: u32) -> usize {
+        size_for_version(&self.fields, &self.offsets, version)
+    }
+
+    /// The size in bytes (including the header) of the latest version of the
+    /// struct.
+    pub const fn size(&self) -> usize {
+        self.size_for_version(self.latest_version())
+    }
+}
+
+impl DynamicStructPacking {
+    /// See `StructPacking::offset_in_struct`.
+    pub fn offset_in_struct(&self, ordinal: usize) -> usize {
+        HEADER_SIZE + self.offsets[ordinal]
+    }
+
+    /// See `StructPacking::bit`.
+    pub fn bit(&self, ordinal: usize) -> Option<u8> {
+        self.fields[ordinal].kind.is_bool().then_some(self.bits[ordinal])
+    }
+
+    /// See `StructPacking::latest_version`.
+    pub fn latest_version(&self) -> u32 {
+        latest_version(&self.fields)
+    }
+
+    /// See `StructPacking::size_for_version`.
+    pub fn size_for_version(&self, version: u32) -> usize {
+        size_for_version(&self.fields, &self.offsets, version)
+    }
+
+    /// See `StructPacking::size`.
+    pub fn size(&self) -> usize {
+        self.size_for_version(self.latest_version())
+    }
+}
+
+/// Implemented by Rust types that can be the fields of a struct declared with
+/// `mojom_struct_layout!`, to tell the packing algorithm how they're encoded.
+pub trait HasPackingKind {
+    const PACKING_KIND: PackingKind;
+    const NULLABLE: bool = false;
+}
+
+macro_rules! impl_has_packing_kind {
+    ($($rust_type:ty => $kind:ident),* $(,)?) => {
+        $(impl HasPackingKind for $rust_type {
+            const PACKING_KIND: PackingKind = PackingKind::$kind;
+        })*
+    };
+}
+
+impl_has_packing_kind! {
+    bool => Bool,
+    i8 => Int8,
+    u8 => UInt8,
+    i16 => Int16,
+    u16 => UInt16,
+    i32 => Int32,
+    u32 => UInt32,
+    i64 => Int64,
+    u64 => UInt64,
+    f32 => Float,
+    f64 => Double,
+    String => Pointer,
+}
+
+impl<T> HasPackingKind for Vec<T> {
+    const PACKING_KIND: PackingKind = PackingKind::Pointer;
+}
+
+impl<T, const N: usize> HasPackingKind for [T; N] {
+    const PACKING_KIND: PackingKind = PackingKind::Pointer;
+}
+
+// Nullable value kinds are split into two fields, which a single Rust field
+// can't express, so `Option` is only supported for reference kinds.
+impl<T: HasPackingKind> HasPackingKind for Option<T> {
+    const PACKING_KIND: PackingKind = {
+        assert!(T::PACKING_KIND.is_reference(), "Nullable value kinds aren't supported");
+        T::PACKING_KIND
+    };
+    const NULLABLE: bool = true;
+}
+
+/// Declares a Rust struct along with its mojom wire layout, which is computed
+/// at compile time and stored in the associated constant `MOJOM_LAYOUT`.
+///
+/// Fields must be listed in ordinal order. Fields added in later versions of
+/// the struct are marked with `#[min_version = N]`. If the layout is invalid,
+/// the struct fails to compile.
+///
+/// ```ignore
+/// mojom_struct_layout! {
+///     pub struct Rect {
+///         pub x: i32,
+///         pub y: i32,
+///         pub visible: bool,
+///         #[min_version = 1]
+///         pub opacity: u8,
+///     }
+/// }
+///
+/// const _: () = assert!(Rect::MOJOM_LAYOUT.offset_in_struct(2) == 16);
+/// const _: () = assert!(Rect::MOJOM_LAYOUT.size_for_version(0) == 24);
+/// ```
+#[macro_export]
+macro_rules! mojom_struct_layout {
+    (
+        $(#[$struct_attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                $(#[min_version = $min_version:literal])?
+                $field_vis:vis $field:ident : $field_ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$struct_attr])*
+        $vis struct $name {
+            $($(#[doc = $doc])* $field_vis $field: $field_ty,)*
+        }
+
+        impl $name {
+            /// The mojom wire layout of this struct, computed at compile time.
+            pub const MOJOM_LAYOUT: $crate::StructPacking<
+                { <[&str]>::len(&[$(stringify!($field)),*]) },
+            > = $crate::pack_struct_const([$(
+                $crate::ConstField::new(
+                    <$field_ty as $crate::HasPackingKind>::PACKING_KIND
+                )
+                .with_nullable(<$field_ty as $crate::HasPackingKind>::NULLABLE)
+                $(.with_min_version($min_version))?
+            ),*]);
+        }
+
+        impl $crate::HasPackingKind for $name {
+            const PACKING_KIND: $crate::PackingKind = $crate::PackingKind::Pointer;
+        }
+
+        // Associated constants are only evaluated when they're used, so use it
+        // here to make sure that an invalid layout is a compile error.
+        const _: () = {
+            let _ = $name::MOJOM_LAYOUT;
+        };
+    };
+}
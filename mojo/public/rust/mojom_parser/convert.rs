@@ -0,0 +1,523 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines the `Mojom` trait, which connects Rust types to the mojom AST.
+//!
+//! A type which implements `Mojom` knows its `MojomType`, and how to convert
+//! its values to and from `MojomValue`s, which is everything we need to encode
+//! and decode it. This module implements it for primitives, strings, arrays,
+//! maps, boxes and `Result`s. For user-defined structs, enums and unions, use
+//! `#[derive(Mojom)]` (see //mojo/public/rust/mojom_derive), which builds on
+//! the helper functions at the bottom of this file. Hand-written
+//! implementations may use them too.
+
+use crate::ast::*;
+use crate::deparse_values::{deparse_struct, deparse_struct_into};
+use crate::pack::pack_mojom_type;
+use crate::parse_primitives::ParserData;
+use crate::parse_values::parse_struct;
+use crate::serialized_size::struct_serialized_size;
+
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// The result of converting from a `MojomValue`. This is just
+/// `anyhow::Result`, named here so that code generated by #[derive(Mojom)]
+/// doesn't need to depend on anyhow itself.
+pub type MojomResult<T> = Result<T>;
+
+/// A Rust type which corresponds to a mojom type.
+pub trait Mojom: Sized {
+    /// The mojom type that values of this type are encoded as.
+    fn mojom_type() -> MojomType;
+
+    /// Human-readable names for `mojom_type()`, for debugging.
+    fn type_descriptor() -> TypeDescriptor {
+        TypeDescriptor::Unnamed
+    }
+
+    fn into_mojom_value(self) -> MojomValue;
+
+    /// Fails if `value` doesn't have the type given by `mojom_type()`, or if
+    /// it isn't a valid value for this type (e.g. an unknown enum value).
+    fn from_mojom_value(value: MojomValue) -> Result<Self>;
+}
+
+macro_rules! impl_mojom_for_primitive {
+    ($($rust_type:ty => $variant:ident,)*) => {
+        $(
+            impl Mojom for $rust_type {
+                fn mojom_type() -> MojomType {
+                    MojomType::$variant
+                }
+
+                fn into_mojom_value(self) -> MojomValue {
+                    MojomValue::$variant(self)
+                }
+
+                fn from_mojom_value(value: MojomValue) -> Result<Self> {
+                    match value {
+                        MojomValue::$variant(value) => Ok(value),
+                        _ => bail!("Expected a {}, but got {:?}", stringify!($rust_type), value),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_mojom_for_primitive! {
+    bool => Bool,
+    i8 => Int8,
+    u8 => UInt8,
+    i16 => Int16,
+    u16 => UInt16,
+    i32 => Int32,
+    u32 => UInt32,
+    i64 => Int64,
+    u64 => UInt64,
+    f32 => Float,
+    f64 => Double,
+    String => String,
+}
+
+/// Convert each element of an array value.
+fn elements_from_mojom_value<T: Mojom>(value: MojomValue) -> Result<Vec<T>> {
+    let MojomValue::Array(elements) = value else {
+        bail!("Expected an array, but got {:?}", value)
+    };
+    elements
+        .into_iter()
+        .enumerate()
+        .map(|(idx, element)| {
+            T::from_mojom_value(element).with_context(|| format!("In array element {}", idx))
+        })
+        .collect()
+}
+
+impl<T: Mojom> Mojom for Vec<T> {
+    fn mojom_type() -> MojomType {
+        MojomType::Array { element_type: Box::new(T::mojom_type()), num_elements: None }
+    }
+
+    fn type_descriptor() -> TypeDescriptor {
+        TypeDescriptor::Array { element: Box::new(T::type_descriptor()) }
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        MojomValue::Array(self.into_iter().map(T::into_mojom_value).collect())
+    }
+
+    fn from_mojom_value(value: MojomValue) -> Result<Self> {
+        elements_from_mojom_value(value)
+    }
+}
+
+impl<T: Mojom, const N: usize> Mojom for [T; N] {
+    fn mojom_type() -> MojomType {
+        MojomType::Array { element_type: Box::new(T::mojom_type()), num_elements: Some(N) }
+    }
+
+    fn type_descriptor() -> TypeDescriptor {
+        TypeDescriptor::Array { element: Box::new(T::type_descriptor()) }
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        MojomValue::Array(self.into_iter().map(T::into_mojom_value).collect())
+    }
+
+    fn from_mojom_value(value: MojomValue) -> Result<Self> {
+        let elements: Vec<T> = elements_from_mojom_value(value)?;
+        let len = elements.len();
+        elements.try_into().map_err(|_| anyhow::anyhow!("Expected {} elements, but got {}", N, len))
+    }
+}
+
+/// A `Box<T>` is encoded as `T`, so that a struct or union can hold itself.
+impl<T: Mojom> Mojom for Box<T> {
+    fn mojom_type() -> MojomType {
+        T::mojom_type()
+    }
+
+    fn type_descriptor() -> TypeDescriptor {
+        T::type_descriptor()
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        (*self).into_mojom_value()
+    }
+
+    fn from_mojom_value(value: MojomValue) -> Result<Self> {
+        T::from_mojom_value(value).map(Box::new)
+    }
+}
+
+/// Convert the entries of a map to a map value. See `MojomType::map`.
+fn entries_into_mojom_value<K: Mojom, V: Mojom>(
+    entries: impl Iterator<Item = (K, V)>,
+) -> MojomValue {
+    let (keys, values) =
+        entries.map(|(key, value)| (key.into_mojom_value(), value.into_mojom_value())).unzip();
+    MojomValue::Struct(vec![MojomValue::Array(keys), MojomValue::Array(values)])
+}
+
+/// Convert a map value to its entries, in the order they were encoded.
+/// Fails if there aren't as many values as keys, like
+/// `VALIDATION_ERROR_DIFFERENT_SIZED_ARRAYS_IN_MAP` in C++.
+fn entries_from_mojom_value<K: Mojom, V: Mojom>(
+    value: MojomValue,
+) -> Result<impl Iterator<Item = (K, V)>> {
+    let MojomValue::Struct(fields) = value else { bail!("Expected a map, but got {:?}", value) };
+    let Ok([keys, values]) = <[MojomValue; 2]>::try_from(fields) else {
+        bail!("Expected a map to have keys and values")
+    };
+    let keys: Vec<K> = elements_from_mojom_value(keys).context("In the map's keys")?;
+    let values: Vec<V> = elements_from_mojom_value(values).context("In the map's values")?;
+    if keys.len() != values.len() {
+        bail!("The map has {} keys, but {} values", keys.len(), values.len())
+    }
+    Ok(keys.into_iter().zip(values))
+}
+
+/// A `map<K, V>`. If a key is repeated, the first value wins, like in C++.
+impl<K: Mojom + Eq + std::hash::Hash, V: Mojom> Mojom for HashMap<K, V> {
+    fn mojom_type() -> MojomType {
+        MojomType::map(K::mojom_type(), V::mojom_type())
+    }
+
+    fn type_descriptor() -> TypeDescriptor {
+        TypeDescriptor::map(K::type_descriptor(), V::type_descriptor())
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        entries_into_mojom_value(self.into_iter())
+    }
+
+    fn from_mojom_value(value: MojomValue) -> Result<Self> {
+        let mut map = HashMap::new();
+        for (key, value) in entries_from_mojom_value(value)? {
+            map.entry(key).or_insert(value);
+        }
+        Ok(map)
+    }
+}
+
+/// A `map<K, V>` whose keys are kept in order, so they're encoded in order
+/// too. If a key is repeated, the first value wins, like in C++.
+impl<K: Mojom + Ord, V: Mojom> Mojom for BTreeMap<K, V> {
+    fn mojom_type() -> MojomType {
+        MojomType::map(K::mojom_type(), V::mojom_type())
+    }
+
+    fn type_descriptor() -> TypeDescriptor {
+        TypeDescriptor::map(K::type_descriptor(), V::type_descriptor())
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        entries_into_mojom_value(self.into_iter())
+    }
+
+    fn from_mojom_value(value: MojomValue) -> Result<Self> {
+        let mut map = BTreeMap::new();
+        for (key, value) in entries_from_mojom_value(value)? {
+            map.entry(key).or_insert(value);
+        }
+        Ok(map)
+    }
+}
+
+/// The response of a method declared with `=> result<T, E>`. Like the C++ and
+/// TypeScript bindings, it's encoded as a union whose fields are `success` and
+/// `failure`, in that order.
+impl<T: Mojom, E: Mojom> Mojom for std::result::Result<T, E> {
+    fn mojom_type() -> MojomType {
+        MojomType::Union { variants: vec![T::mojom_type(), E::mojom_type()] }
+    }
+
+    fn type_descriptor() -> TypeDescriptor {
+        TypeDescriptor::Union {
+            name: "Result".to_string(),
+            variants: vec![
+                ("success".to_string(), T::type_descriptor()),
+                ("failure".to_string(), E::type_descriptor()),
+            ],
+        }
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        match self {
+            Ok(value) => MojomValue::Union { tag: 0, value: Box::new(value.into_mojom_value()) },
+            Err(error) => MojomValue::Union { tag: 1, value: Box::new(error.into_mojom_value()) },
+        }
+    }
+
+    fn from_mojom_value(value: MojomValue) -> Result<Self> {
+        match union_variant_from_mojom_value(value, "Result", 2)? {
+            (0, value) => field_from_mojom_value(value, "Result", "success").map(Ok),
+            (_, value) => field_from_mojom_value(value, "Result", "failure").map(Err),
+        }
+    }
+}
+
+/// Encode `value`, whose type must be a struct, in the mojom wire format.
+pub fn encode_mojom<T: Mojom>(value: T) -> Result<Vec<u8>> {
+    let (packed_field_types, min_versions) = pack_struct_type::<T>()?;
+    let MojomValue::Struct(field_values) = value.into_mojom_value() else {
+        unreachable!("Values of struct types are always structs")
+    };
+    let mut data = vec![];
+    deparse_struct(&mut data, &field_values, &packed_field_types, &min_versions)?;
+    Ok(data)
+}
+
+/// Encode `value` like `encode_mojom`, when it may contain handles.
+///
+/// Handles are encoded as their index in the list of handles sent alongside
+/// the message, which the receiver requires to be in the order that the
+/// handles appear in the encoding. `Mojom` implementations number handles in
+/// the order that they convert them instead, so this renumbers them, and
+/// returns the original index of each handle in the new order.
+pub fn encode_mojom_with_handles<T: Mojom>(value: T) -> Result<(Vec<u8>, Vec<u32>)> {
+    let (prepared, handle_order) = prepare_mojom_with_handles(value)?;
+    Ok((prepared.to_bytes()?, handle_order))
+}
+
+/// Convert and pack `value` like `encode_mojom_with_handles`, but leave the
+/// encoding until we know where it goes, e.g. straight into the buffer of a
+/// message on a message pipe.
+pub fn prepare_mojom_with_handles<T: Mojom>(value: T) -> Result<(PreparedStruct, Vec<u32>)> {
+    let (packed_fields, min_versions) = pack_struct_type::<T>()?;
+    let MojomValue::Struct(mut field_values) = value.into_mojom_value() else {
+        unreachable!("Values of struct types are always structs")
+    };
+    let mut handle_order = vec![];
+    renumber_struct_handles(&mut field_values, &packed_fields, &mut handle_order)?;
+    let size = struct_serialized_size(&field_values, &packed_fields)?;
+    Ok((PreparedStruct { field_values, packed_fields, min_versions, size }, handle_order))
+}
+
+/// A struct which is ready to be encoded, once we know its exact size.
+#[derive(Debug)]
+pub struct PreparedStruct {
+    field_values: Vec<MojomValue>,
+    packed_fields: Vec<MojomWireType>,
+    min_versions: Vec<u32>,
+    size: usize,
+}
+
+impl PreparedStruct {
+    /// The number of bytes that the struct is encoded as.
+    pub fn serialized_size(&self) -> usize {
+        self.size
+    }
+
+    /// Encode the struct into `buffer`, which must be exactly
+    /// `serialized_size()` bytes. Fails if a value doesn't match its type.
+    pub fn deparse_into(&self, buffer: &mut [u8]) -> Result<()> {
+        if buffer.len() != self.size {
+            bail!("The struct needs {} bytes, but the buffer has {}", self.size, buffer.len())
+        }
+        deparse_struct_into(buffer, &self.field_values, &self.packed_fields, &self.min_versions)?;
+        Ok(())
+    }
+
+    /// Encode the struct into a new buffer.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.size);
+        deparse_struct(&mut data, &self.field_values, &self.packed_fields, &self.min_versions)?;
+        Ok(data)
+    }
+}
+
+/// Renumber the handles in the fields of a struct in the order that they're
+/// encoded: fields in wire order, with the data that each pointer points to
+/// before the following fields. Values which don't match their type are left
+/// for `deparse_struct` to complain about.
+fn renumber_struct_handles(
+    field_values: &mut [MojomValue],
+    packed_fields: &[MojomWireType],
+    handle_order: &mut Vec<u32>,
+) -> Result<()> {
+    for packed_field in packed_fields {
+        let ordinal = match packed_field {
+            MojomWireType::Leaf { ordinal, .. }
+            | MojomWireType::Pointer { ordinal, .. }
+            | MojomWireType::Union { ordinal, .. } => *ordinal,
+            MojomWireType::Bitfield { .. } => continue,
+        };
+        if let Some(value) = field_values.get_mut(ordinal) {
+            renumber_handles(value, packed_field, handle_order)?;
+        }
+    }
+    Ok(())
+}
+
+fn renumber_handles(
+    value: &mut MojomValue,
+    wire_type: &MojomWireType,
+    handle_order: &mut Vec<u32>,
+) -> Result<()> {
+    match (wire_type, value) {
+        (MojomWireType::Leaf { .. }, MojomValue::Handle { index })
+        | (MojomWireType::Leaf { .. }, MojomValue::PendingRemote { index, .. }) => {
+            handle_order.push(*index);
+            *index = u32::try_from(handle_order.len() - 1).context("Too many handles")?;
+        }
+        (MojomWireType::Pointer { nested_data_type, .. }, value) => {
+            match (&*nested_data_type.resolve(), value) {
+                (
+                    PackedStructuredType::Struct { packed_field_types, .. },
+                    MojomValue::Struct(field_values),
+                ) => renumber_struct_handles(field_values, packed_field_types, handle_order)?,
+                (PackedStructuredType::Array { element_type, .. }, MojomValue::Array(elements)) => {
+                    for element in elements {
+                        renumber_handles(element, element_type, handle_order)?;
+                    }
+                }
+                (PackedStructuredType::Union { variants }, MojomValue::Union { tag, value }) => {
+                    if let Some(variant) = variants.get(*tag) {
+                        renumber_handles(value, variant, handle_order)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        (MojomWireType::Union { variants, .. }, MojomValue::Union { tag, value }) => {
+            if let Some(variant) = variants.get(*tag) {
+                renumber_handles(value, variant, handle_order)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Decode a value of type `T`, which must be a struct, from the mojom wire
+/// format.
+pub fn decode_mojom<T: Mojom>(bytes: &[u8]) -> Result<T> {
+    let (packed_field_types, min_versions) = pack_struct_type::<T>()?;
+    let mut data = ParserData::new(bytes);
+    let fields = parse_struct(&mut data, &packed_field_types, &min_versions)?;
+    if data.remaining_bytes() != 0 {
+        bail!("There were {} trailing bytes after the struct", data.remaining_bytes())
+    }
+    T::from_mojom_value(MojomValue::Struct(fields))
+}
+
+/// Return the packed fields of `T`'s type, which must be a struct, and the
+/// version which added each of them.
+fn pack_struct_type<T: Mojom>() -> Result<(Vec<MojomWireType>, Vec<u32>)> {
+    let ty = T::mojom_type();
+    if !matches!(ty, MojomType::Struct { .. }) {
+        bail!("Only structs can be encoded at the top level, but got {:?}", ty)
+    }
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, min_versions },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        unreachable!("Structs always pack to pointers")
+    };
+    Ok((packed_field_types, min_versions))
+}
+
+/******************************************************************************
+ * Helpers for implementing `Mojom`, used by #[derive(Mojom)].
+ ******************************************************************************/
+
+/// Return the fields of a struct value of the struct `struct_name`, which has
+/// `num_fields` fields.
+///
+/// A value from an older version of the struct may be missing the fields
+/// which were added after it, so only the first `num_required_fields` fields
+/// must be present.
+pub fn struct_fields_from_mojom_value(
+    value: MojomValue,
+    struct_name: &str,
+    num_fields: usize,
+    num_required_fields: usize,
+) -> Result<Vec<MojomValue>> {
+    let MojomValue::Struct(fields) = value else {
+        bail!("Expected struct {}, but got {:?}", struct_name, value)
+    };
+    if fields.len() < num_required_fields || fields.len() > num_fields {
+        bail!(
+            "Struct {} has {} fields ({} required), but got {}",
+            struct_name,
+            num_fields,
+            num_required_fields,
+            fields.len()
+        )
+    }
+    Ok(fields)
+}
+
+/// Convert the value of the field `field_name` of the struct `struct_name`.
+pub fn field_from_mojom_value<T: Mojom>(
+    value: MojomValue,
+    struct_name: &str,
+    field_name: &str,
+) -> Result<T> {
+    T::from_mojom_value(value).with_context(|| format!("In field {}.{}", struct_name, field_name))
+}
+
+/// The mojom type of a nullable field whose Rust type is `Option<T>`.
+pub fn nullable_mojom_type<T: Mojom>() -> MojomType {
+    MojomType::Nullable(Box::new(T::mojom_type()))
+}
+
+/// Convert the value of a nullable field.
+pub fn nullable_into_mojom_value<T: Mojom>(value: Option<T>) -> MojomValue {
+    value.map_or(MojomValue::Null, T::into_mojom_value)
+}
+
+/// Convert the value of the nullable field `field_name` of the struct
+/// `struct_name`.
+pub fn nullable_field_from_mojom_value<T: Mojom>(
+    value: MojomValue,
+    struct_name: &str,
+    field_name: &str,
+) -> Result<Option<T>> {
+    match value {
+        MojomValue::Null => Ok(None),
+        value => field_from_mojom_value(value, struct_name, field_name).map(Some),
+    }
+}
+
+/// Check that `value` is a value of the enum `enum_name`, whose variants have
+/// the values in `known_values`, and return it.
+///
+/// If the enum is extensible, `default_value` is the value of its default
+/// variant, which unknown values are mapped to. Otherwise, unknown values are
+/// errors.
+pub fn enum_from_mojom_value(
+    value: MojomValue,
+    enum_name: &str,
+    known_values: &[i32],
+    default_value: Option<i32>,
+) -> Result<i32> {
+    let MojomValue::Int32(value) = value else {
+        bail!("Expected enum {}, but got {:?}", enum_name, value)
+    };
+    if known_values.contains(&value) {
+        return Ok(value);
+    }
+    default_value.with_context(|| format!("Unknown value {} for enum {}", value, enum_name))
+}
+
+/// Return the tag and value of a value of the union `union_name`, which has
+/// `num_variants` fields.
+pub fn union_variant_from_mojom_value(
+    value: MojomValue,
+    union_name: &str,
+    num_variants: usize,
+) -> Result<(Ordinal, MojomValue)> {
+    let MojomValue::Union { tag, value } = value else {
+        bail!("Expected union {}, but got {:?}", union_name, value)
+    };
+    if tag >= num_variants {
+        bail!("Unknown tag {} for union {}, which has {} fields", tag, union_name, num_variants)
+    }
+    Ok((tag, *value))
+}
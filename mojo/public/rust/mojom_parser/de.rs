@@ -0,0 +1,379 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Decodes mojom values into Rust types which implement `serde::Deserialize`.
+//!
+//! This is the inverse of ser.rs, and uses the same mapping from the serde
+//! data model to mojom types.
+//!
+//! The mojom wire format isn't self-describing, so before we can parse
+//! anything we need the `MojomType` of the value we're decoding. We get it by
+//! tracing: we deserialize a dummy value of the Rust type using a deserializer
+//! which records each type it's asked for, and hands back placeholder values
+//! (zeros, empty strings, etc). Sequences are given a single placeholder
+//! element, so we can see the type of their elements.
+//!
+//! Once the data has been parsed into a `MojomValue`, which *is*
+//! self-describing, we deserialize the Rust value from that.
+
+use crate::ast::*;
+use crate::pack::pack_mojom_type;
+use crate::parse_primitives::ParserData;
+use crate::parse_values::parse_struct;
+use crate::ser::SerdeError;
+
+use anyhow::{bail, Result};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+
+type SerdeResult<T> = std::result::Result<T, SerdeError>;
+
+/// How deeply types may be nested when tracing. This is what stops us tracing
+/// recursive types forever.
+const MAX_TRACE_DEPTH: usize = 64;
+
+fn unsupported<T>(what: &str) -> SerdeResult<T> {
+    Err(SerdeError(format!("{} can't be decoded from mojom yet", what)))
+}
+
+/// Return the mojom type that values of `T` are encoded as.
+pub fn mojom_type_of<T: DeserializeOwned>() -> Result<MojomType> {
+    let mut ty = None;
+    T::deserialize(TypeTracer { ty: &mut ty, depth: 0 })?;
+    Ok(ty.expect("Tracing always records a type when it succeeds"))
+}
+
+/// Convert a mojom value to a `T`.
+pub fn from_mojom_value<T: DeserializeOwned>(value: MojomValue) -> Result<T> {
+    Ok(T::deserialize(value)?)
+}
+
+/// Decode a `T`, which must deserialize as a struct, from the mojom wire
+/// format.
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let ty = mojom_type_of::<T>()?;
+    if !matches!(ty, MojomType::Struct { .. }) {
+        bail!("Only structs can be decoded at the top level, but got {:?}", ty)
+    }
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, min_versions },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        unreachable!("Structs always pack to pointers")
+    };
+    let mut data = ParserData::new(bytes);
+    let fields = parse_struct(&mut data, &packed_field_types, &min_versions)?;
+    if data.remaining_bytes() != 0 {
+        bail!("There were {} trailing bytes after the struct", data.remaining_bytes())
+    }
+    from_mojom_value(MojomValue::Struct(fields))
+}
+
+/// A deserializer which records the type it's asked to deserialize in `ty`,
+/// and produces a placeholder value of that type.
+struct TypeTracer<'a> {
+    ty: &'a mut Option<MojomType>,
+    depth: usize,
+}
+
+impl TypeTracer<'_> {
+    /// Trace `len` elements, returning their types and the visitor's value.
+    fn trace_seq<'de, V: Visitor<'de>>(
+        &self,
+        len: usize,
+        visitor: V,
+    ) -> SerdeResult<(Vec<MojomType>, V::Value)> {
+        if self.depth >= MAX_TRACE_DEPTH {
+            return Err(SerdeError(
+                "Type is nested too deeply; recursive types aren't supported".to_string(),
+            ));
+        }
+        let mut seq = TraceSeq { types: vec![], remaining: len, depth: self.depth + 1 };
+        let value = visitor.visit_seq(&mut seq)?;
+        Ok((seq.types, value))
+    }
+
+    fn record<T>(self, ty: MojomType, value: T) -> SerdeResult<T> {
+        *self.ty = Some(ty);
+        Ok(value)
+    }
+}
+
+/// Hands out placeholder elements for a sequence or struct which is being
+/// traced, recording the type of each.
+struct TraceSeq {
+    types: Vec<MojomType>,
+    remaining: usize,
+    depth: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for TraceSeq {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> SerdeResult<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let mut ty = None;
+        let value = seed.deserialize(TypeTracer { ty: &mut ty, depth: self.depth })?;
+        self.types.push(ty.expect("Tracing always records a type when it succeeds"));
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Define the tracing functions for primitive types.
+macro_rules! trace_primitives {
+    ($($deserialize:ident => $visit:ident($placeholder:expr), $mojom_type:ident;)*) => {
+        $(
+            fn $deserialize<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+                let value = visitor.$visit($placeholder)?;
+                self.record(MojomType::$mojom_type, value)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for TypeTracer<'_> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> SerdeResult<V::Value> {
+        unsupported("Self-describing type")
+    }
+
+    trace_primitives! {
+        deserialize_bool => visit_bool(false), Bool;
+        deserialize_i8 => visit_i8(0), Int8;
+        deserialize_i16 => visit_i16(0), Int16;
+        deserialize_i32 => visit_i32(0), Int32;
+        deserialize_i64 => visit_i64(0), Int64;
+        deserialize_u8 => visit_u8(0), UInt8;
+        deserialize_u16 => visit_u16(0), UInt16;
+        deserialize_u32 => visit_u32(0), UInt32;
+        deserialize_u64 => visit_u64(0), UInt64;
+        deserialize_f32 => visit_f32(0.0), Float;
+        deserialize_f64 => visit_f64(0.0), Double;
+        deserialize_str => visit_str(""), String;
+        deserialize_string => visit_string(String::new()), String;
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, _visitor: V) -> SerdeResult<V::Value> {
+        unsupported("char")
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        let value = visitor.visit_byte_buf(vec![])?;
+        self.record(
+            MojomType::Array { element_type: Box::new(MojomType::UInt8), num_elements: None },
+            value,
+        )
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        let mut ty = None;
+        let value = visitor.visit_some(TypeTracer { ty: &mut ty, depth: self.depth })?;
+        match ty.expect("Tracing always records a type when it succeeds") {
+            MojomType::Nullable(_) => unsupported("Nested Option"),
+            ty => self.record(MojomType::Nullable(Box::new(ty)), value),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        let value = visitor.visit_unit()?;
+        self.record(MojomType::Struct { fields: vec![], min_versions: vec![] }, value)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        let (types, value) = self.trace_seq(1, visitor)?;
+        // If the visitor didn't ask for an element, its type doesn't matter.
+        let element_type = Box::new(types.into_iter().next().unwrap_or(MojomType::UInt8));
+        self.record(MojomType::Array { element_type, num_elements: None }, value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> SerdeResult<V::Value> {
+        let (types, value) = self.trace_seq(len, visitor)?;
+        let mut types = types.into_iter();
+        let element_type = types.next().unwrap_or(MojomType::UInt8);
+        if let Some(other) = types.find(|ty| *ty != element_type) {
+            return Err(SerdeError(format!(
+                "Array elements must all have the same type, but got {:?} and {:?}",
+                element_type, other
+            )));
+        }
+        let element_type = Box::new(element_type);
+        self.record(MojomType::Array { element_type, num_elements: Some(len) }, value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        let (fields, value) = self.trace_seq(len, visitor)?;
+        self.record(MojomType::Struct { fields, min_versions: vec![] }, value)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> SerdeResult<V::Value> {
+        unsupported("Map")
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        let (fields, value) = self.trace_seq(fields.len(), visitor)?;
+        self.record(MojomType::Struct { fields, min_versions: vec![] }, value)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        // Use the first variant as the placeholder.
+        let value = visitor.visit_enum(IntoDeserializer::<SerdeError>::into_deserializer(0u32))?;
+        self.record(MojomType::Int32, value)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> SerdeResult<V::Value> {
+        unsupported("Identifier")
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> SerdeResult<V::Value> {
+        unsupported("Ignored value")
+    }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for MojomValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Since `MojomValue`s know their own types, deserializing from them is mostly
+/// a matter of passing each value to the visitor.
+impl<'de> de::Deserializer<'de> for MojomValue {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            MojomValue::Bool(value) => visitor.visit_bool(value),
+            MojomValue::Int8(value) => visitor.visit_i8(value),
+            MojomValue::UInt8(value) => visitor.visit_u8(value),
+            MojomValue::Int16(value) => visitor.visit_i16(value),
+            MojomValue::UInt16(value) => visitor.visit_u16(value),
+            MojomValue::Int32(value) => visitor.visit_i32(value),
+            MojomValue::UInt32(value) => visitor.visit_u32(value),
+            MojomValue::Int64(value) => visitor.visit_i64(value),
+            MojomValue::UInt64(value) => visitor.visit_u64(value),
+            MojomValue::Float(value) => visitor.visit_f32(value),
+            MojomValue::Double(value) => visitor.visit_f64(value),
+            MojomValue::String(value) => visitor.visit_string(value),
+            MojomValue::Struct(values) | MojomValue::Array(values) => {
+                let mut seq = de::value::SeqDeserializer::new(values.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            MojomValue::Null => visitor.visit_none(),
+            // FOR_RELEASE: Map unions to enums with data, once the serializer
+            // supports them too.
+            MojomValue::Union { .. } => {
+                Err(SerdeError(format!("Unions aren't supported by serde yet, but got {:?}", self)))
+            }
+            // Endpoints and handles only mean something to the bindings, which
+            // use the `Mojom` trait instead.
+            MojomValue::PendingRemote { .. }
+            | MojomValue::PendingAssociatedRemote { .. }
+            | MojomValue::PendingAssociatedReceiver { .. }
+            | MojomValue::Handle { .. } => Err(SerdeError(format!(
+                "Endpoints and handles aren't supported by serde, but got {:?}",
+                self
+            ))),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            MojomValue::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            MojomValue::Struct(values) if values.is_empty() => visitor.visit_unit(),
+            _ => Err(SerdeError(format!("Expected an empty struct, got {:?}", self))),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        let MojomValue::Int32(value) = self else {
+            return Err(SerdeError(format!("Expected an int32 for enum {}, got {:?}", name, self)));
+        };
+        let index = u32::try_from(value)
+            .map_err(|_| SerdeError(format!("Invalid value {} for enum {}", value, name)))?;
+        visitor.visit_enum(IntoDeserializer::<SerdeError>::into_deserializer(index))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Default values of mojom types, and building struct values which leave out
+//! the fields that have defaults.
+//!
+//! A struct field may declare a default value in the mojom file, e.g.
+//! `int32 x = 5;`, which the field's `TypeDescriptor` holds. Fields which
+//! don't declare one default to the zero value of their type, as in the C++
+//! bindings: false, 0, an empty string or array, null, and so on.
+
+use crate::ast::*;
+
+use anyhow::{bail, Context, Result};
+
+impl MojomValue {
+    /// Return the zero value of `ty`. This ignores any defaults declared by
+    /// the fields of structs in `ty`; see `default_with_descriptor`.
+    ///
+    /// A union defaults to its first field, an associated endpoint to
+    /// `INVALID_ENDPOINT_INDEX` and a remote or handle to
+    /// `INVALID_HANDLE_INDEX`, which are only valid to send if they're
+    /// nullable.
+    pub fn default_for(ty: &MojomType) -> MojomValue {
+        MojomValue::default_with_descriptor(ty, &TypeDescriptor::Unnamed)
+    }
+
+    /// Return the default value of `ty`, which `descriptor` describes. Struct
+    /// fields which declare a default value get it, and all other values are
+    /// the same as for `default_for`.
+    pub fn default_with_descriptor(ty: &MojomType, descriptor: &TypeDescriptor) -> MojomValue {
+        match ty {
+            MojomType::Bool => MojomValue::Bool(false),
+            MojomType::Int8 => MojomValue::Int8(0),
+            MojomType::UInt8 => MojomValue::UInt8(0),
+            MojomType::Int16 => MojomValue::Int16(0),
+            MojomType::UInt16 => MojomValue::UInt16(0),
+            MojomType::Int32 => MojomValue::Int32(0),
+            MojomType::UInt32 => MojomValue::UInt32(0),
+            MojomType::Int64 => MojomValue::Int64(0),
+            MojomType::UInt64 => MojomValue::UInt64(0),
+            MojomType::Float => MojomValue::Float(0.0),
+            MojomType::Double => MojomValue::Double(0.0),
+            MojomType::String => MojomValue::String(String::new()),
+            MojomType::Struct { fields, .. } => MojomValue::Struct(
+                fields
+                    .iter()
+                    .enumerate()
+                    .map(|(ordinal, field_type)| match descriptor.field_default(ordinal) {
+                        Some(value) => value.clone(),
+                        None => MojomValue::default_with_descriptor(
+                            field_type,
+                            descriptor.field(ordinal),
+                        ),
+                    })
+                    .collect(),
+            ),
+            // Only build an element if there are any, since a recursive type
+            // may contain empty arrays of itself.
+            MojomType::Array { num_elements: None | Some(0), .. } => MojomValue::Array(vec![]),
+            MojomType::Array { element_type, num_elements: Some(num_elements) } => {
+                let element = match descriptor {
+                    TypeDescriptor::Array { element } => element.as_ref(),
+                    _ => &TypeDescriptor::Unnamed,
+                };
+                let element = MojomValue::default_with_descriptor(element_type, element);
+                MojomValue::Array(vec![element; *num_elements])
+            }
+            MojomType::Union { variants } => match variants.first() {
+                Some(variant) => MojomValue::Union {
+                    tag: 0,
+                    value: Box::new(MojomValue::default_with_descriptor(
+                        variant,
+                        descriptor.field(0),
+                    )),
+                },
+                // Mojom unions always have at least one field.
+                None => MojomValue::Null,
+            },
+            MojomType::PendingRemote => {
+                MojomValue::PendingRemote { index: INVALID_HANDLE_INDEX, version: 0 }
+            }
+            MojomType::PendingAssociatedRemote => {
+                MojomValue::PendingAssociatedRemote { index: INVALID_ENDPOINT_INDEX, version: 0 }
+            }
+            MojomType::PendingAssociatedReceiver => {
+                MojomValue::PendingAssociatedReceiver { index: INVALID_ENDPOINT_INDEX }
+            }
+            MojomType::Handle => MojomValue::Handle { index: INVALID_HANDLE_INDEX },
+            MojomType::Nullable(_) => MojomValue::Null,
+            MojomType::Recursive(recursive_type) => MojomValue::default_with_descriptor(
+                &recursive_type.mojom_type(),
+                &descriptor.resolve(),
+            ),
+        }
+    }
+
+    /// Build a value of the struct type `ty`, which `descriptor` describes,
+    /// from its fields' names and values. Fields which declare a default
+    /// value may be left out, and get their default.
+    pub fn new_struct<'a>(
+        ty: &MojomType,
+        descriptor: &TypeDescriptor,
+        fields: impl IntoIterator<Item = (&'a str, MojomValue)>,
+    ) -> Result<MojomValue> {
+        let (MojomType::Struct { fields: field_types, .. }, TypeDescriptor::Struct { name, .. }) =
+            (ty, descriptor)
+        else {
+            bail!("Expected a struct type and descriptor, but got {:?}", ty)
+        };
+        let mut values: Vec<Option<MojomValue>> = vec![None; field_types.len()];
+        for (field_name, value) in fields {
+            let ordinal = (0..field_types.len())
+                .find(|ordinal| descriptor.field_name(*ordinal) == Some(field_name))
+                .with_context(|| format!("Struct {} has no field {}", name, field_name))?;
+            if values[ordinal].replace(value).is_some() {
+                bail!("Field {}.{} was given more than once", name, field_name)
+            }
+        }
+        let values = values
+            .into_iter()
+            .enumerate()
+            .map(|(ordinal, value)| match value {
+                Some(value) => Ok(value),
+                None => descriptor.field_default(ordinal).cloned().with_context(|| {
+                    format!(
+                        "Field {}.{} has no default, so it must be given",
+                        name,
+                        descriptor.field_name(ordinal).unwrap_or_default()
+                    )
+                }),
+            })
+            .collect::<Result<_>>()?;
+        Ok(MojomValue::Struct(values))
+    }
+}
@@ -2,15 +2,100 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+//! Encodes mojom values and messages.
+//!
+//! Everything is encoded into a single buffer, which is allocated up front
+//! with the exact size computed by serialized_size.rs. The buffer can be a
+//! `Vec<u8>`, or any slice which is big enough, such as the buffer that a
+//! message pipe gives us for a new message.
+
 use crate::ast::*;
+use crate::parse_messages::MessageHeader;
+use crate::serialized_size::*;
 
 use anyhow::{bail, Context, Result};
 
-fn get_field_at_ordinal(
-    field_values: &Vec<(String, MojomValue)>,
-    ordinal: Ordinal,
-) -> Result<&MojomValue> {
-    let (_field_name, field_value) = field_values.get(ordinal).with_context(|| {
+/// Writes to a preallocated buffer, from front to back. Every byte is
+/// written, including padding, so the buffer doesn't need to be zeroed.
+///
+/// The buffer must be big enough for everything that's written to it, which
+/// the callers make sure of using serialized_size.rs. Writing past its end
+/// panics.
+struct Encoder<'a> {
+    data: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(data: &'a mut [u8]) -> Self {
+        Encoder { data, len: 0 }
+    }
+
+    /// The number of bytes written so far.
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.data[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn extend<const N: usize>(&mut self, bytes: [u8; N]) {
+        self.extend_from_slice(&bytes)
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.extend([byte])
+    }
+
+    /// Return the next `len` bytes, for the caller to write, and skip past
+    /// them.
+    fn take(&mut self, len: usize) -> &mut [u8] {
+        let start = self.len;
+        self.len += len;
+        &mut self.data[start..self.len]
+    }
+
+    /// Write `len` zeros.
+    fn zeros(&mut self, len: usize) {
+        self.data[self.len..self.len + len].fill(0);
+        self.len += len;
+    }
+
+    /// Overwrite bytes which were already written, starting at `start`.
+    fn write_at(&mut self, start: usize, bytes: &[u8]) {
+        debug_assert!(start + bytes.len() <= self.len);
+        self.data[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+/// Run `deparse` on an encoder which writes `size` bytes to the end of
+/// `data`. `data` is only ever grown once, and is left as it was if
+/// `deparse` fails.
+fn deparse_into_vec(
+    data: &mut Vec<u8>,
+    size: usize,
+    deparse: impl FnOnce(&mut Encoder) -> Result<()>,
+) -> Result<()> {
+    let initial_bytes = data.len();
+    data.resize(initial_bytes + size, 0);
+    let mut encoder = Encoder::new(&mut data[initial_bytes..]);
+    let result = deparse(&mut encoder);
+    debug_assert!(result.is_err() || encoder.len() == size, "serialized_size was wrong");
+    if result.is_err() {
+        data.truncate(initial_bytes);
+    }
+    result
+}
+
+/// Convert a size or offset to the number of bytes used to encode it.
+fn encoded_size<T: TryFrom<usize>>(size: usize) -> Result<T> {
+    T::try_from(size).map_err(|_| anyhow::anyhow!("Can't encode a size of {} bytes", size))
+}
+
+fn get_field_at_ordinal(field_values: &[MojomValue], ordinal: Ordinal) -> Result<&MojomValue> {
+    let field_value = field_values.get(ordinal).with_context(|| {
         format!(
             "Wire type asked for field with ordinal {}, but there are only {} fields.",
             ordinal,
@@ -33,17 +118,39 @@ fn check_value_has_expected_type(value: &MojomValue, expected_type: &MojomWireTy
             | (PackedLeafType::Int32, MojomValue::Int32(_))
             | (PackedLeafType::UInt32, MojomValue::UInt32(_))
             | (PackedLeafType::Int64, MojomValue::Int64(_))
-            | (PackedLeafType::UInt64, MojomValue::UInt64(_)) => true,
+            | (PackedLeafType::UInt64, MojomValue::UInt64(_))
+            | (PackedLeafType::Float, MojomValue::Float(_))
+            | (PackedLeafType::Double, MojomValue::Double(_))
+            | (PackedLeafType::AssociatedEndpoint { nullable: true }, MojomValue::Null)
+            | (PackedLeafType::AssociatedInterface { nullable: true }, MojomValue::Null)
+            | (PackedLeafType::Interface { nullable: true }, MojomValue::Null)
+            | (PackedLeafType::Handle { nullable: true }, MojomValue::Null)
+            | (PackedLeafType::Interface { .. }, MojomValue::PendingRemote { .. })
+            | (PackedLeafType::Handle { .. }, MojomValue::Handle { .. })
+            | (
+                PackedLeafType::AssociatedEndpoint { .. },
+                MojomValue::PendingAssociatedReceiver { .. },
+            )
+            | (
+                PackedLeafType::AssociatedInterface { .. },
+                MojomValue::PendingAssociatedRemote { .. },
+            ) => true,
             _ => false,
         },
         (MojomWireType::Bitfield { .. }, MojomValue::Bool(_)) => true,
+        (MojomWireType::Pointer { nullable: true, .. }, MojomValue::Null) => true,
         (MojomWireType::Pointer { nested_data_type, .. }, _) => match (nested_data_type, value) {
             (PackedStructuredType::Struct { .. }, MojomValue::Struct { .. })
             // FOR_RELEASE: Should we care about which type of array this was originally?
             | (PackedStructuredType::Array { .. }, MojomValue::Array { .. })
-            | (PackedStructuredType::Array { .. }, MojomValue::String { .. }) => true,
+            | (PackedStructuredType::Array { .. }, MojomValue::String { .. })
+            | (PackedStructuredType::Union { .. }, MojomValue::Union { .. }) => true,
+            // The value is checked once the type is resolved.
+            (PackedStructuredType::Recursive(_), _) => true,
             _ => false,
         },
+        (MojomWireType::Union { nullable: true, .. }, MojomValue::Null)
+        | (MojomWireType::Union { .. }, MojomValue::Union { .. }) => true,
         _ => false,
     };
     if matches {
@@ -53,24 +160,53 @@ fn check_value_has_expected_type(value: &MojomValue, expected_type: &MojomWireTy
     }
 }
 
-fn pad_to_alignment(data: &mut Vec<u8>, alignment: usize) {
+fn pad_to_alignment(data: &mut Encoder, alignment: usize) {
     let mismatch = data.len() % alignment;
     if mismatch != 0 {
-        data.extend(vec![0; alignment - mismatch])
+        data.zeros(alignment - mismatch)
     }
 }
 
 /// Write out the bytes for a leaf node
-fn deparse_leaf_value(data: &mut Vec<u8>, value: &MojomValue) -> Result<()> {
-    match value {
-        MojomValue::Int8(value) => data.extend(value.to_le_bytes()),
-        MojomValue::UInt8(value) => data.extend(value.to_le_bytes()),
-        MojomValue::Int16(value) => data.extend(value.to_le_bytes()),
-        MojomValue::UInt16(value) => data.extend(value.to_le_bytes()),
-        MojomValue::Int32(value) => data.extend(value.to_le_bytes()),
-        MojomValue::UInt32(value) => data.extend(value.to_le_bytes()),
-        MojomValue::Int64(value) => data.extend(value.to_le_bytes()),
-        MojomValue::UInt64(value) => data.extend(value.to_le_bytes()),
+fn deparse_leaf_value(
+    data: &mut Encoder,
+    leaf_type: &PackedLeafType,
+    value: &MojomValue,
+) -> Result<()> {
+    match (leaf_type, value) {
+        (_, MojomValue::Int8(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::UInt8(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::Int16(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::UInt16(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::Int32(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::UInt32(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::Int64(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::UInt64(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::Float(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::Double(value)) => data.extend(value.to_le_bytes()),
+        (_, MojomValue::PendingRemote { index, version })
+        | (_, MojomValue::PendingAssociatedRemote { index, version }) => {
+            data.extend(index.to_le_bytes());
+            data.extend(version.to_le_bytes());
+        }
+        (_, MojomValue::PendingAssociatedReceiver { index }) => data.extend(index.to_le_bytes()),
+        // Null associated endpoints have an invalid index, and a version of 0
+        // for remotes.
+        (PackedLeafType::AssociatedEndpoint { .. }, MojomValue::Null) => {
+            data.extend(INVALID_ENDPOINT_INDEX.to_le_bytes())
+        }
+        (PackedLeafType::AssociatedInterface { .. }, MojomValue::Null) => {
+            data.extend(INVALID_ENDPOINT_INDEX.to_le_bytes());
+            data.extend(0u32.to_le_bytes());
+        }
+        (_, MojomValue::Handle { index }) => data.extend(index.to_le_bytes()),
+        (PackedLeafType::Handle { .. }, MojomValue::Null) => {
+            data.extend(INVALID_HANDLE_INDEX.to_le_bytes())
+        }
+        (PackedLeafType::Interface { .. }, MojomValue::Null) => {
+            data.extend(INVALID_HANDLE_INDEX.to_le_bytes());
+            data.extend(0u32.to_le_bytes());
+        }
         _ => bail!("deparse_leaf_value: {:?} is not a leaf value", value),
     }
     Ok(())
@@ -82,12 +218,25 @@ fn deparse_leaf_value(data: &mut Vec<u8>, value: &MojomValue) -> Result<()> {
 
 enum NestedData<'a> {
     Struct {
-        field_values: &'a Vec<(String, MojomValue)>,
-        packed_fields: &'a Vec<(String, MojomWireType)>,
+        field_values: &'a [MojomValue],
+        packed_fields: &'a [MojomWireType],
+        min_versions: &'a [u32],
     },
     Array {
-        elements: &'a Vec<MojomValue>,
-        element_type: &'a Box<MojomWireType>,
+        elements: &'a [MojomValue],
+        element_type: &'a MojomWireType,
+        array_type: &'a PackedArrayType,
+    },
+    String(&'a str),
+    /// A union which is pointed to by a field of another union.
+    Union {
+        value: &'a MojomValue,
+        variants: &'a [MojomWireType],
+    },
+    /// A value of a recursive type, which is resolved when it's written.
+    Recursive {
+        value: &'a MojomValue,
+        ty: RecursiveType,
     },
 }
 /// Information about a nested struct/array, which we will emit later
@@ -99,42 +248,93 @@ struct NestedDataInfo<'a> {
     ptr_loc: usize,
 }
 
-/// Overwrite data[start..start+len] with the contents of `value`.
-/// Panics if the range doesn't exist or the `value` is too short, probably.
-fn write_to_slice(data: &mut Vec<u8>, start: usize, len: usize, value: &[u8]) {
-    let struct_size_field: &mut [u8] = &mut data[start..start + len];
-    struct_size_field.copy_from_slice(&value[0..len]);
-}
-
+/// Append the encoding of a struct with the given fields to `data`. It's
+/// encoded as the latest version that `min_versions` has fields for (see
+/// `PackedStructuredType::Struct`).
 pub fn deparse_struct(
     data: &mut Vec<u8>,
-    field_values: &Vec<(String, MojomValue)>,
-    packed_fields: &Vec<(String, MojomWireType)>,
+    field_values: &[MojomValue],
+    packed_fields: &[MojomWireType],
+    min_versions: &[u32],
+) -> Result<()> {
+    let size = struct_serialized_size(field_values, packed_fields)?;
+    deparse_into_vec(data, size, |encoder| {
+        encode_struct(encoder, field_values, packed_fields, min_versions)
+    })
+}
+
+/// Write the encoding of a struct with the given fields to the start of
+/// `buffer`, and return the number of bytes written. Fails if `buffer` is
+/// smaller than `struct_serialized_size()`.
+pub fn deparse_struct_into(
+    buffer: &mut [u8],
+    field_values: &[MojomValue],
+    packed_fields: &[MojomWireType],
+    min_versions: &[u32],
+) -> Result<usize> {
+    let size = struct_serialized_size(field_values, packed_fields)?;
+    if buffer.len() < size {
+        bail!("The struct needs {} bytes, but the buffer only has {}", size, buffer.len())
+    }
+    let mut encoder = Encoder::new(buffer);
+    encode_struct(&mut encoder, field_values, packed_fields, min_versions)?;
+    debug_assert_eq!(encoder.len(), size, "serialized_size was wrong");
+    Ok(size)
+}
+
+/// Whether one of the struct's bitfields holds a has-value flag for the field
+/// with the given ordinal, i.e. whether it's a nullable value field.
+fn has_value_flag(packed_fields: &[MojomWireType], ordinal: Ordinal) -> bool {
+    packed_fields.iter().any(|packed_field| match packed_field {
+        MojomWireType::Bitfield { ordinals, has_value_bits } => ordinals
+            .iter()
+            .enumerate()
+            .any(|(idx, bit)| *bit == Some(ordinal) && (has_value_bits >> idx) & 1 == 1),
+        _ => false,
+    })
+}
+
+fn encode_struct(
+    data: &mut Encoder,
+    field_values: &[MojomValue],
+    packed_fields: &[MojomWireType],
+    min_versions: &[u32],
 ) -> Result<()> {
     let initial_bytes = data.len();
     // Write the struct's header
     data.extend([0; 4]); // Size; we'll fill this in later
-    data.extend([0; 4]); // Version number; not supported yet
+    data.extend(struct_latest_version(min_versions).to_le_bytes());
 
     // Go through all the fields and either write them to the vector, or
     // (for nested data) prepare for them to be written later, in order.
     let mut nested_data_infos: Vec<NestedDataInfo> = vec![];
-    for (_name, packed_field) in packed_fields {
+    for packed_field in packed_fields {
         match packed_field {
-            MojomWireType::Leaf { ordinal, leaf_type: _ } => {
+            MojomWireType::Leaf { ordinal, leaf_type } => {
                 let leaf_value = get_field_at_ordinal(field_values, *ordinal)?;
-                check_value_has_expected_type(leaf_value, packed_field)?;
                 pad_to_alignment(data, packed_field.alignment());
-                deparse_leaf_value(data, leaf_value)?
+                // The value of a null nullable value field is left as 0.
+                if leaf_value == &MojomValue::Null && has_value_flag(packed_fields, *ordinal) {
+                    data.extend_from_slice(&[0; 8][..packed_field.size()]);
+                    continue;
+                }
+                check_value_has_expected_type(leaf_value, packed_field)?;
+                deparse_leaf_value(data, leaf_type, leaf_value)?
             }
-            MojomWireType::Bitfield { ordinals } => {
+            MojomWireType::Bitfield { ordinals, has_value_bits } => {
                 let mut iter = ordinals.into_iter().enumerate();
                 let mut bitfield: u8 = 0;
                 // Construct the bitfield bit-by-bit
                 while let Some((idx, Some(ordinal))) = iter.next() {
                     let bit_value = get_field_at_ordinal(field_values, *ordinal)?;
-                    if let MojomValue::Bool(bit) = bit_value {
+                    if (has_value_bits >> idx) & 1 == 1 {
+                        bitfield |= ((bit_value != &MojomValue::Null) as u8) << idx;
+                    } else if let MojomValue::Bool(bit) = bit_value {
                         bitfield |= (*bit as u8) << idx;
+                    } else if bit_value == &MojomValue::Null
+                        && has_value_flag(packed_fields, *ordinal)
+                    {
+                        // The value of a null nullable bool is left as 0.
                     } else {
                         // We know this will fail, but calling it lets us avoid
                         // writing a custom error message here.
@@ -144,57 +344,373 @@ pub fn deparse_struct(
                 // Now we've set all the bits, write it to the wire
                 data.push(bitfield)
             }
-            MojomWireType::Pointer { ordinal, nested_data_type } => {
+            MojomWireType::Pointer { ordinal, nested_data_type, nullable } => {
                 let nested_data_value = get_field_at_ordinal(field_values, *ordinal)?;
                 pad_to_alignment(data, 8);
-                let nested_data = match (nested_data_value, nested_data_type) {
-                    (
-                        MojomValue::Struct(nested_data_fields),
-                        PackedStructuredType::Struct { packed_field_types },
-                    ) => NestedData::Struct {
-                        field_values: nested_data_fields,
-                        packed_fields: packed_field_types,
-                    },
-                    (
-                        MojomValue::Array(nested_data_fields),
-                        PackedStructuredType::Array { element_type, .. },
-                    ) => NestedData::Array { elements: nested_data_fields, element_type },
-                    _ => bail!(
-                        "Unexpected type for nested data: Expected {:?}, got {:?}",
-                        nested_data_type,
-                        nested_data_value
-                    ),
-                };
-                nested_data_infos.push(NestedDataInfo { nested_data, ptr_loc: data.len() });
+                // Null pointers are left as 0.
+                if let Some(nested_data) =
+                    get_nested_data(nested_data_value, nested_data_type, *nullable)?
+                {
+                    nested_data_infos.push(NestedDataInfo { nested_data, ptr_loc: data.len() });
+                }
                 // Allocate space for the pointer, we'll write to it later.
                 pad_to_alignment(data, 8);
                 data.extend([0; 8]);
             }
+            MojomWireType::Union { ordinal, variants, nullable } => {
+                let union_value = get_field_at_ordinal(field_values, *ordinal)?;
+                pad_to_alignment(data, 8);
+                deparse_inline_union(
+                    data,
+                    union_value,
+                    variants,
+                    *nullable,
+                    &mut nested_data_infos,
+                )?
+            }
         }
     }
 
     // Struct bodies must be a multiple of 8 bytes.
     pad_to_alignment(data, 8);
 
-    let bytes_written = data.len() - initial_bytes;
+    let bytes_written: u32 = encoded_size(data.len() - initial_bytes)?;
     // Write the length of the struct to the first 4 bytes of the header
-    write_to_slice(data, initial_bytes, 4, &usize::to_le_bytes(bytes_written));
+    data.write_at(initial_bytes, &bytes_written.to_le_bytes());
+
+    deparse_nested_data(data, nested_data_infos)
+}
+
+/// Write a message header, which must come before the message's payload.
+///
+/// This writes a version 1 header, which is all that's needed unless the
+/// message carries associated interfaces. See `deparse_message`.
+pub fn deparse_message_header(data: &mut Vec<u8>, header: &MessageHeader) {
+    let result = deparse_into_vec(data, 32, |encoder| {
+        encode_message_header(encoder, header);
+        Ok(())
+    });
+    debug_assert!(result.is_ok());
+}
+
+fn encode_message_header(data: &mut Encoder, header: &MessageHeader) {
+    data.extend(32u32.to_le_bytes()); // Size in bytes
+    data.extend(1u32.to_le_bytes()); // Version number
+    deparse_header_fields(data, header);
+}
+
+fn deparse_header_fields(data: &mut Encoder, header: &MessageHeader) {
+    data.extend(header.interface_id.to_le_bytes());
+    data.extend(header.name.to_le_bytes());
+    data.extend(header.flags.to_le_bytes());
+    data.extend(header.trace_nonce.to_le_bytes());
+    data.extend(header.request_id.to_le_bytes());
+}
+
+/// Write an entire message, given its already-encoded payload and the IDs of
+/// the associated interfaces that the payload refers to by index.
+///
+/// Messages without associated interfaces get a version 1 header. Otherwise
+/// they need a version 2 header, which points to the payload and to the
+/// array of interface IDs that follows it.
+pub fn deparse_message(
+    data: &mut Vec<u8>,
+    header: &MessageHeader,
+    payload: &[u8],
+    interface_ids: &[u32],
+) -> Result<()> {
+    let size = message_serialized_size(payload.len(), interface_ids)?;
+    deparse_into_vec(data, size, |encoder| {
+        encode_message(encoder, header, payload.len(), interface_ids, |buffer| {
+            buffer.copy_from_slice(payload);
+            Ok(())
+        })
+    })
+}
+
+/// Write an entire message to the start of `buffer`, like `deparse_message`,
+/// and return the number of bytes written. Fails if `buffer` is smaller than
+/// `message_serialized_size()`.
+pub fn deparse_message_into(
+    buffer: &mut [u8],
+    header: &MessageHeader,
+    payload: &[u8],
+    interface_ids: &[u32],
+) -> Result<usize> {
+    deparse_message_with(buffer, header, payload.len(), interface_ids, |payload_buffer| {
+        payload_buffer.copy_from_slice(payload);
+        Ok(())
+    })
+}
+
+/// Write an entire message to the start of `buffer`, like
+/// `deparse_message_into`, where `write_payload` encodes the payload of
+/// `payload_size` bytes straight into the part of `buffer` it's given, e.g.
+/// with `PreparedStruct::deparse_into`.
+pub fn deparse_message_with(
+    buffer: &mut [u8],
+    header: &MessageHeader,
+    payload_size: usize,
+    interface_ids: &[u32],
+    write_payload: impl FnOnce(&mut [u8]) -> Result<()>,
+) -> Result<usize> {
+    let size = message_serialized_size(payload_size, interface_ids)?;
+    if buffer.len() < size {
+        bail!("The message needs {} bytes, but the buffer only has {}", size, buffer.len())
+    }
+    let mut encoder = Encoder::new(buffer);
+    encode_message(&mut encoder, header, payload_size, interface_ids, write_payload)?;
+    debug_assert_eq!(encoder.len(), size, "serialized_size was wrong");
+    Ok(size)
+}
+
+fn encode_message(
+    data: &mut Encoder,
+    header: &MessageHeader,
+    payload_size: usize,
+    interface_ids: &[u32],
+    write_payload: impl FnOnce(&mut [u8]) -> Result<()>,
+) -> Result<()> {
+    if interface_ids.is_empty() {
+        encode_message_header(data, header);
+        return write_payload(data.take(payload_size));
+    }
+    let initial_bytes = data.len();
+    data.extend(48u32.to_le_bytes()); // Size in bytes
+    data.extend(2u32.to_le_bytes()); // Version number
+    deparse_header_fields(data, header);
+    // Pointers are relative to their own location. The payload directly
+    // follows the header, and the interface IDs follow the payload.
+    let padded_payload_len = payload_size.next_multiple_of(8);
+    data.extend(16u64.to_le_bytes());
+    data.extend(u64::try_from(8 + padded_payload_len)?.to_le_bytes());
+    write_payload(data.take(payload_size))?;
+    pad_to_alignment(data, 8);
+    debug_assert_eq!(data.len(), initial_bytes + 48 + padded_payload_len);
+
+    let ids: Vec<MojomValue> = interface_ids.iter().copied().map(MojomValue::UInt32).collect();
+    let element_type = MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::UInt32 };
+    encode_array(data, &ids, &element_type, &PackedArrayType::UnsizedArray)
+}
+
+/// Match up the value of a pointer field with the type of the data it points
+/// to. Returns `None` if the value is null, which is only allowed if the
+/// pointer is nullable.
+fn get_nested_data<'a>(
+    value: &'a MojomValue,
+    ty: &'a PackedStructuredType,
+    nullable: bool,
+) -> Result<Option<NestedData<'a>>> {
+    Ok(Some(match (value, ty) {
+        (MojomValue::Null, _) if nullable => return Ok(None),
+        (
+            MojomValue::Struct(field_values),
+            PackedStructuredType::Struct { packed_field_types, min_versions },
+        ) => NestedData::Struct { field_values, packed_fields: packed_field_types, min_versions },
+        (MojomValue::Array(elements), PackedStructuredType::Array { element_type, array_type }) => {
+            NestedData::Array { elements, element_type, array_type }
+        }
+        (
+            MojomValue::String(value),
+            PackedStructuredType::Array { array_type: PackedArrayType::String, .. },
+        ) => NestedData::String(value),
+        (MojomValue::Union { .. }, PackedStructuredType::Union { variants }) => {
+            NestedData::Union { value, variants }
+        }
+        (_, PackedStructuredType::Recursive(ty)) => NestedData::Recursive { value, ty: *ty },
+        _ => bail!("Unexpected type for nested data: Expected {:?}, got {:?}", ty, value),
+    }))
+}
 
+/// Write out the nested data which the pointers in `nested_data_infos` point
+/// to, and fill in the pointers.
+fn deparse_nested_data(data: &mut Encoder, nested_data_infos: Vec<NestedDataInfo>) -> Result<()> {
     for nested_data_info in nested_data_infos {
         // Write to this nested data's pointer.
-        let bytes_from_ptr = data.len() - nested_data_info.ptr_loc;
-        write_to_slice(data, nested_data_info.ptr_loc, 8, &usize::to_le_bytes(bytes_from_ptr));
+        let bytes_from_ptr: u64 = encoded_size(data.len() - nested_data_info.ptr_loc)?;
+        data.write_at(nested_data_info.ptr_loc, &bytes_from_ptr.to_le_bytes());
+
+        encode_nested_data(data, nested_data_info.nested_data)?;
+    }
 
-        match nested_data_info.nested_data {
-            NestedData::Struct { field_values, packed_fields } => {
-                deparse_struct(data, field_values, packed_fields)?
+    Ok(())
+}
+
+/// Write out a nested struct, array, string or union, which a pointer has
+/// already been written to.
+fn encode_nested_data(data: &mut Encoder, nested_data: NestedData) -> Result<()> {
+    match nested_data {
+        NestedData::Struct { field_values, packed_fields, min_versions } => {
+            encode_struct(data, field_values, packed_fields, min_versions)
+        }
+        NestedData::Array { elements, element_type, array_type } => {
+            encode_array(data, elements, element_type, array_type)
+        }
+        NestedData::String(value) => deparse_string(data, value),
+        NestedData::Union { value, variants } => {
+            // A null union is represented by a null pointer, so the union
+            // itself can't be null.
+            let mut union_nested_data_infos = vec![];
+            deparse_inline_union(data, value, variants, false, &mut union_nested_data_infos)?;
+            deparse_nested_data(data, union_nested_data_infos)
+        }
+        NestedData::Recursive { value, ty } => {
+            // Null values were handled before the pointer was written.
+            let ty = PackedStructuredType::Recursive(ty).resolve().into_owned();
+            match get_nested_data(value, &ty, false)? {
+                Some(nested_data) => encode_nested_data(data, nested_data),
+                None => unreachable!("Only nullable values can be null"),
             }
-            NestedData::Array { elements, element_type } => {
-                let _ = (elements, element_type); // Selectively silence unused code warning
-                bail!("Arrays not yet implemented")
+        }
+    }
+}
+
+/// Write out a union which is stored inline in a struct, array or (if it's
+/// pointed to by another union) by itself. If the union's value has nested
+/// data, it's added to `nested_data_infos`, to be written after the enclosing
+/// object.
+fn deparse_inline_union<'a>(
+    data: &mut Encoder,
+    value: &'a MojomValue,
+    variants: &'a [MojomWireType],
+    nullable: bool,
+    nested_data_infos: &mut Vec<NestedDataInfo<'a>>,
+) -> Result<()> {
+    let (tag, variant_value) = match value {
+        // Null unions are all zeros, including their size.
+        MojomValue::Null if nullable => {
+            data.zeros(16);
+            return Ok(());
+        }
+        MojomValue::Union { tag, value } => (*tag, value.as_ref()),
+        _ => bail!("Expected a union, but got {:?}", value),
+    };
+    let variant = variants
+        .get(tag)
+        .with_context(|| format!("Union has {} fields, but got tag {}", variants.len(), tag))?;
+    let wire_tag =
+        u32::try_from(tag).with_context(|| format!("Union tag {} doesn't fit in a u32", tag))?;
+    data.extend(16u32.to_le_bytes());
+    data.extend(wire_tag.to_le_bytes());
+
+    let value_start = data.len();
+    check_value_has_expected_type(variant_value, variant)?;
+    match variant {
+        MojomWireType::Leaf { leaf_type, .. } => {
+            deparse_leaf_value(data, leaf_type, variant_value)?
+        }
+        // A bool is stored in the lowest bit of the first byte.
+        MojomWireType::Bitfield { .. } => {
+            if let MojomValue::Bool(bit) = variant_value {
+                data.push(*bit as u8);
+            }
+        }
+        MojomWireType::Pointer { nested_data_type, nullable, .. } => {
+            if let Some(nested_data) = get_nested_data(variant_value, nested_data_type, *nullable)?
+            {
+                nested_data_infos.push(NestedDataInfo { nested_data, ptr_loc: data.len() });
             }
         }
+        MojomWireType::Union { .. } => bail!("Unions can't be stored inline in other unions"),
     }
+    // Fill the rest of the 8-byte slot. Pointers are filled in later.
+    data.zeros(value_start + 8 - data.len());
+    Ok(())
+}
+
+/// Write the 8-byte header of an array with `num_elements` elements. The size
+/// is left as 0, to be filled in by `finish_array` once the elements have been
+/// written.
+fn start_array(data: &mut Encoder, num_elements: usize) -> Result<()> {
+    let num_elements = u32::try_from(num_elements)
+        .with_context(|| format!("Arrays can't have {} elements", num_elements))?;
+    data.extend([0; 4]); // Size; we'll fill this in later
+    data.extend(num_elements.to_le_bytes());
+    Ok(())
+}
 
+/// Fill in the size of an array which started at `initial_bytes`, and pad it
+/// so that whatever comes next is 8-byte aligned.
+fn finish_array(data: &mut Encoder, initial_bytes: usize) -> Result<()> {
+    // Unlike structs, the size of an array doesn't include the final padding.
+    let bytes_written: u32 = encoded_size(data.len() - initial_bytes)?;
+    data.write_at(initial_bytes, &bytes_written.to_le_bytes());
+    pad_to_alignment(data, 8);
     Ok(())
 }
+
+/// Append the encoding of an array with the given elements to `data`.
+pub fn deparse_array(
+    data: &mut Vec<u8>,
+    elements: &[MojomValue],
+    element_type: &MojomWireType,
+    array_type: &PackedArrayType,
+) -> Result<()> {
+    let size = array_serialized_size(elements, element_type)?;
+    deparse_into_vec(data, size, |encoder| {
+        encode_array(encoder, elements, element_type, array_type)
+    })
+}
+
+fn encode_array(
+    data: &mut Encoder,
+    elements: &[MojomValue],
+    element_type: &MojomWireType,
+    array_type: &PackedArrayType,
+) -> Result<()> {
+    if let PackedArrayType::SizedArray(expected_elements) = array_type
+        && elements.len() != *expected_elements
+    {
+        bail!("Expected an array of {} elements, but got {}", expected_elements, elements.len())
+    }
+    let initial_bytes = data.len();
+    start_array(data, elements.len())?;
+
+    let mut nested_data_infos: Vec<NestedDataInfo> = vec![];
+    match element_type {
+        MojomWireType::Leaf { leaf_type, .. } => {
+            for element in elements {
+                check_value_has_expected_type(element, element_type)?;
+                deparse_leaf_value(data, leaf_type, element)?;
+            }
+        }
+        // Bools are packed 8 to a byte, starting with the LSB.
+        MojomWireType::Bitfield { .. } => {
+            for chunk in elements.chunks(8) {
+                let mut bitfield: u8 = 0;
+                for (idx, element) in chunk.iter().enumerate() {
+                    check_value_has_expected_type(element, element_type)?;
+                    if let MojomValue::Bool(bit) = element {
+                        bitfield |= (*bit as u8) << idx;
+                    }
+                }
+                data.push(bitfield);
+            }
+        }
+        MojomWireType::Pointer { nested_data_type, nullable, .. } => {
+            for element in elements {
+                if let Some(nested_data) = get_nested_data(element, nested_data_type, *nullable)? {
+                    nested_data_infos.push(NestedDataInfo { nested_data, ptr_loc: data.len() });
+                }
+                // Allocate space for the pointer, we'll write to it later.
+                data.extend([0; 8]);
+            }
+        }
+        MojomWireType::Union { variants, nullable, .. } => {
+            for element in elements {
+                deparse_inline_union(data, element, variants, *nullable, &mut nested_data_infos)?;
+            }
+        }
+    }
+
+    finish_array(data, initial_bytes)?;
+    deparse_nested_data(data, nested_data_infos)
+}
+
+/// Strings are encoded as arrays of UTF-8 bytes.
+fn deparse_string(data: &mut Encoder, value: &str) -> Result<()> {
+    let initial_bytes = data.len();
+    start_array(data, value.len())?;
+    data.extend_from_slice(value.as_bytes());
+    finish_array(data, initial_bytes)
+}
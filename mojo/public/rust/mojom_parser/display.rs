@@ -0,0 +1,234 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Human-readable formatting of mojom values and message headers, for
+//! debugging.
+//!
+//! A `MojomValue` doesn't know the names of its fields, so on its own it's
+//! displayed with their ordinals instead. To show the names, pair it with a
+//! `TypeDescriptor` using `MojomValue::with_descriptor`, which also shows the
+//! names of enum values, e.g. `Color.kRed`. Either way, the alternate form
+//! (`{:#}`) puts each field and element on its own line.
+
+use crate::ast::*;
+use crate::parse_messages::*;
+
+use std::fmt;
+
+/// A value paired with the descriptor of its type, which is displayed with
+/// the names of its fields.
+pub struct DescribedValue<'a> {
+    value: &'a MojomValue,
+    descriptor: &'a TypeDescriptor,
+}
+
+impl MojomValue {
+    /// Return something which displays this value with the names in
+    /// `descriptor`, which should describe the value's type.
+    pub fn with_descriptor<'a>(&'a self, descriptor: &'a TypeDescriptor) -> DescribedValue<'a> {
+        DescribedValue { value: self, descriptor }
+    }
+}
+
+impl fmt::Display for MojomValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_descriptor(&TypeDescriptor::Unnamed).fmt(f)
+    }
+}
+
+impl fmt::Display for DescribedValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let multiline = f.alternate();
+        let mut writer = ValueWriter { f, multiline, depth: 0 };
+        writer.write_value(self.value, self.descriptor)
+    }
+}
+
+/// Writes a value, keeping track of how deeply it's nested for the alternate
+/// form.
+struct ValueWriter<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    multiline: bool,
+    depth: usize,
+}
+
+impl ValueWriter<'_, '_> {
+    fn write_value(&mut self, value: &MojomValue, descriptor: &TypeDescriptor) -> fmt::Result {
+        let descriptor = &*descriptor.resolve();
+        match value {
+            MojomValue::Bool(value) => write!(self.f, "{}", value),
+            MojomValue::Int8(value) => write!(self.f, "{}", value),
+            MojomValue::UInt8(value) => write!(self.f, "{}", value),
+            MojomValue::Int16(value) => write!(self.f, "{}", value),
+            MojomValue::UInt16(value) => write!(self.f, "{}", value),
+            MojomValue::Int32(value) => match descriptor {
+                TypeDescriptor::Enum { name, values } => {
+                    match values.iter().find(|(_, v)| v == value) {
+                        Some((value_name, _)) => write!(self.f, "{}.{}", name, value_name),
+                        None => write!(self.f, "{}({})", name, value),
+                    }
+                }
+                _ => write!(self.f, "{}", value),
+            },
+            MojomValue::UInt32(value) => write!(self.f, "{}", value),
+            MojomValue::Int64(value) => write!(self.f, "{}", value),
+            MojomValue::UInt64(value) => write!(self.f, "{}", value),
+            MojomValue::Float(value) => write!(self.f, "{}", value),
+            MojomValue::Double(value) => write!(self.f, "{}", value),
+            MojomValue::String(value) => write!(self.f, "{:?}", value),
+            MojomValue::Struct(fields) => {
+                let name = match descriptor {
+                    TypeDescriptor::Struct { name, .. } => name.as_str(),
+                    _ => "struct",
+                };
+                self.write_fields(name, fields.iter().enumerate(), descriptor)
+            }
+            MojomValue::Union { tag, value } => {
+                let name = match descriptor {
+                    TypeDescriptor::Union { name, .. } => name.as_str(),
+                    _ => "union",
+                };
+                self.write_fields(name, [(*tag, value.as_ref())].into_iter(), descriptor)
+            }
+            MojomValue::Array(elements) => {
+                let element_descriptor = match descriptor {
+                    TypeDescriptor::Array { element } => element.as_ref(),
+                    _ => &TypeDescriptor::Unnamed,
+                };
+                self.write_list(
+                    "[",
+                    "]",
+                    elements.iter().map(|element| (None, element, element_descriptor)),
+                )
+            }
+            MojomValue::PendingRemote { index, version } => {
+                write!(self.f, "pending_remote {{ index: ")?;
+                write_index(self.f, *index, INVALID_HANDLE_INDEX)?;
+                write!(self.f, ", version: {} }}", version)
+            }
+            MojomValue::PendingAssociatedRemote { index, version } => {
+                write!(self.f, "pending_associated_remote {{ index: ")?;
+                write_index(self.f, *index, INVALID_ENDPOINT_INDEX)?;
+                write!(self.f, ", version: {} }}", version)
+            }
+            MojomValue::PendingAssociatedReceiver { index } => {
+                write!(self.f, "pending_associated_receiver {{ index: ")?;
+                write_index(self.f, *index, INVALID_ENDPOINT_INDEX)?;
+                write!(self.f, " }}")
+            }
+            MojomValue::Handle { index } => {
+                write!(self.f, "handle {{ index: ")?;
+                write_index(self.f, *index, INVALID_HANDLE_INDEX)?;
+                write!(self.f, " }}")
+            }
+            MojomValue::Null => write!(self.f, "null"),
+        }
+    }
+
+    /// Write the fields of a struct or union, with their names from
+    /// `descriptor` if it has them, or else their ordinals.
+    fn write_fields<'v>(
+        &mut self,
+        name: &str,
+        fields: impl Iterator<Item = (Ordinal, &'v MojomValue)>,
+        descriptor: &TypeDescriptor,
+    ) -> fmt::Result {
+        write!(self.f, "{} ", name)?;
+        self.write_list(
+            "{",
+            "}",
+            fields.map(|(ordinal, value)| {
+                let label = match descriptor.field_name(ordinal) {
+                    Some(field_name) => field_name.to_string(),
+                    None => ordinal.to_string(),
+                };
+                (Some(label), value, descriptor.field(ordinal))
+            }),
+        )
+    }
+
+    /// Write a bracketed list of values, each with an optional label.
+    fn write_list<'v>(
+        &mut self,
+        open: &str,
+        close: &str,
+        items: impl Iterator<Item = (Option<String>, &'v MojomValue, &'v TypeDescriptor)>,
+    ) -> fmt::Result {
+        let mut items = items.peekable();
+        if items.peek().is_none() {
+            return write!(self.f, "{}{}", open, close);
+        }
+        // Single-line lists of fields have spaces inside the braces, like
+        // Rust's.
+        let padding = if open == "{" { " " } else { "" };
+        write!(self.f, "{}", open)?;
+        self.depth += 1;
+        let mut first = true;
+        for (label, value, descriptor) in items {
+            if self.multiline {
+                writeln!(self.f, "{}", if first { "" } else { "," })?;
+                write!(self.f, "{:1$}", "", self.depth * 2)?;
+            } else {
+                write!(self.f, "{}", if first { padding } else { ", " })?;
+            }
+            first = false;
+            if let Some(label) = label {
+                write!(self.f, "{}: ", label)?;
+            }
+            self.write_value(value, descriptor)?;
+        }
+        self.depth -= 1;
+        if self.multiline {
+            writeln!(self.f, ",")?;
+            write!(self.f, "{:1$}{2}", "", self.depth * 2, close)
+        } else {
+            write!(self.f, "{}{}", padding, close)
+        }
+    }
+}
+
+/// Write the index of an endpoint or handle, or `invalid` if it's `invalid`.
+fn write_index(f: &mut fmt::Formatter<'_>, index: u32, invalid: u32) -> fmt::Result {
+    if index == invalid {
+        write!(f, "invalid")
+    } else {
+        write!(f, "{}", index)
+    }
+}
+
+/// The names of the `MESSAGE_FLAG_*` constants, for display.
+const MESSAGE_FLAG_NAMES: [(u32, &str); 5] = [
+    (MESSAGE_FLAG_EXPECTS_RESPONSE, "EXPECTS_RESPONSE"),
+    (MESSAGE_FLAG_IS_RESPONSE, "IS_RESPONSE"),
+    (MESSAGE_FLAG_IS_SYNC, "IS_SYNC"),
+    (MESSAGE_FLAG_NO_INTERRUPT, "NO_INTERRUPT"),
+    (MESSAGE_FLAG_IS_URGENT, "IS_URGENT"),
+];
+
+impl fmt::Display for MessageHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "interface {}, method {}, flags ", self.interface_id, self.name)?;
+        let mut names: Vec<String> = MESSAGE_FLAG_NAMES
+            .iter()
+            .filter(|(flag, _)| self.flags & flag != 0)
+            .map(|(_, name)| name.to_string())
+            .collect();
+        let known_flags = MESSAGE_FLAG_NAMES.iter().fold(0, |flags, (flag, _)| flags | flag);
+        if self.flags & !known_flags != 0 {
+            names.push(format!("{:#x}", self.flags & !known_flags));
+        }
+        if names.is_empty() {
+            write!(f, "none")?;
+        } else {
+            write!(f, "{}", names.join(" | "))?;
+        }
+        if self.trace_nonce != 0 {
+            write!(f, ", trace nonce {}", self.trace_nonce)?;
+        }
+        if self.expects_response() || self.is_response() {
+            write!(f, ", request {}", self.request_id)?;
+        }
+        Ok(())
+    }
+}
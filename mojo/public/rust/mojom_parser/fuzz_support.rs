@@ -0,0 +1,191 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Entry points for the mojom_parser fuzzers.
+//!
+//! Each fuzzer input is split into two parts: a schema, from which we build a
+//! random `MojomType`, and the data which we try to parse as a value of that
+//! type. The first byte of the input gives the length of the schema. Keeping
+//! the two apart means that mutating the data doesn't also change the type.
+//!
+//! The fuzzers check that:
+//! 1. The parser never panics, no matter what the type and data are.
+//! 2. Any value the parser accepts can be deparsed, and parsing the result
+//!    gives back the same value.
+//!
+//! The C++ side of each fuzzer (e.g. parse_struct_fuzzer.cc) just forwards
+//! its input to one of the functions here.
+
+chromium::import! {
+    "//mojo/public/rust/mojom_parser";
+}
+
+use mojom_parser::*;
+
+#[cxx::bridge(namespace = "mojom_parser")]
+mod ffi {
+    extern "Rust" {
+        fn fuzz_parse_struct(data: &[u8]);
+        fn fuzz_parse_message(data: &[u8]);
+    }
+}
+
+/// How deeply structs and unions may be nested in a random type.
+const MAX_DEPTH: usize = 4;
+/// The maximum number of fields in each struct of a random type.
+const MAX_FIELDS: u8 = 16;
+
+/// Reads the schema part of a fuzzer input, one decision at a time.
+///
+/// This is a minimal version of `arbitrary::Unstructured`. Once we run out of
+/// bytes, every decision takes its first option, so any input produces a
+/// (finite) type.
+struct SchemaReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl SchemaReader<'_> {
+    /// Return a number in the range [0, n).
+    fn choose(&mut self, n: u8) -> u8 {
+        match self.bytes.split_first() {
+            Some((byte, rest)) => {
+                self.bytes = rest;
+                byte % n
+            }
+            None => 0,
+        }
+    }
+
+    fn read_type(&mut self, depth: usize) -> MojomType {
+        // Only allow nested structs and unions if we haven't hit the depth
+        // limit.
+        let num_kinds = if depth < MAX_DEPTH { 15 } else { 13 };
+        let ty = match self.choose(num_kinds) {
+            0 => MojomType::Bool,
+            1 => MojomType::Int8,
+            2 => MojomType::UInt8,
+            3 => MojomType::Int16,
+            4 => MojomType::UInt16,
+            5 => MojomType::Int32,
+            6 => MojomType::UInt32,
+            7 => MojomType::Int64,
+            8 => MojomType::UInt64,
+            9 => MojomType::Float,
+            10 => MojomType::Double,
+            11 => MojomType::String,
+            12 => {
+                let num_elements = match self.choose(2) {
+                    0 => None,
+                    _ => Some(self.choose(8) as usize),
+                };
+                // Array elements can't be nested structs, to keep the depth
+                // bounded.
+                let element_type = Box::new(self.read_type(MAX_DEPTH));
+                MojomType::Array { element_type, num_elements }
+            }
+            13 => self.read_struct(depth + 1),
+            _ => self.read_union(depth + 1),
+        };
+        // Only pointer kinds and unions can be nullable.
+        match ty {
+            MojomType::String
+            | MojomType::Array { .. }
+            | MojomType::Struct { .. }
+            | MojomType::Union { .. }
+                if self.choose(2) == 1 =>
+            {
+                MojomType::Nullable(Box::new(ty))
+            }
+            _ => ty,
+        }
+    }
+
+    fn read_struct(&mut self, depth: usize) -> MojomType {
+        let num_fields = self.choose(MAX_FIELDS + 1);
+        let mut fields = vec![];
+        for _ in 0..num_fields {
+            let ty = self.read_type(depth);
+            // Struct fields can also be nullable value kinds.
+            fields.push(if ty.is_value_kind() && self.choose(2) == 1 {
+                MojomType::Nullable(Box::new(ty))
+            } else {
+                ty
+            });
+        }
+        MojomType::Struct { fields, min_versions: vec![] }
+    }
+
+    fn read_union(&mut self, depth: usize) -> MojomType {
+        // Unions must have at least one field.
+        let num_variants = self.choose(MAX_FIELDS) + 1;
+        MojomType::Union { variants: (0..num_variants).map(|_| self.read_type(depth)).collect() }
+    }
+}
+
+/// Split a fuzzer input into a random struct type, packed and ready for
+/// parsing, and the data to parse.
+fn split_input(input: &[u8]) -> (Vec<MojomWireType>, &[u8]) {
+    let (schema, data) = match input.split_first() {
+        Some((schema_len, rest)) => rest.split_at((*schema_len as usize).min(rest.len())),
+        None => (input, input),
+    };
+    let ty = SchemaReader { bytes: schema }.read_struct(0);
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        unreachable!("Structs always pack to pointers")
+    };
+    (packed_field_types, data)
+}
+
+/// Check that `fields`, which the parser accepted, survive being deparsed and
+/// parsed again. Returns the deparsed bytes.
+fn check_round_trip(fields: &[MojomValue], packed_field_types: &[MojomWireType]) -> Vec<u8> {
+    let mut deparsed = vec![];
+    deparse_struct(&mut deparsed, fields, packed_field_types, &[])
+        .expect("Values accepted by the parser must deparse");
+    let reparsed = parse_struct(&mut ParserData::new(&deparsed), packed_field_types, &[])
+        .expect("Deparsed values must parse");
+    assert_eq!(reparsed, fields, "Parsing deparsed values must give the same values");
+    deparsed
+}
+
+/// Parse the data part of the input as a struct of the random type.
+pub fn fuzz_parse_struct(input: &[u8]) {
+    let (packed_field_types, data) = split_input(input);
+    if let Ok(fields) = parse_struct(&mut ParserData::new(data), &packed_field_types, &[]) {
+        check_round_trip(&fields, &packed_field_types);
+    }
+}
+
+/// Parse the data part of the input as a whole message, whose payload is a
+/// struct of the random type.
+pub fn fuzz_parse_message(input: &[u8]) {
+    let (packed_field_types, data) = split_input(input);
+    let message_type = MojomWireType::Pointer {
+        ordinal: 0,
+        nested_data_type: PackedStructuredType::Struct {
+            packed_field_types: packed_field_types.clone(),
+        },
+        nullable: false,
+    };
+    let Ok(MojomValue::Struct(fields)) = parse_message(data, &message_type) else {
+        return;
+    };
+    let payload = check_round_trip(&fields, &packed_field_types);
+
+    // Put the deparsed payload in a new message, with a version 0 header:
+    // num_bytes, version, interface ID, message name, flags, trace nonce.
+    let mut message: Vec<u8> =
+        [24u32, 0, 0, 0, 0, 0].iter().flat_map(|n| n.to_le_bytes()).collect();
+    message.extend(payload);
+    let reparsed = parse_message(&message, &message_type).expect("Deparsed messages must parse");
+    assert_eq!(
+        reparsed,
+        MojomValue::Struct(fields),
+        "Parsing deparsed messages must give the same values"
+    );
+}
@@ -0,0 +1,182 @@
+#!/usr/bin/env python3
+# Copyright 2025 The Chromium Authors
+# Use of this source code is governed by a BSD-style license that can be
+# found in the LICENSE file.
+"""Exports the struct layouts computed by pack.py as JSON test fixtures.
+
+The Rust packing algorithms (pack.rs and const_pack.rs) re-implement
+mojo/public/tools/mojom/mojom/generate/pack.py. To make sure they agree, this
+script runs pack.py over every struct (and every method parameter struct) in
+the *.test-mojom files under mojo/, and writes the resulting layouts to
+test_data/pack_fixtures.json. The Rust unit tests then pack the same structs
+and compare the results.
+
+It also copies the parsed module of test_data/reflection.test-mojom to
+test_data/reflection.test-mojom-module, which the tests for mojom_module.rs
+load.
+
+The fixtures are checked in, so this only needs to be re-run when the set of
+test mojoms changes:
+
+  mojo/public/rust/mojom_parser/generate_pack_fixtures.py
+"""
+
+import argparse
+import glob
+import json
+import os
+import shutil
+import subprocess
+import sys
+import tempfile
+
+_SCRIPT_DIR = os.path.dirname(os.path.abspath(__file__))
+_SRC_ROOT = os.path.normpath(os.path.join(_SCRIPT_DIR, '../../../..'))
+_MOJOM_TOOLS_DIR = os.path.join(_SRC_ROOT, 'mojo/public/tools/mojom')
+_PARSER_SCRIPT = os.path.join(_MOJOM_TOOLS_DIR, 'mojom_parser.py')
+
+sys.path.insert(0, _MOJOM_TOOLS_DIR)
+# pylint: disable=wrong-import-position
+from mojom.generate import generator
+from mojom.generate import module as mojom
+
+_INT_KINDS = {
+    mojom.INT8.spec: 'int8',
+    mojom.UINT8.spec: 'uint8',
+    mojom.INT16.spec: 'int16',
+    mojom.UINT16.spec: 'uint16',
+    mojom.INT32.spec: 'int32',
+    mojom.UINT32.spec: 'uint32',
+    mojom.INT64.spec: 'int64',
+    mojom.UINT64.spec: 'uint64',
+}
+
+
+def _PackingKind(kind):
+  """Returns the name of the class of |kind| as far as packing is concerned.
+
+  The Rust side maps each of these names to a size and alignment."""
+  if kind.spec == mojom.BOOL.spec:
+    return 'bool'
+  if kind.spec in _INT_KINDS:
+    return _INT_KINDS[kind.spec]
+  if kind.spec == mojom.FLOAT.spec:
+    return 'float'
+  if kind.spec == mojom.DOUBLE.spec:
+    return 'double'
+  if isinstance(kind, mojom.Enum):
+    return 'enum'
+  if isinstance(kind, mojom.Union):
+    return 'union'
+  if isinstance(kind, (mojom.Interface, mojom.PendingRemote,
+                       mojom.PendingAssociatedRemote)):
+    return 'interface'
+  if (isinstance(kind, (mojom.PendingReceiver, mojom.PendingAssociatedReceiver))
+      or mojom.IsAnyHandleKind(kind)):
+    return 'handle'
+  if isinstance(kind, (mojom.Array, mojom.Map, mojom.Struct)) or (
+      mojom.IsStringKind(kind)):
+    return 'pointer'
+  raise Exception('Unknown kind: %s' % kind.spec)
+
+
+def _ExportStruct(struct, source_file):
+  packed = struct.packed
+  padding = [
+      offset for offset, byte in enumerate(struct.bytes) if byte.is_padding
+  ]
+  fields = []
+  for packed_field in packed.packed_fields_in_ordinal_order:
+    name = packed_field.field.mojom_name
+    # Nullable value types are split into two packed fields; mirror the names
+    # that pack.py gives them.
+    if packed_field.sub_ordinal is not None:
+      name += '_$flag' if packed_field.sub_ordinal == 0 else '_$value'
+    fields.append({
+        'name': name,
+        'kind': _PackingKind(packed_field.field.kind),
+        'nullable': packed_field.field.kind.is_nullable,
+        'min_version': packed_field.min_version,
+        'offset': packed_field.offset,
+        'bit': packed_field.bit,
+    })
+  return {
+      'file': source_file,
+      'struct': struct.mojom_name,
+      'fields': fields,
+      'padding': padding,
+      'versions': [{
+          'version': version.version,
+          'num_bytes': version.num_bytes
+      } for version in struct.versions],
+  }
+
+
+def _ExportModule(module, source_file):
+  generator.AddComputedData(module)
+  structs = list(module.structs)
+  for interface in module.interfaces:
+    for method in interface.methods:
+      structs.append(method.param_struct)
+      if method.response_param_struct:
+        structs.append(method.response_param_struct)
+  return [_ExportStruct(struct, source_file) for struct in structs]
+
+
+def main():
+  parser = argparse.ArgumentParser(description=__doc__)
+  parser.add_argument('--output',
+                      default=os.path.join(_SCRIPT_DIR, 'test_data',
+                                           'pack_fixtures.json'),
+                      help='path of the JSON file to write')
+  parser.add_argument('--module-output',
+                      default=os.path.join(_SCRIPT_DIR, 'test_data',
+                                           'reflection.test-mojom-module'),
+                      help='path to copy the parsed reflection module to')
+  args = parser.parse_args()
+
+  mojo_dir = os.path.join(_SRC_ROOT, 'mojo')
+  test_mojoms = sorted(
+      glob.glob(os.path.join(mojo_dir, '**', '*.test-mojom'), recursive=True))
+  # Test mojoms may import regular mojoms, so parse those too.
+  all_mojoms = test_mojoms + sorted(
+      glob.glob(os.path.join(mojo_dir, '**', '*.mojom'), recursive=True))
+
+  with tempfile.TemporaryDirectory() as modules_dir:
+    subprocess.run([
+        sys.executable, _PARSER_SCRIPT, '--input-root', _SRC_ROOT,
+        '--output-root', modules_dir, '--mojoms', *all_mojoms
+    ],
+                   check=True)
+
+    fixtures = []
+    for mojom_file in test_mojoms:
+      source_file = os.path.relpath(mojom_file, _SRC_ROOT)
+      with open(os.path.join(modules_dir, source_file + '-module'),
+                'rb') as f:
+        fixtures.extend(_ExportModule(mojom.Module.Load(f), source_file))
+
+    reflection_mojom = os.path.relpath(
+        os.path.join(_SCRIPT_DIR, 'test_data', 'reflection.test-mojom'),
+        _SRC_ROOT)
+    shutil.copyfile(os.path.join(modules_dir, reflection_mojom + '-module'),
+                    args.module_output)
+
+  # Write one field per line, which keeps the file reasonably compact while
+  # still producing readable diffs.
+  with open(args.output, 'w') as f:
+    f.write('[\n')
+    for idx, fixture in enumerate(fixtures):
+      fields = ',\n'.join('   ' + json.dumps(field)
+                          for field in fixture['fields'])
+      f.write(' {\n')
+      for key in ['file', 'struct', 'padding', 'versions']:
+        f.write('  %s: %s,\n' % (json.dumps(key), json.dumps(fixture[key])))
+      f.write('  "fields": [\n%s\n  ]\n' % fields if fields else
+              '  "fields": []\n')
+      f.write(' }%s\n' % (',' if idx + 1 < len(fixtures) else ''))
+    f.write(']\n')
+
+
+if __name__ == '__main__':
+  main()
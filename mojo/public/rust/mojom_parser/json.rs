@@ -0,0 +1,402 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Converts mojom values and messages to and from JSON, e.g. to write message
+//! fixtures by hand, or to log decoded messages in a form that can be diffed.
+//!
+//! The conversion is driven by the value's type, so that it's lossless: each
+//! integer is checked to fit in its type on the way back, and the names in
+//! the type's descriptor are used for fields and enum values.
+//!
+//! * Structs are objects keyed by field name, or by ordinal if the descriptor
+//!   doesn't have the names. Every field must be present, except for the
+//!   ones which declare a default value in the mojom file.
+//! * Unions are objects with a single key, which is the name of the active
+//!   field.
+//! * Arrays are arrays, and null values of nullable types are `null`.
+//! * Enums are the name of their value, or the number if it has no name.
+//!   Either is accepted on the way back.
+//! * Associated endpoints are placeholders which hold the index of their
+//!   interface ID in the message, e.g. `{"index": 0, "version": 1}` for a
+//!   `pending_associated_remote`.
+//!
+//! A whole message is an object with its "header", the "method" that it's
+//! for, the "interface_ids" of its associated endpoints, if any, and its
+//! "payload". When converting a message from JSON, the header's fields all
+//! default to 0, and the method may be given by name instead of by ordinal.
+
+use crate::ast::*;
+use crate::deparse_values::*;
+use crate::mojom_module::*;
+use crate::pack::pack_mojom_type;
+use crate::parse_messages::*;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+
+/// Convert `value`, which has the type `ty` described by `descriptor`, to
+/// JSON.
+pub fn value_to_json(
+    value: &MojomValue,
+    ty: &MojomType,
+    descriptor: &TypeDescriptor,
+) -> Result<Value> {
+    Ok(match (ty, value) {
+        (MojomType::Nullable(_), MojomValue::Null) => Value::Null,
+        (MojomType::Nullable(ty), value) => value_to_json(value, ty, descriptor)?,
+        (MojomType::Bool, MojomValue::Bool(value)) => json!(value),
+        (MojomType::Int8, MojomValue::Int8(value)) => json!(value),
+        (MojomType::UInt8, MojomValue::UInt8(value)) => json!(value),
+        (MojomType::Int16, MojomValue::Int16(value)) => json!(value),
+        (MojomType::UInt16, MojomValue::UInt16(value)) => json!(value),
+        (MojomType::Int32, MojomValue::Int32(value)) => match descriptor {
+            TypeDescriptor::Enum { values, .. } => match values.iter().find(|(_, v)| v == value) {
+                Some((name, _)) => json!(name),
+                None => json!(value),
+            },
+            _ => json!(value),
+        },
+        (MojomType::UInt32, MojomValue::UInt32(value)) => json!(value),
+        (MojomType::Int64, MojomValue::Int64(value)) => json!(value),
+        (MojomType::UInt64, MojomValue::UInt64(value)) => json!(value),
+        (MojomType::Float, MojomValue::Float(value)) => float_to_json((*value).into()),
+        (MojomType::Double, MojomValue::Double(value)) => float_to_json(*value),
+        (MojomType::String, MojomValue::String(value)) => json!(value),
+        (MojomType::Struct { fields, min_versions }, MojomValue::Struct(values)) => {
+            // Values parsed from older versions of the struct lack the fields
+            // that were added since, which are left out of the JSON too.
+            let num_required_fields = (0..fields.len())
+                .rposition(|ordinal| min_versions.get(ordinal).is_none_or(|version| *version == 0))
+                .map_or(0, |ordinal| ordinal + 1);
+            if values.len() < num_required_fields || values.len() > fields.len() {
+                bail!("Expected {} fields, but got {}", fields.len(), values.len())
+            }
+            let mut object = Map::new();
+            for (ordinal, (ty, value)) in fields.iter().zip(values).enumerate() {
+                let name = field_name(descriptor, ordinal);
+                let json = value_to_json(value, ty, descriptor.field(ordinal))
+                    .with_context(|| format!("In field {}", name))?;
+                object.insert(name, json);
+            }
+            Value::Object(object)
+        }
+        (MojomType::Union { variants }, MojomValue::Union { tag, value }) => {
+            let ty = variants.get(*tag).with_context(|| format!("Unknown union tag {}", tag))?;
+            let name = field_name(descriptor, *tag);
+            let json = value_to_json(value, ty, descriptor.field(*tag))
+                .with_context(|| format!("In field {}", name))?;
+            Value::Object(Map::from_iter([(name, json)]))
+        }
+        (MojomType::Array { element_type, .. }, MojomValue::Array(elements)) => {
+            let element_descriptor = match descriptor {
+                TypeDescriptor::Array { element } => element.as_ref(),
+                _ => &TypeDescriptor::Unnamed,
+            };
+            let elements = elements
+                .iter()
+                .enumerate()
+                .map(|(index, element)| {
+                    value_to_json(element, element_type, element_descriptor)
+                        .with_context(|| format!("In element {}", index))
+                })
+                .collect::<Result<_>>()?;
+            Value::Array(elements)
+        }
+        (MojomType::PendingRemote, MojomValue::PendingRemote { index, version })
+        | (
+            MojomType::PendingAssociatedRemote,
+            MojomValue::PendingAssociatedRemote { index, version },
+        ) => json!({ "index": index, "version": version }),
+        (MojomType::PendingAssociatedReceiver, MojomValue::PendingAssociatedReceiver { index }) => {
+            json!({ "index": index })
+        }
+        (MojomType::Handle, MojomValue::Handle { index }) => json!({ "index": index }),
+        (MojomType::Recursive(recursive_type), value) => {
+            value_to_json(value, &recursive_type.mojom_type(), &descriptor.resolve())?
+        }
+        (ty, value) => bail!("Expected a value of type {:?}, but got {:?}", ty, value),
+    })
+}
+
+/// Convert JSON to a value of the type `ty`, which is described by
+/// `descriptor`.
+pub fn value_from_json(
+    json: &Value,
+    ty: &MojomType,
+    descriptor: &TypeDescriptor,
+) -> Result<MojomValue> {
+    Ok(match ty {
+        MojomType::Nullable(_) if json.is_null() => MojomValue::Null,
+        MojomType::Nullable(ty) => value_from_json(json, ty, descriptor)?,
+        MojomType::Bool => {
+            MojomValue::Bool(json.as_bool().with_context(|| expected("a bool", json))?)
+        }
+        MojomType::Int8 => MojomValue::Int8(int_from_json(json, "an int8")?),
+        MojomType::UInt8 => MojomValue::UInt8(int_from_json(json, "a uint8")?),
+        MojomType::Int16 => MojomValue::Int16(int_from_json(json, "an int16")?),
+        MojomType::UInt16 => MojomValue::UInt16(int_from_json(json, "a uint16")?),
+        MojomType::Int32 => match (descriptor, json) {
+            (TypeDescriptor::Enum { name, values }, Value::String(value_name)) => {
+                let value = values
+                    .iter()
+                    .find(|(n, _)| n == value_name)
+                    .with_context(|| format!("{} has no value named {}", name, value_name))?;
+                MojomValue::Int32(value.1)
+            }
+            _ => MojomValue::Int32(int_from_json(json, "an int32")?),
+        },
+        MojomType::UInt32 => MojomValue::UInt32(int_from_json(json, "a uint32")?),
+        MojomType::Int64 => MojomValue::Int64(int_from_json(json, "an int64")?),
+        MojomType::UInt64 => MojomValue::UInt64(int_from_json(json, "a uint64")?),
+        MojomType::Float => MojomValue::Float(f32_from_json(json)?),
+        MojomType::Double => MojomValue::Double(float_from_json(json, "a double")?),
+        MojomType::String => {
+            MojomValue::String(json.as_str().with_context(|| expected("a string", json))?.into())
+        }
+        MojomType::Struct { fields, .. } => {
+            let object = json.as_object().with_context(|| expected("a struct", json))?;
+            let names: Vec<String> =
+                (0..fields.len()).map(|ordinal| field_name(descriptor, ordinal)).collect();
+            if let Some(key) = object.keys().find(|key| !names.contains(key)) {
+                bail!("Unknown field {}", key)
+            }
+            let values = fields
+                .iter()
+                .zip(&names)
+                .enumerate()
+                .map(|(ordinal, (ty, name))| match object.get(name) {
+                    Some(json) => value_from_json(json, ty, descriptor.field(ordinal))
+                        .with_context(|| format!("In field {}", name)),
+                    None => descriptor
+                        .field_default(ordinal)
+                        .cloned()
+                        .with_context(|| format!("Missing field {}", name)),
+                })
+                .collect::<Result<_>>()?;
+            MojomValue::Struct(values)
+        }
+        MojomType::Union { variants } => {
+            let object = json.as_object().with_context(|| expected("a union", json))?;
+            let mut entries = object.iter();
+            let (Some((name, json)), None) = (entries.next(), entries.next()) else {
+                bail!("A union must have exactly one field, but got {}", json)
+            };
+            let tag = (0..variants.len())
+                .find(|tag| field_name(descriptor, *tag) == *name)
+                .with_context(|| format!("Unknown field {}", name))?;
+            let value = value_from_json(json, &variants[tag], descriptor.field(tag))
+                .with_context(|| format!("In field {}", name))?;
+            MojomValue::Union { tag, value: Box::new(value) }
+        }
+        MojomType::Array { element_type, num_elements } => {
+            let elements = json.as_array().with_context(|| expected("an array", json))?;
+            if let Some(num_elements) = num_elements
+                && elements.len() != *num_elements
+            {
+                bail!("Expected {} elements, but got {}", num_elements, elements.len())
+            }
+            let element_descriptor = match descriptor {
+                TypeDescriptor::Array { element } => element.as_ref(),
+                _ => &TypeDescriptor::Unnamed,
+            };
+            let elements = elements
+                .iter()
+                .enumerate()
+                .map(|(index, element)| {
+                    value_from_json(element, element_type, element_descriptor)
+                        .with_context(|| format!("In element {}", index))
+                })
+                .collect::<Result<_>>()?;
+            MojomValue::Array(elements)
+        }
+        MojomType::PendingRemote => MojomValue::PendingRemote {
+            index: int_from_json(&json["index"], "a handle index")?,
+            version: int_from_json(&json["version"], "an interface version")?,
+        },
+        MojomType::PendingAssociatedRemote => MojomValue::PendingAssociatedRemote {
+            index: int_from_json(&json["index"], "an endpoint index")?,
+            version: int_from_json(&json["version"], "an interface version")?,
+        },
+        MojomType::PendingAssociatedReceiver => MojomValue::PendingAssociatedReceiver {
+            index: int_from_json(&json["index"], "an endpoint index")?,
+        },
+        MojomType::Handle => {
+            MojomValue::Handle { index: int_from_json(&json["index"], "a handle index")? }
+        }
+        MojomType::Recursive(recursive_type) => {
+            value_from_json(json, &recursive_type.mojom_type(), &descriptor.resolve())?
+        }
+    })
+}
+
+/// The name of a struct or union field in JSON.
+fn field_name(descriptor: &TypeDescriptor, ordinal: Ordinal) -> String {
+    match descriptor.field_name(ordinal) {
+        Some(name) => name.to_string(),
+        None => ordinal.to_string(),
+    }
+}
+
+fn expected(what: &str, json: &Value) -> String {
+    format!("Expected {}, but got {}", what, json)
+}
+
+fn int_from_json<T: TryFrom<i64> + TryFrom<u64>>(json: &Value, what: &str) -> Result<T> {
+    let value = match json {
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(value), _) => T::try_from(value).ok(),
+            (_, Some(value)) => T::try_from(value).ok(),
+            _ => None,
+        },
+        _ => None,
+    };
+    value.with_context(|| expected(what, json))
+}
+
+/// JSON numbers can't be infinite or NaN, so those are written as the strings
+/// "inf", "-inf" and "NaN" instead.
+fn float_to_json(value: f64) -> Value {
+    if value.is_finite() {
+        json!(value)
+    } else {
+        json!(value.to_string())
+    }
+}
+
+fn float_from_json(json: &Value, what: &str) -> Result<f64> {
+    let value = match json {
+        Value::Number(number) => number.as_f64(),
+        Value::String(value) => value.parse().ok().filter(|value: &f64| !value.is_finite()),
+        _ => None,
+    };
+    value.with_context(|| expected(what, json))
+}
+
+/// Parse a float like `float_from_json`, but fail if it's finite and too big
+/// for an `f32`, rather than turning it into infinity.
+fn f32_from_json(json: &Value) -> Result<f32> {
+    let value = float_from_json(json, "a float")?;
+    // Every float is exactly representable as a double, so this only loses
+    // precision if the JSON has more digits than the float does.
+    let float = value as f32;
+    if value.is_finite() && float.is_infinite() {
+        bail!("{} is out of range for a float", value)
+    }
+    Ok(float)
+}
+
+impl TypeInfo {
+    /// Convert a value of this type to JSON.
+    pub fn value_to_json(&self, value: &MojomValue) -> Result<Value> {
+        value_to_json(value, &self.mojom_type, &self.descriptor)
+    }
+
+    /// Convert JSON to a value of this type.
+    pub fn value_from_json(&self, json: &Value) -> Result<MojomValue> {
+        value_from_json(json, &self.mojom_type, &self.descriptor)
+    }
+}
+
+fn header_to_json(header: &MessageHeader) -> Value {
+    json!({
+        "interface_id": header.interface_id,
+        "name": header.name,
+        "flags": header.flags,
+        "trace_nonce": header.trace_nonce,
+        "request_id": header.request_id,
+    })
+}
+
+fn header_from_json(json: &Value) -> Result<MessageHeader> {
+    let object = match json {
+        Value::Null => &Map::new(),
+        json => json.as_object().with_context(|| expected("a message header", json))?,
+    };
+    let field = |name: &str| -> Result<u64> {
+        match object.get(name) {
+            Some(json) => int_from_json(json, "an integer").with_context(|| format!("In {}", name)),
+            None => Ok(0),
+        }
+    };
+    let known_fields = ["interface_id", "name", "flags", "trace_nonce", "request_id"];
+    if let Some(key) = object.keys().find(|key| !known_fields.contains(&key.as_str())) {
+        bail!("Unknown message header field {}", key)
+    }
+    Ok(MessageHeader {
+        interface_id: field("interface_id")?.try_into()?,
+        name: field("name")?.try_into()?,
+        flags: field("flags")?.try_into()?,
+        trace_nonce: field("trace_nonce")?.try_into()?,
+        request_id: field("request_id")?,
+    })
+}
+
+impl MojomModule {
+    /// Decode a whole message which was sent to or from `interface`, and
+    /// convert it to JSON.
+    pub fn message_to_json(&self, interface: &str, message: &[u8]) -> Result<Value> {
+        let decoded = self.decode_message(interface, message)?;
+        let (_, ty) = self.interface(interface)?.payload_type(&decoded.header)?;
+        let mut json = Map::new();
+        json.insert("header".to_string(), header_to_json(&decoded.header));
+        json.insert("method".to_string(), json!(decoded.method.name));
+        if !decoded.interface_ids.is_empty() {
+            json.insert("interface_ids".to_string(), json!(decoded.interface_ids));
+        }
+        json.insert("payload".to_string(), ty.value_to_json(&decoded.value)?);
+        Ok(Value::Object(json))
+    }
+
+    /// Encode a message to or from `interface` from JSON, in the format that
+    /// `message_to_json` returns.
+    pub fn message_from_json(&self, interface: &str, json: &Value) -> Result<Vec<u8>> {
+        let object = json.as_object().with_context(|| expected("a message", json))?;
+        let known_fields = ["header", "method", "interface_ids", "payload"];
+        if let Some(key) = object.keys().find(|key| !known_fields.contains(&key.as_str())) {
+            bail!("Unknown message field {}", key)
+        }
+        let header_json = object.get("header").unwrap_or(&Value::Null);
+        let mut header = header_from_json(header_json).context("In the message header")?;
+        let interface = self.interface(interface)?;
+        if let Some(method) = object.get("method") {
+            let method = method.as_str().with_context(|| expected("a method name", method))?;
+            let ordinal = interface
+                .methods
+                .iter()
+                .find(|info| info.name == method)
+                .with_context(|| format!("{} has no method {}", interface.name, method))?
+                .ordinal;
+            if header_json.get("name").is_some() && header.name != ordinal {
+                bail!(
+                    "The header's name is {}, but {} has ordinal {}",
+                    header.name,
+                    method,
+                    ordinal
+                )
+            }
+            header.name = ordinal;
+        }
+        let interface_ids: Vec<u32> = match object.get("interface_ids") {
+            Some(ids) => serde_json::from_value(ids.clone()).context("In the interface IDs")?,
+            None => vec![],
+        };
+
+        let (_, ty) = interface.payload_type(&header)?;
+        let payload_json = object.get("payload").context("The message has no payload")?;
+        let value = ty.value_from_json(payload_json).context("In the message payload")?;
+        let MojomValue::Struct(fields) = value else { unreachable!("Payloads are structs") };
+        let MojomWireType::Pointer {
+            nested_data_type: PackedStructuredType::Struct { packed_field_types, min_versions },
+            ..
+        } = pack_mojom_type(&ty.mojom_type, 0)
+        else {
+            unreachable!("Payloads are structs")
+        };
+        let mut payload = vec![];
+        deparse_struct(&mut payload, &fields, &packed_field_types, &min_versions)?;
+        let mut message = vec![];
+        deparse_message(&mut message, &header, &payload, &interface_ids)?;
+        Ok(message)
+    }
+}
@@ -7,16 +7,45 @@
 // FOR_RELEASE: Figure out the organization of this crate, what needs to be
 // public, etc. For now, just export everything blindly.
 
+chromium::import! {
+    "//mojo/public/rust/mojom_derive";
+}
+
 mod ast;
+mod const_pack;
+mod convert;
+mod de;
+mod defaults;
 mod deparse_values;
+mod display;
+mod json;
+mod mojom_module;
 mod pack;
 mod parse_messages;
 mod parse_primitives;
 mod parse_values;
+mod pickle;
+mod ser;
+mod serialized_size;
+mod traits;
+mod validation_context;
+mod view;
 
 pub use crate::ast::*;
+pub use crate::const_pack::*;
+pub use crate::convert::*;
+pub use crate::de::*;
 pub use crate::deparse_values::*;
+pub use crate::display::*;
+pub use crate::json::*;
+pub use crate::mojom_module::*;
 pub use crate::pack::*;
 pub use crate::parse_messages::*;
 pub use crate::parse_primitives::ParserData;
 pub use crate::parse_values::*;
+pub use crate::ser::*;
+pub use crate::serialized_size::*;
+pub use crate::traits::*;
+pub use crate::validation_context::*;
+pub use crate::view::*;
+pub use mojom_derive::Mojom;
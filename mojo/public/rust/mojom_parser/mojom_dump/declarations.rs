@@ -0,0 +1,306 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Parses the struct and union declarations that tell mojom_dump how to decode
+//! a message. They're written like a subset of mojom:
+//!
+//! ```text
+//! // Comments run to the end of the line.
+//! struct Point { int32 x; int32 y; };
+//! union Shape { Point point; array<Point> polygon; };
+//! struct DrawParams { Shape shape; string? label; array<uint8, 4> color; };
+//! ```
+//!
+//! Field types may be any primitive that mojom_parser supports, `string`,
+//! `array<T>`, `array<T, N>`, `pending_remote<I>`, `pending_receiver<I>`,
+//! `pending_associated_remote<I>`, `pending_associated_receiver<I>`, or the name of a struct or union declared
+//! anywhere in the file, followed by `?` if they're nullable. Enums are
+//! `int32`s on the wire, so write them as that.
+
+use mojom_parser::{MojomType, TypeDescriptor};
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Number(usize),
+    Punctuation(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.split("//").next().unwrap_or_default();
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+            if "{};<>,?".contains(c) {
+                tokens.push((Token::Punctuation(c), line_number));
+                continue;
+            }
+            if !c.is_ascii_alphanumeric() && c != '_' {
+                bail!("Line {}: unexpected character {:?}", line_number, c)
+            }
+            let mut end = start + c.len_utf8();
+            while let Some((index, c)) =
+                chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_' || *c == '.')
+            {
+                end = index + c.len_utf8();
+            }
+            let word = &line[start..end];
+            let token =
+                if c.is_ascii_digit() {
+                    Token::Number(word.parse().with_context(|| {
+                        format!("Line {}: invalid number {}", line_number, word)
+                    })?)
+                } else {
+                    Token::Identifier(word.to_string())
+                };
+            tokens.push((token, line_number));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A field type as written, before the names in it are resolved.
+#[derive(Debug, Clone)]
+enum TypeExpr {
+    Primitive(MojomType),
+    Array(Box<TypeExpr>, Option<usize>),
+    Named(String),
+    Nullable(Box<TypeExpr>),
+}
+
+struct Declaration {
+    is_union: bool,
+    fields: Vec<(String, TypeExpr)>,
+}
+
+/// The structs and unions declared in a file.
+pub struct Declarations {
+    declarations: HashMap<String, Declaration>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token> {
+        let (token, _) = self.tokens.get(self.position).context("Unexpected end of file")?;
+        self.position += 1;
+        Ok(token.clone())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        let index = self.position.min(self.tokens.len()).saturating_sub(1);
+        self.tokens.get(index).map_or(0, |(_, line)| *line)
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Identifier(name) => Ok(name),
+            token => bail!("Line {}: expected a name, but got {:?}", self.line(), token),
+        }
+    }
+
+    fn expect(&mut self, punctuation: char) -> Result<()> {
+        match self.next()? {
+            Token::Punctuation(c) if c == punctuation => Ok(()),
+            token => bail!("Line {}: expected {:?}, but got {:?}", self.line(), punctuation, token),
+        }
+    }
+
+    fn skip(&mut self, punctuation: char) -> bool {
+        if self.peek() == Some(&Token::Punctuation(punctuation)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn type_expr(&mut self) -> Result<TypeExpr> {
+        let name = self.identifier()?;
+        let ty = match name.as_str() {
+            "bool" => TypeExpr::Primitive(MojomType::Bool),
+            "int8" => TypeExpr::Primitive(MojomType::Int8),
+            "uint8" => TypeExpr::Primitive(MojomType::UInt8),
+            "int16" => TypeExpr::Primitive(MojomType::Int16),
+            "uint16" => TypeExpr::Primitive(MojomType::UInt16),
+            "int32" => TypeExpr::Primitive(MojomType::Int32),
+            "uint32" => TypeExpr::Primitive(MojomType::UInt32),
+            "int64" => TypeExpr::Primitive(MojomType::Int64),
+            "uint64" => TypeExpr::Primitive(MojomType::UInt64),
+            "float" => TypeExpr::Primitive(MojomType::Float),
+            "double" => TypeExpr::Primitive(MojomType::Double),
+            "string" => TypeExpr::Primitive(MojomType::String),
+            "array" => {
+                self.expect('<')?;
+                let element = self.type_expr()?;
+                let num_elements = if self.skip(',') {
+                    match self.next()? {
+                        Token::Number(n) => Some(n),
+                        token => bail!(
+                            "Line {}: expected an array size, but got {:?}",
+                            self.line(),
+                            token
+                        ),
+                    }
+                } else {
+                    None
+                };
+                self.expect('>')?;
+                TypeExpr::Array(Box::new(element), num_elements)
+            }
+            "pending_remote"
+            | "pending_receiver"
+            | "pending_associated_remote"
+            | "pending_associated_receiver" => {
+                // The interface doesn't affect the encoding.
+                self.expect('<')?;
+                self.identifier()?;
+                self.expect('>')?;
+                TypeExpr::Primitive(match name.as_str() {
+                    "pending_remote" => MojomType::PendingRemote,
+                    // Pending receivers are just message pipes.
+                    "pending_receiver" => MojomType::Handle,
+                    "pending_associated_remote" => MojomType::PendingAssociatedRemote,
+                    _ => MojomType::PendingAssociatedReceiver,
+                })
+            }
+            _ => TypeExpr::Named(name),
+        };
+        if self.skip('?') {
+            if let TypeExpr::Primitive(primitive) = &ty
+                && !matches!(
+                    primitive,
+                    MojomType::String
+                        | MojomType::PendingRemote
+                        | MojomType::Handle
+                        | MojomType::PendingAssociatedRemote
+                        | MojomType::PendingAssociatedReceiver
+                )
+            {
+                bail!("Line {}: nullable {:?}s aren't supported yet", self.line(), primitive)
+            }
+            return Ok(TypeExpr::Nullable(Box::new(ty)));
+        }
+        Ok(ty)
+    }
+
+    fn declaration(&mut self) -> Result<(String, Declaration)> {
+        let is_union = match self.identifier()?.as_str() {
+            "struct" => false,
+            "union" => true,
+            keyword => {
+                bail!("Line {}: expected struct or union, but got {}", self.line(), keyword)
+            }
+        };
+        let name = self.identifier()?;
+        self.expect('{')?;
+        let mut fields = vec![];
+        while !self.skip('}') {
+            let ty = self.type_expr()?;
+            let field_name = self.identifier()?;
+            self.expect(';')?;
+            fields.push((field_name, ty));
+        }
+        self.skip(';');
+        Ok((name, Declaration { is_union, fields }))
+    }
+}
+
+impl Declarations {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+        let mut declarations = HashMap::new();
+        while parser.peek().is_some() {
+            let (name, declaration) = parser.declaration()?;
+            if declarations.insert(name.clone(), declaration).is_some() {
+                bail!("{} is declared more than once", name)
+            }
+        }
+        Ok(Declarations { declarations })
+    }
+
+    /// Return the type of the struct or union `name`, and its descriptor.
+    pub fn resolve(&self, name: &str) -> Result<(MojomType, TypeDescriptor)> {
+        self.resolve_named(name, &mut vec![])
+    }
+
+    /// `containing` is the names of the declarations that we're resolving
+    /// `name` inside, which it mustn't contain.
+    fn resolve_named(
+        &self,
+        name: &str,
+        containing: &mut Vec<String>,
+    ) -> Result<(MojomType, TypeDescriptor)> {
+        let declaration =
+            self.declarations.get(name).with_context(|| format!("Unknown type {}", name))?;
+        if containing.iter().any(|containing| containing == name) {
+            bail!("{} contains itself, which isn't supported yet", name)
+        }
+        containing.push(name.to_string());
+        let mut types = vec![];
+        let mut descriptors = vec![];
+        for (field_name, ty) in &declaration.fields {
+            let (ty, descriptor) = self
+                .resolve_expr(ty, containing)
+                .with_context(|| format!("In field {}.{}", name, field_name))?;
+            types.push(ty);
+            descriptors.push((field_name.clone(), descriptor));
+        }
+        containing.pop();
+        Ok(if declaration.is_union {
+            (
+                MojomType::Union { variants: types },
+                TypeDescriptor::Union { name: name.to_string(), variants: descriptors },
+            )
+        } else {
+            (
+                MojomType::Struct { fields: types, min_versions: vec![] },
+                TypeDescriptor::Struct {
+                    name: name.to_string(),
+                    fields: descriptors,
+                    defaults: vec![],
+                },
+            )
+        })
+    }
+
+    fn resolve_expr(
+        &self,
+        ty: &TypeExpr,
+        containing: &mut Vec<String>,
+    ) -> Result<(MojomType, TypeDescriptor)> {
+        Ok(match ty {
+            TypeExpr::Primitive(ty) => (ty.clone(), TypeDescriptor::Unnamed),
+            TypeExpr::Array(element, num_elements) => {
+                let (element_type, element) = self.resolve_expr(element, containing)?;
+                (
+                    MojomType::Array {
+                        element_type: Box::new(element_type),
+                        num_elements: *num_elements,
+                    },
+                    TypeDescriptor::Array { element: Box::new(element) },
+                )
+            }
+            TypeExpr::Named(name) => self.resolve_named(name, containing)?,
+            TypeExpr::Nullable(inner) => {
+                let (ty, descriptor) = self.resolve_expr(inner, containing)?;
+                (MojomType::Nullable(Box::new(ty)), descriptor)
+            }
+        })
+    }
+}
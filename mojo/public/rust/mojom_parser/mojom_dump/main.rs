@@ -0,0 +1,215 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Decodes a mojom message and prints it, or says exactly why it's invalid.
+//! This is the Rust counterpart of mojo/public/cpp/bindings/message_dumper.h,
+//! for debugging exchanges between Rust and C++.
+//!
+//! Usage:
+//!
+//! ```text
+//! mojom_dump --module=<.mojom-module file> --interface=<interface> [<message file>]
+//! mojom_dump --module=<.mojom-module file> --type=<struct> [<message file>]
+//! mojom_dump --types=<declarations file> --type=<struct> [<message file>]
+//! ```
+//!
+//! The types can come from the `.mojom-module` file that mojom_parser.py
+//! wrote for the mojom file which declares them, which is next to the
+//! generated bindings in the build directory. Given `--interface`, the message
+//! header says which method's parameters or response parameters to decode the
+//! payload as. Alternatively, a declarations file can describe the message's
+//! payload; see declarations.rs for its syntax. `--type` names the struct to
+//! decode the payload as, e.g. a method's parameters. If no message file is
+//! given, the message is read from stdin as hex, e.g. as copied from a log,
+//! ignoring whitespace.
+//!
+//! Pass `--no-header` if the data is just an encoded struct, rather than a
+//! whole message, and `--json` to print the message as JSON (see
+//! mojom_parser's json.rs), e.g. to use it as a test fixture. With `--type`,
+//! only the payload is printed as JSON.
+
+chromium::import! {
+    "//mojo/public/rust/mojom_parser";
+}
+
+mod declarations;
+
+use declarations::Declarations;
+use mojom_parser::*;
+
+use anyhow::{bail, Context, Result};
+use std::io::Read;
+use std::process::ExitCode;
+
+/// Where the types to decode the message with come from.
+enum Types {
+    Module { path: String, interface: Option<String> },
+    Declarations { path: String },
+}
+
+struct Args {
+    types: Types,
+    type_name: Option<String>,
+    message_path: Option<String>,
+    has_header: bool,
+    json: bool,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut types_path = None;
+    let mut module_path = None;
+    let mut interface = None;
+    let mut type_name = None;
+    let mut message_path = None;
+    let mut has_header = true;
+    let mut json = false;
+    for arg in std::env::args().skip(1) {
+        if let Some(path) = arg.strip_prefix("--types=") {
+            types_path = Some(path.to_string());
+        } else if let Some(path) = arg.strip_prefix("--module=") {
+            module_path = Some(path.to_string());
+        } else if let Some(name) = arg.strip_prefix("--interface=") {
+            interface = Some(name.to_string());
+        } else if let Some(name) = arg.strip_prefix("--type=") {
+            type_name = Some(name.to_string());
+        } else if arg == "--no-header" {
+            has_header = false;
+        } else if arg == "--json" {
+            json = true;
+        } else if arg.starts_with("--") || message_path.is_some() {
+            bail!("Unexpected argument {}", arg)
+        } else {
+            message_path = Some(arg);
+        }
+    }
+    let types = match (module_path, types_path) {
+        (Some(path), None) => {
+            if interface.is_some() == type_name.is_some() {
+                bail!("Pass exactly one of --interface=<interface> and --type=<struct>")
+            }
+            if interface.is_some() && !has_header {
+                bail!("--interface needs the message header to know which method to decode")
+            }
+            Types::Module { path, interface }
+        }
+        (None, Some(path)) => {
+            if interface.is_some() {
+                bail!("--interface needs a --module=<.mojom-module file>")
+            }
+            if type_name.is_none() {
+                bail!("Missing --type=<struct>")
+            }
+            Types::Declarations { path }
+        }
+        _ => bail!(
+            "Pass exactly one of --module=<.mojom-module file> and --types=<declarations file>"
+        ),
+    };
+    Ok(Args { types, type_name, message_path, has_header, json })
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        bail!("The hex on stdin has an odd number of digits")
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).unwrap_or_default();
+            u8::from_str_radix(pair, 16).with_context(|| format!("Invalid hex byte {:?}", pair))
+        })
+        .collect()
+}
+
+fn read_message(path: Option<&str>) -> Result<Vec<u8>> {
+    match path {
+        Some(path) => std::fs::read(path).with_context(|| format!("Couldn't read {}", path)),
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).context("Couldn't read stdin")?;
+            parse_hex(&text)
+        }
+    }
+}
+
+/// Return the type of the struct `name`, and its descriptor.
+fn resolve_struct(types: &Types, name: &str) -> Result<(MojomType, TypeDescriptor)> {
+    let (ty, descriptor) = match types {
+        Types::Module { path, .. } => {
+            let module = MojomModule::load(path)?;
+            let info = module.get_type(name)?;
+            (info.mojom_type.clone(), info.descriptor.clone())
+        }
+        Types::Declarations { path } => {
+            let text =
+                std::fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path))?;
+            let declarations = Declarations::parse(&text)
+                .with_context(|| format!("In the declarations in {}", path))?;
+            declarations.resolve(name)?
+        }
+    };
+    if !matches!(ty, MojomType::Struct { .. }) {
+        bail!("{} must be a struct", name)
+    }
+    Ok((ty, descriptor))
+}
+
+fn print_header(header: &MessageHeader, interface_ids: &[u32]) {
+    println!("header: {}", header);
+    if !interface_ids.is_empty() {
+        println!("interface IDs: {:?}", interface_ids);
+    }
+}
+
+fn dump(args: &Args) -> Result<()> {
+    if let Types::Module { path, interface: Some(interface) } = &args.types {
+        let module = MojomModule::load(path)?;
+        let message = read_message(args.message_path.as_deref())?;
+        if args.json {
+            println!("{:#}", module.message_to_json(interface, &message)?);
+            return Ok(());
+        }
+        let decoded = module.decode_message(interface, &message)?;
+        print_header(&decoded.header, &decoded.interface_ids);
+        println!("method: {}", decoded.method.name);
+        println!("{:#}", decoded.value.with_descriptor(decoded.descriptor));
+        return Ok(());
+    }
+
+    // parse_args() made sure that there's a type if there's no interface.
+    let type_name = args.type_name.as_deref().unwrap_or_default();
+    let (ty, descriptor) = resolve_struct(&args.types, type_name)?;
+    let message = read_message(args.message_path.as_deref())?;
+    let payload = if args.has_header {
+        let (header, payload, interface_ids) =
+            parse_message_parts(&message).context("In the message header")?;
+        if !args.json {
+            print_header(&header, &interface_ids);
+        }
+        payload
+    } else {
+        &message
+    };
+    let value = parse_struct_payload(payload, &ty).context("In the message payload")?;
+    if args.json {
+        println!("{:#}", value_to_json(&value, &ty, &descriptor)?);
+    } else {
+        println!("{:#}", value.with_descriptor(&descriptor));
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result = parse_args().and_then(|args| dump(&args));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            // The alternate form includes the context, which says where in
+            // the message the error is.
+            eprintln!("error: {:#}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,666 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Loads the `.mojom-module` files that mojom_parser.py writes for every
+//! mojom file it parses, and builds the `MojomType` and `TypeDescriptor` of
+//! each struct, union and enum in them, and of each interface method's
+//! parameters. This lets tools decode any message reflectively, without
+//! generated code. The values of the module's constants, and the defaults
+//! that struct fields declare, are loaded too.
+//!
+//! A `.mojom-module` file is a pickled `mojom.generate.module.Module`, which
+//! we read with pickle.rs. Types which use features that mojom_parser doesn't
+//! support yet (e.g. recursive types) are still listed, but looking them up
+//! returns an error saying why they aren't supported.
+
+use crate::ast::*;
+use crate::parse_messages::*;
+use crate::pickle::*;
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The type of a struct, union or enum, and its descriptor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeInfo {
+    pub mojom_type: MojomType,
+    pub descriptor: TypeDescriptor,
+}
+
+/// A type or constant which was loaded successfully, or why it couldn't be.
+type Loaded<T> = std::result::Result<T, String>;
+type LoadedType = Loaded<TypeInfo>;
+
+fn loaded<T>(item: &Loaded<T>) -> Result<&T> {
+    item.as_ref().map_err(|reason| anyhow!("{}", reason))
+}
+
+/// A method of an interface.
+#[derive(Debug)]
+pub struct MethodInfo {
+    pub name: String,
+    pub ordinal: u32,
+    params: LoadedType,
+    response_params: Option<LoadedType>,
+}
+
+impl MethodInfo {
+    /// The struct that holds the method's parameters.
+    pub fn params(&self) -> Result<&TypeInfo> {
+        loaded(&self.params)
+            .with_context(|| format!("The parameters of {} aren't supported", self.name))
+    }
+
+    /// The struct that holds the method's response parameters, or `None` if
+    /// the method has no response.
+    pub fn response_params(&self) -> Result<Option<&TypeInfo>> {
+        self.response_params
+            .as_ref()
+            .map(|ty| {
+                loaded(ty).with_context(|| {
+                    format!("The response parameters of {} aren't supported", self.name)
+                })
+            })
+            .transpose()
+    }
+}
+
+/// An interface, and its methods.
+#[derive(Debug)]
+pub struct InterfaceInfo {
+    /// The interface's fully-qualified name, e.g. "mojo.test.Echo".
+    pub name: String,
+    pub version: u32,
+    pub methods: Vec<MethodInfo>,
+}
+
+impl InterfaceInfo {
+    pub fn method(&self, ordinal: u32) -> Result<&MethodInfo> {
+        self.methods
+            .iter()
+            .find(|method| method.ordinal == ordinal)
+            .with_context(|| format!("{} has no method with ordinal {}", self.name, ordinal))
+    }
+
+    /// Return the method that a message with the given header is for, and the
+    /// type of its payload, which is the method's parameters or response
+    /// parameters.
+    pub fn payload_type(&self, header: &MessageHeader) -> Result<(&MethodInfo, &TypeInfo)> {
+        let method = self.method(header.name)?;
+        let ty = if header.is_response() {
+            method.response_params()?.with_context(|| {
+                format!("The message is a response, but {} has no response", method.name)
+            })?
+        } else {
+            method.params()?
+        };
+        Ok((method, ty))
+    }
+}
+
+/// A message which was decoded using the types in a `MojomModule`.
+#[derive(Debug)]
+pub struct DecodedMessage<'a> {
+    pub header: MessageHeader,
+    pub interface_ids: Vec<u32>,
+    /// The method that the message is a request to, or a response from.
+    pub method: &'a MethodInfo,
+    /// The method's parameters or response parameters.
+    pub value: MojomValue,
+    /// Describes `value`.
+    pub descriptor: &'a TypeDescriptor,
+}
+
+/// The types declared in a mojom file.
+#[derive(Debug)]
+pub struct MojomModule {
+    path: String,
+    namespace: String,
+    /// Keyed by fully-qualified name.
+    types: BTreeMap<String, LoadedType>,
+    /// Keyed by fully-qualified name.
+    interfaces: BTreeMap<String, InterfaceInfo>,
+    /// Keyed by fully-qualified name, e.g. "mojo.test.Struct.kConstant".
+    constants: BTreeMap<String, Loaded<MojomValue>>,
+}
+
+impl MojomModule {
+    /// Load the `.mojom-module` file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data =
+            std::fs::read(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+        Self::parse(&data).with_context(|| format!("In {}", path.display()))
+    }
+
+    /// Load the contents of a `.mojom-module` file.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let pickle = unpickle(data)?;
+        let module = pickle.root();
+        if pickle.class_name(module) != Some("Module") {
+            bail!("A .mojom-module file must contain a pickled Module")
+        }
+        let loader = Loader { pickle: &pickle };
+        let mut result = MojomModule {
+            path: loader.string(module, "path")?,
+            namespace: loader.string(module, "mojom_namespace")?,
+            types: BTreeMap::new(),
+            interfaces: BTreeMap::new(),
+            constants: BTreeMap::new(),
+        };
+
+        let mut enums = loader.list(module, "enums")?.to_vec();
+        let mut constants = loader.list(module, "constants")?.to_vec();
+        for key in ["structs", "unions"] {
+            for kind in loader.list(module, key)? {
+                result.add_type(&loader, kind)?;
+                enums.extend_from_slice(loader.list(kind, "enums").unwrap_or_default());
+                constants.extend_from_slice(loader.list(kind, "constants").unwrap_or_default());
+            }
+        }
+        for interface in loader.list(module, "interfaces")? {
+            let info = loader.interface(interface)?;
+            result.interfaces.insert(info.name.clone(), info);
+            enums.extend_from_slice(loader.list(interface, "enums")?);
+            constants.extend_from_slice(loader.list(interface, "constants")?);
+        }
+        for kind in &enums {
+            result.add_type(&loader, kind)?;
+        }
+        for constant in &constants {
+            result.add_constant(&loader, constant)?;
+        }
+        Ok(result)
+    }
+
+    fn add_type(&mut self, loader: &Loader, kind: &PickleValue) -> Result<()> {
+        let name = loader.qualified_name(kind)?;
+        let ty = loader.kind(kind, &mut vec![]).map_err(|error| format!("{:#}", error));
+        self.types.insert(name, ty);
+        Ok(())
+    }
+
+    fn add_constant(&mut self, loader: &Loader, constant: &PickleValue) -> Result<()> {
+        let name = loader.string(constant, "mojom_name")?;
+        let name = match loader.attribute(constant, "parent_kind")? {
+            PickleValue::None => self.qualify(&name),
+            parent => format!("{}.{}", loader.qualified_name(parent)?, name),
+        };
+        let value = loader.constant(constant).map_err(|error| format!("{:#}", error));
+        self.constants.insert(name, value);
+        Ok(())
+    }
+
+    /// The path of the mojom file, relative to its input root.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The module's namespace, e.g. "mojo.test".
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// The fully-qualified names of the structs, unions and enums declared in
+    /// the module, including ones which aren't supported.
+    pub fn type_names(&self) -> impl Iterator<Item = &str> {
+        self.types.keys().map(String::as_str)
+    }
+
+    /// Look up a struct, union or enum by its fully-qualified name, or by its
+    /// name relative to the module's namespace.
+    pub fn get_type(&self, name: &str) -> Result<&TypeInfo> {
+        let ty = self
+            .types
+            .get(name)
+            .or_else(|| self.types.get(&self.qualify(name)))
+            .with_context(|| format!("{} doesn't declare {}", self.path, name))?;
+        loaded(ty).with_context(|| format!("{} isn't supported", name))
+    }
+
+    /// The fully-qualified names of the constants declared in the module,
+    /// including the ones nested in structs and interfaces, and ones which
+    /// aren't supported.
+    pub fn constant_names(&self) -> impl Iterator<Item = &str> {
+        self.constants.keys().map(String::as_str)
+    }
+
+    /// Look up the value of a constant by its fully-qualified name, or by its
+    /// name relative to the module's namespace, e.g. "Struct.kConstant".
+    pub fn constant(&self, name: &str) -> Result<&MojomValue> {
+        let value = self
+            .constants
+            .get(name)
+            .or_else(|| self.constants.get(&self.qualify(name)))
+            .with_context(|| format!("{} doesn't declare constant {}", self.path, name))?;
+        loaded(value).with_context(|| format!("{} isn't supported", name))
+    }
+
+    pub fn interfaces(&self) -> impl Iterator<Item = &InterfaceInfo> {
+        self.interfaces.values()
+    }
+
+    /// Look up an interface by its fully-qualified name, or by its name
+    /// relative to the module's namespace.
+    pub fn interface(&self, name: &str) -> Result<&InterfaceInfo> {
+        self.interfaces
+            .get(name)
+            .or_else(|| self.interfaces.get(&self.qualify(name)))
+            .with_context(|| format!("{} doesn't declare interface {}", self.path, name))
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        if self.namespace.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", self.namespace, name)
+        }
+    }
+
+    /// Decode a whole message which was sent to or from `interface`. The
+    /// header says which method it's for, and whether it holds the method's
+    /// parameters or its response parameters.
+    pub fn decode_message<'a>(
+        &'a self,
+        interface: &str,
+        message: &[u8],
+    ) -> Result<DecodedMessage<'a>> {
+        let interface = self.interface(interface)?;
+        let (header, payload, interface_ids) =
+            parse_message_parts(message).context("In the message header")?;
+        let (method, ty) = interface.payload_type(&header)?;
+        let value =
+            parse_struct_payload(payload, &ty.mojom_type).context("In the message payload")?;
+        Ok(DecodedMessage { header, interface_ids, method, value, descriptor: &ty.descriptor })
+    }
+}
+
+/// Builds types from the objects in a pickled module.
+struct Loader<'a> {
+    pickle: &'a Pickle,
+}
+
+impl<'a> Loader<'a> {
+    /// Return an attribute of a kind or other object. Most of the attributes
+    /// of user-defined kinds live in a separate `shared_definition` dict,
+    /// which they share with the nullable version of the kind, so look there
+    /// too.
+    fn attribute(&self, object: &PickleValue, name: &str) -> Result<&'a PickleValue> {
+        let shared_definition = self.pickle.get(object, "shared_definition");
+        self.pickle
+            .get(object, name)
+            .or_else(|| self.pickle.get(shared_definition?, name))
+            .with_context(|| format!("Expected the pickled module to have an attribute {}", name))
+    }
+
+    fn string(&self, object: &PickleValue, name: &str) -> Result<String> {
+        match self.attribute(object, name)? {
+            PickleValue::String(value) => Ok(value.clone()),
+            value => bail!("Expected {} to be a string, but got {:?}", name, value),
+        }
+    }
+
+    fn optional_string(&self, object: &PickleValue, name: &str) -> Result<Option<String>> {
+        match self.attribute(object, name)? {
+            PickleValue::None => Ok(None),
+            _ => self.string(object, name).map(Some),
+        }
+    }
+
+    fn int(&self, object: &PickleValue, name: &str) -> Result<i64> {
+        match self.attribute(object, name)? {
+            PickleValue::Int(value) => Ok(*value),
+            value => bail!("Expected {} to be an integer, but got {:?}", name, value),
+        }
+    }
+
+    fn bool(&self, object: &PickleValue, name: &str) -> Result<bool> {
+        match self.attribute(object, name)? {
+            PickleValue::Bool(value) => Ok(*value),
+            value => bail!("Expected {} to be a bool, but got {:?}", name, value),
+        }
+    }
+
+    fn list(&self, object: &PickleValue, name: &str) -> Result<&'a [PickleValue]> {
+        let value = self.attribute(object, name)?;
+        self.pickle
+            .elements(value)
+            .with_context(|| format!("Expected {} to be a list, but got {:?}", name, value))
+    }
+
+    /// Return the name of a user-defined kind, including its namespace and
+    /// the kinds it's nested in, e.g. "mojo.test.Struct.Enum".
+    fn qualified_name(&self, kind: &PickleValue) -> Result<String> {
+        let name = self.string(kind, "mojom_name")?;
+        let prefix = match self.attribute(kind, "parent_kind")? {
+            PickleValue::None => {
+                let module = self.attribute(kind, "module")?;
+                self.optional_string(module, "mojom_namespace")?.unwrap_or_default()
+            }
+            parent => self.qualified_name(parent)?,
+        };
+        Ok(if prefix.is_empty() { name } else { format!("{}.{}", prefix, name) })
+    }
+
+    /// Return the fields of a struct or union, in ordinal order.
+    fn fields(&self, kind: &PickleValue) -> Result<Vec<&'a PickleValue>> {
+        let mut fields = vec![];
+        for field in self.list(kind, "fields")? {
+            fields.push((self.int(field, "ordinal")?, field));
+        }
+        fields.sort_by_key(|(ordinal, _)| *ordinal);
+        for (index, (ordinal, _)) in fields.iter().enumerate() {
+            if *ordinal != index as i64 {
+                bail!("The fields' ordinals must be 0 to {}", fields.len() - 1)
+            }
+        }
+        Ok(fields.into_iter().map(|(_, field)| field).collect())
+    }
+
+    /// Whether a kind is a nullable value kind, e.g. `int32?`. These are
+    /// encoded as a has-value flag and a value, which only structs support.
+    fn is_nullable_value(&self, kind: &PickleValue) -> Result<bool> {
+        let class =
+            self.pickle.class_name(kind).context("Expected a kind in the pickled module")?;
+        Ok(matches!(class, "ValueKind" | "Enum") && self.bool(kind, "is_nullable")?)
+    }
+
+    /// Build the type of a kind. `containing` is the names of the structs and
+    /// unions that we're building the kind inside, which it mustn't contain.
+    fn kind(&self, kind: &PickleValue, containing: &mut Vec<String>) -> Result<TypeInfo> {
+        let class =
+            self.pickle.class_name(kind).context("Expected a kind in the pickled module")?;
+        let spec = self.string(kind, "spec")?;
+        let nullable = self.bool(kind, "is_nullable")?;
+        let unnamed = |mojom_type| TypeInfo { mojom_type, descriptor: TypeDescriptor::Unnamed };
+        let info = match class {
+            "ValueKind" => unnamed(match spec.trim_start_matches('?') {
+                "b" => MojomType::Bool,
+                "i8" => MojomType::Int8,
+                "u8" => MojomType::UInt8,
+                "i16" => MojomType::Int16,
+                "u16" => MojomType::UInt16,
+                "i32" => MojomType::Int32,
+                "u32" => MojomType::UInt32,
+                "i64" => MojomType::Int64,
+                "u64" => MojomType::UInt64,
+                "f" => MojomType::Float,
+                "d" => MojomType::Double,
+                _ => bail!("Unknown kind {}", spec),
+            }),
+            "ReferenceKind" if spec.trim_start_matches('?') == "s" => unnamed(MojomType::String),
+            // All kinds of handle are encoded the same way.
+            "ReferenceKind" if spec.trim_start_matches('?').starts_with('h') => {
+                unnamed(MojomType::Handle)
+            }
+            "Struct" | "Union" => {
+                let name = self.qualified_name(kind)?;
+                if containing.contains(&name) {
+                    bail!("{} contains itself, which isn't supported yet", name)
+                }
+                containing.push(name);
+                let name = self.string(kind, "mojom_name")?;
+                let mut types = vec![];
+                let mut descriptors = vec![];
+                let mut defaults = vec![];
+                let mut min_versions = vec![];
+                for field in self.fields(kind)? {
+                    let field_name = self.string(field, "mojom_name")?;
+                    let field_kind = self.attribute(field, "kind")?;
+                    let field_info = self
+                        .kind(field_kind, containing)
+                        .with_context(|| format!("In field {}.{}", name, field_name))?;
+                    if class == "Union" && self.is_nullable_value(field_kind)? {
+                        bail!(
+                            "In field {}.{}: Nullable values in unions aren't supported yet",
+                            name,
+                            field_name
+                        )
+                    }
+                    // Union fields can't declare defaults.
+                    let default = match self.pickle.get(field, "default") {
+                        None | Some(PickleValue::None) => None,
+                        Some(default) => {
+                            Some(self.value(default, &field_info).with_context(|| {
+                                format!("In the default of field {}.{}", name, field_name)
+                            })?)
+                        }
+                    };
+                    // Fields only have a version if they're [MinVersion]ed.
+                    let min_version = match self
+                        .pickle
+                        .get(field, "attributes")
+                        .and_then(|attributes| self.pickle.get(attributes, "MinVersion"))
+                    {
+                        None => 0,
+                        Some(PickleValue::Int(version)) => u32::try_from(*version)?,
+                        Some(version) => bail!("Expected a MinVersion, but got {:?}", version),
+                    };
+                    types.push(field_info.mojom_type);
+                    descriptors.push((field_name, field_info.descriptor));
+                    defaults.push(default);
+                    min_versions.push(min_version);
+                }
+                containing.pop();
+                if defaults.iter().all(Option::is_none) {
+                    defaults.clear();
+                }
+                if min_versions.iter().all(|version| *version == 0) {
+                    min_versions.clear();
+                }
+                if class == "Struct" {
+                    TypeInfo {
+                        mojom_type: MojomType::Struct { fields: types, min_versions },
+                        descriptor: TypeDescriptor::Struct { name, fields: descriptors, defaults },
+                    }
+                } else {
+                    TypeInfo {
+                        mojom_type: MojomType::Union { variants: types },
+                        descriptor: TypeDescriptor::Union { name, variants: descriptors },
+                    }
+                }
+            }
+            "Enum" => {
+                let mut values = vec![];
+                for field in self.list(kind, "fields")? {
+                    let value = self.int(field, "numeric_value")?;
+                    values.push((self.string(field, "mojom_name")?, value.try_into()?));
+                }
+                TypeInfo {
+                    mojom_type: MojomType::Int32,
+                    descriptor: TypeDescriptor::Enum {
+                        name: self.string(kind, "mojom_name")?,
+                        values,
+                    },
+                }
+            }
+            "Array" => {
+                let element_kind = self.attribute(kind, "kind")?;
+                if self.is_nullable_value(element_kind)? {
+                    bail!("Arrays of nullable values aren't supported yet")
+                }
+                let element = self.kind(element_kind, containing)?;
+                let num_elements = match self.attribute(kind, "length")? {
+                    PickleValue::None => None,
+                    _ => Some(self.int(kind, "length")?.try_into()?),
+                };
+                TypeInfo {
+                    mojom_type: MojomType::Array {
+                        element_type: Box::new(element.mojom_type),
+                        num_elements,
+                    },
+                    descriptor: TypeDescriptor::Array { element: Box::new(element.descriptor) },
+                }
+            }
+            "PendingRemote" => unnamed(MojomType::PendingRemote),
+            // Pending receivers are just message pipes.
+            "PendingReceiver" => unnamed(MojomType::Handle),
+            "PendingAssociatedRemote" => unnamed(MojomType::PendingAssociatedRemote),
+            "PendingAssociatedReceiver" => unnamed(MojomType::PendingAssociatedReceiver),
+            "Map" => {
+                let value_kind = self.attribute(kind, "value_kind")?;
+                if self.is_nullable_value(value_kind)? {
+                    bail!("Maps with nullable values aren't supported yet")
+                }
+                let key = self.kind(self.attribute(kind, "key_kind")?, containing)?;
+                let value = self.kind(value_kind, containing)?;
+                TypeInfo {
+                    mojom_type: MojomType::map(key.mojom_type, value.mojom_type),
+                    descriptor: TypeDescriptor::map(key.descriptor, value.descriptor),
+                }
+            }
+            _ => bail!("{}s aren't supported yet", class),
+        };
+        Ok(if nullable {
+            TypeInfo { mojom_type: MojomType::Nullable(Box::new(info.mojom_type)), ..info }
+        } else {
+            info
+        })
+    }
+
+    /// Return the value of a constant.
+    fn constant(&self, constant: &PickleValue) -> Result<MojomValue> {
+        let ty = self.kind(self.attribute(constant, "kind")?, &mut vec![])?;
+        self.value(self.attribute(constant, "value")?, &ty)
+    }
+
+    /// Return the value of a constant's value or a field's default, which has
+    /// the type `ty`. It's either a literal, or refers to a constant or an
+    /// enum value.
+    fn value(&self, value: &PickleValue, ty: &TypeInfo) -> Result<MojomValue> {
+        if let PickleValue::String(literal) = value {
+            return literal_value(literal, ty);
+        }
+        match self.pickle.class_name(value) {
+            Some("ConstantValue") => self.constant(self.attribute(value, "constant")?),
+            Some("EnumValue") => {
+                let field = self.attribute(value, "field")?;
+                Ok(MojomValue::Int32(self.int(field, "numeric_value")?.try_into()?))
+            }
+            Some("BuiltinValue") => builtin_value(&self.string(value, "value")?),
+            _ => bail!("Expected a value in the pickled module, but got {:?}", value),
+        }
+    }
+
+    fn interface(&self, interface: &PickleValue) -> Result<InterfaceInfo> {
+        let name = self.qualified_name(interface)?;
+        let mut methods = vec![];
+        for method in self.list(interface, "methods")? {
+            let params = self.attribute(method, "param_struct")?;
+            let response_params = match self.attribute(method, "response_param_struct")? {
+                PickleValue::None => None,
+                response_params => Some(response_params),
+            };
+            let build = |kind| self.kind(kind, &mut vec![]).map_err(|error| format!("{:#}", error));
+            methods.push(MethodInfo {
+                name: self.string(method, "mojom_name")?,
+                ordinal: self.int(method, "ordinal")?.try_into()?,
+                params: build(params),
+                response_params: response_params.map(build),
+            });
+        }
+        Ok(InterfaceInfo { name, version: self.int(interface, "version")?.try_into()?, methods })
+    }
+}
+
+/// Parse a literal from a mojom file, e.g. `-0x10`, `true` or `"hi"`, as a
+/// value of the type `ty`. `default` is the default value of a struct.
+fn literal_value(literal: &str, ty: &TypeInfo) -> Result<MojomValue> {
+    let mojom_type = match &ty.mojom_type {
+        MojomType::Nullable(inner) => inner,
+        mojom_type => mojom_type,
+    };
+    if literal == "default" {
+        return Ok(MojomValue::default_with_descriptor(mojom_type, &ty.descriptor));
+    }
+    Ok(match mojom_type {
+        MojomType::Bool => match literal {
+            "true" => MojomValue::Bool(true),
+            "false" => MojomValue::Bool(false),
+            _ => bail!("Expected a bool, but got {}", literal),
+        },
+        MojomType::Int8 => MojomValue::Int8(int_literal(literal)?),
+        MojomType::UInt8 => MojomValue::UInt8(int_literal(literal)?),
+        MojomType::Int16 => MojomValue::Int16(int_literal(literal)?),
+        MojomType::UInt16 => MojomValue::UInt16(int_literal(literal)?),
+        MojomType::Int32 => MojomValue::Int32(int_literal(literal)?),
+        MojomType::UInt32 => MojomValue::UInt32(int_literal(literal)?),
+        MojomType::Int64 => MojomValue::Int64(int_literal(literal)?),
+        MojomType::UInt64 => MojomValue::UInt64(int_literal(literal)?),
+        MojomType::Float => MojomValue::Float(float_literal(literal)? as f32),
+        MojomType::Double => MojomValue::Double(float_literal(literal)?),
+        MojomType::String => MojomValue::String(string_literal(literal)?),
+        _ => bail!("Unexpected value {} for a {:?}", literal, mojom_type),
+    })
+}
+
+/// Parse a decimal or hexadecimal integer literal, which may have a sign.
+fn int_literal<T: TryFrom<i128>>(literal: &str) -> Result<T> {
+    let (negative, digits) = match literal.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, literal.strip_prefix('+').unwrap_or(literal)),
+    };
+    let magnitude = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse(),
+    }
+    .with_context(|| format!("Invalid integer {}", literal))?;
+    let value = if negative { -magnitude } else { magnitude };
+    T::try_from(value).map_err(|_| anyhow!("{} is out of range", literal))
+}
+
+/// Parse a floating point literal, e.g. `1.5e3`. Integer literals are also
+/// allowed, and may be hexadecimal.
+fn float_literal(literal: &str) -> Result<f64> {
+    if let Ok(value) = int_literal::<i64>(literal) {
+        return Ok(value as f64);
+    }
+    literal.parse().map_err(|_| anyhow!("Invalid floating point number {}", literal))
+}
+
+/// Return the value of one of the builtin constants for infinity and NaN,
+/// e.g. `double.INFINITY`.
+fn builtin_value(name: &str) -> Result<MojomValue> {
+    Ok(match name {
+        "float.INFINITY" => MojomValue::Float(f32::INFINITY),
+        "float.NEGATIVE_INFINITY" => MojomValue::Float(f32::NEG_INFINITY),
+        "float.NAN" => MojomValue::Float(f32::NAN),
+        "double.INFINITY" => MojomValue::Double(f64::INFINITY),
+        "double.NEGATIVE_INFINITY" => MojomValue::Double(f64::NEG_INFINITY),
+        "double.NAN" => MojomValue::Double(f64::NAN),
+        _ => bail!("Unknown builtin value {}", name),
+    })
+}
+
+/// Parse a quoted string literal, with C-style escapes.
+fn string_literal(literal: &str) -> Result<String> {
+    let contents = literal
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .with_context(|| format!("Expected a string, but got {}", literal))?;
+    let mut ret = String::new();
+    let mut chars = contents.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        ret.push(match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'')) => c,
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16)
+                    .with_context(|| format!("Invalid escape \\x{} in {}", hex, literal))?
+                    .into()
+            }
+            c => bail!("Unsupported escape \\{} in {}", c.unwrap_or_default(), literal),
+        });
+    }
+    Ok(ret)
+}
@@ -0,0 +1,477 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Tests for loading types from `.mojom-module` files, and for converting
+//! values of those types to and from JSON.
+//!
+//! test_data/reflection.test-mojom-module is the module that mojom_parser.py
+//! wrote for test_data/reflection.test-mojom, and is copied there by
+//! generate_pack_fixtures.py.
+
+use rust_gtest_interop::prelude::*;
+
+use mojom_parser::*;
+use serde_json::json;
+
+const MODULE: &[u8] = include_bytes!("test_data/reflection.test-mojom-module");
+
+fn unnamed_fields(names: &[&str]) -> Vec<(String, TypeDescriptor)> {
+    names.iter().map(|name| (name.to_string(), TypeDescriptor::Unnamed)).collect()
+}
+
+fn encode_message(header: &MessageHeader, ty: &MojomType, value: &[MojomValue]) -> Vec<u8> {
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = pack_mojom_type(ty, 0)
+    else {
+        panic!("Messages must be structs")
+    };
+    let mut payload = vec![];
+    deparse_struct(&mut payload, value, &packed_field_types, &[]).unwrap();
+    let mut message = vec![];
+    deparse_message(&mut message, header, &payload, &[]).unwrap();
+    message
+}
+
+#[gtest(MojomParserTestSuit, MojomModuleTest)]
+fn test_mojom_module() {
+    let module = MojomModule::parse(MODULE).unwrap();
+    expect_eq!(module.path(), "mojo/public/rust/mojom_parser/test_data/reflection.test-mojom");
+    expect_eq!(module.namespace(), "mojom_parser.test");
+    expect_eq!(
+        module.type_names().collect::<Vec<_>>(),
+        vec![
+            "mojom_parser.test.Color",
+            "mojom_parser.test.Connection",
+            "mojom_parser.test.Defaults",
+            "mojom_parser.test.Drawing",
+            "mojom_parser.test.Drawing.Style",
+            "mojom_parser.test.Ids",
+            "mojom_parser.test.Index",
+            "mojom_parser.test.Optionals",
+            "mojom_parser.test.Point",
+            "mojom_parser.test.Scale",
+            "mojom_parser.test.Shape",
+            "mojom_parser.test.Tree",
+        ]
+    );
+
+    let point = module.get_type("mojom_parser.test.Point").unwrap();
+    expect_eq!(
+        point.mojom_type,
+        MojomType::Struct { fields: vec![MojomType::Int32; 2], min_versions: vec![] }
+    );
+    expect_eq!(
+        point.descriptor,
+        TypeDescriptor::Struct {
+            name: "Point".to_string(),
+            fields: unnamed_fields(&["x", "y"]),
+            defaults: vec![],
+        }
+    );
+    // Names can also be relative to the module's namespace.
+    expect_eq!(module.get_type("Point").unwrap(), point);
+
+    let color = module.get_type("Color").unwrap();
+    expect_eq!(color.mojom_type, MojomType::Int32);
+    expect_eq!(
+        color.descriptor,
+        TypeDescriptor::Enum {
+            name: "Color".to_string(),
+            values: vec![
+                ("kRed".to_string(), 0),
+                ("kGreen".to_string(), 5),
+                ("kBlue".to_string(), 6)
+            ],
+        }
+    );
+
+    // The fields are in ordinal order, not the order they're declared in.
+    let shape = module.get_type("Shape").unwrap();
+    let drawing = module.get_type("Drawing").unwrap();
+    expect_eq!(
+        drawing.mojom_type,
+        MojomType::Struct {
+            fields: vec![
+                MojomType::Array {
+                    element_type: Box::new(shape.mojom_type.clone()),
+                    num_elements: None
+                },
+                MojomType::String,
+                MojomType::Int32,
+                MojomType::Nullable(Box::new(shape.mojom_type.clone())),
+                MojomType::Int32,
+                MojomType::Nullable(Box::new(MojomType::PendingAssociatedRemote)),
+            ],
+            min_versions: vec![],
+        }
+    );
+    expect_eq!(drawing.descriptor.field_name(0), Some("shapes"));
+    expect_eq!(drawing.descriptor.field_name(5), Some("observer"));
+    expect_eq!(drawing.descriptor.field(3), &shape.descriptor);
+    expect_eq!(drawing.descriptor.field(4), &module.get_type("Drawing.Style").unwrap().descriptor);
+
+    let index = module.get_type("Index").unwrap();
+    expect_eq!(
+        index.mojom_type,
+        MojomType::Struct {
+            fields: vec![MojomType::map(MojomType::String, MojomType::Int32)],
+            min_versions: vec![],
+        }
+    );
+    expect_eq!(
+        index.descriptor.field(0),
+        &TypeDescriptor::map(TypeDescriptor::Unnamed, TypeDescriptor::Unnamed)
+    );
+
+    // Unsupported types are listed, but can't be used.
+    let error = format!("{:#}", module.get_type("Tree").unwrap_err());
+    expect_true!(error.contains("contains itself"), "{}", error);
+    let error = format!("{:#}", module.get_type("Ids").unwrap_err());
+    expect_true!(error.contains("Arrays of nullable values"), "{}", error);
+    expect_true!(module.get_type("Triangle").is_err());
+
+    let canvas = module.interface("Canvas").unwrap();
+    expect_eq!(canvas.name, "mojom_parser.test.Canvas");
+    expect_eq!(
+        canvas
+            .methods
+            .iter()
+            .map(|method| (method.name.as_str(), method.ordinal))
+            .collect::<Vec<_>>(),
+        vec![("Draw", 0), ("Clear", 5), ("Zoom", 6), ("Find", 7)]
+    );
+    let draw = canvas.method(0).unwrap();
+    expect_eq!(
+        draw.params().unwrap().mojom_type,
+        MojomType::Struct { fields: vec![drawing.mojom_type.clone()], min_versions: vec![] }
+    );
+    let response_params = draw.response_params().unwrap().unwrap();
+    expect_eq!(response_params.descriptor.field_name(1), Some("bounds"));
+    expect_eq!(canvas.method(5).unwrap().response_params().unwrap(), None);
+    let find = canvas.method(7).unwrap().params().unwrap();
+    expect_eq!(find.descriptor.field(0), &index.descriptor);
+    expect_true!(canvas.method(8).is_err());
+
+    // Messages are decoded with the parameters of the method in their header.
+    let header = MessageHeader {
+        name: 0,
+        flags: MESSAGE_FLAG_IS_RESPONSE,
+        request_id: 4,
+        ..Default::default()
+    };
+    let bounds = MojomValue::Array(vec![
+        MojomValue::Struct(vec![MojomValue::Int32(1), MojomValue::Int32(2)]),
+        MojomValue::Struct(vec![MojomValue::Int32(3), MojomValue::Int32(4)]),
+    ]);
+    let message = encode_message(
+        &header,
+        &response_params.mojom_type,
+        &[MojomValue::Bool(true), bounds.clone()],
+    );
+    let decoded = module.decode_message("mojom_parser.test.Canvas", &message).unwrap();
+    expect_eq!(decoded.header, header);
+    expect_eq!(decoded.method.name, "Draw");
+    expect_eq!(decoded.value, MojomValue::Struct(vec![MojomValue::Bool(true), bounds]));
+    expect_eq!(
+        decoded.value.with_descriptor(decoded.descriptor).to_string(),
+        "Canvas_Draw_ResponseParams { success: true, bounds: [Point { x: 1, y: 2 }, \
+         Point { x: 3, y: 4 }] }"
+    );
+
+    let header = MessageHeader { name: 5, ..Default::default() };
+    let message =
+        encode_message(&header, &MojomType::Struct { fields: vec![], min_versions: vec![] }, &[]);
+    expect_eq!(module.decode_message("Canvas", &message).unwrap().method.name, "Clear");
+    let header = MessageHeader { name: 5, flags: MESSAGE_FLAG_IS_RESPONSE, ..header };
+    let message =
+        encode_message(&header, &MojomType::Struct { fields: vec![], min_versions: vec![] }, &[]);
+    expect_true!(module.decode_message("Canvas", &message).is_err());
+
+    // Enum values are displayed with their names.
+    let value = MojomValue::Struct(vec![MojomValue::Int32(5), MojomValue::Int32(7)]);
+    let descriptor = TypeDescriptor::Struct {
+        name: "Colors".to_string(),
+        fields: vec![
+            ("a".to_string(), color.descriptor.clone()),
+            ("b".to_string(), color.descriptor.clone()),
+        ],
+        defaults: vec![],
+    };
+    expect_eq!(
+        value.with_descriptor(&descriptor).to_string(),
+        "Colors { a: Color.kGreen, b: Color(7) }"
+    );
+
+    expect_true!(MojomModule::parse(b"not a pickle").is_err());
+    expect_true!(MojomModule::parse(&MODULE[..MODULE.len() - 1]).is_err());
+}
+
+#[gtest(MojomParserTestSuit, MojomModuleDefaultsTest)]
+fn test_mojom_module_defaults() {
+    let module = MojomModule::parse(MODULE).unwrap();
+    expect_eq!(
+        module.constant_names().collect::<Vec<_>>(),
+        vec![
+            "mojom_parser.test.Canvas.kMaxShapes",
+            "mojom_parser.test.Defaults.kSmall",
+            "mojom_parser.test.kBig",
+            "mojom_parser.test.kDefaultSize",
+            "mojom_parser.test.kFavoriteColor",
+            "mojom_parser.test.kGreeting",
+            "mojom_parser.test.kRatio",
+        ]
+    );
+    expect_eq!(module.constant("kDefaultSize").unwrap(), &MojomValue::Int32(10));
+    expect_eq!(module.constant("kBig").unwrap(), &MojomValue::UInt64(u64::MAX));
+    expect_eq!(module.constant("kGreeting").unwrap(), &MojomValue::String("hi\tthere".into()));
+    expect_eq!(module.constant("kFavoriteColor").unwrap(), &MojomValue::Int32(5));
+    expect_eq!(module.constant("Defaults.kSmall").unwrap(), &MojomValue::Int8(-3));
+    expect_eq!(
+        module.constant("mojom_parser.test.Canvas.kMaxShapes").unwrap(),
+        &MojomValue::UInt32(100)
+    );
+    expect_eq!(module.constant("kRatio").unwrap(), &MojomValue::Double(1.5));
+    expect_true!(module.constant("kTau").is_err());
+
+    // Fields' defaults may be literals, constants or enum values.
+    let defaults = module.get_type("Defaults").unwrap();
+    let origin = MojomValue::Struct(vec![MojomValue::Int32(0); 2]);
+    let expected = [
+        Some(MojomValue::Int32(10)),
+        Some(MojomValue::Int8(-3)),
+        Some(MojomValue::UInt64(u64::MAX)),
+        Some(MojomValue::Bool(true)),
+        Some(MojomValue::String("hi\tthere".into())),
+        Some(MojomValue::String("yo".into())),
+        Some(MojomValue::Int32(6)),
+        Some(origin.clone()),
+        Some(MojomValue::Int16(-16)),
+        None,
+        Some(MojomValue::Int32(7)),
+    ];
+    for (ordinal, expected) in expected.iter().enumerate() {
+        expect_eq!(defaults.descriptor.field_default(ordinal), expected.as_ref(), "{}", ordinal);
+    }
+    expect_eq!(
+        MojomValue::default_with_descriptor(&defaults.mojom_type, &defaults.descriptor),
+        MojomValue::Struct(
+            expected.iter().map(|value| value.clone().unwrap_or(MojomValue::Int32(0))).collect()
+        )
+    );
+
+    // Defaulted fields can be left out of hand-written values and JSON.
+    let value = MojomValue::new_struct(
+        &defaults.mojom_type,
+        &defaults.descriptor,
+        [("no_default", MojomValue::Int32(1)), ("size", MojomValue::Int32(2))],
+    )
+    .unwrap();
+    let MojomValue::Struct(fields) = &value else { unreachable!() };
+    expect_eq!(fields[0], MojomValue::Int32(2));
+    expect_eq!(fields[7], origin);
+    expect_eq!(fields[9], MojomValue::Int32(1));
+    expect_eq!(defaults.value_from_json(&json!({ "no_default": 1, "size": 2 })).unwrap(), value);
+    expect_true!(defaults.value_from_json(&json!({ "size": 2 })).is_err());
+
+    // Floating point defaults may be integers or builtin values, which JSON
+    // writes as strings, since it has no infinite numbers.
+    let scale = module.get_type("Scale").unwrap();
+    expect_eq!(
+        scale.mojom_type,
+        MojomType::Struct {
+            fields: vec![MojomType::Float, MojomType::Double],
+            min_versions: vec![]
+        }
+    );
+    let value = MojomValue::default_with_descriptor(&scale.mojom_type, &scale.descriptor);
+    expect_eq!(
+        value,
+        MojomValue::Struct(vec![MojomValue::Float(1.0), MojomValue::Double(f64::INFINITY)])
+    );
+    let json = json!({ "factor": 1.0, "limit": "inf" });
+    expect_eq!(scale.value_to_json(&value).unwrap(), json);
+    expect_eq!(scale.value_from_json(&json).unwrap(), value);
+    expect_true!(scale.value_from_json(&json!({ "factor": "1", "limit": 2 })).is_err());
+    // Finite numbers which don't fit in a float aren't turned into infinity.
+    expect_true!(scale.value_from_json(&json!({ "factor": 1e300, "limit": 2 })).is_err());
+    expect_true!(scale.value_from_json(&json!({ "factor": -1e39, "limit": 2 })).is_err());
+    let max = scale.value_from_json(&json!({ "factor": f32::MAX, "limit": 2 })).unwrap();
+    expect_eq!(max, MojomValue::Struct(vec![MojomValue::Float(f32::MAX), MojomValue::Double(2.0)]));
+
+    // Nullable values may have defaults too, and are null otherwise.
+    let optionals = module.get_type("Optionals").unwrap();
+    expect_eq!(
+        optionals.mojom_type,
+        MojomType::Struct {
+            fields: vec![
+                MojomType::Nullable(Box::new(MojomType::Int32)),
+                MojomType::Nullable(Box::new(MojomType::Bool)),
+                MojomType::Nullable(Box::new(MojomType::Int32)),
+            ],
+            min_versions: vec![]
+        }
+    );
+    let value = MojomValue::default_with_descriptor(&optionals.mojom_type, &optionals.descriptor);
+    expect_eq!(
+        value,
+        MojomValue::Struct(vec![MojomValue::Null, MojomValue::Bool(true), MojomValue::Null])
+    );
+    let json = json!({ "count": null, "flag": true, "color": null });
+    expect_eq!(optionals.value_to_json(&value).unwrap(), json);
+    expect_eq!(optionals.value_from_json(&json).unwrap(), value);
+    let value = optionals.value_from_json(&json!({ "count": 3, "color": "kBlue" })).unwrap();
+    expect_eq!(
+        value,
+        MojomValue::Struct(vec![
+            MojomValue::Int32(3),
+            MojomValue::Bool(true),
+            MojomValue::Int32(6)
+        ])
+    );
+
+    // Remotes are sent with their version, and receivers are just handles.
+    let connection = module.get_type("Connection").unwrap();
+    expect_eq!(
+        connection.mojom_type,
+        MojomType::Struct {
+            fields: vec![
+                MojomType::PendingRemote,
+                MojomType::Nullable(Box::new(MojomType::Handle)),
+            ],
+            min_versions: vec![]
+        }
+    );
+    let value = MojomValue::Struct(vec![
+        MojomValue::PendingRemote { index: 0, version: 1 },
+        MojomValue::Handle { index: 1 },
+    ]);
+    let json = json!({ "canvas": { "index": 0, "version": 1 }, "receiver": { "index": 1 } });
+    expect_eq!(connection.value_to_json(&value).unwrap(), json);
+    expect_eq!(connection.value_from_json(&json).unwrap(), value);
+
+    // Maps are keys and values in parallel arrays.
+    let index = module.get_type("Index").unwrap();
+    let value = MojomValue::Struct(vec![MojomValue::Struct(vec![
+        MojomValue::Array(vec![MojomValue::String("a".to_string())]),
+        MojomValue::Array(vec![MojomValue::Int32(1)]),
+    ])]);
+    let json = json!({ "entries": { "keys": ["a"], "values": [1] } });
+    expect_eq!(index.value_to_json(&value).unwrap(), json);
+    expect_eq!(index.value_from_json(&json).unwrap(), value);
+}
+
+#[gtest(MojomParserTestSuit, JsonTest)]
+fn test_json() {
+    let module = MojomModule::parse(MODULE).unwrap();
+    let drawing = module.get_type("Drawing").unwrap();
+    let point = |x, y| MojomValue::Struct(vec![MojomValue::Int32(x), MojomValue::Int32(y)]);
+    let value = MojomValue::Struct(vec![
+        MojomValue::Array(vec![
+            MojomValue::Union { tag: 0, value: Box::new(point(1, 2)) },
+            MojomValue::Union { tag: 1, value: Box::new(MojomValue::Array(vec![point(3, 4)])) },
+        ]),
+        MojomValue::String("sketch".to_string()),
+        MojomValue::Int32(5),
+        MojomValue::Null,
+        MojomValue::Int32(9),
+        MojomValue::PendingAssociatedRemote { index: 0, version: 2 },
+    ]);
+    let json = json!({
+        "shapes": [{ "point": { "x": 1, "y": 2 } }, { "polygon": [{ "x": 3, "y": 4 }] }],
+        "name": "sketch",
+        "color": "kGreen",
+        "highlight": null,
+        // Enum values without names are numbers.
+        "style": 9,
+        "observer": { "index": 0, "version": 2 },
+    });
+    expect_eq!(drawing.value_to_json(&value).unwrap(), json);
+    expect_eq!(drawing.value_from_json(&json).unwrap(), value);
+
+    // Enum values may be given by number too.
+    let mut by_number = json.clone();
+    by_number["color"] = json!(5);
+    expect_eq!(drawing.value_from_json(&by_number).unwrap(), value);
+
+    // The JSON must match the type exactly.
+    let mistakes = [
+        ("color", json!("kPurple")),
+        ("name", json!(7)),
+        ("shapes", json!([{ "point": { "x": 1, "y": 2 }, "polygon": [] }])),
+        ("shapes", json!([{ "point": { "x": 1, "y": 2, "z": 3 } }])),
+        ("shapes", json!([{ "point": { "x": 1 } }])),
+        ("shapes", json!([{ "point": { "x": 1, "y": 2147483648i64 } }])),
+        ("observer", json!({ "index": -1, "version": 0 })),
+    ];
+    for (field, mistake) in mistakes {
+        let mut wrong = json.clone();
+        wrong[field] = mistake;
+        expect_true!(drawing.value_from_json(&wrong).is_err(), "{}", wrong);
+    }
+    let mut extra_field = json.clone();
+    extra_field["size"] = json!(1);
+    expect_true!(drawing.value_from_json(&extra_field).is_err());
+    expect_true!(drawing.value_to_json(&MojomValue::Int32(1)).is_err());
+
+    // Without names, fields are keyed by ordinal.
+    let ty = MojomType::Struct {
+        fields: vec![
+            MojomType::UInt8,
+            MojomType::Array { element_type: Box::new(MojomType::Int64), num_elements: Some(2) },
+        ],
+        min_versions: vec![],
+    };
+    let value = MojomValue::Struct(vec![
+        MojomValue::UInt8(255),
+        MojomValue::Array(vec![MojomValue::Int64(i64::MIN), MojomValue::Int64(i64::MAX)]),
+    ]);
+    let json = json!({ "0": 255, "1": [i64::MIN, i64::MAX] });
+    expect_eq!(value_to_json(&value, &ty, &TypeDescriptor::Unnamed).unwrap(), json);
+    expect_eq!(value_from_json(&json, &ty, &TypeDescriptor::Unnamed).unwrap(), value);
+    let wrong = json!({ "0": 256, "1": [0, 0] });
+    expect_true!(value_from_json(&wrong, &ty, &TypeDescriptor::Unnamed).is_err());
+    let wrong = json!({ "0": 0, "1": [0] });
+    expect_true!(value_from_json(&wrong, &ty, &TypeDescriptor::Unnamed).is_err());
+
+    // Whole messages convert losslessly too.
+    let header = MessageHeader {
+        name: 0,
+        flags: MESSAGE_FLAG_IS_RESPONSE,
+        request_id: 4,
+        ..Default::default()
+    };
+    let response_params = module.interface("Canvas").unwrap().method(0).unwrap();
+    let response_params = response_params.response_params().unwrap().unwrap();
+    let message = encode_message(
+        &header,
+        &response_params.mojom_type,
+        &[MojomValue::Bool(false), MojomValue::Null],
+    );
+    let json = json!({
+        "header": {
+            "interface_id": 0,
+            "name": 0,
+            "flags": MESSAGE_FLAG_IS_RESPONSE,
+            "trace_nonce": 0,
+            "request_id": 4,
+        },
+        "method": "Draw",
+        "payload": { "success": false, "bounds": null },
+    });
+    expect_eq!(module.message_to_json("Canvas", &message).unwrap(), json);
+    expect_eq!(module.message_from_json("Canvas", &json).unwrap(), message);
+
+    // Hand-written messages can leave out most of the header, and name the
+    // method instead.
+    let json = json!({ "method": "Clear", "payload": {} });
+    let message = module.message_from_json("Canvas", &json).unwrap();
+    let decoded = module.decode_message("Canvas", &message).unwrap();
+    expect_eq!(decoded.header, MessageHeader { name: 5, ..Default::default() });
+    let json = json!({ "header": { "name": 0 }, "method": "Clear", "payload": {} });
+    expect_true!(module.message_from_json("Canvas", &json).is_err());
+    let json = json!({ "method": "Erase", "payload": {} });
+    expect_true!(module.message_from_json("Canvas", &json).is_err());
+}
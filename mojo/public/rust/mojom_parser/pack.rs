@@ -12,14 +12,16 @@
 //! At a high level, the algorithm is as follows: put the fields in declaration
 //! order, and then move fields backwards into empty padding bytes whenever
 //! possible. Note that nested structures (structs and arrays) are represented
-//! as 8-byte pointers.
+//! as 8-byte pointers, while unions are stored inline in 16 bytes.
 
-// FOR_RELEASE: There are additional complications for nullable types (which
-// get split into two fields), and booleans (which get packed together as
-// bitfields.)
+// Booleans are packed together as bitfields, and nullable value kinds (e.g.
+// `int32?`) are split into two fields: a has-value flag, which is packed like
+// a bool, followed by the value itself.
 
 use crate::ast::*;
 
+use std::borrow::Cow;
+
 /// Return the number of bytes we need to skip to reach the given alignment.
 fn bytes_to_align(current_offset: usize, required_alignment: usize) -> usize {
     return (required_alignment - (current_offset % required_alignment)) % required_alignment;
@@ -28,9 +30,7 @@ fn bytes_to_align(current_offset: usize, required_alignment: usize) -> usize {
 /// Represents a single field which has been packed into wire format, with enough
 /// information to both construct the binary representation and map back to the
 /// original type.
-struct PackedField<'a> {
-    /// The name of the field in the original struct definition.
-    name: &'a str,
+struct PackedField {
     /// The type of the field, which has been recursively packed.
     ty: MojomWireType,
     /// Number of bytes from the beginning of the struct to the start of the field.
@@ -39,22 +39,24 @@ struct PackedField<'a> {
     end_offset: usize,
 }
 
-impl<'a> PackedField<'a> {
+impl PackedField {
     /// Create a new PackedField given the original field's information and its location
-    fn new(name: &'a str, ty: MojomWireType, start_offset: usize) -> PackedField<'a> {
-        PackedField { start_offset: start_offset, end_offset: start_offset + ty.size(), name, ty }
+    fn new(ty: MojomWireType, start_offset: usize) -> PackedField {
+        PackedField { start_offset: start_offset, end_offset: start_offset + ty.size(), ty }
     }
 }
 
 /// Checks if the packed field is a bitfield with an empty slot, and inserts
-/// the ordinal if so.
+/// the ordinal if so. `has_value` is set if the bit is the has-value flag of a
+/// nullable value field rather than a bool.
 ///
 /// Returns true if the bool was successfully packed, and false otherwise.
-fn try_pack_bool(ordinal: Ordinal, packed_field: &mut MojomWireType) -> bool {
+fn try_pack_bool(ordinal: Ordinal, has_value: bool, packed_field: &mut MojomWireType) -> bool {
     match packed_field {
-        MojomWireType::Bitfield { ordinals } => {
+        MojomWireType::Bitfield { ordinals, has_value_bits } => {
             if let Some(first_empty_slot) = ordinals.into_iter().position(|opt| opt.is_none()) {
                 ordinals[first_empty_slot] = Some(ordinal);
+                *has_value_bits |= (has_value as u8) << first_empty_slot;
                 return true;
             } else {
                 return false;
@@ -66,36 +68,58 @@ fn try_pack_bool(ordinal: Ordinal, packed_field: &mut MojomWireType) -> bool {
     }
 }
 
+/// Split the fields of a struct into the fields that get packed, as their
+/// ordinal, type, and whether they're the has-value flag of a nullable value
+/// field. The flag comes immediately before the value, like in pack.py.
+fn struct_packing_fields(fields: &[MojomType]) -> Vec<(Ordinal, &MojomType, bool)> {
+    let mut ret = vec![];
+    for (ordinal, field_ty) in fields.iter().enumerate() {
+        match field_ty {
+            MojomType::Nullable(inner_type) if inner_type.is_value_kind() => {
+                ret.push((ordinal, &MojomType::Bool, true));
+                ret.push((ordinal, &**inner_type, false));
+            }
+            _ => ret.push((ordinal, field_ty, false)),
+        }
+    }
+    ret
+}
+
 /// Transform the fields of a Mojom struct into their packed representation.
 /// This uses the basic algorithm from mojo/public/tools/mojom/mojom/generate/pack.py
-fn pack_struct(fields: &Vec<(String, MojomType)>) -> Vec<(String, MojomWireType)> {
+fn pack_struct(fields: &[MojomType]) -> Vec<MojomWireType> {
     let mut packed_fields: Vec<PackedField> = vec![];
     let mut total_length = 0;
     // For each field, see if we can fit it between two existing packed fields.
     // If not, put it at the end.
-    'outer: for (ordinal, (field_name, field_ty)) in fields.iter().enumerate() {
+    'outer: for (ordinal, field_ty, has_value) in struct_packing_fields(fields) {
         let is_bool = match field_ty {
             MojomType::Bool => true,
             _ => false,
         };
         // Recursively pack any structs this field contains
-        let field_ty = pack_mojom_type(field_ty, ordinal);
+        let field_ty = match pack_mojom_type(field_ty, ordinal) {
+            MojomWireType::Bitfield { ordinals, .. } => {
+                MojomWireType::Bitfield { ordinals, has_value_bits: has_value as u8 }
+            }
+            field_ty => field_ty,
+        };
         let field_size = field_ty.size();
+        let field_alignment = field_ty.alignment();
         // Try every pair (i-1, i) of adjacent packed fields.
         for i in 1..packed_fields.len() {
             let end_of_last_field = packed_fields[i - 1].end_offset;
             let empty_space = packed_fields[i].start_offset - end_of_last_field;
-            if is_bool && try_pack_bool(ordinal, &mut packed_fields[i - 1].ty) {
+            if is_bool && try_pack_bool(ordinal, has_value, &mut packed_fields[i - 1].ty) {
                 continue 'outer;
             };
             // If we fit, then pack this field here
-            if (field_size + bytes_to_align(end_of_last_field, field_size)) <= empty_space {
+            if (field_size + bytes_to_align(end_of_last_field, field_alignment)) <= empty_space {
                 packed_fields.insert(
                     i,
                     PackedField::new(
-                        field_name,
                         field_ty,
-                        end_of_last_field + bytes_to_align(end_of_last_field, field_size),
+                        end_of_last_field + bytes_to_align(end_of_last_field, field_alignment),
                     ),
                 );
                 continue 'outer;
@@ -106,7 +130,7 @@ fn pack_struct(fields: &Vec<(String, MojomType)>) -> Vec<(String, MojomWireType)
         // so do that now
         if is_bool
             && let Some(last_packed_field) = packed_fields.last_mut()
-            && try_pack_bool(ordinal, &mut last_packed_field.ty)
+            && try_pack_bool(ordinal, has_value, &mut last_packed_field.ty)
         {
             continue;
         }
@@ -114,9 +138,8 @@ fn pack_struct(fields: &Vec<(String, MojomType)>) -> Vec<(String, MojomWireType)
         // If we get all the way here then we failed to pack the field anywhere
         // earlier, so add it to the end.
         let packed_field = PackedField::new(
-            field_name,
             field_ty,
-            total_length + bytes_to_align(total_length, field_size),
+            total_length + bytes_to_align(total_length, field_alignment),
         );
         total_length = packed_field.end_offset;
         packed_fields.push(packed_field);
@@ -124,20 +147,36 @@ fn pack_struct(fields: &Vec<(String, MojomType)>) -> Vec<(String, MojomWireType)
 
     // Transform each packed field back into a regular MojomType
     // Also recursively pack each one, to handle nested structs.
-    return packed_fields
-        .into_iter()
-        .map(|packed_field| (packed_field.name.to_string(), packed_field.ty))
-        .collect();
+    return packed_fields.into_iter().map(|packed_field| packed_field.ty).collect();
+}
+
+/// Pack the fields of a union. Each field is stored in the union's 8-byte
+/// value, so fields which are themselves unions become pointers.
+fn pack_union_variants(variants: &[MojomType]) -> Vec<MojomWireType> {
+    variants
+        .iter()
+        .enumerate()
+        .map(|(tag, variant)| match pack_mojom_type(variant, tag) {
+            MojomWireType::Union { ordinal, variants, nullable } => MojomWireType::Pointer {
+                ordinal,
+                nested_data_type: PackedStructuredType::Union { variants },
+                nullable,
+            },
+            wire_type => wire_type,
+        })
+        .collect()
 }
 
 /// Given a MojomType, return its packed representation.
 pub fn pack_mojom_type(ty: &MojomType, ordinal: Ordinal) -> MojomWireType {
     match ty {
-        MojomType::Struct { fields } => MojomWireType::Pointer {
+        MojomType::Struct { fields, min_versions } => MojomWireType::Pointer {
             ordinal,
             nested_data_type: PackedStructuredType::Struct {
                 packed_field_types: pack_struct(fields),
+                min_versions: min_versions.clone(),
             },
+            nullable: false,
         },
         MojomType::Array { element_type, num_elements } => {
             let array_type = match num_elements {
@@ -151,6 +190,7 @@ pub fn pack_mojom_type(ty: &MojomType, ordinal: Ordinal) -> MojomWireType {
                     element_type: Box::new(pack_mojom_type(element_type, 0)),
                     array_type,
                 },
+                nullable: false,
             }
         }
         // Strings are packed as byte arrays
@@ -160,6 +200,53 @@ pub fn pack_mojom_type(ty: &MojomType, ordinal: Ordinal) -> MojomWireType {
                 element_type: Box::new(pack_mojom_type(&MojomType::UInt8, 0)),
                 array_type: PackedArrayType::String,
             },
+            nullable: false,
+        },
+        MojomType::Union { variants } => MojomWireType::Union {
+            ordinal,
+            variants: pack_union_variants(variants),
+            nullable: false,
+        },
+        // A nullable pointer or union is packed the same way as a
+        // non-nullable one. Nullable value kinds are split into two fields by
+        // `pack_struct`, so they can't appear anywhere else.
+        MojomType::Nullable(inner_type) => match pack_mojom_type(inner_type, ordinal) {
+            MojomWireType::Pointer { ordinal, nested_data_type, .. } => {
+                MojomWireType::Pointer { ordinal, nested_data_type, nullable: true }
+            }
+            MojomWireType::Union { ordinal, variants, .. } => {
+                MojomWireType::Union { ordinal, variants, nullable: true }
+            }
+            MojomWireType::Leaf {
+                ordinal,
+                leaf_type: PackedLeafType::AssociatedEndpoint { .. },
+            } => MojomWireType::Leaf {
+                ordinal,
+                leaf_type: PackedLeafType::AssociatedEndpoint { nullable: true },
+            },
+            MojomWireType::Leaf {
+                ordinal,
+                leaf_type: PackedLeafType::AssociatedInterface { .. },
+            } => MojomWireType::Leaf {
+                ordinal,
+                leaf_type: PackedLeafType::AssociatedInterface { nullable: true },
+            },
+            MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::Interface { .. } } => {
+                MojomWireType::Leaf {
+                    ordinal,
+                    leaf_type: PackedLeafType::Interface { nullable: true },
+                }
+            }
+            MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::Handle { .. } } => {
+                MojomWireType::Leaf {
+                    ordinal,
+                    leaf_type: PackedLeafType::Handle { nullable: true },
+                }
+            }
+            _ => panic!(
+                "Nullable value kinds are only supported in structs, but got {:?}",
+                inner_type
+            ),
         },
         MojomType::Int8 => MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::Int8 },
         MojomType::Int16 => MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::Int16 },
@@ -169,8 +256,50 @@ pub fn pack_mojom_type(ty: &MojomType, ordinal: Ordinal) -> MojomWireType {
         MojomType::UInt16 => MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::UInt16 },
         MojomType::UInt32 => MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::UInt32 },
         MojomType::UInt64 => MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::UInt64 },
+        MojomType::Float => MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::Float },
+        MojomType::Double => MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::Double },
+        MojomType::PendingRemote => MojomWireType::Leaf {
+            ordinal,
+            leaf_type: PackedLeafType::Interface { nullable: false },
+        },
+        MojomType::PendingAssociatedRemote => MojomWireType::Leaf {
+            ordinal,
+            leaf_type: PackedLeafType::AssociatedInterface { nullable: false },
+        },
+        MojomType::PendingAssociatedReceiver => MojomWireType::Leaf {
+            ordinal,
+            leaf_type: PackedLeafType::AssociatedEndpoint { nullable: false },
+        },
+        MojomType::Handle => {
+            MojomWireType::Leaf { ordinal, leaf_type: PackedLeafType::Handle { nullable: false } }
+        }
         MojomType::Bool => MojomWireType::Bitfield {
             ordinals: [Some(ordinal), None, None, None, None, None, None, None],
+            has_value_bits: 0,
         },
+        // Packing the definition would never end, so that's left until it's
+        // needed.
+        MojomType::Recursive(recursive_type) => MojomWireType::Pointer {
+            ordinal,
+            nested_data_type: PackedStructuredType::Recursive(*recursive_type),
+            nullable: false,
+        },
+    }
+}
+
+impl PackedStructuredType {
+    /// If this is a `Recursive` type, pack its definition, which is one level
+    /// deep. Otherwise, return the type as is.
+    pub fn resolve(&self) -> Cow<'_, PackedStructuredType> {
+        let PackedStructuredType::Recursive(recursive_type) = self else {
+            return Cow::Borrowed(self);
+        };
+        Cow::Owned(match pack_mojom_type(&recursive_type.mojom_type(), 0) {
+            MojomWireType::Pointer { nested_data_type, .. } => nested_data_type,
+            MojomWireType::Union { variants, .. } => PackedStructuredType::Union { variants },
+            wire_type => {
+                panic!("Recursive types must be encoded as pointers, but got {:?}", wire_type)
+            }
+        })
     }
 }
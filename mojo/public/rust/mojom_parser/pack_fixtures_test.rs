@@ -0,0 +1,329 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Differential tests for the packing algorithms.
+//!
+//! pack.rs and const_pack.rs both re-implement pack.py, so rather than writing
+//! out expected layouts by hand, we compare them against layouts which were
+//! computed by pack.py itself. test_data/pack_fixtures.json holds the layout
+//! of every struct in the *.test-mojom files under mojo/, and is produced by
+//! generate_pack_fixtures.py.
+//!
+//! Each fixture lists the struct's packed fields in ordinal order. Nullable
+//! value fields have already been split into their flag and value fields by
+//! pack.py, so we treat these as two separate fields, which packs the same way.
+
+use rust_gtest_interop::prelude::*;
+
+use mojom_parser::*;
+use serde_json_lenient::Value;
+
+const FIXTURES: &str = include_str!("test_data/pack_fixtures.json");
+
+/// A single packed field, as computed by pack.py.
+struct FixtureField {
+    name: String,
+    kind: String,
+    nullable: bool,
+    min_version: u32,
+    offset: usize,
+    bit: u8,
+}
+
+/// The layout of a single struct, as computed by pack.py.
+struct Fixture {
+    /// The file and name of the struct, for error messages.
+    name: String,
+    /// Packed fields, in ordinal order.
+    fields: Vec<FixtureField>,
+    /// Indices of the payload bytes which aren't part of any field.
+    padding: Vec<usize>,
+    /// (version, num_bytes) for each version of the struct.
+    versions: Vec<(u32, usize)>,
+}
+
+fn as_usize(value: &Value) -> usize {
+    value.as_u64().expect("Fixture values must be unsigned integers") as usize
+}
+
+fn load_fixtures() -> Vec<Fixture> {
+    let json: Value = serde_json_lenient::from_str(FIXTURES).expect("Fixtures must be valid JSON");
+    let fixtures = json.as_array().expect("Fixtures must be a list");
+    fixtures
+        .iter()
+        .map(|fixture| Fixture {
+            name: format!(
+                "{}:{}",
+                fixture["file"].as_str().unwrap(),
+                fixture["struct"].as_str().unwrap()
+            ),
+            fields: fixture["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|field| FixtureField {
+                    name: field["name"].as_str().unwrap().to_string(),
+                    kind: field["kind"].as_str().unwrap().to_string(),
+                    nullable: field["nullable"].as_bool().unwrap(),
+                    min_version: as_usize(&field["min_version"]) as u32,
+                    offset: as_usize(&field["offset"]),
+                    bit: as_usize(&field["bit"]) as u8,
+                })
+                .collect(),
+            padding: fixture["padding"].as_array().unwrap().iter().map(as_usize).collect(),
+            versions: fixture["versions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|version| {
+                    (as_usize(&version["version"]) as u32, as_usize(&version["num_bytes"]))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Map the kinds exported by generate_pack_fixtures.py to `PackingKind`s.
+/// Kinds which we don't otherwise support are replaced by a kind of the same
+/// size and alignment.
+fn packing_kind(kind: &str) -> PackingKind {
+    match kind {
+        "bool" => PackingKind::Bool,
+        "int8" => PackingKind::Int8,
+        "uint8" => PackingKind::UInt8,
+        "int16" => PackingKind::Int16,
+        "uint16" => PackingKind::UInt16,
+        "int32" | "enum" => PackingKind::Int32,
+        "uint32" => PackingKind::UInt32,
+        "float" => PackingKind::Float,
+        "int64" => PackingKind::Int64,
+        "uint64" => PackingKind::UInt64,
+        "double" => PackingKind::Double,
+        "handle" => PackingKind::Handle,
+        "interface" => PackingKind::Interface,
+        "union" => PackingKind::Union,
+        "pointer" => PackingKind::Pointer,
+        _ => panic!("Unknown kind in fixtures: {kind}"),
+    }
+}
+
+/// The same as `packing_kind`, for the runtime packer.
+fn mojom_type(kind: &str) -> MojomType {
+    match kind {
+        "bool" => MojomType::Bool,
+        "int8" => MojomType::Int8,
+        "uint8" => MojomType::UInt8,
+        "int16" => MojomType::Int16,
+        "uint16" => MojomType::UInt16,
+        "int32" | "enum" => MojomType::Int32,
+        "uint32" => MojomType::UInt32,
+        "handle" => MojomType::Handle,
+        "float" => MojomType::Float,
+        "int64" => MojomType::Int64,
+        "uint64" => MojomType::UInt64,
+        "double" => MojomType::Double,
+        "pointer" => MojomType::String,
+        "union" => MojomType::Union { variants: vec![MojomType::Int32] },
+        "interface" => MojomType::PendingRemote,
+        _ => panic!("Unknown kind in fixtures: {kind}"),
+    }
+}
+
+/// Return the indices of the bytes in a payload of `payload_size` bytes which
+/// aren't covered by any of the given (offset, size) ranges.
+fn padding_bytes(payload_size: usize, ranges: impl Iterator<Item = (usize, usize)>) -> Vec<usize> {
+    let mut is_padding = vec![true; payload_size];
+    for (offset, size) in ranges {
+        is_padding[offset..offset + size].fill(false);
+    }
+    (0..payload_size).filter(|idx| is_padding[*idx]).collect()
+}
+
+fn round_up_to_8(n: usize) -> usize {
+    n.div_ceil(8) * 8
+}
+
+/// Compare `pack_struct_dynamic` against a fixture, returning a description of
+/// each difference.
+fn check_const_pack(fixture: &Fixture) -> Vec<String> {
+    let fields = fixture
+        .fields
+        .iter()
+        .map(|field| {
+            ConstField::new(packing_kind(&field.kind))
+                .with_min_version(field.min_version)
+                .with_nullable(field.nullable)
+        })
+        .collect();
+    let packing = match pack_struct_dynamic(fields) {
+        Ok(packing) => packing,
+        Err(err) => return vec![format!("{}: const pack failed: {err}", fixture.name)],
+    };
+
+    let mut errors = vec![];
+    for (ordinal, field) in fixture.fields.iter().enumerate() {
+        let expected_bit = (field.kind == "bool").then_some(field.bit);
+        let actual = (packing.offset_in_struct(ordinal) - 8, packing.bit(ordinal));
+        if actual != (field.offset, expected_bit) {
+            errors.push(format!(
+                "{}: const pack put {} at {actual:?}, expected {:?}",
+                fixture.name,
+                field.name,
+                (field.offset, expected_bit)
+            ));
+        }
+    }
+
+    let payload_size = packing.size() - 8;
+    let padding = padding_bytes(
+        payload_size,
+        (0..fixture.fields.len())
+            .map(|ordinal| (packing.offsets[ordinal], packing.fields[ordinal].kind.size())),
+    );
+    if padding != fixture.padding {
+        errors.push(format!(
+            "{}: const pack has padding {padding:?}, expected {:?}",
+            fixture.name, fixture.padding
+        ));
+    }
+
+    for (version, num_bytes) in &fixture.versions {
+        if packing.size_for_version(*version) != *num_bytes {
+            errors.push(format!(
+                "{}: const pack has size {} for version {version}, expected {num_bytes}",
+                fixture.name,
+                packing.size_for_version(*version)
+            ));
+        }
+    }
+    errors
+}
+
+/// The fields of the struct described by a fixture, as the runtime packer
+/// sees them. pack.py splits each nullable value field into a `<name>_$flag`
+/// bool and a `<name>_$value`, which are combined into a single nullable field.
+struct RuntimeFields {
+    types: Vec<MojomType>,
+    min_versions: Vec<u32>,
+    /// The indices in `fixture.fields` of each field's value, and of its
+    /// has-value flag if it has one.
+    fixture_indices: Vec<(usize, Option<usize>)>,
+}
+
+fn runtime_fields(fixture: &Fixture) -> RuntimeFields {
+    let mut ret = RuntimeFields { types: vec![], min_versions: vec![], fixture_indices: vec![] };
+    let mut iter = fixture.fields.iter().enumerate();
+    while let Some((idx, field)) = iter.next() {
+        if field.name.ends_with("_$flag") {
+            let (value_idx, value_field) = iter.next().expect("Flags are followed by values");
+            ret.types.push(MojomType::Nullable(Box::new(mojom_type(&value_field.kind))));
+            ret.fixture_indices.push((value_idx, Some(idx)));
+        } else {
+            ret.types.push(mojom_type(&field.kind));
+            ret.fixture_indices.push((idx, None));
+        }
+        ret.min_versions.push(field.min_version);
+    }
+    ret
+}
+
+/// Compare `pack_mojom_type` against a fixture, returning a description of
+/// each difference.
+fn check_runtime_pack(fixture: &Fixture) -> Vec<String> {
+    let RuntimeFields { types: fields, min_versions, fixture_indices } = runtime_fields(fixture);
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = pack_mojom_type(&MojomType::Struct { fields, min_versions: min_versions.clone() }, 0)
+    else {
+        unreachable!("Structs always pack to pointers")
+    };
+
+    // Walk the wire types to find where each field ended up, the same way the
+    // parser does.
+    let mut locations: Vec<Option<(usize, Option<u8>)>> = vec![None; fixture.fields.len()];
+    let mut ranges = vec![];
+    let mut offset: usize = 0;
+    for wire_type in &packed_field_types {
+        offset = offset.next_multiple_of(wire_type.alignment());
+        match wire_type {
+            MojomWireType::Leaf { ordinal, .. }
+            | MojomWireType::Pointer { ordinal, .. }
+            | MojomWireType::Union { ordinal, .. } => {
+                locations[fixture_indices[*ordinal].0] = Some((offset, None));
+            }
+            MojomWireType::Bitfield { ordinals, has_value_bits } => {
+                for (bit, ordinal) in ordinals.iter().enumerate() {
+                    if let Some(ordinal) = ordinal {
+                        let (value_idx, flag_idx) = fixture_indices[*ordinal];
+                        let idx = if (has_value_bits >> bit) & 1 == 1 {
+                            flag_idx.expect("Only nullable value fields have flags")
+                        } else {
+                            value_idx
+                        };
+                        locations[idx] = Some((offset, Some(bit as u8)));
+                    }
+                }
+            }
+        }
+        ranges.push((offset, wire_type.size()));
+        offset += wire_type.size();
+    }
+
+    let mut errors = vec![];
+    for (field, location) in fixture.fields.iter().zip(locations) {
+        let expected_bit = (field.kind == "bool").then_some(field.bit);
+        if location != Some((field.offset, expected_bit)) {
+            errors.push(format!(
+                "{}: runtime pack put {} at {location:?}, expected {:?}",
+                fixture.name,
+                field.name,
+                (field.offset, expected_bit)
+            ));
+        }
+    }
+
+    let payload_size = round_up_to_8(offset);
+    let padding = padding_bytes(payload_size, ranges.into_iter());
+    if padding != fixture.padding {
+        errors.push(format!(
+            "{}: runtime pack has padding {padding:?}, expected {:?}",
+            fixture.name, fixture.padding
+        ));
+    }
+
+    // The packed fields are always laid out as the latest version.
+    let (_, latest_num_bytes) = fixture.versions.last().unwrap();
+    if payload_size + 8 != *latest_num_bytes {
+        errors.push(format!(
+            "{}: runtime pack has size {}, expected {latest_num_bytes}",
+            fixture.name,
+            payload_size + 8
+        ));
+    }
+
+    for (version, num_bytes) in &fixture.versions {
+        let size = struct_version_size(&packed_field_types, &min_versions, *version);
+        if size != *num_bytes {
+            errors.push(format!(
+                "{}: runtime pack has size {size} for version {version}, expected {num_bytes}",
+                fixture.name
+            ));
+        }
+    }
+    errors
+}
+
+#[gtest(MojomParserTestSuit, PackFixturesTest)]
+fn test_pack_fixtures() {
+    let fixtures = load_fixtures();
+    // Make sure we actually loaded something.
+    expect_gt!(fixtures.len(), 100);
+
+    let errors: Vec<String> = fixtures.iter().flat_map(check_const_pack).collect();
+    expect_eq!(errors, Vec::<String>::new());
+
+    let errors: Vec<String> = fixtures.iter().flat_map(check_runtime_pack).collect();
+    expect_eq!(errors, Vec::<String>::new());
+}
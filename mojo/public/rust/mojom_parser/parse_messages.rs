@@ -8,69 +8,199 @@
 //! (in versions 2+) by a footer of associated interface IDs.
 
 use crate::ast::*;
+use crate::pack::pack_mojom_type;
 use crate::parse_primitives::ParserData;
-use anyhow::{bail, Result};
+use crate::parse_values::{parse_array, parse_struct};
+use anyhow::{bail, Context, Result};
 
-/// Parse the header of a Mojom message.
-/// The format is described in mojo/public/cpp/bindings/lib/bindings_internal.h
-// FOR_RELEASE: Actually return something instead of ignoring it.
+/// Set if the sender of a request expects a response to it.
+pub const MESSAGE_FLAG_EXPECTS_RESPONSE: u32 = 1 << 0;
+/// Set if a message is the response to a request.
+pub const MESSAGE_FLAG_IS_RESPONSE: u32 = 1 << 1;
+/// Set if the sender of a request is blocking until it gets the response.
+pub const MESSAGE_FLAG_IS_SYNC: u32 = 1 << 2;
+/// Set if the sender of a sync request doesn't allow other sync messages to be
+/// dispatched while it waits.
+pub const MESSAGE_FLAG_NO_INTERRUPT: u32 = 1 << 3;
+/// Set if a message should be dispatched ahead of non-urgent messages.
+pub const MESSAGE_FLAG_IS_URGENT: u32 = 1 << 4;
+
+/// The header of a Mojom message.
+/// The format is described in mojo/public/cpp/bindings/lib/message_internal.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MessageHeader {
+    /// Which of the interfaces sharing a message pipe the message is for.
+    pub interface_id: u32,
+    /// The ordinal of the method that the message calls or responds to.
+    pub name: u32,
+    /// A combination of the `MESSAGE_FLAG_*` constants.
+    pub flags: u32,
+    pub trace_nonce: u32,
+    /// Matches responses to their requests. Only meaningful if
+    /// `MESSAGE_FLAG_EXPECTS_RESPONSE` or `MESSAGE_FLAG_IS_RESPONSE` is set.
+    pub request_id: u64,
+}
+
+impl MessageHeader {
+    pub fn expects_response(&self) -> bool {
+        self.flags & MESSAGE_FLAG_EXPECTS_RESPONSE != 0
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.flags & MESSAGE_FLAG_IS_RESPONSE != 0
+    }
+
+    pub fn is_sync(&self) -> bool {
+        self.flags & MESSAGE_FLAG_IS_SYNC != 0
+    }
+}
+
+/// Where the payload of a message lies, relative to the start of the message.
+struct PayloadBounds {
+    start: usize,
+    /// The end of the payload, if the message says where it is.
+    end: Option<usize>,
+}
+
+/// Check that the size recorded in a message header matches the number of bytes
+/// that its version requires.
+fn check_header_size(size_in_bytes: usize, bytes_parsed: usize) -> Result<()> {
+    if size_in_bytes != bytes_parsed {
+        bail!(
+            "Message header claimed to have {} bytes, but its version has {} bytes",
+            size_in_bytes,
+            bytes_parsed
+        )
+    }
+    Ok(())
+}
+
+/// Return the offset that a pointer at `location` points to, or None if it is
+/// null.
+fn pointer_target(location: usize, pointer: u64) -> Result<Option<usize>> {
+    if pointer == 0 {
+        return Ok(None);
+    }
+    let offset: usize = pointer.try_into()?;
+    location.checked_add(offset).map(Some).context("Message header pointer overflowed")
+}
+
+/// Parse the header of a Mojom message, and find where its payload is.
 // FOR_RELEASE: I think the header is literally treated as a mojom struct.
 // In the future, we should parse it by defining the appropriate mojom type and
 // parsing it. However, that requires us to support versions in general. For
 // now, just handle the different possible header versions manually.
-fn parse_header(data: &mut ParserData) -> Result<()> {
+fn parse_header_and_payload_bounds(
+    data: &mut ParserData,
+) -> Result<(MessageHeader, PayloadBounds)> {
     use crate::parse_primitives::*;
     let size_in_bytes: usize = parse_u32(data)?.try_into()?;
     let version_number = parse_u32(data)?;
-    let _interface_id = parse_u32(data)?;
-    let _msg_name = parse_u32(data)?;
-    let _flags = parse_u32(data)?;
-    let _trace_nonce = parse_u32(data)?;
+    let mut header = MessageHeader {
+        interface_id: parse_u32(data)?,
+        name: parse_u32(data)?,
+        flags: parse_u32(data)?,
+        trace_nonce: parse_u32(data)?,
+        request_id: 0,
+    };
+    let request_id_flags = MESSAGE_FLAG_EXPECTS_RESPONSE | MESSAGE_FLAG_IS_RESPONSE;
+    if header.flags & request_id_flags == request_id_flags {
+        bail!("A message can't both expect a response and be a response")
+    }
     if version_number == 0 {
-        assert!(size_in_bytes == data.bytes_parsed());
-        return Ok(());
+        if header.flags & request_id_flags != 0 {
+            bail!("Version 0 message headers have no request ID, but the flags need one")
+        }
+        check_header_size(size_in_bytes, data.bytes_parsed())?;
+        return Ok((header, PayloadBounds { start: data.bytes_parsed(), end: None }));
     }
 
-    let _request_id = parse_u64(data)?;
+    header.request_id = parse_u64(data)?;
     if version_number == 1 {
-        assert!(size_in_bytes == data.bytes_parsed());
-        return Ok(());
+        check_header_size(size_in_bytes, data.bytes_parsed())?;
+        return Ok((header, PayloadBounds { start: data.bytes_parsed(), end: None }));
     }
 
-    let _payload_ptr = parse_u64(data)?;
-    let _interface_ids_ptr = parse_u64(data)?;
-    // FOR_RELEASE: validate the payload pointer somehow?
-    if version_number == 2 {
-        assert!(size_in_bytes == data.bytes_parsed());
-        return Ok(());
+    let payload_ptr_location = data.bytes_parsed();
+    let payload_ptr = parse_u64(data)?;
+    let interface_ids_ptr_location = data.bytes_parsed();
+    let interface_ids_ptr = parse_u64(data)?;
+    if version_number >= 3 {
+        let _creation_timeticks_us = parse_u64(data)?;
     }
+    if version_number > 3 {
+        // Later versions may add fields that we don't know about, so skip
+        // them.
+        let unknown_bytes = size_in_bytes.checked_sub(data.bytes_parsed()).with_context(|| {
+            format!("Message header claimed to have only {} bytes", size_in_bytes)
+        })?;
+        parse_padding(data, unknown_bytes)?;
+    }
+    check_header_size(size_in_bytes, data.bytes_parsed())?;
 
-    let _creation_timeticks_us = parse_u64(data)?;
-
-    if version_number == 3 {
-        assert!(size_in_bytes == data.bytes_parsed());
-        return Ok(());
+    // FOR_RELEASE: The payload pointer may legally point past some padding,
+    // but every sender we know of puts the payload right after the header.
+    let payload_start = pointer_target(payload_ptr_location, payload_ptr)?;
+    if payload_start != Some(data.bytes_parsed()) {
+        bail!("The message payload must directly follow the header")
     }
+    // The associated interface IDs follow the payload.
+    let payload_end = pointer_target(interface_ids_ptr_location, interface_ids_ptr)?;
+    Ok((header, PayloadBounds { start: data.bytes_parsed(), end: payload_end }))
+}
+
+/// Split a Mojom message into its header and its (still encoded) payload.
+pub fn parse_message_header(data: &[u8]) -> Result<(MessageHeader, &[u8])> {
+    let (header, bounds) = parse_header_and_payload_bounds(&mut ParserData::new(data))?;
+    let end = bounds.end.unwrap_or(data.len());
+    let payload = data
+        .get(bounds.start..end)
+        .with_context(|| format!("Message payload ends at {}, after the message", end))?;
+    Ok((header, payload))
+}
 
-    bail!("Bad version number")
+/// Split a Mojom message into its header, its (still encoded) payload, and
+/// the IDs of the associated interfaces that the payload refers to by index.
+/// Only version 2+ headers can have interface IDs.
+pub fn parse_message_parts(data: &[u8]) -> Result<(MessageHeader, &[u8], Vec<u32>)> {
+    let (header, bounds) = parse_header_and_payload_bounds(&mut ParserData::new(data))?;
+    let Some(end) = bounds.end else {
+        return Ok((header, &data[bounds.start..], vec![]));
+    };
+    let payload = data
+        .get(bounds.start..end)
+        .with_context(|| format!("Message payload ends at {}, after the message", end))?;
+    let mut ids_data = ParserData::new(&data[end..]);
+    let element_type = MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::UInt32 };
+    let ids = match parse_array(&mut ids_data, &element_type, &PackedArrayType::UnsizedArray)
+        .context("In the message's interface IDs")?
+    {
+        MojomValue::Array(ids) => ids,
+        value => bail!("Expected an array of interface IDs, but got {:?}", value),
+    };
+    let interface_ids = ids
+        .into_iter()
+        .map(|id| match id {
+            MojomValue::UInt32(id) => Ok(id),
+            value => bail!("Expected an interface ID, but got {:?}", value),
+        })
+        .collect::<Result<_>>()?;
+    Ok((header, payload, interface_ids))
 }
 
 /// Parse an entire mojom message, given the format of the encoded data
-// FOR_RELEASE: We'll need to handle associated interface IDs after the message,
-// even if we just ignore them. We'll also need to take in more information
-// about the message, so we know e.g. the possible message IDs that can appear
-// in the header.
+// FOR_RELEASE: We'll need to take in more information about the message, so we
+// know e.g. the possible message IDs that can appear in the header.
 pub fn parse_message(data_slice: &[u8], ty: &MojomWireType) -> Result<MojomValue> {
-    let mut data = ParserData::new(data_slice);
-    let _ = parse_header(&mut data)?;
+    let (_, payload) = parse_message_header(data_slice)?;
+    let mut data = ParserData::new(payload);
     match ty {
         MojomWireType::Pointer {
-            nested_data_type: PackedStructuredType::Struct { packed_field_types },
+            nested_data_type: PackedStructuredType::Struct { packed_field_types, min_versions },
             ..
         } => {
-            let ret = crate::parse_values::parse_struct(&mut data, packed_field_types)?;
+            let ret = parse_struct(&mut data, packed_field_types, min_versions)?;
             if data.remaining_bytes() != 0 {
-                // We don't support the interface ID struct yet
                 bail!("There were {} trailing bytes in the message", data.remaining_bytes())
             } else {
                 Ok(MojomValue::Struct(ret))
@@ -79,3 +209,21 @@ pub fn parse_message(data_slice: &[u8], ty: &MojomWireType) -> Result<MojomValue
         _ => bail!("All message bodies are structs"),
     }
 }
+
+/// Parse a message payload, which must be a struct of type `ty` that fills
+/// all of `payload`.
+pub fn parse_struct_payload(payload: &[u8], ty: &MojomType) -> Result<MojomValue> {
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, min_versions },
+        ..
+    } = pack_mojom_type(ty, 0)
+    else {
+        bail!("Messages can only be decoded as structs")
+    };
+    let mut data = ParserData::new(payload);
+    let fields = parse_struct(&mut data, &packed_field_types, &min_versions)?;
+    if data.remaining_bytes() != 0 {
+        bail!("There were {} trailing bytes after the struct", data.remaining_bytes())
+    }
+    Ok(MojomValue::Struct(fields))
+}
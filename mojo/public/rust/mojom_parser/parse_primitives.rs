@@ -24,7 +24,7 @@
 
 // FOR_RELEASE: Also, we should standardize our returned error messages.
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use std::any::type_name;
 
 /// The input to a parser
@@ -67,6 +67,21 @@ pub fn parse_padding(data: &mut ParserData, bytes_to_parse: usize) -> Result<()>
     Ok(())
 }
 
+/// Skips the next `bytes_to_parse` bytes, which must exist and all be zero.
+pub fn parse_zero_padding(data: &mut ParserData, bytes_to_parse: usize) -> Result<()> {
+    let padding = data.remaining_bytes.get(..bytes_to_parse).with_context(|| {
+        anyhow!(
+            "Tried to parse {} padding bytes, but only {} remained",
+            bytes_to_parse,
+            data.remaining_bytes.len()
+        )
+    })?;
+    if let Some(idx) = padding.iter().position(|byte| *byte != 0) {
+        bail!("Padding byte at offset {} was not zero", data.bytes_parsed + idx)
+    }
+    parse_padding(data, bytes_to_parse)
+}
+
 // Declares a function named $name, which takes a byte slice (&[u8]), reads the
 // first $size_in_bytes entries, and interprets them as a value of type
 // $target_type, assuming they are in little-endian order.
@@ -102,10 +117,8 @@ declare_primitive_parser!(i16, 2, parse_i16);
 declare_primitive_parser!(i32, 4, parse_i32);
 declare_primitive_parser!(i64, 8, parse_i64);
 
-// FOR_RELEASE: Make sure the float format matches, the mojom doc specifies
-// little-endian IEEE-754 format.
-// Need to ensure
-// (a) that's what actually appears on the wire and
-// (b) that's what rust from_le_bytes looks for.
-// declare_primitive_parser!(f32, 4, parse_f32);
-// declare_primitive_parser!(f64, 8, parse_f64);
+// Floating point numbers are in little-endian IEEE-754 format, which is what
+// the C++ bindings write (they copy the in-memory representation, and mojo
+// only runs on little-endian platforms), and what from_le_bytes reads.
+declare_primitive_parser!(f32, 4, parse_f32);
+declare_primitive_parser!(f64, 8, parse_f64);
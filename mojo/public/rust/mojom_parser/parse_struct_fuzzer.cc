@@ -0,0 +1,14 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+#include <stddef.h>
+#include <stdint.h>
+
+#include "mojo/public/rust/mojom_parser/fuzz_support.rs.h"
+
+// The fuzzing logic lives in fuzz_support.rs; see there for details.
+extern "C" int LLVMFuzzerTestOneInput(const uint8_t* data, size_t size) {
+  mojom_parser::fuzz_parse_struct(rust::Slice<const uint8_t>(data, size));
+  return 0;
+}
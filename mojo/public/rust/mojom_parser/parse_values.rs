@@ -18,6 +18,10 @@
 
 use crate::ast::*;
 use crate::parse_primitives::*;
+use crate::serialized_size::{
+    array_inline_size, field_min_version, struct_latest_version, struct_version_size,
+};
+use crate::validation_context::ValidationContext;
 
 use anyhow::{bail, Context, Result};
 
@@ -32,21 +36,63 @@ fn parse_leaf_element(data: &mut ParserData, ty: &PackedLeafType) -> Result<Mojo
         PackedLeafType::Int16 => Ok(MojomValue::Int16(parse_i16(data)?)),
         PackedLeafType::Int32 => Ok(MojomValue::Int32(parse_i32(data)?)),
         PackedLeafType::Int64 => Ok(MojomValue::Int64(parse_i64(data)?)),
+        PackedLeafType::Float => Ok(MojomValue::Float(parse_f32(data)?)),
+        PackedLeafType::Double => Ok(MojomValue::Double(parse_f64(data)?)),
+        PackedLeafType::AssociatedEndpoint { nullable } => {
+            let index = parse_u32(data)?;
+            parse_endpoint(index, *nullable, MojomValue::PendingAssociatedReceiver { index })
+        }
+        PackedLeafType::AssociatedInterface { nullable } => {
+            let index = parse_u32(data)?;
+            let version = parse_u32(data)?;
+            parse_endpoint(index, *nullable, MojomValue::PendingAssociatedRemote { index, version })
+        }
+        PackedLeafType::Interface { nullable } => {
+            let index = parse_u32(data)?;
+            let version = parse_u32(data)?;
+            match (index, nullable) {
+                (INVALID_HANDLE_INDEX, true) => Ok(MojomValue::Null),
+                (INVALID_HANDLE_INDEX, false) => bail!("Non-nullable remote was null"),
+                (index, _) => Ok(MojomValue::PendingRemote { index, version }),
+            }
+        }
+        PackedLeafType::Handle { nullable } => match (parse_u32(data)?, nullable) {
+            (INVALID_HANDLE_INDEX, true) => Ok(MojomValue::Null),
+            (INVALID_HANDLE_INDEX, false) => bail!("Non-nullable handle was null"),
+            (index, _) => Ok(MojomValue::Handle { index }),
+        },
+    }
+}
+
+/// Return `value`, an associated endpoint with the given index, or Null if
+/// the index says that it's absent.
+fn parse_endpoint(index: u32, nullable: bool, value: MojomValue) -> Result<MojomValue> {
+    match (index, nullable) {
+        (INVALID_ENDPOINT_INDEX, true) => Ok(MojomValue::Null),
+        (INVALID_ENDPOINT_INDEX, false) => bail!("Non-nullable associated endpoint was null"),
+        _ => Ok(value),
+    }
+}
+
+/// Skip `bytes_to_skip` bytes of padding. If `must_be_zero` is false, they
+/// may hold anything, since the data may have a greater version than our
+/// mojom file knows about, with new fields in what we think is padding.
+fn skip_padding(data: &mut ParserData, bytes_to_skip: usize, must_be_zero: bool) -> Result<()> {
+    if must_be_zero {
+        parse_zero_padding(data, bytes_to_skip)
+    } else {
+        parse_padding(data, bytes_to_skip)
     }
 }
 
 /// Parse and ignore the contents of as many bytes as necessary to meet the
-/// given alignment requirement.
-///
-/// Does not validate that the skipped bytes were 0, since it's possible that
-/// we're parsing a message with a version greater than our mojom file knows
-/// about.
-fn skip_to_alignment(data: &mut ParserData, alignment: usize) -> Result<()> {
+/// given alignment requirement. See `skip_padding` for `must_be_zero`.
+fn skip_to_alignment(data: &mut ParserData, alignment: usize, must_be_zero: bool) -> Result<()> {
     let mismatch = data.bytes_parsed() % alignment;
     if mismatch == 0 {
         Ok(())
     } else {
-        parse_padding(data, alignment - mismatch)
+        skip_padding(data, alignment - mismatch, must_be_zero)
     }
 }
 
@@ -60,81 +106,393 @@ fn skip_to_alignment(data: &mut ParserData, alignment: usize) -> Result<()> {
 /// Information about a nested struct/array, which we expect to see later.
 struct NestedDataInfo<'a> {
     ty: &'a PackedStructuredType,
-    field_name: String,
+    /// The ordinal of the field which points to this data, or its index if the
+    /// pointer is an array element.
     ordinal: Ordinal,
     /// The expected location of the nested data, as an offset in bytes from the
-    /// start of the enclosing struct
+    /// start of the enclosing struct or array
     expected_offset: usize,
+    /// If the pointer is the value of a union, the tag of the union's field.
+    /// The nested data is then wrapped in a `MojomValue::Union` once parsed.
+    union_tag: Option<Ordinal>,
 }
 
-/// Return the highest ordinal that appears in a packed struct.
+/// A struct field or array element which may have nested data that we haven't
+/// reached yet.
+enum InlineValue<'a> {
+    /// The value had no nested data (or was null), so it's already parsed.
+    Value(MojomValue),
+    Nested(NestedDataInfo<'a>),
+}
+
+/// Return the number of fields in a packed struct, i.e. one more than the
+/// highest ordinal that appears in it.
 // FOR_RELEASE: It would be nicer to store this in the wire type so we
 // don't need to compute it each time.
-fn get_max_ordinal(fields: &Vec<(String, MojomWireType)>) -> Ordinal {
+fn get_num_fields(fields: &[MojomWireType]) -> usize {
     use std::cmp::max;
-    let mut max_so_far: Ordinal = 0;
-    for (_, wire_type) in fields.into_iter() {
+    let mut num_so_far: usize = 0;
+    for wire_type in fields.iter() {
         match wire_type {
-            MojomWireType::Leaf { ordinal, .. } => max_so_far = max(max_so_far, *ordinal),
-            MojomWireType::Pointer { ordinal, .. } => max_so_far = max(max_so_far, *ordinal),
-            MojomWireType::Bitfield { ordinals } => {
+            MojomWireType::Leaf { ordinal, .. } => num_so_far = max(num_so_far, *ordinal + 1),
+            MojomWireType::Pointer { ordinal, .. } | MojomWireType::Union { ordinal, .. } => {
+                num_so_far = max(num_so_far, *ordinal + 1)
+            }
+            MojomWireType::Bitfield { ordinals, .. } => {
                 let mut iter = ordinals.into_iter();
                 while let Some(Some(ordinal)) = iter.next() {
-                    max_so_far = max(max_so_far, *ordinal)
+                    num_so_far = max(num_so_far, *ordinal + 1)
+                }
+            }
+        }
+    }
+    return num_so_far;
+}
+
+/// Store the value of the field with the given ordinal. Fails if the ordinal is
+/// out of range, or if the packed type had more than one field with the same
+/// ordinal.
+fn set_field(ret: &mut [Option<MojomValue>], ordinal: Ordinal, value: MojomValue) -> Result<()> {
+    let num_fields = ret.len();
+    let slot = ret.get_mut(ordinal).with_context(|| {
+        format!("Field ordinal {} is out of range for a struct with {} fields", ordinal, num_fields)
+    })?;
+    if slot.is_some() {
+        bail!("Packed struct had more than one field with ordinal {}", ordinal);
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+/// Store the value of a struct field, or record its nested data so that we
+/// can parse it once we reach it.
+fn record_field<'a>(
+    ret: &mut [Option<MojomValue>],
+    nested_data_list: &mut Vec<NestedDataInfo<'a>>,
+    ordinal: Ordinal,
+    value: InlineValue<'a>,
+) -> Result<()> {
+    match value {
+        InlineValue::Value(value) => set_field(ret, ordinal, value),
+        InlineValue::Nested(nested_data) => {
+            nested_data_list.push(nested_data);
+            Ok(())
+        }
+    }
+}
+
+/// Parse a pointer to nested data, and return the information we need to
+/// parse the data when we reach it. `initial_bytes_parsed` is where the
+/// enclosing struct or array began. A null pointer is parsed as
+/// `MojomValue::Null`, which is only allowed if the pointer is nullable.
+///
+/// The enclosing object must already have been claimed in `context`, so that
+/// pointers into it (or before it) are rejected.
+fn parse_pointer<'a>(
+    data: &mut ParserData,
+    context: &ValidationContext,
+    initial_bytes_parsed: usize,
+    ty: &'a PackedStructuredType,
+    nullable: bool,
+    ordinal: Ordinal,
+) -> Result<InlineValue<'a>> {
+    let pointer_value = parse_u64(data)?;
+    if pointer_value == 0 {
+        if !nullable {
+            bail!("Non-nullable pointer with ordinal (or index) {} was null", ordinal)
+        }
+        return Ok(InlineValue::Value(MojomValue::Null));
+    }
+    // Pointers are relative to their own location, i.e. not counting the
+    // bytes we just parsed.
+    let pointer_location = data.bytes_parsed() - 8;
+    let target = context.validate_pointer(data, pointer_location, pointer_value)?;
+    let expected_offset = target - initial_bytes_parsed;
+    Ok(InlineValue::Nested(NestedDataInfo { ty, ordinal, expected_offset, union_tag: None }))
+}
+
+/// Parse a union which is stored inline in a struct, array or (if it's
+/// pointed to by another union) by itself. `initial_bytes_parsed` is where
+/// the enclosing object began.
+///
+/// A union is 16 bytes: its size (always 16, or 0 if it's null), its tag, and
+/// an 8-byte slot holding the value of the field with that tag.
+fn parse_inline_union<'a>(
+    data: &mut ParserData,
+    context: &ValidationContext,
+    initial_bytes_parsed: usize,
+    variants: &'a [MojomWireType],
+    nullable: bool,
+    ordinal: Ordinal,
+) -> Result<InlineValue<'a>> {
+    let size_in_bytes = parse_u32(data)?;
+    let tag: Ordinal = parse_u32(data)?.try_into()?;
+    if size_in_bytes == 0 {
+        if !nullable {
+            bail!("Non-nullable union with ordinal (or index) {} was null", ordinal)
+        }
+        parse_padding(data, 8)?;
+        return Ok(InlineValue::Value(MojomValue::Null));
+    }
+    if size_in_bytes != 16 {
+        bail!(
+            "Union with ordinal (or index) {} claimed to have {} bytes, but unions have 16 bytes",
+            ordinal,
+            size_in_bytes
+        )
+    }
+    let variant = variants.get(tag).with_context(|| {
+        format!("Union with ordinal (or index) {} had unknown tag {}", ordinal, tag)
+    })?;
+
+    let value_start = data.bytes_parsed();
+    let value = match variant {
+        MojomWireType::Leaf { leaf_type, .. } => parse_leaf_element(data, leaf_type)?,
+        // A bool is stored in the lowest bit of the first byte.
+        MojomWireType::Bitfield { .. } => MojomValue::Bool(parse_u8(data)? & 1 == 1),
+        MojomWireType::Pointer { nested_data_type, nullable, .. } => {
+            let value = parse_pointer(
+                data,
+                context,
+                initial_bytes_parsed,
+                nested_data_type,
+                *nullable,
+                ordinal,
+            )?;
+            match value {
+                InlineValue::Value(value) => value,
+                InlineValue::Nested(nested_data) => {
+                    return Ok(InlineValue::Nested(NestedDataInfo {
+                        union_tag: Some(tag),
+                        ..nested_data
+                    }));
                 }
             }
         }
+        MojomWireType::Union { .. } => bail!("Unions can't be stored inline in other unions"),
+    };
+    // Skip the rest of the 8-byte slot.
+    parse_padding(data, value_start + 8 - data.bytes_parsed())?;
+    Ok(InlineValue::Value(MojomValue::Union { tag, value: Box::new(value) }))
+}
+
+/// Finish parsing a value which was stored inline in the struct or array
+/// which began at `initial_bytes_parsed`, by parsing its nested data (if it
+/// has any). We must have reached the nested data.
+fn finish_inline_value(
+    data: &mut ParserData,
+    context: &mut ValidationContext,
+    initial_bytes_parsed: usize,
+    value: InlineValue,
+) -> Result<MojomValue> {
+    match value {
+        InlineValue::Value(value) => Ok(value),
+        InlineValue::Nested(nested_data) => {
+            parse_nested_data(data, context, initial_bytes_parsed, &nested_data)
+        }
     }
-    return max_so_far;
 }
 
+/// Skip over any bytes remaining in a struct or array which began at
+/// `initial_bytes_parsed` and claimed to be `size_in_bytes` long, not counting
+/// its nested data. See `skip_padding` for `must_be_zero`.
+fn skip_to_end(
+    data: &mut ParserData,
+    initial_bytes_parsed: usize,
+    size_in_bytes: usize,
+    kind: &str,
+    must_be_zero: bool,
+) -> Result<()> {
+    let bytes_parsed_so_far = data.bytes_parsed() - initial_bytes_parsed;
+    if bytes_parsed_so_far > size_in_bytes {
+        bail!(
+            "{} claimed to have {} bytes, but we somehow parsed {} bytes",
+            kind,
+            size_in_bytes,
+            bytes_parsed_so_far
+        )
+    } else if bytes_parsed_so_far < size_in_bytes {
+        skip_padding(data, size_in_bytes - bytes_parsed_so_far, must_be_zero)?
+    }
+    Ok(())
+}
+
+/// Parse a nested struct or array, which we've reached after the end of its
+/// enclosing struct or array (which began at `initial_bytes_parsed`).
+fn parse_nested_data(
+    data: &mut ParserData,
+    context: &mut ValidationContext,
+    initial_bytes_parsed: usize,
+    nested_data: &NestedDataInfo,
+) -> Result<MojomValue> {
+    // Nested data is required to appear in the same order as the (packed) fields
+    // of the struct. So the expected offset is only useful for validation.
+    let bytes_parsed_so_far = data.bytes_parsed() - initial_bytes_parsed;
+    if nested_data.expected_offset != bytes_parsed_so_far {
+        bail!(
+            "Nested data with ordinal (or index) {} was at {} bytes from the beginning of \
+             its enclosing struct or array, but expected to be at {} bytes",
+            nested_data.ordinal,
+            bytes_parsed_so_far,
+            nested_data.expected_offset
+        );
+    }
+    let value = match &*nested_data.ty.resolve() {
+        PackedStructuredType::Struct { packed_field_types, min_versions } => MojomValue::Struct(
+            parse_struct_with_context(data, context, packed_field_types, min_versions)?,
+        ),
+        PackedStructuredType::Array { element_type, array_type } => {
+            parse_array_with_context(data, context, element_type, array_type)?
+        }
+        PackedStructuredType::Union { variants } => {
+            // The union has no header of its own, so its nested data is
+            // relative to where it begins. A null union is represented by a
+            // null pointer, so the union itself can't be null.
+            context.enter_nested()?;
+            let initial_bytes_parsed = data.bytes_parsed();
+            context.claim_memory(data, initial_bytes_parsed, 16)?;
+            let value =
+                parse_inline_union(data, context, initial_bytes_parsed, variants, false, 0)?;
+            let value = finish_inline_value(data, context, initial_bytes_parsed, value)?;
+            context.leave_nested();
+            value
+        }
+        PackedStructuredType::Recursive(_) => unreachable!("Resolved types aren't recursive"),
+    };
+    Ok(match nested_data.union_tag {
+        Some(tag) => MojomValue::Union { tag, value: Box::new(value) },
+        None => value,
+    })
+}
+
+/// Parse a struct whose fields have the given packed types, and were added
+/// in the given versions (see `PackedStructuredType::Struct`), along with its
+/// nested data, using a fresh `ValidationContext`.
 pub fn parse_struct(
     data: &mut ParserData,
-    fields: &Vec<(String, MojomWireType)>,
-) -> Result<Vec<(String, MojomValue)>> {
+    fields: &[MojomWireType],
+    min_versions: &[u32],
+) -> Result<Vec<MojomValue>> {
+    parse_struct_with_context(data, &mut ValidationContext::new(), fields, min_versions)
+}
+
+/// Like `parse_struct`, but validating with the given `context`, e.g. to use a
+/// different maximum recursion depth.
+///
+/// Like in C++, a struct from a version that we know about must be exactly
+/// the size of that version, and one from a newer version must be at least the
+/// size of the latest one. If it's from an older version, the fields which
+/// were added after it are left out, so fewer fields are returned; they're
+/// always the last ones, since versions only add fields. Unless it's from a
+/// newer version, it can't have any fields that we don't know about, so all
+/// of its padding must be zero.
+pub fn parse_struct_with_context(
+    data: &mut ParserData,
+    context: &mut ValidationContext,
+    fields: &[MojomWireType],
+    min_versions: &[u32],
+) -> Result<Vec<MojomValue>> {
+    context.enter_nested()?;
     let initial_bytes_parsed = data.bytes_parsed();
 
     // Parse the struct header
     let size_in_bytes: usize = parse_u32(data)?.try_into()?;
-    let _version_number = parse_u32(data)?; // We're ignoring versioning for now
+    let version_number = parse_u32(data)?;
+    let is_known_version = version_number <= struct_latest_version(min_versions);
+    let expected_size = struct_version_size(fields, min_versions, version_number);
+    if is_known_version && size_in_bytes != expected_size {
+        bail!(
+            "Struct claimed to have {} bytes, but its header and fields for version {} take {} \
+             bytes",
+            size_in_bytes,
+            version_number,
+            expected_size
+        )
+    }
+    if size_in_bytes < expected_size {
+        bail!(
+            "Struct claimed to have {} bytes, but its header and fields for version {} need at \
+             least {} bytes",
+            size_in_bytes,
+            version_number,
+            expected_size
+        )
+    }
+    context.claim_memory(data, initial_bytes_parsed, size_in_bytes)?;
+    let padding_must_be_zero = is_known_version;
+    let is_present = |ordinal: Ordinal| field_min_version(min_versions, ordinal) <= version_number;
 
     let mut nested_data_list: Vec<NestedDataInfo> = vec![];
+    // Nullable value fields whose has-value flag is unset.
+    let mut null_ordinals: Vec<Ordinal> = vec![];
 
-    // Pre-allocate space for the parsed values, so we can write directly into them by
-    // index. We have to provide dummy values since rust won't allow uninitialized memory.
+    // Fields appear on the wire in packed order, not ordinal order. Collect
+    // them into slots indexed by ordinal, so we can write directly into them.
+    let mut ret: Vec<Option<MojomValue>> = vec![None; get_num_fields(fields)];
+    for mojom_wire_type in fields {
+        // Fields from later versions are either past the end of an older
+        // struct, or in what it packed as padding, so skip them. Their
+        // bitfields always start with a bit from a later version.
+        if !is_present(mojom_wire_type.first_ordinal()) {
+            let offset = (data.bytes_parsed() - initial_bytes_parsed)
+                .next_multiple_of(mojom_wire_type.alignment());
+            if offset + mojom_wire_type.size() <= size_in_bytes {
+                skip_to_alignment(data, mojom_wire_type.alignment(), padding_must_be_zero)?;
+                skip_padding(data, mojom_wire_type.size(), padding_must_be_zero)?;
+            }
+            continue;
+        }
 
-    let mut ret: Vec<(String, MojomValue)> =
-        vec![(String::new(), MojomValue::Int8(0)); get_max_ordinal(fields) + 1];
-    for (name, mojom_wire_type) in fields {
         // Make sure we're at the right alignment for this field
-        skip_to_alignment(data, mojom_wire_type.alignment())?;
+        skip_to_alignment(data, mojom_wire_type.alignment(), padding_must_be_zero)?;
 
         match mojom_wire_type {
             // Nested structured data, record for later
-            MojomWireType::Pointer { ordinal, nested_data_type } => {
-                let pointer_value = parse_u64(data)?;
-                let nested_info = NestedDataInfo {
-                    ty: nested_data_type,
-                    ordinal: *ordinal,
-                    field_name: name.clone(),
-                    expected_offset: data.bytes_parsed() - initial_bytes_parsed
-                        - 8 // Don't count the bytes we just parsed
-                        + usize::try_from(pointer_value)
-                            .context("Pointer value {pointer_value} doesn't fit into usize?!")?,
-                };
-                nested_data_list.push(nested_info);
+            MojomWireType::Pointer { ordinal, nested_data_type, nullable } => {
+                let value = parse_pointer(
+                    data,
+                    context,
+                    initial_bytes_parsed,
+                    nested_data_type,
+                    *nullable,
+                    *ordinal,
+                )?;
+                record_field(&mut ret, &mut nested_data_list, *ordinal, value)?;
+            }
+            // Unions are stored inline, but may point to nested data.
+            MojomWireType::Union { ordinal, variants, nullable } => {
+                let value = parse_inline_union(
+                    data,
+                    context,
+                    initial_bytes_parsed,
+                    variants,
+                    *nullable,
+                    *ordinal,
+                )?;
+                record_field(&mut ret, &mut nested_data_list, *ordinal, value)?;
             }
             // Nested leaf data, just parse it
             MojomWireType::Leaf { ordinal, leaf_type } => {
                 let parsed_value = parse_leaf_element(data, leaf_type)?;
-                ret[*ordinal] = (name.clone(), parsed_value);
+                set_field(&mut ret, *ordinal, parsed_value)?;
             }
-            MojomWireType::Bitfield { ordinals } => {
+            MojomWireType::Bitfield { ordinals, has_value_bits } => {
                 let mut iter = ordinals.into_iter().enumerate();
                 let parsed_bits = parse_u8(data)?;
                 while let Some((idx, Some(ordinal))) = iter.next() {
                     let bit = (parsed_bits >> idx) & 1;
-                    ret[*ordinal] = (name.clone(), MojomValue::Bool(bit == 1))
+                    if !is_present(*ordinal) {
+                        continue;
+                    }
+                    // The value of a nullable field is stored separately, and
+                    // replaced with Null below if the field has no value.
+                    if (has_value_bits >> idx) & 1 == 1 {
+                        if bit == 0 {
+                            null_ordinals.push(*ordinal);
+                        }
+                    } else {
+                        set_field(&mut ret, *ordinal, MojomValue::Bool(bit == 1))?;
+                    }
                 }
             }
         };
@@ -142,40 +500,141 @@ pub fn parse_struct(
 
     // We've reached the end of the struct (not including nested data!)
     // Make sure we parsed the expected number of bytes.
-    let bytes_parsed_so_far = data.bytes_parsed() - initial_bytes_parsed;
-    if bytes_parsed_so_far > size_in_bytes {
+    skip_to_end(data, initial_bytes_parsed, size_in_bytes, "Struct", padding_must_be_zero)?;
+
+    for nested_data in nested_data_list {
+        let parsed_data = parse_nested_data(data, context, initial_bytes_parsed, &nested_data)?;
+        set_field(&mut ret, nested_data.ordinal, parsed_data)?;
+    }
+    context.leave_nested();
+
+    for ordinal in null_ordinals {
+        ret[ordinal] = Some(MojomValue::Null);
+    }
+
+    // Every ordinal should have been filled in, unless the packed type skipped
+    // one, or it's from a later version than the struct.
+    let num_present = (0..ret.len()).take_while(|ordinal| is_present(*ordinal)).count();
+    ret.truncate(num_present);
+    ret.into_iter()
+        .enumerate()
+        .map(|(ordinal, value)| {
+            value.with_context(|| format!("Packed struct had no field with ordinal {}", ordinal))
+        })
+        .collect()
+}
+
+/// Parse an array (or string) whose elements have the given type, along with
+/// its nested data.
+///
+/// Arrays have the same 8-byte header as structs, except that the second
+/// field is the number of elements rather than the version. The elements
+/// follow, with no padding between them; bools are packed 8 to a byte.
+pub fn parse_array(
+    data: &mut ParserData,
+    element_type: &MojomWireType,
+    array_type: &PackedArrayType,
+) -> Result<MojomValue> {
+    parse_array_with_context(data, &mut ValidationContext::new(), element_type, array_type)
+}
+
+/// Like `parse_array`, but validating with the given `context`.
+pub fn parse_array_with_context(
+    data: &mut ParserData,
+    context: &mut ValidationContext,
+    element_type: &MojomWireType,
+    array_type: &PackedArrayType,
+) -> Result<MojomValue> {
+    context.enter_nested()?;
+    let initial_bytes_parsed = data.bytes_parsed();
+
+    // Parse the array header
+    let size_in_bytes: usize = parse_u32(data)?.try_into()?;
+    let num_elements: usize = parse_u32(data)?.try_into()?;
+    if let PackedArrayType::SizedArray(expected_elements) = array_type
+        && num_elements != *expected_elements
+    {
+        bail!("Expected an array of {} elements, but got {}", expected_elements, num_elements)
+    }
+    let min_size = array_inline_size(num_elements, element_type)
+        .with_context(|| format!("Array with {} elements is too large", num_elements))?;
+    if size_in_bytes < min_size {
         bail!(
-            "Struct claimed to have {} bytes, but we somehow parsed {} bytes",
+            "Array claimed to have {} bytes, but its {} elements need {} bytes",
             size_in_bytes,
-            bytes_parsed_so_far
+            num_elements,
+            min_size
         )
-    } else if bytes_parsed_so_far < size_in_bytes {
-        parse_padding(data, size_in_bytes - bytes_parsed_so_far)?
     }
+    context.claim_memory(data, initial_bytes_parsed, size_in_bytes)?;
 
-    for nested_data in nested_data_list {
-        // Nested data is required to appear in the same order as the (packed) fields
-        // of the struct. So the expected offset is only useful for validation.
-        let bytes_parsed_so_far = data.bytes_parsed() - initial_bytes_parsed;
-        if nested_data.expected_offset != bytes_parsed_so_far {
-            bail!(
-                "Nested field {} was at {} bytes from the beginning of the struct, \
-                 but expected to be at {} bytes",
-                nested_data.field_name,
-                bytes_parsed_so_far,
-                nested_data.expected_offset
-            );
-        }
-        let parsed_data = match nested_data.ty {
-            PackedStructuredType::Struct { packed_field_types } => {
-                let parsed_fields = parse_struct(data, packed_field_types)?;
-                MojomValue::Struct(parsed_fields)
+    // Don't trust num_elements when allocating; parsing will fail as soon as
+    // we run out of data.
+    let mut elements: Vec<MojomValue> = vec![];
+    // Elements which may have nested data, in order.
+    let mut inline_elements: Vec<InlineValue> = vec![];
+    match element_type {
+        MojomWireType::Leaf { leaf_type, .. } => {
+            for _ in 0..num_elements {
+                elements.push(parse_leaf_element(data, leaf_type)?);
             }
-            PackedStructuredType::Array { .. } => {
-                bail!("Arrays are not yet implemented");
+        }
+        MojomWireType::Bitfield { .. } => {
+            for first_idx in (0..num_elements).step_by(8) {
+                let parsed_bits = parse_u8(data)?;
+                let num_bits = (num_elements - first_idx).min(8);
+                elements.extend(
+                    (0..num_bits).map(|bit| MojomValue::Bool((parsed_bits >> bit) & 1 == 1)),
+                );
             }
-        };
-        ret[nested_data.ordinal] = (nested_data.field_name, parsed_data);
+        }
+        MojomWireType::Pointer { nested_data_type, nullable, .. } => {
+            for idx in 0..num_elements {
+                inline_elements.push(parse_pointer(
+                    data,
+                    context,
+                    initial_bytes_parsed,
+                    nested_data_type,
+                    *nullable,
+                    idx,
+                )?);
+            }
+        }
+        MojomWireType::Union { variants, nullable, .. } => {
+            for idx in 0..num_elements {
+                inline_elements.push(parse_inline_union(
+                    data,
+                    context,
+                    initial_bytes_parsed,
+                    variants,
+                    *nullable,
+                    idx,
+                )?);
+            }
+        }
+    }
+
+    skip_to_end(data, initial_bytes_parsed, size_in_bytes, "Array", true)?;
+    // Unlike structs, the size of an array doesn't include the padding needed
+    // to make the next object 8-byte aligned.
+    skip_to_alignment(data, 8, false)?;
+
+    for element in inline_elements {
+        elements.push(finish_inline_value(data, context, initial_bytes_parsed, element)?);
+    }
+    context.leave_nested();
+
+    if *array_type == PackedArrayType::String {
+        let bytes = elements
+            .into_iter()
+            .map(|element| match element {
+                MojomValue::UInt8(byte) => Ok(byte),
+                _ => bail!("Strings must be arrays of bytes, but got {:?}", element),
+            })
+            .collect::<Result<Vec<u8>>>()?;
+        return Ok(MojomValue::String(
+            String::from_utf8(bytes).context("String was not valid UTF-8")?,
+        ));
     }
-    Ok(ret)
+    Ok(MojomValue::Array(elements))
 }
@@ -0,0 +1,482 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Reads the Python pickles that mojom_parser.py writes its `.mojom-module`
+//! files as.
+//!
+//! This isn't a general unpickler: it understands the opcodes of protocols 2
+//! to 5 that are needed for plain data and for objects which are rebuilt from
+//! their `__dict__`, which is all that mojom.generate.module pickles. Objects
+//! aren't instantiated, of course; they're just a class name and their
+//! attributes.
+//!
+//! Pickles can contain cycles (e.g. a module refers to its structs, and each
+//! struct refers back to the module), so all compound values live in an arena
+//! in `Pickle`, and refer to each other by index.
+
+use anyhow::{bail, Context, Result};
+
+/// A value in a pickle. Lists, dicts, objects and so on are stored in the
+/// `Pickle` itself, and referred to with `Ref`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PickleValue {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Ref(usize),
+}
+
+#[derive(Debug)]
+pub(crate) enum Compound {
+    List(Vec<PickleValue>),
+    Tuple(Vec<PickleValue>),
+    /// The items of a dict, in insertion order.
+    Dict(Vec<(PickleValue, PickleValue)>),
+    /// A reference to a class or function, as found by `GLOBAL`.
+    Global {
+        module: String,
+        name: String,
+    },
+    /// An instance of a class, whose attributes are set by `BUILD`. `class`
+    /// includes the module, e.g. "mojom.generate.module.Struct".
+    Object {
+        class: String,
+        attributes: Vec<(PickleValue, PickleValue)>,
+    },
+}
+
+/// A whole unpickled value.
+pub(crate) struct Pickle {
+    compounds: Vec<Compound>,
+    root: PickleValue,
+}
+
+impl Pickle {
+    pub(crate) fn root(&self) -> &PickleValue {
+        &self.root
+    }
+
+    fn compound(&self, value: &PickleValue) -> Option<&Compound> {
+        match value {
+            PickleValue::Ref(index) => self.compounds.get(*index),
+            _ => None,
+        }
+    }
+
+    /// Return the name of an object's class, without its module, e.g.
+    /// "Struct".
+    pub(crate) fn class_name<'a>(&'a self, value: &PickleValue) -> Option<&'a str> {
+        match self.compound(value)? {
+            Compound::Object { class, .. } => class.rsplit('.').next(),
+            _ => None,
+        }
+    }
+
+    /// Return the value of `key` in a dict, or of the attribute `key` of an
+    /// object.
+    pub(crate) fn get<'a>(&'a self, value: &PickleValue, key: &str) -> Option<&'a PickleValue> {
+        let items = match self.compound(value)? {
+            Compound::Dict(items) | Compound::Object { attributes: items, .. } => items,
+            _ => return None,
+        };
+        items.iter().find(|(k, _)| matches!(k, PickleValue::String(k) if k == key)).map(|(_, v)| v)
+    }
+
+    /// Return the elements of a list or tuple.
+    pub(crate) fn elements<'a>(&'a self, value: &PickleValue) -> Option<&'a [PickleValue]> {
+        match self.compound(value)? {
+            Compound::List(elements) | Compound::Tuple(elements) => Some(elements),
+            _ => None,
+        }
+    }
+}
+
+// The opcodes, from Python's Lib/pickletools.py.
+const MARK: u8 = b'(';
+const STOP: u8 = b'.';
+const POP: u8 = b'0';
+const POP_MARK: u8 = b'1';
+const DUP: u8 = b'2';
+const BINFLOAT: u8 = b'G';
+const BININT: u8 = b'J';
+const BININT1: u8 = b'K';
+const BININT2: u8 = b'M';
+const NONE: u8 = b'N';
+const REDUCE: u8 = b'R';
+const BINUNICODE: u8 = b'X';
+const EMPTY_LIST: u8 = b']';
+const APPEND: u8 = b'a';
+const BUILD: u8 = b'b';
+const GLOBAL: u8 = b'c';
+const APPENDS: u8 = b'e';
+const BINGET: u8 = b'h';
+const LONG_BINGET: u8 = b'j';
+const BINPUT: u8 = b'q';
+const LONG_BINPUT: u8 = b'r';
+const SETITEM: u8 = b's';
+const TUPLE: u8 = b't';
+const SETITEMS: u8 = b'u';
+const EMPTY_DICT: u8 = b'}';
+const EMPTY_TUPLE: u8 = b')';
+const BINBYTES: u8 = b'B';
+const SHORT_BINBYTES: u8 = b'C';
+const PROTO: u8 = 0x80;
+const NEWOBJ: u8 = 0x81;
+const TUPLE1: u8 = 0x85;
+const TUPLE2: u8 = 0x86;
+const TUPLE3: u8 = 0x87;
+const NEWTRUE: u8 = 0x88;
+const NEWFALSE: u8 = 0x89;
+const LONG1: u8 = 0x8a;
+const SHORT_BINUNICODE: u8 = 0x8c;
+const BINUNICODE8: u8 = 0x8d;
+const BINBYTES8: u8 = 0x8e;
+const NEWOBJ_EX: u8 = 0x92;
+const STACK_GLOBAL: u8 = 0x93;
+const MEMOIZE: u8 = 0x94;
+const FRAME: u8 = 0x95;
+
+/// The state of the pickle machine.
+struct Unpickler<'a> {
+    data: &'a [u8],
+    position: usize,
+    stack: Vec<PickleValue>,
+    /// The stack as it was at each `MARK`.
+    marks: Vec<Vec<PickleValue>>,
+    memo: Vec<Option<PickleValue>>,
+    compounds: Vec<Compound>,
+}
+
+impl<'a> Unpickler<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.position.checked_add(len).context("Pickle length overflowed")?;
+        let bytes = self.data.get(self.position..end).context("Unexpected end of pickle")?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn take_len(&mut self, len_bytes: usize) -> Result<usize> {
+        let mut bytes = [0; 8];
+        bytes[..len_bytes].copy_from_slice(self.take(len_bytes)?);
+        Ok(u64::from_le_bytes(bytes).try_into()?)
+    }
+
+    fn take_line(&mut self) -> Result<String> {
+        let rest = &self.data[self.position..];
+        let len = rest.iter().position(|&b| b == b'\n').context("Unterminated line in pickle")?;
+        let line = std::str::from_utf8(&rest[..len])?.to_string();
+        self.position += len + 1;
+        Ok(line)
+    }
+
+    fn take_string(&mut self, len_bytes: usize) -> Result<PickleValue> {
+        let len = self.take_len(len_bytes)?;
+        Ok(PickleValue::String(std::str::from_utf8(self.take(len)?)?.to_string()))
+    }
+
+    fn take_bytes(&mut self, len_bytes: usize) -> Result<PickleValue> {
+        let len = self.take_len(len_bytes)?;
+        Ok(PickleValue::Bytes(self.take(len)?.to_vec()))
+    }
+
+    fn pop(&mut self) -> Result<PickleValue> {
+        self.stack.pop().context("Pickle stack underflow")
+    }
+
+    fn top(&self) -> Result<&PickleValue> {
+        self.stack.last().context("Pickle stack underflow")
+    }
+
+    /// Pop everything above the last mark, and the mark itself.
+    fn pop_mark(&mut self) -> Result<Vec<PickleValue>> {
+        let below = self.marks.pop().context("Pickle has no mark to pop to")?;
+        Ok(std::mem::replace(&mut self.stack, below))
+    }
+
+    fn push_compound(&mut self, compound: Compound) {
+        self.compounds.push(compound);
+        self.stack.push(PickleValue::Ref(self.compounds.len() - 1));
+    }
+
+    fn compound_mut(&mut self, value: &PickleValue) -> Result<&mut Compound> {
+        match value {
+            PickleValue::Ref(index) => Ok(&mut self.compounds[*index]),
+            value => bail!("Expected a list, dict or object in the pickle, but got {:?}", value),
+        }
+    }
+
+    fn put(&mut self, index: usize) -> Result<()> {
+        let value = self.top()?.clone();
+        if self.memo.len() <= index {
+            self.memo.resize(index + 1, None);
+        }
+        self.memo[index] = Some(value);
+        Ok(())
+    }
+
+    fn get(&mut self, index: usize) -> Result<()> {
+        let value = self.memo.get(index).cloned().flatten();
+        self.stack.push(value.with_context(|| format!("Pickle memo has no entry {}", index))?);
+        Ok(())
+    }
+
+    fn append(&mut self, elements: Vec<PickleValue>) -> Result<()> {
+        let list = self.top()?.clone();
+        match self.compound_mut(&list)? {
+            Compound::List(list) => list.extend(elements),
+            compound => bail!("Can't append to {:?}", compound),
+        }
+        Ok(())
+    }
+
+    fn set_items(&mut self, items: Vec<PickleValue>) -> Result<()> {
+        if !items.len().is_multiple_of(2) {
+            bail!("Pickle has a key without a value")
+        }
+        let dict = self.top()?.clone();
+        let Compound::Dict(dict) = self.compound_mut(&dict)? else {
+            bail!("Pickle sets items on something other than a dict")
+        };
+        let mut items = items.into_iter();
+        while let (Some(key), Some(value)) = (items.next(), items.next()) {
+            match dict.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, existing)) => *existing = value,
+                None => dict.push((key, value)),
+            }
+        }
+        Ok(())
+    }
+
+    fn class_name(&self, class: &PickleValue) -> Result<String> {
+        match class {
+            PickleValue::Ref(index) => match &self.compounds[*index] {
+                Compound::Global { module, name } => Ok(format!("{}.{}", module, name)),
+                compound => bail!("Expected a class in the pickle, but got {:?}", compound),
+            },
+            value => bail!("Expected a class in the pickle, but got {:?}", value),
+        }
+    }
+
+    /// Call `callable(*args)`, for `REDUCE`. The only function that we know
+    /// how to call is the constructor of `OrderedDict`, which we treat as a
+    /// regular dict.
+    fn reduce(&mut self, callable: PickleValue, args: PickleValue) -> Result<()> {
+        let name = self.class_name(&callable)?;
+        let no_args = matches!(self.compound_mut(&args)?, Compound::Tuple(args) if args.is_empty());
+        if name != "collections.OrderedDict" || !no_args {
+            bail!("Unpickling {} isn't supported", name)
+        }
+        self.push_compound(Compound::Dict(vec![]));
+        Ok(())
+    }
+
+    /// Set the attributes of an object, for `BUILD`.
+    fn build(&mut self, state: PickleValue) -> Result<()> {
+        let object = self.top()?.clone();
+        let attributes = match self.compound_mut(&state)? {
+            Compound::Dict(items) => std::mem::take(items),
+            compound => bail!("Unpickling objects with a state of {:?} isn't supported", compound),
+        };
+        match self.compound_mut(&object)? {
+            Compound::Object { attributes: existing, .. } => existing.extend(attributes),
+            compound => bail!("Can't set the attributes of {:?}", compound),
+        }
+        Ok(())
+    }
+
+    /// Execute the next opcode, returning true if it was `STOP`.
+    fn step(&mut self) -> Result<bool> {
+        let [opcode] = self.take_array()?;
+        match opcode {
+            PROTO => {
+                let [version] = self.take_array()?;
+                if !(2..=5).contains(&version) {
+                    bail!("Pickle protocol {} isn't supported", version)
+                }
+            }
+            // Frames are only a hint for buffering.
+            FRAME => {
+                self.take(8)?;
+            }
+            STOP => return Ok(true),
+            MARK => self.marks.push(std::mem::take(&mut self.stack)),
+            POP => {
+                self.pop()?;
+            }
+            POP_MARK => {
+                self.pop_mark()?;
+            }
+            DUP => self.stack.push(self.top()?.clone()),
+            NONE => self.stack.push(PickleValue::None),
+            NEWTRUE => self.stack.push(PickleValue::Bool(true)),
+            NEWFALSE => self.stack.push(PickleValue::Bool(false)),
+            BININT => {
+                let value = i32::from_le_bytes(self.take_array()?);
+                self.stack.push(PickleValue::Int(value.into()))
+            }
+            BININT1 => {
+                let [value] = self.take_array()?;
+                self.stack.push(PickleValue::Int(value.into()))
+            }
+            BININT2 => {
+                let value = u16::from_le_bytes(self.take_array()?);
+                self.stack.push(PickleValue::Int(value.into()))
+            }
+            LONG1 => {
+                let [len] = self.take_array()?;
+                let bytes = self.take(len.into())?;
+                if bytes.len() > 8 {
+                    bail!("Pickle has an integer which doesn't fit in 64 bits")
+                }
+                // Sign-extend the little-endian two's complement bytes.
+                let fill = if bytes.last().is_some_and(|b| b & 0x80 != 0) { 0xff } else { 0 };
+                let mut value = [fill; 8];
+                value[..bytes.len()].copy_from_slice(bytes);
+                self.stack.push(PickleValue::Int(i64::from_le_bytes(value)))
+            }
+            BINFLOAT => {
+                let value = f64::from_be_bytes(self.take_array()?);
+                self.stack.push(PickleValue::Float(value))
+            }
+            SHORT_BINUNICODE => {
+                let value = self.take_string(1)?;
+                self.stack.push(value)
+            }
+            BINUNICODE => {
+                let value = self.take_string(4)?;
+                self.stack.push(value)
+            }
+            BINUNICODE8 => {
+                let value = self.take_string(8)?;
+                self.stack.push(value)
+            }
+            SHORT_BINBYTES => {
+                let value = self.take_bytes(1)?;
+                self.stack.push(value)
+            }
+            BINBYTES => {
+                let value = self.take_bytes(4)?;
+                self.stack.push(value)
+            }
+            BINBYTES8 => {
+                let value = self.take_bytes(8)?;
+                self.stack.push(value)
+            }
+            EMPTY_LIST => self.push_compound(Compound::List(vec![])),
+            EMPTY_DICT => self.push_compound(Compound::Dict(vec![])),
+            EMPTY_TUPLE => self.push_compound(Compound::Tuple(vec![])),
+            TUPLE => {
+                let elements = self.pop_mark()?;
+                self.push_compound(Compound::Tuple(elements))
+            }
+            TUPLE1 | TUPLE2 | TUPLE3 => {
+                let len = usize::from(opcode - TUPLE1 + 1);
+                let start = self.stack.len().checked_sub(len).context("Pickle stack underflow")?;
+                let elements = self.stack.split_off(start);
+                self.push_compound(Compound::Tuple(elements))
+            }
+            APPEND => {
+                let element = self.pop()?;
+                self.append(vec![element])?
+            }
+            APPENDS => {
+                let elements = self.pop_mark()?;
+                self.append(elements)?
+            }
+            SETITEM => {
+                let value = self.pop()?;
+                let key = self.pop()?;
+                self.set_items(vec![key, value])?
+            }
+            SETITEMS => {
+                let items = self.pop_mark()?;
+                self.set_items(items)?
+            }
+            MEMOIZE => {
+                let index = self.memo.len();
+                self.put(index)?
+            }
+            BINPUT => {
+                let [index] = self.take_array()?;
+                self.put(index.into())?
+            }
+            LONG_BINPUT => {
+                let index = self.take_len(4)?;
+                self.put(index)?
+            }
+            BINGET => {
+                let [index] = self.take_array()?;
+                self.get(index.into())?
+            }
+            LONG_BINGET => {
+                let index = self.take_len(4)?;
+                self.get(index)?
+            }
+            GLOBAL => {
+                let module = self.take_line()?;
+                let name = self.take_line()?;
+                self.push_compound(Compound::Global { module, name })
+            }
+            STACK_GLOBAL => {
+                let name = self.pop()?;
+                let module = self.pop()?;
+                let (PickleValue::String(module), PickleValue::String(name)) = (module, name)
+                else {
+                    bail!("STACK_GLOBAL needs a module name and a class name")
+                };
+                self.push_compound(Compound::Global { module, name })
+            }
+            NEWOBJ | NEWOBJ_EX => {
+                // Objects that are rebuilt from their `__dict__` are created
+                // without any interesting arguments, so ignore them.
+                if opcode == NEWOBJ_EX {
+                    self.pop()?;
+                }
+                self.pop()?;
+                let class = self.pop()?;
+                let class = self.class_name(&class)?;
+                self.push_compound(Compound::Object { class, attributes: vec![] })
+            }
+            REDUCE => {
+                let args = self.pop()?;
+                let callable = self.pop()?;
+                self.reduce(callable, args)?
+            }
+            BUILD => {
+                let state = self.pop()?;
+                self.build(state)?
+            }
+            opcode => bail!("Pickle opcode {:#x} isn't supported", opcode),
+        }
+        Ok(false)
+    }
+}
+
+/// Unpickle `data`.
+pub(crate) fn unpickle(data: &[u8]) -> Result<Pickle> {
+    let mut unpickler = Unpickler {
+        data,
+        position: 0,
+        stack: vec![],
+        marks: vec![],
+        memo: vec![],
+        compounds: vec![],
+    };
+    while !unpickler
+        .step()
+        .with_context(|| format!("At byte {} of the pickle", unpickler.position))?
+    {}
+    let root = unpickler.pop()?;
+    if unpickler.position != data.len() {
+        bail!("There were {} trailing bytes after the pickle", data.len() - unpickler.position)
+    }
+    Ok(Pickle { compounds: unpickler.compounds, root })
+}
@@ -0,0 +1,393 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Encodes Rust types which implement `serde::Serialize` as mojom values.
+//!
+//! Serializing a value produces both a `MojomValue` and the `MojomType` that
+//! it has, which is then packed and deparsed as usual. The serde data model
+//! maps onto mojom like so:
+//! - bools and integers map to the mojom type of the same width.
+//! - strings map to mojom strings, and byte arrays to `array<uint8>`.
+//! - structs (including tuple structs and unit structs) map to mojom structs,
+//!   with fields in declaration order. The field names are ignored, so the
+//!   Rust struct must declare its fields in the same order as the mojom one.
+//! - sequences map to mojom arrays, and fixed-size arrays to sized arrays.
+//! - enums with only unit variants map to mojom enums, i.e. int32s, using the
+//!   index of the variant as its value.
+//! - floats and doubles map to mojom floats and doubles.
+//! - newtype structs are transparent.
+//! - `Some` maps to a nullable value of the inner type. `None` fails to
+//!   serialize, since serde doesn't say what type it would have had, and a
+//!   null int32 is encoded differently from a null string. Types with `None`
+//!   fields should implement the `Mojom` trait instead.
+//!
+//! Anything else (maps, and enums with data) isn't supported yet, and fails to
+//! serialize.
+//!
+//! The inverse of this module is de.rs.
+
+use crate::ast::*;
+use crate::deparse_values::deparse_struct;
+use crate::pack::pack_mojom_type;
+
+use anyhow::{bail, Result};
+use serde::ser::{self, Serialize};
+use std::fmt::Display;
+
+/// The error type for serde. The functions in this module and de.rs convert it
+/// to `anyhow::Error` before returning it; it's only public because
+/// `MojomValue` implements `serde::Deserializer`.
+#[derive(Debug)]
+pub struct SerdeError(pub(crate) String);
+
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+impl serde::de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+type SerdeResult<T> = std::result::Result<T, SerdeError>;
+
+fn unsupported<T>(what: &str) -> SerdeResult<T> {
+    Err(SerdeError(format!("{} can't be encoded as mojom yet", what)))
+}
+
+/// Convert `value` to a mojom value, along with its type.
+pub fn to_mojom_value<T: Serialize + ?Sized>(value: &T) -> Result<(MojomType, MojomValue)> {
+    Ok(value.serialize(MojomSerializer)?)
+}
+
+/// Encode `value`, which must serialize as a struct, in the mojom wire
+/// format.
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let (ty, value) = to_mojom_value(value)?;
+    let MojomValue::Struct(field_values) = value else {
+        bail!("Only structs can be encoded at the top level, but got {:?}", value)
+    };
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, min_versions },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        unreachable!("Structs always pack to pointers")
+    };
+    let mut data = vec![];
+    deparse_struct(&mut data, &field_values, &packed_field_types, &min_versions)?;
+    Ok(data)
+}
+
+/// Serializes a value into a `(MojomType, MojomValue)` pair.
+struct MojomSerializer;
+
+/// Collects the elements of a sequence, which must all have the same type.
+struct SerializeArray {
+    element_type: Option<MojomType>,
+    elements: Vec<MojomValue>,
+    /// The number of elements, if the sequence is a fixed-size array.
+    num_elements: Option<usize>,
+}
+
+impl SerializeArray {
+    fn add<T: Serialize + ?Sized>(&mut self, element: &T) -> SerdeResult<()> {
+        let (ty, value) = element.serialize(MojomSerializer)?;
+        match &self.element_type {
+            Some(element_type) if *element_type != ty => {
+                return Err(SerdeError(format!(
+                    "Array elements must all have the same type, but got {:?} and {:?}",
+                    element_type, ty
+                )));
+            }
+            Some(_) => (),
+            None => self.element_type = Some(ty),
+        }
+        self.elements.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> SerdeResult<(MojomType, MojomValue)> {
+        // An empty array is encoded the same way whatever its element type is,
+        // so it doesn't matter what we pick here.
+        let element_type = Box::new(self.element_type.unwrap_or(MojomType::UInt8));
+        Ok((
+            MojomType::Array { element_type, num_elements: self.num_elements },
+            MojomValue::Array(self.elements),
+        ))
+    }
+}
+
+/// Collects the fields of a struct, in declaration order.
+#[derive(Default)]
+struct SerializeStruct {
+    fields: Vec<MojomType>,
+    values: Vec<MojomValue>,
+}
+
+impl SerializeStruct {
+    fn add<T: Serialize + ?Sized>(&mut self, field: &T) -> SerdeResult<()> {
+        let (ty, value) = field.serialize(MojomSerializer)?;
+        self.fields.push(ty);
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> SerdeResult<(MojomType, MojomValue)> {
+        Ok((
+            MojomType::Struct { fields: self.fields, min_versions: vec![] },
+            MojomValue::Struct(self.values),
+        ))
+    }
+}
+
+impl ser::Serializer for MojomSerializer {
+    type Ok = (MojomType, MojomValue);
+    type Error = SerdeError;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeStruct;
+    type SerializeTupleVariant = ser::Impossible<Self::Ok, SerdeError>;
+    type SerializeMap = ser::Impossible<Self::Ok, SerdeError>;
+    type SerializeStruct = SerializeStruct;
+    type SerializeStructVariant = ser::Impossible<Self::Ok, SerdeError>;
+
+    fn serialize_bool(self, v: bool) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::Bool, MojomValue::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::Int8, MojomValue::Int8(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::Int16, MojomValue::Int16(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::Int32, MojomValue::Int32(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::Int64, MojomValue::Int64(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::UInt8, MojomValue::UInt8(v)))
+    }
+
+    fn serialize_u16(self, v: u16) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::UInt16, MojomValue::UInt16(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::UInt32, MojomValue::UInt32(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::UInt64, MojomValue::UInt64(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::Float, MojomValue::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::Double, MojomValue::Double(v)))
+    }
+
+    fn serialize_char(self, _v: char) -> SerdeResult<Self::Ok> {
+        unsupported("char")
+    }
+
+    fn serialize_str(self, v: &str) -> SerdeResult<Self::Ok> {
+        Ok((MojomType::String, MojomValue::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> SerdeResult<Self::Ok> {
+        Ok((
+            MojomType::Array { element_type: Box::new(MojomType::UInt8), num_elements: None },
+            MojomValue::Array(v.iter().map(|byte| MojomValue::UInt8(*byte)).collect()),
+        ))
+    }
+
+    fn serialize_none(self) -> SerdeResult<Self::Ok> {
+        unsupported("None, whose type isn't known,")
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> SerdeResult<Self::Ok> {
+        match value.serialize(self)? {
+            (MojomType::Nullable(_), _) => unsupported("Nested Option"),
+            (ty, value) => Ok((MojomType::Nullable(Box::new(ty)), value)),
+        }
+    }
+
+    fn serialize_unit(self) -> SerdeResult<Self::Ok> {
+        SerializeStruct::default().finish()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> SerdeResult<Self::Ok> {
+        SerializeStruct::default().finish()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> SerdeResult<Self::Ok> {
+        let value = i32::try_from(variant_index)
+            .map_err(|_| SerdeError(format!("Enum variant {} is too large", variant_index)))?;
+        Ok((MojomType::Int32, MojomValue::Int32(value)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> SerdeResult<Self::Ok> {
+        value.serialize(self)
+    }
+
+    // FOR_RELEASE: Enums with data could map to unions once we support them.
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> SerdeResult<Self::Ok> {
+        unsupported("Enum variant with data")
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> SerdeResult<Self::SerializeSeq> {
+        Ok(SerializeArray {
+            element_type: None,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+            num_elements: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> SerdeResult<Self::SerializeTuple> {
+        Ok(SerializeArray {
+            element_type: None,
+            elements: Vec::with_capacity(len),
+            num_elements: Some(len),
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeTupleStruct> {
+        Ok(SerializeStruct::default())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeTupleVariant> {
+        unsupported("Enum variant with data")
+    }
+
+    // FOR_RELEASE: Support maps.
+    fn serialize_map(self, _len: Option<usize>) -> SerdeResult<Self::SerializeMap> {
+        unsupported("Map")
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeStruct> {
+        Ok(SerializeStruct::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> SerdeResult<Self::SerializeStructVariant> {
+        unsupported("Enum variant with data")
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = (MojomType, MojomValue);
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.add(value)
+    }
+
+    fn end(self) -> SerdeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = (MojomType, MojomValue);
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.add(value)
+    }
+
+    fn end(self) -> SerdeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeStruct {
+    type Ok = (MojomType, MojomValue);
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.add(value)
+    }
+
+    fn end(self) -> SerdeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeStruct {
+    type Ok = (MojomType, MojomValue);
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> SerdeResult<()> {
+        self.add(value)
+    }
+
+    // Skipping a field would shift the ordinals of every later field.
+    fn skip_field(&mut self, key: &'static str) -> SerdeResult<()> {
+        Err(SerdeError(format!("Field {} can't be skipped", key)))
+    }
+
+    fn end(self) -> SerdeResult<Self::Ok> {
+        self.finish()
+    }
+}
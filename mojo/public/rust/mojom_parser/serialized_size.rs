@@ -0,0 +1,237 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Computes the exact number of bytes that a value is encoded as, without
+//! encoding it, like message_size_estimator.cc in the C++ bindings. The
+//! deparsers use this to allocate the whole encoding up front, so that they
+//! can write it into a single buffer, such as the one a message pipe gives us
+//! for a new message.
+//!
+//! Every size is checked for overflow. Nothing may be 4 GiB or larger, since
+//! structs and arrays store their size in 32 bits, and so do messages.
+
+use crate::ast::*;
+
+use anyhow::{bail, Context, Result};
+
+/// The largest number of bytes that anything can be encoded as.
+const MAX_SERIALIZED_SIZE: usize = u32::MAX as usize;
+
+/// Add two sizes, failing if the result is too large to encode.
+fn add(a: usize, b: usize) -> Result<usize> {
+    a.checked_add(b)
+        .filter(|size| *size <= MAX_SERIALIZED_SIZE)
+        .with_context(|| format!("Can't encode more than {} bytes", MAX_SERIALIZED_SIZE))
+}
+
+/// Return the number of bytes that `value` is encoded as, as a value of
+/// `wire_type`, including all the data it points to.
+///
+/// For a pointer, this is the size of the struct, array or union that it
+/// points to, or 0 if it's null. So for a top-level struct, whose wire type
+/// comes from `pack_mojom_type`, this is the size of the whole encoded struct.
+pub fn serialized_size(value: &MojomValue, wire_type: &MojomWireType) -> Result<usize> {
+    match wire_type {
+        MojomWireType::Leaf { .. } | MojomWireType::Bitfield { .. } => Ok(wire_type.size()),
+        MojomWireType::Pointer { nested_data_type, nullable, .. } => {
+            nested_data_size(value, nested_data_type, *nullable)
+        }
+        MojomWireType::Union { variants, nullable, .. } => {
+            add(wire_type.size(), union_nested_data_size(value, variants, *nullable)?)
+        }
+    }
+}
+
+/// Return the number of bytes that `deparse_struct` writes for a struct with
+/// the given fields.
+pub fn struct_serialized_size(
+    field_values: &[MojomValue],
+    packed_fields: &[MojomWireType],
+) -> Result<usize> {
+    let mut nested_size = 0;
+    for packed_field in packed_fields {
+        nested_size = add(nested_size, field_nested_data_size(field_values, packed_field)?)?;
+    }
+    add(struct_inline_size(packed_fields), nested_size)
+}
+
+/// Return the size of a struct with the given fields, including its header
+/// and the padding at the end, but not the data that it points to.
+pub(crate) fn struct_inline_size(packed_fields: &[MojomWireType]) -> usize {
+    // The header, which holds the size and version.
+    let mut inline_size: usize = 8;
+    for packed_field in packed_fields {
+        // Packed structs are tiny, so this can't overflow.
+        inline_size = inline_size.next_multiple_of(packed_field.alignment()) + packed_field.size();
+    }
+    inline_size.next_multiple_of(8)
+}
+
+/// Return the size of the given version of a struct whose fields were added in
+/// `min_versions` (see `PackedStructuredType::Struct`), including its header
+/// and the padding at the end. This is the smallest size that a struct of that
+/// version may have; for versions later than we know about, it's the size of
+/// the latest one.
+pub fn struct_version_size(
+    packed_fields: &[MojomWireType],
+    min_versions: &[u32],
+    version: u32,
+) -> usize {
+    let mut inline_size: usize = 8;
+    let mut version_size: usize = 8;
+    for packed_field in packed_fields {
+        inline_size = inline_size.next_multiple_of(packed_field.alignment()) + packed_field.size();
+        // Later fields may fill the padding between earlier ones, so this
+        // isn't always the last field of the version.
+        if field_min_version(min_versions, packed_field.first_ordinal()) <= version {
+            version_size = inline_size;
+        }
+    }
+    version_size.next_multiple_of(8)
+}
+
+/// Return the version of a struct which added the field with the given
+/// ordinal.
+pub(crate) fn field_min_version(min_versions: &[u32], ordinal: Ordinal) -> u32 {
+    min_versions.get(ordinal).copied().unwrap_or(0)
+}
+
+/// Return the latest version of a struct whose fields were added in
+/// `min_versions`, which is written in its header.
+pub(crate) fn struct_latest_version(min_versions: &[u32]) -> u32 {
+    min_versions.iter().copied().max().unwrap_or(0)
+}
+
+/// Return the number of bytes that `deparse_message` writes for a message
+/// with a payload of `payload_size` bytes, which passes the given associated
+/// interfaces.
+pub fn message_serialized_size(payload_size: usize, interface_ids: &[u32]) -> Result<usize> {
+    if interface_ids.is_empty() {
+        // A version 1 header.
+        return add(32, payload_size);
+    }
+    // A version 2 header, then the payload, then an array of interface IDs,
+    // each of which starts 8-byte aligned.
+    let ids_size = interface_ids
+        .len()
+        .checked_mul(4)
+        .and_then(|size| add(8, size).ok())
+        .context("Too many interface IDs")?;
+    add(add(48, payload_size.next_multiple_of(8))?, ids_size.next_multiple_of(8))
+}
+
+/// Return the size of the data that a pointer field points to, or 0 if it's
+/// null.
+fn nested_data_size(
+    value: &MojomValue,
+    ty: &PackedStructuredType,
+    nullable: bool,
+) -> Result<usize> {
+    match (value, ty) {
+        (MojomValue::Null, _) if nullable => Ok(0),
+        (MojomValue::Struct(field_values), PackedStructuredType::Struct { packed_field_types, .. }) => {
+            struct_serialized_size(field_values, packed_field_types)
+        }
+        (MojomValue::Array(elements), PackedStructuredType::Array { element_type, .. }) => {
+            array_serialized_size(elements, element_type)
+        }
+        (
+            MojomValue::String(value),
+            PackedStructuredType::Array { array_type: PackedArrayType::String, .. },
+        ) => Ok(add(8, value.len())?.next_multiple_of(8)),
+        (MojomValue::Union { .. }, PackedStructuredType::Union { variants }) => {
+            add(16, union_nested_data_size(value, variants, false)?)
+        }
+        (_, PackedStructuredType::Recursive(_)) => nested_data_size(value, &ty.resolve(), nullable),
+        _ => bail!("Unexpected type for nested data: Expected {:?}, got {:?}", ty, value),
+    }
+}
+
+/// Return the size of the data that a struct field points to, if any.
+fn field_nested_data_size(
+    field_values: &[MojomValue],
+    packed_field: &MojomWireType,
+) -> Result<usize> {
+    let field_value = |ordinal: Ordinal| {
+        field_values.get(ordinal).with_context(|| {
+            format!(
+                "Wire type asked for field with ordinal {}, but there are only {} fields.",
+                ordinal,
+                field_values.len()
+            )
+        })
+    };
+    match packed_field {
+        MojomWireType::Leaf { .. } | MojomWireType::Bitfield { .. } => Ok(0),
+        MojomWireType::Pointer { ordinal, nested_data_type, nullable } => {
+            nested_data_size(field_value(*ordinal)?, nested_data_type, *nullable)
+        }
+        MojomWireType::Union { ordinal, variants, nullable } => {
+            union_nested_data_size(field_value(*ordinal)?, variants, *nullable)
+        }
+    }
+}
+
+/// Return the size of the data that an inline union points to, if its active
+/// field is a pointer.
+fn union_nested_data_size(
+    value: &MojomValue,
+    variants: &[MojomWireType],
+    nullable: bool,
+) -> Result<usize> {
+    let (tag, variant_value) = match value {
+        MojomValue::Null if nullable => return Ok(0),
+        MojomValue::Union { tag, value } => (*tag, value.as_ref()),
+        _ => bail!("Expected a union, but got {:?}", value),
+    };
+    let variant = variants
+        .get(tag)
+        .with_context(|| format!("Union has {} fields, but got tag {}", variants.len(), tag))?;
+    match variant {
+        MojomWireType::Pointer { nested_data_type, nullable, .. } => {
+            nested_data_size(variant_value, nested_data_type, *nullable)
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Return the number of bytes that `deparse_array` writes for an array with
+/// the given elements, including the padding after it.
+pub fn array_serialized_size(
+    elements: &[MojomValue],
+    element_type: &MojomWireType,
+) -> Result<usize> {
+    let num_elements = elements.len();
+    let inline_size = array_inline_size(num_elements, element_type)
+        .with_context(|| format!("An array of {} elements is too large to encode", num_elements))?;
+    let mut nested_size = 0;
+    for element in elements {
+        let element_nested_size = match element_type {
+            MojomWireType::Pointer { nested_data_type, nullable, .. } => {
+                nested_data_size(element, nested_data_type, *nullable)?
+            }
+            MojomWireType::Union { variants, nullable, .. } => {
+                union_nested_data_size(element, variants, *nullable)?
+            }
+            MojomWireType::Leaf { .. } | MojomWireType::Bitfield { .. } => break,
+        };
+        nested_size = add(nested_size, element_nested_size)?;
+    }
+    add(inline_size.next_multiple_of(8), nested_size)
+}
+
+/// Return the size of an array of `num_elements` elements, including its
+/// header but not the padding after it or the data that it points to, or None
+/// if that's too large to encode.
+pub(crate) fn array_inline_size(
+    num_elements: usize,
+    element_type: &MojomWireType,
+) -> Option<usize> {
+    let elements_size = match element_type {
+        // Bools are packed 8 to a byte.
+        MojomWireType::Bitfield { .. } => Some(num_elements.div_ceil(8)),
+        _ => num_elements.checked_mul(element_type.size()),
+    };
+    elements_size.and_then(|size| add(8, size).ok())
+}
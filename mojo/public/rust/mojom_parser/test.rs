@@ -21,6 +21,10 @@ use rust_gtest_interop::prelude::*;
 
 use anyhow::Result;
 use mojom_parser::*;
+use serde::{Deserialize, Serialize};
+
+mod mojom_module_test;
+mod pack_fixtures_test;
 
 /// Represents a type defined in a Mojom file.
 ///
@@ -38,7 +42,7 @@ struct TestType {
     /// Human-readable type name for debugging output
     type_name: &'static str,
     /// Contents of a PackedStructuredType::Struct
-    expected_packed_fields: Vec<(String, MojomWireType)>,
+    expected_packed_fields: Vec<MojomWireType>,
     // Imagine there's a third `constructor` entry here.
 }
 
@@ -49,7 +53,9 @@ impl TestType {
             ordinal,
             nested_data_type: PackedStructuredType::Struct {
                 packed_field_types: self.expected_packed_fields.clone(),
+                min_versions: vec![],
             },
+            nullable: false,
         }
     }
 
@@ -68,7 +74,7 @@ impl TestType {
 
     fn parse(&self, data: &[u8]) -> Result<MojomValue> {
         let mut data = ParserData::new(data);
-        let parsed_fields = parse_struct(&mut data, &self.expected_packed_fields)?;
+        let parsed_fields = parse_struct(&mut data, &self.expected_packed_fields, &[])?;
         Ok(MojomValue::Struct(parsed_fields))
     }
 }
@@ -76,10 +82,6 @@ impl TestType {
 // We'll be creating all our test types at global scope, so we can reference
 // them from various testing functions.
 
-// FOR_RELEASE: String field names are nice for debugging, but they shouldn't
-// be in the final version because they're not necessary and they get partially
-// lost during packing. So for now we'll just put in empty strings as dummies.
-
 #[gtest(MojomParserTestSuit, BoolTest)]
 fn test_bools() {
     // Used for probing how bools are packed. We should see the first 8 bools in a
@@ -89,42 +91,30 @@ fn test_bools() {
         type_name: "TenBoolsAndAByte",
         base_type: MojomType::Struct {
             fields: vec![
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::UInt8),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::UInt8,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
             ],
+            min_versions: vec![],
         },
         expected_packed_fields: vec![
-            (
-                "".to_string(),
-                MojomWireType::Bitfield {
-                    ordinals: [
-                        Some(0),
-                        Some(1),
-                        Some(2),
-                        Some(3),
-                        Some(4),
-                        Some(6),
-                        Some(7),
-                        Some(8),
-                    ],
-                },
-            ),
-            ("".to_string(), MojomWireType::Leaf { ordinal: 5, leaf_type: PackedLeafType::UInt8 }),
-            (
-                "".to_string(),
-                MojomWireType::Bitfield {
-                    ordinals: [Some(9), Some(10), None, None, None, None, None, None],
-                },
-            ),
+            MojomWireType::Bitfield {
+                ordinals: [Some(0), Some(1), Some(2), Some(3), Some(4), Some(6), Some(7), Some(8)],
+                has_value_bits: 0,
+            },
+            MojomWireType::Leaf { ordinal: 5, leaf_type: PackedLeafType::UInt8 },
+            MojomWireType::Bitfield {
+                ordinals: [Some(9), Some(10), None, None, None, None, None, None],
+                has_value_bits: 0,
+            },
         ],
     };
 
@@ -142,17 +132,17 @@ fn test_bools() {
         e10: bool,
     ) -> MojomValue {
         MojomValue::Struct(vec![
-            ("".to_string(), MojomValue::Bool(e0)),
-            ("".to_string(), MojomValue::Bool(e1)),
-            ("".to_string(), MojomValue::Bool(e2)),
-            ("".to_string(), MojomValue::Bool(e3)),
-            ("".to_string(), MojomValue::Bool(e4)),
-            ("".to_string(), MojomValue::UInt8(e5)),
-            ("".to_string(), MojomValue::Bool(e6)),
-            ("".to_string(), MojomValue::Bool(e7)),
-            ("".to_string(), MojomValue::Bool(e8)),
-            ("".to_string(), MojomValue::Bool(e9)),
-            ("".to_string(), MojomValue::Bool(e10)),
+            MojomValue::Bool(e0),
+            MojomValue::Bool(e1),
+            MojomValue::Bool(e2),
+            MojomValue::Bool(e3),
+            MojomValue::Bool(e4),
+            MojomValue::UInt8(e5),
+            MojomValue::Bool(e6),
+            MojomValue::Bool(e7),
+            MojomValue::Bool(e8),
+            MojomValue::Bool(e9),
+            MojomValue::Bool(e10),
         ])
     }
 
@@ -162,42 +152,30 @@ fn test_bools() {
         type_name: "TenBoolsAndTwoBytes",
         base_type: MojomType::Struct {
             fields: vec![
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::UInt16),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
-                ("".to_string(), MojomType::Bool),
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::UInt16,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
+                MojomType::Bool,
             ],
+            min_versions: vec![],
         },
         expected_packed_fields: vec![
-            (
-                "".to_string(),
-                MojomWireType::Bitfield {
-                    ordinals: [
-                        Some(0),
-                        Some(1),
-                        Some(2),
-                        Some(3),
-                        Some(4),
-                        Some(6),
-                        Some(7),
-                        Some(8),
-                    ],
-                },
-            ),
-            (
-                "".to_string(),
-                MojomWireType::Bitfield {
-                    ordinals: [Some(9), Some(10), None, None, None, None, None, None],
-                },
-            ),
-            ("".to_string(), MojomWireType::Leaf { ordinal: 5, leaf_type: PackedLeafType::UInt16 }),
+            MojomWireType::Bitfield {
+                ordinals: [Some(0), Some(1), Some(2), Some(3), Some(4), Some(6), Some(7), Some(8)],
+                has_value_bits: 0,
+            },
+            MojomWireType::Bitfield {
+                ordinals: [Some(9), Some(10), None, None, None, None, None, None],
+                has_value_bits: 0,
+            },
+            MojomWireType::Leaf { ordinal: 5, leaf_type: PackedLeafType::UInt16 },
         ],
     };
 
@@ -215,17 +193,17 @@ fn test_bools() {
         e10: bool,
     ) -> MojomValue {
         MojomValue::Struct(vec![
-            ("".to_string(), MojomValue::Bool(e0)),
-            ("".to_string(), MojomValue::Bool(e1)),
-            ("".to_string(), MojomValue::Bool(e2)),
-            ("".to_string(), MojomValue::Bool(e3)),
-            ("".to_string(), MojomValue::Bool(e4)),
-            ("".to_string(), MojomValue::UInt16(e5)),
-            ("".to_string(), MojomValue::Bool(e6)),
-            ("".to_string(), MojomValue::Bool(e7)),
-            ("".to_string(), MojomValue::Bool(e8)),
-            ("".to_string(), MojomValue::Bool(e9)),
-            ("".to_string(), MojomValue::Bool(e10)),
+            MojomValue::Bool(e0),
+            MojomValue::Bool(e1),
+            MojomValue::Bool(e2),
+            MojomValue::Bool(e3),
+            MojomValue::Bool(e4),
+            MojomValue::UInt16(e5),
+            MojomValue::Bool(e6),
+            MojomValue::Bool(e7),
+            MojomValue::Bool(e8),
+            MojomValue::Bool(e9),
+            MojomValue::Bool(e10),
         ])
     }
 
@@ -265,3 +243,1776 @@ fn test_bools() {
     assert_eq!(one_byte_val, ten_bools_and_a_byte_ty.parse(&one_byte_data).unwrap());
     assert_eq!(two_byte_val, ten_bools_and_two_bytes_ty.parse(&two_byte_data).unwrap())
 }
+
+#[gtest(MojomParserTestSuit, ViewTest)]
+fn test_views() {
+    // A struct with a leaf, a string, a byte array, and a nested struct.
+    let ty = MojomType::Struct {
+        fields: vec![
+            MojomType::UInt32,
+            MojomType::String,
+            MojomType::Array { element_type: Box::new(MojomType::UInt8), num_elements: None },
+            MojomType::Struct {
+                fields: vec![MojomType::Bool, MojomType::Int16],
+                min_versions: vec![],
+            },
+        ],
+        min_versions: vec![],
+    };
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(&ty, 0)).unwrap();
+
+    let data: [u8; 88] = [
+        0x28, 0x00, 0x00, 0x00, // Header: Size in bytes (40)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0xef, 0xbe, 0xad, 0xde, // UInt32
+        0x00, 0x00, 0x00, 0x00, // Padding
+        0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to string (+24)
+        0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to byte array (+32)
+        0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to struct (+40)
+        // String
+        0x0a, 0x00, 0x00, 0x00, // Header: Size in bytes (10)
+        0x02, 0x00, 0x00, 0x00, // Header: Number of elements (2)
+        0x68, 0x69, // "hi"
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
+        // Byte array
+        0x0b, 0x00, 0x00, 0x00, // Header: Size in bytes (11)
+        0x03, 0x00, 0x00, 0x00, // Header: Number of elements (3)
+        0x01, 0x02, 0x03, // Elements
+        0x00, 0x00, 0x00, 0x00, 0x00, // Padding
+        // Nested struct
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x01, // Bitfield
+        0x00, // Padding
+        0xfe, 0xff, // Int16
+        0x00, 0x00, 0x00, 0x00, // Padding
+    ];
+
+    let view = view_struct(&data, &layout).unwrap();
+    expect_eq!(view.num_fields(), 4);
+    expect_eq!(view.field(0), Some(MojomValueRef::UInt32(0xdeadbeef)));
+    expect_eq!(view.field(1), Some(MojomValueRef::String("hi")));
+    match view.field(2) {
+        Some(MojomValueRef::Array(array)) => expect_eq!(array.as_bytes(), Some(&[1u8, 2, 3][..])),
+        other => panic!("Expected a byte array, got {:?}", other),
+    }
+    match view.field(3) {
+        Some(MojomValueRef::Struct(nested)) => {
+            expect_eq!(nested.field(0), Some(MojomValueRef::Bool(true)));
+            expect_eq!(nested.field(1), Some(MojomValueRef::Int16(-2)));
+        }
+        other => panic!("Expected a struct, got {:?}", other),
+    }
+    expect_eq!(view.field(4), None);
+
+    // Pointers must point exactly where the nested data is.
+    let mut bad_pointer = data;
+    bad_pointer[16] = 0x20;
+    expect_true!(view_struct(&bad_pointer, &layout).is_err());
+
+    // Strings must be valid UTF-8.
+    let mut bad_string = data;
+    bad_string[48] = 0xff;
+    expect_true!(view_struct(&bad_string, &layout).is_err());
+
+    // Padding inside an array's size must be zero.
+    let mut padded_array = data;
+    padded_array[56] = 0x0c;
+    expect_true!(view_struct(&padded_array, &layout).is_ok());
+    padded_array[67] = 0xff;
+    expect_true!(view_struct(&padded_array, &layout).is_err());
+
+    // Structs can't be smaller than their fields.
+    let mut bad_size = data;
+    bad_size[0] = 0x20;
+    expect_true!(view_struct(&bad_size, &layout).is_err());
+
+    // Truncated data is rejected up front, rather than when it's accessed.
+    expect_true!(view_struct(&data[..80], &layout).is_err());
+}
+
+#[gtest(MojomParserTestSuit, DescriptorTest)]
+fn test_descriptors() {
+    // struct Outer { int8 a; Inner b; array<Inner> c; } with struct Inner { bool x; }
+    let inner = TypeDescriptor::Struct {
+        name: "Inner".to_string(),
+        fields: vec![("x".to_string(), TypeDescriptor::Unnamed)],
+        defaults: vec![],
+    };
+    let outer = TypeDescriptor::Struct {
+        name: "Outer".to_string(),
+        fields: vec![
+            ("a".to_string(), TypeDescriptor::Unnamed),
+            ("b".to_string(), inner.clone()),
+            ("c".to_string(), TypeDescriptor::Array { element: Box::new(inner.clone()) }),
+        ],
+        defaults: vec![],
+    };
+
+    expect_eq!(outer.field_name(0), Some("a"));
+    expect_eq!(outer.field_name(1), Some("b"));
+    expect_eq!(outer.field_name(3), None);
+    expect_eq!(outer.field(1), &inner);
+    expect_eq!(outer.field(1).field_name(0), Some("x"));
+    expect_eq!(outer.field(0), &TypeDescriptor::Unnamed);
+    expect_eq!(outer.field(7), &TypeDescriptor::Unnamed);
+    expect_eq!(TypeDescriptor::Unnamed.field_name(0), None);
+
+    // A packed type which skips an ordinal can't be parsed into a value.
+    let skips_ordinal = vec![
+        MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::UInt32 },
+        MojomWireType::Leaf { ordinal: 2, leaf_type: PackedLeafType::UInt32 },
+    ];
+    let data: [u8; 16] = [
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x01, 0x00, 0x00, 0x00, // Ordinal 0
+        0x02, 0x00, 0x00, 0x00, // Ordinal 2
+    ];
+    expect_true!(parse_struct(&mut ParserData::new(&data), &skips_ordinal, &[]).is_err());
+}
+
+mojom_struct_layout! {
+    /// The same shape as TenBoolsAndAByte in test_bools, plus a version 1
+    /// field which fits in the padding and a version 2 field which doesn't.
+    struct TenBoolsAndAByteV2 {
+        e0: bool,
+        e1: bool,
+        e2: bool,
+        e3: bool,
+        e4: bool,
+        e5: u8,
+        e6: bool,
+        e7: bool,
+        e8: bool,
+        e9: bool,
+        e10: bool,
+        #[min_version = 1]
+        e11: u32,
+        #[min_version = 2]
+        e12: u64,
+    }
+}
+
+#[gtest(MojomParserTestSuit, ConstPackTest)]
+fn test_const_pack() {
+    const LAYOUT: StructPacking<13> = TenBoolsAndAByteV2::MOJOM_LAYOUT;
+
+    // The first 8 bools share a byte, then the uint8, then the last two bools.
+    for (ordinal, bit) in [(0, 0), (1, 1), (2, 2), (3, 3), (4, 4), (6, 5), (7, 6), (8, 7)] {
+        expect_eq!(LAYOUT.offset_in_struct(ordinal), 8);
+        expect_eq!(LAYOUT.bit(ordinal), Some(bit));
+    }
+    expect_eq!(LAYOUT.offset_in_struct(5), 9);
+    expect_eq!(LAYOUT.bit(5), None);
+    expect_eq!(LAYOUT.offset_in_struct(9), 10);
+    expect_eq!(LAYOUT.bit(9), Some(0));
+    expect_eq!(LAYOUT.offset_in_struct(10), 10);
+    expect_eq!(LAYOUT.bit(10), Some(1));
+    expect_eq!(LAYOUT.offset_in_struct(11), 12);
+    expect_eq!(LAYOUT.offset_in_struct(12), 16);
+
+    expect_eq!(LAYOUT.latest_version(), 2);
+    expect_eq!(LAYOUT.size_for_version(0), 16);
+    expect_eq!(LAYOUT.size_for_version(1), 16);
+    expect_eq!(LAYOUT.size_for_version(2), 24);
+    expect_eq!(LAYOUT.size(), 24);
+
+    // The layout agrees with the one used by the runtime parser.
+    let data: [u8; 16] = [
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x87, // Bitfield 1
+        0xcd, // One Byte
+        0x02, // Bitfield 2
+        0x00, // Padding
+        0x00, 0x00, 0x00, 0x00, // Padding
+    ];
+    let mut fields = vec![MojomType::Bool; 11];
+    fields[5] = MojomType::UInt8;
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(
+        &MojomType::Struct { fields, min_versions: vec![] },
+        0,
+    ))
+    .unwrap();
+    let view = view_struct(&data, &layout).unwrap();
+    for ordinal in 0..11 {
+        let offset = LAYOUT.offset_in_struct(ordinal);
+        let expected = match LAYOUT.bit(ordinal) {
+            Some(bit) => MojomValueRef::Bool((data[offset] >> bit) & 1 == 1),
+            None => MojomValueRef::UInt8(data[offset]),
+        };
+        expect_eq!(view.field(ordinal), Some(expected));
+    }
+}
+
+/// Inputs which used to make the parser panic, found by the fuzzers in
+/// fuzz_support.rs. They should all be rejected with an error instead.
+#[gtest(MojomParserTestSuit, MalformedInputTest)]
+fn test_malformed_input() {
+    let empty_struct = MojomWireType::Pointer {
+        ordinal: 0,
+        nested_data_type: PackedStructuredType::Struct {
+            packed_field_types: vec![],
+            min_versions: vec![],
+        },
+        nullable: false,
+    };
+
+    // A version 0 message header whose size is too big for its version.
+    let bad_header_size: [u8; 32] = [
+        0x20, 0x00, 0x00, 0x00, // Header: Size in bytes (32, should be 24)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x00, 0x00, 0x00, 0x00, // Header: Interface ID
+        0x00, 0x00, 0x00, 0x00, // Header: Message name
+        0x00, 0x00, 0x00, 0x00, // Header: Flags
+        0x00, 0x00, 0x00, 0x00, // Header: Trace nonce
+        0x08, 0x00, 0x00, 0x00, // Payload: Size in bytes (8)
+        0x00, 0x00, 0x00, 0x00, // Payload: Version number (0)
+    ];
+    expect_true!(parse_message(&bad_header_size, &empty_struct).is_err());
+    // The same message with the correct size is fine.
+    let mut good_header_size = bad_header_size;
+    good_header_size[0] = 0x18;
+    expect_eq!(
+        parse_message(&good_header_size, &empty_struct).unwrap(),
+        MojomValue::Struct(vec![])
+    );
+
+    // A pointer whose offset overflows when added to its location.
+    let one_pointer = vec![empty_struct.clone()];
+    let bad_pointer: [u8; 16] = [
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // Pointer (u64::MAX)
+    ];
+    expect_true!(parse_struct(&mut ParserData::new(&bad_pointer), &one_pointer, &[]).is_err());
+
+    // A packed type with two fields with the same ordinal.
+    let repeats_ordinal = vec![
+        MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::UInt32 },
+        MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::UInt32 },
+    ];
+    let data: [u8; 16] = [
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x01, 0x00, 0x00, 0x00, // Ordinal 0
+        0x02, 0x00, 0x00, 0x00, // Ordinal 0 again
+    ];
+    expect_true!(parse_struct(&mut ParserData::new(&data), &repeats_ordinal, &[]).is_err());
+}
+
+/// Inputs which are well-formed enough to parse, but break the rules that
+/// `ValidationContext` enforces.
+#[gtest(MojomParserTestSuit, ValidationTest)]
+fn test_validation() {
+    // Views must be validated exactly as strictly as parsing.
+    fn rejects(data: &[u8], fields: &[MojomWireType], min_versions: &[u32]) -> bool {
+        let layout = StructLayout::new(fields, min_versions);
+        parse_struct(&mut ParserData::new(data), fields, min_versions).is_err()
+            && view_struct(data, &layout).is_err()
+    }
+
+    let empty_struct = MojomWireType::Pointer {
+        ordinal: 0,
+        nested_data_type: PackedStructuredType::Struct {
+            packed_field_types: vec![],
+            min_versions: vec![],
+        },
+        nullable: false,
+    };
+    let pointer_and_int = vec![
+        empty_struct.clone(),
+        MojomWireType::Leaf { ordinal: 1, leaf_type: PackedLeafType::UInt64 },
+    ];
+    let mut data: [u8; 32] = [
+        0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer (16)
+        0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Ordinal 1
+        0x08, 0x00, 0x00, 0x00, // Nested: Size in bytes (8)
+        0x00, 0x00, 0x00, 0x00, // Nested: Version number (0)
+    ];
+    expect_eq!(
+        parse_struct(&mut ParserData::new(&data), &pointer_and_int, &[]).unwrap(),
+        vec![MojomValue::Struct(vec![]), MojomValue::UInt64(5)]
+    );
+    expect_true!(view_struct(&data, &StructLayout::new(&pointer_and_int, &[])).is_ok());
+    // A pointer back into its own struct.
+    data[8] = 0x08;
+    expect_true!(rejects(&data, &pointer_and_int, &[]));
+    // A pointer to an unaligned offset.
+    data[8] = 0x11;
+    expect_true!(rejects(&data, &pointer_and_int, &[]));
+    // A pointer past the end of the data.
+    data[8] = 0x18;
+    expect_true!(rejects(&data, &pointer_and_int, &[]));
+    data[8] = 0x10;
+
+    // Headers which are smaller than the header itself, or than the fields.
+    let mut too_small = data;
+    too_small[0] = 0x04;
+    expect_true!(rejects(&too_small, &pointer_and_int, &[]));
+    too_small[0] = 0x10;
+    expect_true!(rejects(&too_small, &pointer_and_int, &[]));
+    // A header which claims more bytes than there are.
+    let mut too_big = data;
+    too_big[0] = 0x28;
+    expect_true!(rejects(&too_big, &pointer_and_int, &[]));
+
+    // A version we know about must be exactly its size.
+    let int32_field = vec![MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::Int32 }];
+    let mut oversized: [u8; 24] = [
+        0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24, should be 16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x01, 0x00, 0x00, 0x00, // Ordinal 0, then padding
+        0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, // Extra bytes
+        0x00, 0x00, 0x00, 0x00, //
+    ];
+    expect_true!(rejects(&oversized, &int32_field, &[]));
+    // But a newer version may be bigger, since it has fields we don't know
+    // about.
+    oversized[4] = 0x01;
+    expect_eq!(
+        parse_struct(&mut ParserData::new(&oversized), &int32_field, &[]).unwrap(),
+        vec![MojomValue::Int32(1)]
+    );
+
+    // Padding must be zero in versions we know about, which can't have any
+    // fields we don't know about, but may be anything in newer versions.
+    let padded = vec![
+        MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::UInt8 },
+        MojomWireType::Leaf { ordinal: 1, leaf_type: PackedLeafType::UInt32 },
+    ];
+    let mut data: [u8; 16] = [
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x01, 0xff, 0x00, 0x00, // Ordinal 0, then padding
+        0x02, 0x00, 0x00, 0x00, // Ordinal 1
+    ];
+    expect_true!(rejects(&data, &padded, &[]));
+    data[4] = 0x01;
+    expect_eq!(
+        parse_struct(&mut ParserData::new(&data), &padded, &[]).unwrap(),
+        vec![MojomValue::UInt8(1), MojomValue::UInt32(2)]
+    );
+    // That includes versions after 0.
+    expect_true!(rejects(&data, &padded, &[0, 1]));
+    data[9] = 0x00;
+    expect_eq!(
+        parse_struct(&mut ParserData::new(&data), &padded, &[0, 1]).unwrap(),
+        vec![MojomValue::UInt8(1), MojomValue::UInt32(2)]
+    );
+
+    // An array whose size doesn't cover its elements.
+    let u32_element = MojomWireType::Leaf { ordinal: 0, leaf_type: PackedLeafType::UInt32 };
+    let short_array: [u8; 16] = [
+        0x0c, 0x00, 0x00, 0x00, // Header: Size in bytes (12, should be 16)
+        0x02, 0x00, 0x00, 0x00, // Header: Number of elements (2)
+        0x01, 0x00, 0x00, 0x00, // Element 0
+        0x02, 0x00, 0x00, 0x00, // Element 1
+    ];
+    let array_type = PackedArrayType::UnsizedArray;
+    expect_true!(
+        parse_array(&mut ParserData::new(&short_array), &u32_element, &array_type).is_err()
+    );
+    // The padding after an array's elements must be zero.
+    let mut padded_array: [u8; 16] = [
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x01, 0x00, 0x00, 0x00, // Header: Number of elements (1)
+        0x01, 0x00, 0x00, 0x00, // Element 0
+        0xff, 0x00, 0x00, 0x00, // Padding
+    ];
+    expect_true!(
+        parse_array(&mut ParserData::new(&padded_array), &u32_element, &array_type).is_err()
+    );
+    padded_array[12] = 0x00;
+    expect_eq!(
+        parse_array(&mut ParserData::new(&padded_array), &u32_element, &array_type).unwrap(),
+        MojomValue::Array(vec![MojomValue::UInt32(1)])
+    );
+
+    // Structs nested `depth` levels deep, counting the outermost one.
+    fn nested_struct(depth: usize) -> (Vec<MojomWireType>, Vec<MojomValue>) {
+        if depth == 1 {
+            return (vec![], vec![]);
+        }
+        let (packed_field_types, fields) = nested_struct(depth - 1);
+        let wire_type = MojomWireType::Pointer {
+            ordinal: 0,
+            nested_data_type: PackedStructuredType::Struct {
+                packed_field_types,
+                min_versions: vec![],
+            },
+            nullable: false,
+        };
+        (vec![wire_type], vec![MojomValue::Struct(fields)])
+    }
+    let (packed_field_types, fields) = nested_struct(10);
+    let mut data = vec![];
+    deparse_struct(&mut data, &fields, &packed_field_types, &[]).unwrap();
+    let mut context = ValidationContext::with_max_recursion_depth(10);
+    expect_eq!(
+        parse_struct_with_context(
+            &mut ParserData::new(&data),
+            &mut context,
+            &packed_field_types,
+            &[]
+        )
+        .unwrap(),
+        fields
+    );
+    let mut context = ValidationContext::with_max_recursion_depth(9);
+    expect_true!(parse_struct_with_context(
+        &mut ParserData::new(&data),
+        &mut context,
+        &packed_field_types,
+        &[]
+    )
+    .is_err());
+    let layout = StructLayout::new(&packed_field_types, &[]);
+    let mut context = ValidationContext::with_max_recursion_depth(10);
+    expect_true!(view_struct_with_context(&data, &mut context, &layout).is_ok());
+    let mut context = ValidationContext::with_max_recursion_depth(9);
+    expect_true!(view_struct_with_context(&data, &mut context, &layout).is_err());
+    let (packed_field_types, fields) = nested_struct(DEFAULT_MAX_RECURSION_DEPTH + 1);
+    let mut data = vec![];
+    deparse_struct(&mut data, &fields, &packed_field_types, &[]).unwrap();
+    expect_true!(rejects(&data, &packed_field_types, &[]));
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SerdeSimple {
+    id: u32,
+    name: String,
+    values: Vec<u16>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum SerdeColor {
+    Red,
+    Green,
+    Blue,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SerdePoint(i16, i16);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SerdeNested {
+    flag: bool,
+    simple: SerdeSimple,
+    color: SerdeColor,
+    flags: [bool; 3],
+    points: Vec<SerdePoint>,
+    names: Vec<String>,
+    other_flag: bool,
+    big: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SerdeOptional {
+    count: Option<u32>,
+    name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SerdeFloat {
+    single: f32,
+    double: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SerdeChar {
+    value: char,
+}
+
+#[gtest(MojomParserTestSuit, SerdeTest)]
+fn test_serde() {
+    let simple = SerdeSimple { id: 7, name: "hi".to_string(), values: vec![1, 2, 3] };
+    let expected: [u8; 64] = [
+        0x20, 0x00, 0x00, 0x00, // Header: Size in bytes (32)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x07, 0x00, 0x00, 0x00, // id
+        0x00, 0x00, 0x00, 0x00, // Padding
+        0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to name
+        0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to values
+        0x0a, 0x00, 0x00, 0x00, // name: Size in bytes (10)
+        0x02, 0x00, 0x00, 0x00, // name: Number of elements (2)
+        b'h', b'i', 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // name: Data and padding
+        0x0e, 0x00, 0x00, 0x00, // values: Size in bytes (14)
+        0x03, 0x00, 0x00, 0x00, // values: Number of elements (3)
+        0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00, 0x00, // values: Data and padding
+    ];
+    expect_eq!(to_bytes(&simple).unwrap(), expected);
+    expect_eq!(from_bytes::<SerdeSimple>(&expected).unwrap(), simple);
+
+    // The traced type must match the type that serialization produces.
+    let nested = SerdeNested {
+        flag: true,
+        simple,
+        color: SerdeColor::Blue,
+        flags: [false, true, true],
+        points: vec![SerdePoint(1, -1), SerdePoint(-300, 300)],
+        names: vec!["one".to_string(), String::new(), "three".to_string()],
+        other_flag: false,
+        big: -1 << 40,
+    };
+    let (ty, value) = to_mojom_value(&nested).unwrap();
+    expect_eq!(mojom_type_of::<SerdeNested>().unwrap(), ty);
+    expect_eq!(from_mojom_value::<SerdeNested>(value).unwrap(), nested);
+    let bytes = to_bytes(&nested).unwrap();
+    expect_eq!(from_bytes::<SerdeNested>(&bytes).unwrap(), nested);
+
+    let float = SerdeFloat { single: 0.5, double: -2.0 };
+    let float_bytes = to_bytes(&float).unwrap();
+    expect_eq!(
+        float_bytes,
+        vec![
+            0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+            0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+            0x00, 0x00, 0x00, 0x3f, // single, then padding
+            0x00, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x00, // double
+            0x00, 0x00, 0x00, 0xc0, //
+        ]
+    );
+    expect_eq!(from_bytes::<SerdeFloat>(&float_bytes).unwrap(), float);
+
+    // Empty arrays don't need to know their element type.
+    let empty = SerdeSimple { id: 0, name: String::new(), values: vec![] };
+    expect_eq!(from_bytes::<SerdeSimple>(&to_bytes(&empty).unwrap()).unwrap(), empty);
+
+    // Options map to nullable values.
+    let optional = SerdeOptional { count: Some(3), name: Some("three".to_string()) };
+    let (ty, value) = to_mojom_value(&optional).unwrap();
+    expect_eq!(mojom_type_of::<SerdeOptional>().unwrap(), ty);
+    expect_eq!(
+        ty,
+        MojomType::Struct {
+            fields: vec![
+                MojomType::Nullable(Box::new(MojomType::UInt32)),
+                MojomType::Nullable(Box::new(MojomType::String)),
+            ],
+            min_versions: vec![],
+        }
+    );
+    expect_eq!(from_mojom_value::<SerdeOptional>(value).unwrap(), optional);
+    expect_eq!(from_bytes::<SerdeOptional>(&to_bytes(&optional).unwrap()).unwrap(), optional);
+    // `None` can be decoded, but not encoded, since its type isn't known.
+    let none = SerdeOptional { count: None, name: None };
+    expect_true!(to_bytes(&none).is_err());
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        unreachable!("Structs always pack to pointers")
+    };
+    let mut none_bytes = vec![];
+    deparse_struct(
+        &mut none_bytes,
+        &[MojomValue::Null, MojomValue::Null],
+        &packed_field_types,
+        &[],
+    )
+    .unwrap();
+    expect_eq!(from_bytes::<SerdeOptional>(&none_bytes).unwrap(), none);
+    expect_true!(to_bytes(&SerdeOptional { count: None, ..optional }).is_err());
+
+    // Unsupported types are errors, as are trailing bytes and enum values
+    // with no corresponding variant.
+    expect_true!(to_bytes(&SerdeChar { value: 'a' }).is_err());
+    expect_true!(from_bytes::<SerdeChar>(&[]).is_err());
+    expect_true!(to_bytes(&7u32).is_err());
+    let mut trailing = expected.to_vec();
+    trailing.extend([0; 8]);
+    expect_true!(from_bytes::<SerdeSimple>(&trailing).is_err());
+    expect_true!(from_mojom_value::<SerdeColor>(MojomValue::Int32(3)).is_err());
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DerivePoint(i16, i16);
+
+#[derive(Debug, PartialEq, Clone, Copy, Mojom)]
+enum DeriveColor {
+    Red = 1,
+    Green = 2,
+    Blue = 4,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Mojom)]
+#[mojom(extensible)]
+enum DeriveShape {
+    #[mojom(default)]
+    Unknown,
+    Circle,
+    Square,
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveStruct {
+    id: u32,
+    color: DeriveColor,
+    #[mojom(nullable)]
+    name: Option<String>,
+    #[mojom(nullable)]
+    point: Option<DerivePoint>,
+    flags: [bool; 3],
+    shapes: Vec<DeriveShape>,
+    #[mojom(min_version = 1)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveNullableName {
+    id: u32,
+    #[mojom(nullable)]
+    name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveName {
+    id: u32,
+    name: String,
+}
+
+#[gtest(MojomParserTestSuit, DeriveTest)]
+fn test_derive() {
+    expect_eq!(
+        DeriveStruct::mojom_type(),
+        MojomType::Struct {
+            fields: vec![
+                MojomType::UInt32,
+                MojomType::Int32,
+                MojomType::Nullable(Box::new(MojomType::String)),
+                MojomType::Nullable(Box::new(MojomType::Struct {
+                    fields: vec![MojomType::Int16, MojomType::Int16],
+                    min_versions: vec![],
+                })),
+                MojomType::Array { element_type: Box::new(MojomType::Bool), num_elements: Some(3) },
+                MojomType::Array { element_type: Box::new(MojomType::Int32), num_elements: None },
+                MojomType::Array { element_type: Box::new(MojomType::String), num_elements: None },
+            ],
+            min_versions: vec![0, 0, 0, 0, 0, 0, 1],
+        }
+    );
+    let descriptor = DeriveStruct::type_descriptor();
+    expect_eq!(descriptor.field_name(3), Some("point"));
+    expect_eq!(descriptor.field(3).field_name(1), Some("1"));
+
+    let value = DeriveStruct {
+        id: 12,
+        color: DeriveColor::Blue,
+        name: Some("name".to_string()),
+        point: None,
+        flags: [true, false, true],
+        shapes: vec![DeriveShape::Square, DeriveShape::Circle],
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+    let bytes = encode_mojom(value).unwrap();
+    let decoded: DeriveStruct = decode_mojom(&bytes).unwrap();
+    expect_eq!(decoded.color, DeriveColor::Blue);
+    expect_eq!(decoded.point, None);
+    expect_eq!(decoded.name.as_deref(), Some("name"));
+    expect_eq!(decoded.shapes, vec![DeriveShape::Square, DeriveShape::Circle]);
+
+    // The views agree with the derived type about where things are.
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(&DeriveStruct::mojom_type(), 0));
+    let layout = layout.unwrap();
+    let view = view_struct(&bytes, &layout).unwrap();
+    expect_eq!(view.field(1), Some(MojomValueRef::Int32(4)));
+    expect_eq!(view.field(2), Some(MojomValueRef::String("name")));
+    expect_eq!(view.field(3), Some(MojomValueRef::Null));
+
+    // Null pointers are encoded as 0, and only allowed for nullable fields.
+    let null_name: [u8; 24] = [
+        0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x07, 0x00, 0x00, 0x00, // id
+        0x00, 0x00, 0x00, 0x00, // Padding
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Null pointer to name
+    ];
+    let value = DeriveNullableName { id: 7, name: None };
+    expect_eq!(encode_mojom(value).unwrap(), null_name);
+    expect_eq!(
+        decode_mojom::<DeriveNullableName>(&null_name).unwrap(),
+        DeriveNullableName { id: 7, name: None }
+    );
+    expect_true!(decode_mojom::<DeriveName>(&null_name).is_err());
+    let name_layout = StructLayout::from_wire_type(&pack_mojom_type(&DeriveName::mojom_type(), 0));
+    expect_true!(view_struct(&null_name, &name_layout.unwrap()).is_err());
+
+    // Unknown values are only allowed for extensible enums.
+    expect_eq!(DeriveColor::Green.into_mojom_value(), MojomValue::Int32(2));
+    expect_true!(DeriveColor::from_mojom_value(MojomValue::Int32(3)).is_err());
+    expect_eq!(DeriveShape::from_mojom_value(MojomValue::Int32(2)).unwrap(), DeriveShape::Square);
+    expect_eq!(DeriveShape::from_mojom_value(MojomValue::Int32(7)).unwrap(), DeriveShape::Unknown);
+
+    // Values from version 0 don't have the fields added in version 1.
+    let version_0 = MojomValue::Struct(vec![
+        MojomValue::UInt32(1),
+        MojomValue::Int32(1),
+        MojomValue::Null,
+        MojomValue::Struct(vec![MojomValue::Int16(-1), MojomValue::Int16(1)]),
+        MojomValue::Array(vec![MojomValue::Bool(false); 3]),
+        MojomValue::Array(vec![]),
+    ]);
+    let decoded = DeriveStruct::from_mojom_value(version_0.clone()).unwrap();
+    expect_eq!(decoded.point, Some(DerivePoint(-1, 1)));
+    expect_eq!(decoded.tags, Vec::<String>::new());
+    let MojomValue::Struct(mut missing_field) = version_0 else { unreachable!() };
+    missing_field.pop();
+    expect_true!(DeriveStruct::from_mojom_value(MojomValue::Struct(missing_field)).is_err());
+}
+
+const DERIVE_DEFAULT_ID: u32 = 40;
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveDefaults {
+    #[mojom(default = DERIVE_DEFAULT_ID + 2)]
+    id: u32,
+    color: DeriveColor,
+    #[mojom(min_version = 1, default = "unnamed".to_string())]
+    name: String,
+    #[mojom(min_version = 1)]
+    count: u8,
+}
+
+#[gtest(MojomParserTestSuit, DefaultsTest)]
+fn test_defaults() {
+    // struct S { bool a; string b; array<int8, 2> c; U? d; U e; } with
+    // union U { uint16 x; string y; }
+    let union = MojomType::Union { variants: vec![MojomType::UInt16, MojomType::String] };
+    let ty = MojomType::Struct {
+        fields: vec![
+            MojomType::Bool,
+            MojomType::String,
+            MojomType::Array { element_type: Box::new(MojomType::Int8), num_elements: Some(2) },
+            MojomType::Nullable(Box::new(union.clone())),
+            union,
+        ],
+        min_versions: vec![],
+    };
+    let zero = MojomValue::Struct(vec![
+        MojomValue::Bool(false),
+        MojomValue::String(String::new()),
+        MojomValue::Array(vec![MojomValue::Int8(0); 2]),
+        MojomValue::Null,
+        MojomValue::Union { tag: 0, value: Box::new(MojomValue::UInt16(0)) },
+    ]);
+    expect_eq!(MojomValue::default_for(&ty), zero);
+
+    // Declared defaults take precedence, but only with a descriptor.
+    let names = ["a", "b", "c", "d", "e"].map(|name| (name.to_string(), TypeDescriptor::Unnamed));
+    let descriptor = TypeDescriptor::Struct {
+        name: "S".to_string(),
+        fields: names.to_vec(),
+        defaults: vec![Some(MojomValue::Bool(true)), None, None, None, None],
+    };
+    let MojomValue::Struct(mut fields) = zero.clone() else { unreachable!() };
+    fields[0] = MojomValue::Bool(true);
+    expect_eq!(
+        MojomValue::default_with_descriptor(&ty, &descriptor),
+        MojomValue::Struct(fields.clone())
+    );
+
+    // Only fields with declared defaults may be left out.
+    fields[1] = MojomValue::String("b".to_string());
+    let mut given: Vec<_> =
+        names.iter().map(|(name, _)| name.as_str()).zip(fields.clone()).collect();
+    given.remove(0);
+    expect_eq!(
+        MojomValue::new_struct(&ty, &descriptor, given.clone()).unwrap(),
+        MojomValue::Struct(fields)
+    );
+    expect_true!(MojomValue::new_struct(&ty, &descriptor, given[1..].to_vec()).is_err());
+    given.push(("b", MojomValue::String(String::new())));
+    expect_true!(MojomValue::new_struct(&ty, &descriptor, given.clone()).is_err());
+    given.pop();
+    given.push(("f", MojomValue::Null));
+    expect_true!(MojomValue::new_struct(&ty, &descriptor, given).is_err());
+
+    // Derived structs list their defaults, and use them for the fields that
+    // values from older versions don't have.
+    let descriptor = DeriveDefaults::type_descriptor();
+    expect_eq!(descriptor.field_default(0), Some(&MojomValue::UInt32(42)));
+    expect_eq!(descriptor.field_default(1), None);
+    expect_eq!(descriptor.field_default(2), Some(&MojomValue::String("unnamed".to_string())));
+    expect_eq!(descriptor.field_default(3), None);
+    let version_0 = MojomValue::Struct(vec![MojomValue::UInt32(1), MojomValue::Int32(2)]);
+    expect_eq!(
+        DeriveDefaults::from_mojom_value(version_0).unwrap(),
+        DeriveDefaults { id: 1, color: DeriveColor::Green, name: "unnamed".to_string(), count: 0 }
+    );
+    let TypeDescriptor::Struct { defaults, .. } = DerivePoint::type_descriptor() else {
+        unreachable!()
+    };
+    expect_true!(defaults.is_empty());
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveFloats {
+    single: f32,
+    double: f64,
+    #[mojom(min_version = 1, default = f32::NAN)]
+    added: f32,
+}
+
+#[gtest(MojomParserTestSuit, FloatTest)]
+fn test_floats() {
+    // Floats are packed like the integers of the same size.
+    let value = DeriveFloats { single: 1.5, double: f64::NEG_INFINITY, added: 0.0 };
+    let bytes = encode_mojom(value).unwrap();
+    expect_eq!(
+        bytes,
+        vec![
+            0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+            0x01, 0x00, 0x00, 0x00, // Header: Version number (1)
+            0x00, 0x00, 0xc0, 0x3f, // single
+            0x00, 0x00, 0x00, 0x00, // added
+            0x00, 0x00, 0x00, 0x00, // double
+            0x00, 0x00, 0xf0, 0xff, //
+        ]
+    );
+    expect_eq!(
+        decode_mojom::<DeriveFloats>(&bytes).unwrap(),
+        DeriveFloats { single: 1.5, double: f64::NEG_INFINITY, added: 0.0 }
+    );
+
+    // NaN isn't equal to itself, so check the fields individually.
+    let mut version_0 = bytes[..24].to_vec();
+    version_0[4] = 0x00;
+    version_0[12..16].fill(0);
+    let decoded = decode_mojom::<DeriveFloats>(&version_0).unwrap();
+    expect_eq!(decoded.single, 1.5);
+    expect_true!(decoded.added.is_nan());
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveOptionals {
+    #[mojom(nullable)]
+    count: Option<i32>,
+    #[mojom(nullable)]
+    flag: Option<bool>,
+    #[mojom(nullable)]
+    color: Option<DeriveColor>,
+    #[mojom(nullable)]
+    scale: Option<f32>,
+}
+
+#[gtest(MojomParserTestSuit, NullableValueTest)]
+fn test_nullable_values() {
+    // Each field is split into a has-value flag, which is packed like a bool,
+    // followed by the value. Null values are written as 0.
+    let value =
+        DeriveOptionals { count: Some(7), flag: Some(false), color: None, scale: Some(0.5) };
+    let bytes = encode_mojom(value).unwrap();
+    expect_eq!(
+        bytes,
+        vec![
+            0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+            0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+            0x13, // Bitfield: count, flag, flag's value, color, scale
+            0x00, 0x00, 0x00, // Padding
+            0x07, 0x00, 0x00, 0x00, // count
+            0x00, 0x00, 0x00, 0x00, // color
+            0x00, 0x00, 0x00, 0x3f, // scale
+        ]
+    );
+    expect_eq!(
+        decode_mojom::<DeriveOptionals>(&bytes).unwrap(),
+        DeriveOptionals { count: Some(7), flag: Some(false), color: None, scale: Some(0.5) }
+    );
+
+    let layout =
+        StructLayout::from_wire_type(&pack_mojom_type(&DeriveOptionals::mojom_type(), 0)).unwrap();
+    let view = view_struct(&bytes, &layout).unwrap();
+    expect_eq!(view.field(0), Some(MojomValueRef::Int32(7)));
+    expect_eq!(view.field(1), Some(MojomValueRef::Bool(false)));
+    expect_eq!(view.field(2), Some(MojomValueRef::Null));
+    expect_eq!(view.field(3), Some(MojomValueRef::Float(0.5)));
+
+    // Values without a has-value flag are ignored.
+    let mut no_count = bytes.clone();
+    no_count[8] &= !0x01;
+    expect_eq!(
+        decode_mojom::<DeriveOptionals>(&no_count).unwrap(),
+        DeriveOptionals { count: None, flag: Some(false), color: None, scale: Some(0.5) }
+    );
+
+    let none = DeriveOptionals { count: None, flag: None, color: None, scale: None };
+    let bytes = encode_mojom(none).unwrap();
+    expect_true!(bytes[8..].iter().all(|byte| *byte == 0));
+    expect_eq!(
+        decode_mojom::<DeriveOptionals>(&bytes).unwrap(),
+        DeriveOptionals { count: None, flag: None, color: None, scale: None }
+    );
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveVersionedV0 {
+    a: i32,
+    b: i64,
+}
+
+/// A later version of `DeriveVersionedV0`, whose `c` is packed into the hole
+/// after `a`, and whose `d` goes after the end of the version 0 struct.
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveVersionedV1 {
+    a: i32,
+    b: i64,
+    #[mojom(min_version = 1, default = 7)]
+    c: i32,
+    #[mojom(min_version = 1)]
+    d: u64,
+}
+
+#[gtest(MojomParserTestSuit, VersionTest)]
+fn test_versions() {
+    let version_0 = encode_mojom(DeriveVersionedV0 { a: 1, b: 2 }).unwrap();
+    expect_eq!(
+        version_0,
+        vec![
+            0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+            0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+            0x01, 0x00, 0x00, 0x00, // a, then padding
+            0x00, 0x00, 0x00, 0x00, //
+            0x02, 0x00, 0x00, 0x00, // b
+            0x00, 0x00, 0x00, 0x00, //
+        ]
+    );
+
+    // The fields that version 0 doesn't have get their defaults.
+    expect_eq!(
+        decode_mojom::<DeriveVersionedV1>(&version_0).unwrap(),
+        DeriveVersionedV1 { a: 1, b: 2, c: 7, d: 0 }
+    );
+
+    // Structs are encoded as their latest version, and older code ignores the
+    // fields it doesn't know about.
+    let value = DeriveVersionedV1 { a: 1, b: 2, c: 3, d: 4 };
+    let version_1 = encode_mojom(value).unwrap();
+    expect_eq!(version_1.len(), 32);
+    expect_eq!(version_1[4], 1);
+    expect_eq!(&version_1[12..16], &[0x03, 0x00, 0x00, 0x00]);
+    expect_eq!(
+        decode_mojom::<DeriveVersionedV1>(&version_1).unwrap(),
+        DeriveVersionedV1 { a: 1, b: 2, c: 3, d: 4 }
+    );
+    expect_eq!(
+        decode_mojom::<DeriveVersionedV0>(&version_1).unwrap(),
+        DeriveVersionedV0 { a: 1, b: 2 }
+    );
+
+    // Views of older versions don't have the fields added after them.
+    let layout =
+        StructLayout::from_wire_type(&pack_mojom_type(&DeriveVersionedV1::mojom_type(), 0))
+            .unwrap();
+    let view = view_struct(&version_0, &layout).unwrap();
+    expect_eq!(view.version(), 0);
+    expect_eq!(view.field(0), Some(MojomValueRef::Int32(1)));
+    expect_eq!(view.field(1), Some(MojomValueRef::Int64(2)));
+    expect_eq!(view.field(2), None);
+    expect_eq!(view.field(3), None);
+    let view = view_struct(&version_1, &layout).unwrap();
+    expect_eq!(view.field(2), Some(MojomValueRef::Int32(3)));
+    expect_eq!(view.field(3), Some(MojomValueRef::UInt64(4)));
+
+    // Version 0 structs can't have anything in the hole that `c` fills later.
+    let mut bad_padding = version_0.clone();
+    bad_padding[12] = 0x03;
+    expect_true!(decode_mojom::<DeriveVersionedV1>(&bad_padding).is_err());
+
+    // A version 1 struct must be big enough for the version 1 fields.
+    let mut too_small = version_0;
+    too_small[4] = 0x01;
+    expect_true!(decode_mojom::<DeriveVersionedV1>(&too_small).is_err());
+    expect_true!(view_struct(&too_small, &layout).is_err());
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+enum DeriveUnion {
+    Number(u32),
+    Name(String),
+    #[mojom(nullable)]
+    Point(Option<DerivePoint>),
+    Inner(DeriveInnerUnion),
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+enum DeriveInnerUnion {
+    Flag(bool),
+    Big(u64),
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveUnionStruct {
+    id: i8,
+    value: DeriveUnion,
+    #[mojom(nullable)]
+    maybe: Option<DeriveInnerUnion>,
+    values: Vec<DeriveUnion>,
+}
+
+#[gtest(MojomParserTestSuit, UnionTest)]
+fn test_unions() {
+    // struct { int8 a; union { int32 x; string s; bool b; } u; union? n; }
+    let union_ty =
+        MojomType::Union { variants: vec![MojomType::Int32, MojomType::String, MojomType::Bool] };
+    let ty = MojomType::Struct {
+        fields: vec![
+            MojomType::Int8,
+            union_ty.clone(),
+            MojomType::Nullable(Box::new(union_ty.clone())),
+        ],
+        min_versions: vec![],
+    };
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        panic!("Structs must pack to pointers")
+    };
+    expect_eq!(packed_field_types.len(), 3);
+
+    let data: [u8; 64] = [
+        0x30, 0x00, 0x00, 0x00, // Header: Size in bytes (48)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0xff, // Int8
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
+        0x10, 0x00, 0x00, 0x00, // Union: Size in bytes (16)
+        0x01, 0x00, 0x00, 0x00, // Union: Tag (1)
+        0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Union: Pointer to string (+24)
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Null union
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Null union
+        // String
+        0x0a, 0x00, 0x00, 0x00, // Header: Size in bytes (10)
+        0x02, 0x00, 0x00, 0x00, // Header: Number of elements (2)
+        0x68, 0x69, // "hi"
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
+    ];
+    let value = vec![
+        MojomValue::Int8(-1),
+        MojomValue::Union { tag: 1, value: Box::new(MojomValue::String("hi".to_string())) },
+        MojomValue::Null,
+    ];
+    expect_eq!(parse_struct(&mut ParserData::new(&data), &packed_field_types, &[]).unwrap(), value);
+    let mut deparsed = vec![];
+    deparse_struct(&mut deparsed, &value, &packed_field_types, &[]).unwrap();
+    expect_eq!(deparsed, data);
+
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(&ty, 0)).unwrap();
+    let view = view_struct(&data, &layout).unwrap();
+    match view.field(1) {
+        Some(MojomValueRef::Union(union)) => {
+            expect_eq!(union.tag(), 1);
+            expect_eq!(union.value(), MojomValueRef::String("hi"));
+        }
+        other => panic!("Expected a union, got {:?}", other),
+    }
+    expect_eq!(view.field(2), Some(MojomValueRef::Null));
+
+    // Only nullable unions may be null.
+    let mut null_union = data;
+    null_union[16] = 0x00;
+    expect_true!(parse_struct(&mut ParserData::new(&null_union), &packed_field_types, &[]).is_err());
+    expect_true!(view_struct(&null_union, &layout).is_err());
+
+    // Unknown tags are rejected.
+    let mut bad_tag = data;
+    bad_tag[20] = 0x03;
+    expect_true!(parse_struct(&mut ParserData::new(&bad_tag), &packed_field_types, &[]).is_err());
+    expect_true!(view_struct(&bad_tag, &layout).is_err());
+
+    // Derived unions round-trip, including unions nested in unions and arrays.
+    let value = DeriveUnionStruct {
+        id: 3,
+        value: DeriveUnion::Inner(DeriveInnerUnion::Big(1 << 40)),
+        maybe: Some(DeriveInnerUnion::Flag(true)),
+        values: vec![
+            DeriveUnion::Number(7),
+            DeriveUnion::Name("name".to_string()),
+            DeriveUnion::Point(None),
+            DeriveUnion::Point(Some(DerivePoint(1, 2))),
+        ],
+    };
+    let bytes = encode_mojom(value).unwrap();
+    let decoded: DeriveUnionStruct = decode_mojom(&bytes).unwrap();
+    expect_eq!(decoded.value, DeriveUnion::Inner(DeriveInnerUnion::Big(1 << 40)));
+    expect_eq!(decoded.maybe, Some(DeriveInnerUnion::Flag(true)));
+    expect_eq!(decoded.values[1], DeriveUnion::Name("name".to_string()));
+    expect_eq!(decoded.values[2], DeriveUnion::Point(None));
+    expect_eq!(decoded.values[3], DeriveUnion::Point(Some(DerivePoint(1, 2))));
+
+    let descriptor = DeriveUnionStruct::type_descriptor();
+    expect_eq!(descriptor.field(1).field_name(3), Some("inner"));
+    expect_true!(DeriveUnion::from_mojom_value(MojomValue::Union {
+        tag: 4,
+        value: Box::new(MojomValue::UInt32(0))
+    })
+    .is_err());
+}
+
+/// The union that C++ generates for the response `result<bool, Point>`.
+#[derive(Debug, PartialEq, Mojom)]
+enum DeriveResultUnion {
+    Success(bool),
+    Failure(DerivePoint),
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveResultUnionResponse {
+    result: DeriveResultUnion,
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveResultResponse {
+    result: std::result::Result<bool, DerivePoint>,
+}
+
+#[gtest(MojomParserTestSuit, ResultTest)]
+fn test_results() {
+    // `Result`s are encoded as unions of `success` and `failure`.
+    let data: [u8; 24] = [
+        0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x10, 0x00, 0x00, 0x00, // Union: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Union: Tag (0)
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Union: Bool (true)
+    ];
+    let success = DeriveResultResponse { result: Ok(true) };
+    expect_eq!(encode_mojom(success).unwrap(), data);
+    expect_eq!(
+        decode_mojom::<DeriveResultResponse>(&data).unwrap(),
+        DeriveResultResponse { result: Ok(true) }
+    );
+
+    let failure = encode_mojom(DeriveResultResponse { result: Err(DerivePoint(1, -2)) }).unwrap();
+    let union_failure = encode_mojom(DeriveResultUnionResponse {
+        result: DeriveResultUnion::Failure(DerivePoint(1, -2)),
+    })
+    .unwrap();
+    expect_eq!(failure, union_failure);
+    expect_eq!(
+        decode_mojom::<DeriveResultResponse>(&failure).unwrap(),
+        DeriveResultResponse { result: Err(DerivePoint(1, -2)) }
+    );
+
+    let descriptor = DeriveResultResponse::type_descriptor();
+    expect_eq!(descriptor.field(0).field_name(1), Some("failure"));
+    let mut bad_tag = data;
+    bad_tag[12] = 0x02;
+    expect_true!(decode_mojom::<DeriveResultResponse>(&bad_tag).is_err());
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveMap {
+    entries: std::collections::BTreeMap<u8, u16>,
+}
+
+#[gtest(MojomParserTestSuit, MapTest)]
+fn test_maps() {
+    // Maps are encoded as a struct with an array of keys and an array of
+    // values.
+    let data: [u8; 72] = [
+        0x10, 0x00, 0x00, 0x00, // Header: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to map (8)
+        0x18, 0x00, 0x00, 0x00, // Map: Size in bytes (24)
+        0x00, 0x00, 0x00, 0x00, // Map: Version number (0)
+        0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Map: Pointer to keys (16)
+        0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Map: Pointer to values (24)
+        0x0a, 0x00, 0x00, 0x00, // Keys: Size in bytes (10)
+        0x02, 0x00, 0x00, 0x00, // Keys: Number of elements (2)
+        0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Keys: 1, 2, and padding
+        0x0c, 0x00, 0x00, 0x00, // Values: Size in bytes (12)
+        0x02, 0x00, 0x00, 0x00, // Values: Number of elements (2)
+        0x0a, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, // Values: 10, 20, and padding
+    ];
+    let map = DeriveMap { entries: [(2, 20), (1, 10)].into() };
+    expect_eq!(encode_mojom(map).unwrap(), data);
+    expect_eq!(
+        decode_mojom::<DeriveMap>(&data).unwrap(),
+        DeriveMap { entries: [(1, 10), (2, 20)].into() }
+    );
+
+    let hash_map = std::collections::HashMap::from([("a".to_string(), true)]);
+    let value = hash_map.clone().into_mojom_value();
+    expect_eq!(std::collections::HashMap::from_mojom_value(value).unwrap(), hash_map);
+
+    // There must be a value for every key.
+    let mut missing_value = data;
+    missing_value[60] = 0x01;
+    expect_true!(decode_mojom::<DeriveMap>(&missing_value).is_err());
+    let value = MojomValue::Struct(vec![
+        MojomValue::Array(vec![MojomValue::UInt8(1)]),
+        MojomValue::Array(vec![]),
+    ]);
+    expect_true!(std::collections::BTreeMap::<u8, u16>::from_mojom_value(value).is_err());
+
+    // Repeated keys keep their first value.
+    let value = MojomValue::Struct(vec![
+        MojomValue::Array(vec![MojomValue::UInt8(1), MojomValue::UInt8(1)]),
+        MojomValue::Array(vec![MojomValue::UInt16(10), MojomValue::UInt16(20)]),
+    ]);
+    expect_eq!(
+        std::collections::BTreeMap::<u8, u16>::from_mojom_value(value).unwrap(),
+        [(1, 10)].into()
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Mojom)]
+struct DeriveTree {
+    value: i32,
+    #[mojom(recursive)]
+    children: Vec<DeriveTree>,
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveNode {
+    value: i32,
+    #[mojom(nullable, recursive)]
+    next: Option<Box<DeriveNode>>,
+}
+
+/// Like `mojo_base.mojom.Value`, a union and a struct which contain each
+/// other.
+#[derive(Debug, PartialEq, Mojom)]
+enum DeriveRecursiveValue {
+    Int(i32),
+    #[mojom(recursive)]
+    List(DeriveRecursiveList),
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveRecursiveList {
+    #[mojom(recursive)]
+    values: Vec<DeriveRecursiveValue>,
+}
+
+#[gtest(MojomParserTestSuit, RecursiveTest)]
+fn test_recursive_types() {
+    // Recursive fields are encoded like any other.
+    let data: [u8; 72] = [
+        0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x01, 0x00, 0x00, 0x00, // Int32 (1)
+        0x00, 0x00, 0x00, 0x00, // Padding
+        0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to children (8)
+        0x10, 0x00, 0x00, 0x00, // Children: Size in bytes (16)
+        0x01, 0x00, 0x00, 0x00, // Children: Number of elements (1)
+        0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Children: Pointer to child (8)
+        0x18, 0x00, 0x00, 0x00, // Child: Size in bytes (24)
+        0x00, 0x00, 0x00, 0x00, // Child: Version number (0)
+        0x02, 0x00, 0x00, 0x00, // Child: Int32 (2)
+        0x00, 0x00, 0x00, 0x00, // Child: Padding
+        0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Child: Pointer to children (8)
+        0x08, 0x00, 0x00, 0x00, // Child's children: Size in bytes (8)
+        0x00, 0x00, 0x00, 0x00, // Child's children: Number of elements (0)
+    ];
+    let tree = DeriveTree { value: 1, children: vec![DeriveTree { value: 2, children: vec![] }] };
+    expect_eq!(encode_mojom(tree.clone()).unwrap(), data);
+    expect_eq!(decode_mojom::<DeriveTree>(&data).unwrap(), tree);
+
+    // The type only goes one level deep, and the rest is built when needed.
+    let children = MojomType::Recursive(RecursiveType::of::<Vec<DeriveTree>>());
+    let ty = DeriveTree::mojom_type();
+    expect_eq!(
+        ty,
+        MojomType::Struct {
+            fields: vec![MojomType::Int32, children.clone()],
+            min_versions: vec![]
+        }
+    );
+    expect_eq!(children.resolve().into_owned(), Vec::<DeriveTree>::mojom_type());
+    expect_eq!(
+        MojomValue::default_for(&ty),
+        MojomValue::Struct(vec![MojomValue::Int32(0), MojomValue::Array(vec![])])
+    );
+
+    // Views, JSON and pretty-printing follow recursive types too.
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(&ty, 0)).unwrap();
+    let view = view_struct(&data, &layout).unwrap();
+    let Some(MojomValueRef::Array(children)) = view.field(1) else { panic!("Expected children") };
+    let Some(MojomValueRef::Struct(child)) = children.get(0) else { panic!("Expected a child") };
+    expect_eq!(child.field(0), Some(MojomValueRef::Int32(2)));
+    let value = tree.into_mojom_value();
+    let descriptor = DeriveTree::type_descriptor();
+    let json = serde_json::json!({ "value": 1, "children": [{ "value": 2, "children": [] }] });
+    expect_eq!(value_to_json(&value, &ty, &descriptor).unwrap(), json);
+    expect_eq!(value_from_json(&json, &ty, &descriptor).unwrap(), value);
+    expect_eq!(
+        value.with_descriptor(&descriptor).to_string(),
+        "DeriveTree { value: 1, children: [DeriveTree { value: 2, children: [] }] }"
+    );
+
+    // Nullable recursive fields end the recursion with null.
+    let list = DeriveNode {
+        value: 1,
+        next: Some(Box::new(DeriveNode {
+            value: 2,
+            next: Some(Box::new(DeriveNode { value: 3, next: None })),
+        })),
+    };
+    let encoded = encode_mojom(list).unwrap();
+    expect_eq!(encoded.len(), 72);
+    let list = decode_mojom::<DeriveNode>(&encoded).unwrap();
+    expect_eq!(list.next.unwrap().next.unwrap().value, 3);
+
+    // Unions can be recursive too.
+    let value = DeriveRecursiveList {
+        values: vec![
+            DeriveRecursiveValue::Int(1),
+            DeriveRecursiveValue::List(DeriveRecursiveList {
+                values: vec![DeriveRecursiveValue::List(DeriveRecursiveList { values: vec![] })],
+            }),
+        ],
+    };
+    let encoded = encode_mojom(value).unwrap();
+    let DeriveRecursiveList { values } = decode_mojom(&encoded).unwrap();
+    expect_eq!(values[0], DeriveRecursiveValue::Int(1));
+    expect_eq!(
+        values[1],
+        DeriveRecursiveValue::List(DeriveRecursiveList {
+            values: vec![DeriveRecursiveValue::List(DeriveRecursiveList { values: vec![] })],
+        })
+    );
+
+    // Values which nest too deeply are rejected, rather than overflowing the
+    // stack.
+    let mut deep = DeriveNode { value: 0, next: None };
+    for value in 1..200 {
+        deep = DeriveNode { value, next: Some(Box::new(deep)) };
+    }
+    expect_true!(decode_mojom::<DeriveNode>(&encode_mojom(deep).unwrap()).is_err());
+}
+
+#[gtest(MojomParserTestSuit, AssociatedEndpointTest)]
+fn test_associated_endpoints() {
+    // struct { pending_associated_receiver<I> r; pending_associated_remote<I>? n; uint8 b; }
+    let ty = MojomType::Struct {
+        fields: vec![
+            MojomType::PendingAssociatedReceiver,
+            MojomType::Nullable(Box::new(MojomType::PendingAssociatedRemote)),
+            MojomType::UInt8,
+        ],
+        min_versions: vec![],
+    };
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        panic!("Structs must pack to pointers")
+    };
+
+    let data: [u8; 24] = [
+        0x18, 0x00, 0x00, 0x00, // Header: Size in bytes (24)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x00, 0x00, 0x00, 0x00, // Receiver: Interface ID index (0)
+        0xff, 0xff, 0xff, 0xff, // Remote: Interface ID index (invalid, so null)
+        0x00, 0x00, 0x00, 0x00, // Remote: Version (0)
+        0x07, // UInt8
+        0x00, 0x00, 0x00, // Padding
+    ];
+    let value = vec![
+        MojomValue::PendingAssociatedReceiver { index: 0 },
+        MojomValue::Null,
+        MojomValue::UInt8(7),
+    ];
+    expect_eq!(parse_struct(&mut ParserData::new(&data), &packed_field_types, &[]).unwrap(), value);
+    let mut deparsed = vec![];
+    deparse_struct(&mut deparsed, &value, &packed_field_types, &[]).unwrap();
+    expect_eq!(deparsed, data);
+
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(&ty, 0)).unwrap();
+    let view = view_struct(&data, &layout).unwrap();
+    expect_eq!(view.field(0), Some(MojomValueRef::PendingAssociatedReceiver { index: 0 }));
+    expect_eq!(view.field(1), Some(MojomValueRef::Null));
+
+    let mut non_null = data;
+    non_null[12..20].copy_from_slice(&[0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]);
+    expect_eq!(
+        parse_struct(&mut ParserData::new(&non_null), &packed_field_types, &[]).unwrap()[1],
+        MojomValue::PendingAssociatedRemote { index: 1, version: 2 }
+    );
+
+    // Only nullable endpoints may be invalid.
+    let mut null_receiver = data;
+    null_receiver[8..12].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+    expect_true!(
+        parse_struct(&mut ParserData::new(&null_receiver), &packed_field_types, &[]).is_err()
+    );
+    expect_true!(view_struct(&null_receiver, &layout).is_err());
+
+    // Messages which pass associated interfaces have a version 2 header, with
+    // their IDs after the payload.
+    let header = MessageHeader { interface_id: 5, name: 1, ..Default::default() };
+    let mut message = vec![];
+    deparse_message(&mut message, &header, &data, &[3, 0x80000001]).unwrap();
+    expect_eq!(message.len(), 48 + 24 + 16);
+    expect_eq!(message[4..8], [0x02, 0x00, 0x00, 0x00]);
+    let (parsed_header, payload, interface_ids) = parse_message_parts(&message).unwrap();
+    expect_eq!(parsed_header, header);
+    expect_eq!(payload, data);
+    expect_eq!(interface_ids, vec![3, 0x80000001]);
+
+    // Other messages keep the version 1 header.
+    let mut message = vec![];
+    deparse_message(&mut message, &header, &data, &[]).unwrap();
+    expect_eq!(message[4..8], [0x01, 0x00, 0x00, 0x00]);
+    expect_eq!(parse_message_parts(&message).unwrap(), (header, &data[..], vec![]));
+}
+
+#[gtest(MojomParserTestSuit, RemoteTest)]
+fn test_remotes() {
+    // struct { pending_receiver<I> r; pending_remote<I>? n; pending_remote<I> m; }
+    let ty = MojomType::Struct {
+        fields: vec![
+            MojomType::Handle,
+            MojomType::Nullable(Box::new(MojomType::PendingRemote)),
+            MojomType::PendingRemote,
+        ],
+        min_versions: vec![],
+    };
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = pack_mojom_type(&ty, 0)
+    else {
+        panic!("Structs must pack to pointers")
+    };
+
+    let data: [u8; 32] = [
+        0x20, 0x00, 0x00, 0x00, // Header: Size in bytes (32)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0x00, 0x00, 0x00, 0x00, // Receiver: Handle index (0)
+        0xff, 0xff, 0xff, 0xff, // Nullable remote: Handle index (invalid, so null)
+        0x00, 0x00, 0x00, 0x00, // Nullable remote: Version (0)
+        0x01, 0x00, 0x00, 0x00, // Remote: Handle index (1)
+        0x03, 0x00, 0x00, 0x00, // Remote: Version (3)
+        0x00, 0x00, 0x00, 0x00, // Padding
+    ];
+    let value = vec![
+        MojomValue::Handle { index: 0 },
+        MojomValue::Null,
+        MojomValue::PendingRemote { index: 1, version: 3 },
+    ];
+    expect_eq!(parse_struct(&mut ParserData::new(&data), &packed_field_types, &[]).unwrap(), value);
+    let mut deparsed = vec![];
+    deparse_struct(&mut deparsed, &value, &packed_field_types, &[]).unwrap();
+    expect_eq!(deparsed, data);
+
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(&ty, 0)).unwrap();
+    let view = view_struct(&data, &layout).unwrap();
+    expect_eq!(view.field(1), Some(MojomValueRef::Null));
+    expect_eq!(view.field(2), Some(MojomValueRef::PendingRemote { index: 1, version: 3 }));
+
+    // Only nullable remotes may be invalid.
+    let mut null_remote = data;
+    null_remote[20..24].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+    expect_true!(
+        parse_struct(&mut ParserData::new(&null_remote), &packed_field_types, &[]).is_err()
+    );
+    expect_true!(view_struct(&null_remote, &layout).is_err());
+}
+
+/// A handle for the tests, which is just its index in the message's handles.
+#[derive(Debug, PartialEq)]
+struct TestHandle(u32);
+
+impl Mojom for TestHandle {
+    fn mojom_type() -> MojomType {
+        MojomType::Handle
+    }
+
+    fn into_mojom_value(self) -> MojomValue {
+        MojomValue::Handle { index: self.0 }
+    }
+
+    fn from_mojom_value(value: MojomValue) -> Result<Self> {
+        match value {
+            MojomValue::Handle { index } => Ok(TestHandle(index)),
+            _ => anyhow::bail!("Expected a handle, but got {:?}", value),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveHandleInner {
+    handle: TestHandle,
+}
+
+#[derive(Debug, PartialEq, Mojom)]
+struct DeriveHandles {
+    a: i32,
+    inner: DeriveHandleInner,
+    handle: TestHandle,
+    #[mojom(nullable)]
+    maybe: Option<TestHandle>,
+}
+
+#[gtest(MojomParserTestSuit, HandleTest)]
+fn test_handles() {
+    // `handle` is packed into the hole after `a`, so it's encoded before the
+    // handle in `inner`, even though it's converted after it.
+    let data: [u8; 48] = [
+        0x20, 0x00, 0x00, 0x00, // Header: Size in bytes (32)
+        0x00, 0x00, 0x00, 0x00, // Header: Version number (0)
+        0xff, 0xff, 0xff, 0xff, // a (-1)
+        0x00, 0x00, 0x00, 0x00, // handle: Handle index (0)
+        0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Pointer to inner
+        0xff, 0xff, 0xff, 0xff, // maybe: Handle index (invalid, so null)
+        0x00, 0x00, 0x00, 0x00, // Padding
+        0x10, 0x00, 0x00, 0x00, // inner: Size in bytes (16)
+        0x00, 0x00, 0x00, 0x00, // inner: Version number (0)
+        0x01, 0x00, 0x00, 0x00, // inner.handle: Handle index (1)
+        0x00, 0x00, 0x00, 0x00, // Padding
+    ];
+    let value = DeriveHandles {
+        a: -1,
+        inner: DeriveHandleInner { handle: TestHandle(0) },
+        handle: TestHandle(1),
+        maybe: None,
+    };
+    let (bytes, handle_order) = encode_mojom_with_handles(value).unwrap();
+    expect_eq!(bytes, data);
+    expect_eq!(handle_order, vec![1, 0]);
+    expect_eq!(
+        decode_mojom::<DeriveHandles>(&data).unwrap(),
+        DeriveHandles {
+            a: -1,
+            inner: DeriveHandleInner { handle: TestHandle(1) },
+            handle: TestHandle(0),
+            maybe: None,
+        }
+    );
+
+    let layout = StructLayout::from_wire_type(&pack_mojom_type(&DeriveHandles::mojom_type(), 0));
+    let layout = layout.unwrap();
+    let view = view_struct(&data, &layout).unwrap();
+    expect_eq!(view.field(2), Some(MojomValueRef::Handle { index: 0 }));
+    expect_eq!(view.field(3), Some(MojomValueRef::Null));
+
+    // Only nullable handles may be invalid.
+    let mut null_handle = data;
+    null_handle[12..16].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+    expect_true!(decode_mojom::<DeriveHandles>(&null_handle).is_err());
+    expect_true!(view_struct(&null_handle, &layout).is_err());
+
+    // Values without handles are encoded the same way as by `encode_mojom`.
+    let (bytes, handle_order) = encode_mojom_with_handles(DerivePoint(1, 2)).unwrap();
+    expect_eq!(bytes, encode_mojom(DerivePoint(1, 2)).unwrap());
+    expect_eq!(handle_order, Vec::<u32>::new());
+
+    expect_eq!(
+        MojomValue::default_for(&MojomType::Handle),
+        MojomValue::Handle { index: INVALID_HANDLE_INDEX }
+    );
+    expect_eq!(MojomValue::Handle { index: 3 }.to_string(), "handle { index: 3 }");
+}
+
+#[gtest(MojomParserTestSuit, DisplayTest)]
+fn test_display() {
+    // struct Outer { int8 a; Inner b; array<Inner> c; string? d; U e; }
+    // with struct Inner { bool x; } and union U { string s; uint32 n; }
+    let inner = TypeDescriptor::Struct {
+        name: "Inner".to_string(),
+        fields: vec![("x".to_string(), TypeDescriptor::Unnamed)],
+        defaults: vec![],
+    };
+    let union = TypeDescriptor::Union {
+        name: "U".to_string(),
+        variants: vec![
+            ("s".to_string(), TypeDescriptor::Unnamed),
+            ("n".to_string(), TypeDescriptor::Unnamed),
+        ],
+    };
+    let outer = TypeDescriptor::Struct {
+        name: "Outer".to_string(),
+        fields: vec![
+            ("a".to_string(), TypeDescriptor::Unnamed),
+            ("b".to_string(), inner.clone()),
+            ("c".to_string(), TypeDescriptor::Array { element: Box::new(inner) }),
+            ("d".to_string(), TypeDescriptor::Unnamed),
+            ("e".to_string(), union),
+        ],
+        defaults: vec![],
+    };
+    let value = MojomValue::Struct(vec![
+        MojomValue::Int8(-1),
+        MojomValue::Struct(vec![MojomValue::Bool(true)]),
+        MojomValue::Array(vec![]),
+        MojomValue::Null,
+        MojomValue::Union { tag: 0, value: Box::new(MojomValue::String("hi\n".to_string())) },
+    ]);
+
+    // Without a descriptor, fields are shown by ordinal.
+    expect_eq!(
+        value.to_string(),
+        r#"struct { 0: -1, 1: struct { 0: true }, 2: [], 3: null, 4: union { 0: "hi\n" } }"#
+    );
+    expect_eq!(
+        value.with_descriptor(&outer).to_string(),
+        r#"Outer { a: -1, b: Inner { x: true }, c: [], d: null, e: U { s: "hi\n" } }"#
+    );
+    expect_eq!(
+        format!("{:#}", value.with_descriptor(&outer)),
+        r#"Outer {
+  a: -1,
+  b: Inner {
+    x: true,
+  },
+  c: [],
+  d: null,
+  e: U {
+    s: "hi\n",
+  },
+}"#
+    );
+    expect_eq!(MojomValue::Struct(vec![]).to_string(), "struct {}");
+    expect_eq!(
+        MojomValue::Array(vec![MojomValue::UInt16(1), MojomValue::UInt16(2)]).to_string(),
+        "[1, 2]"
+    );
+    expect_eq!(
+        MojomValue::PendingAssociatedRemote { index: 1, version: 2 }.to_string(),
+        "pending_associated_remote { index: 1, version: 2 }"
+    );
+    expect_eq!(
+        MojomValue::PendingRemote { index: INVALID_HANDLE_INDEX, version: 0 }.to_string(),
+        "pending_remote { index: invalid, version: 0 }"
+    );
+    expect_eq!(
+        MojomValue::PendingAssociatedReceiver { index: INVALID_ENDPOINT_INDEX }.to_string(),
+        "pending_associated_receiver { index: invalid }"
+    );
+
+    let header = MessageHeader { interface_id: 2, name: 7, ..Default::default() };
+    expect_eq!(header.to_string(), "interface 2, method 7, flags none");
+    let header = MessageHeader {
+        flags: MESSAGE_FLAG_IS_RESPONSE | MESSAGE_FLAG_IS_SYNC | 0x100,
+        trace_nonce: 9,
+        request_id: 3,
+        ..header
+    };
+    expect_eq!(
+        header.to_string(),
+        "interface 2, method 7, flags IS_RESPONSE | IS_SYNC | 0x100, trace nonce 9, request 3"
+    );
+}
+
+#[gtest(MojomParserTestSuit, SerializedSizeTest)]
+fn test_serialized_size() {
+    let union_struct = DeriveUnionStruct {
+        id: 3,
+        value: DeriveUnion::Name("name".to_string()),
+        maybe: Some(DeriveInnerUnion::Big(7)),
+        values: vec![
+            DeriveUnion::Point(Some(DerivePoint(1, 2))),
+            DeriveUnion::Point(None),
+            DeriveUnion::Inner(DeriveInnerUnion::Flag(true)),
+            DeriveUnion::Number(9),
+        ],
+    };
+    let derive_struct = DeriveStruct {
+        id: 12,
+        color: DeriveColor::Blue,
+        name: Some("a name that doesn't fit in 8 bytes".to_string()),
+        point: None,
+        flags: [true, false, true],
+        shapes: vec![DeriveShape::Square; 3],
+        tags: vec!["a".to_string(), String::new(), "b".to_string()],
+    };
+    let values = [
+        (DeriveUnionStruct::mojom_type(), union_struct.into_mojom_value()),
+        (DeriveStruct::mojom_type(), derive_struct.into_mojom_value()),
+        (DerivePoint::mojom_type(), DerivePoint(-1, 1).into_mojom_value()),
+    ];
+    for (ty, value) in values {
+        let wire_type = pack_mojom_type(&ty, 0);
+        let MojomWireType::Pointer {
+            nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+            ..
+        } = &wire_type
+        else {
+            panic!("Structs must pack to pointers")
+        };
+        let MojomValue::Struct(fields) = &value else { unreachable!() };
+
+        // The size is exactly what gets written.
+        let mut deparsed = vec![];
+        deparse_struct(&mut deparsed, fields, packed_field_types, &[]).unwrap();
+        let size = serialized_size(&value, &wire_type).unwrap();
+        expect_eq!(size, deparsed.len());
+        expect_eq!(struct_serialized_size(fields, packed_field_types).unwrap(), size);
+
+        // Deparsing into a buffer overwrites every byte, and fails if the
+        // buffer is too small.
+        let mut buffer = vec![0xff; size + 3];
+        expect_eq!(
+            deparse_struct_into(&mut buffer, fields, packed_field_types, &[]).unwrap(),
+            size
+        );
+        expect_eq!(buffer[..size], deparsed);
+        expect_eq!(buffer[size..], [0xff; 3]);
+        expect_true!(
+            deparse_struct_into(&mut buffer[..size - 1], fields, packed_field_types, &[]).is_err()
+        );
+
+        // Whole messages too, with and without associated interfaces.
+        let header = MessageHeader { name: 1, request_id: 2, ..Default::default() };
+        for interface_ids in [&[][..], &[1], &[1, 2, 3]] {
+            let mut message = vec![];
+            deparse_message(&mut message, &header, &deparsed, interface_ids).unwrap();
+            let message_size = message_serialized_size(deparsed.len(), interface_ids).unwrap();
+            expect_eq!(message_size, message.len());
+            let mut buffer = vec![0xff; message_size];
+            deparse_message_into(&mut buffer, &header, &deparsed, interface_ids).unwrap();
+            expect_eq!(buffer, message);
+        }
+    }
+
+    // A prepared struct is encoded straight into the message's buffer.
+    let encoded = encode_mojom(DerivePoint(-1, 1)).unwrap();
+    let (prepared, handle_order) = prepare_mojom_with_handles(DerivePoint(-1, 1)).unwrap();
+    expect_eq!(handle_order, Vec::<u32>::new());
+    expect_eq!(prepared.serialized_size(), encoded.len());
+    expect_eq!(prepared.to_bytes().unwrap(), encoded);
+    let header = MessageHeader { name: 1, request_id: 2, ..Default::default() };
+    for interface_ids in [&[][..], &[1, 2]] {
+        let mut expected = vec![];
+        deparse_message(&mut expected, &header, &encoded, interface_ids).unwrap();
+        let mut buffer = vec![0xff; expected.len()];
+        let written = deparse_message_with(
+            &mut buffer,
+            &header,
+            prepared.serialized_size(),
+            interface_ids,
+            |payload| prepared.deparse_into(payload),
+        )
+        .unwrap();
+        expect_eq!(written, expected.len());
+        expect_eq!(buffer, expected);
+    }
+    expect_true!(prepared.deparse_into(&mut vec![0; encoded.len() + 1]).is_err());
+
+    // Sizes are only computed for values of the right type, and deparsing a
+    // value of the wrong type leaves the buffer as it was.
+    let wire_type = pack_mojom_type(&DerivePoint::mojom_type(), 0);
+    let MojomWireType::Pointer {
+        nested_data_type: PackedStructuredType::Struct { packed_field_types, .. },
+        ..
+    } = &wire_type
+    else {
+        panic!("Structs must pack to pointers")
+    };
+    expect_true!(serialized_size(&MojomValue::Null, &wire_type).is_err());
+    let mut data = vec![1, 2, 3];
+    let wrong = [MojomValue::Int16(1), MojomValue::Int32(2)];
+    expect_true!(deparse_struct(&mut data, &wrong, packed_field_types, &[]).is_err());
+    expect_eq!(data, [1, 2, 3]);
+}
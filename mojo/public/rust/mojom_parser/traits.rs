@@ -0,0 +1,144 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `MojomTraits`, which maps mojom types onto arbitrary Rust types,
+//! like `StructTraits`, `EnumTraits` and `UnionTraits` do in C++.
+//!
+//! Normally, a field whose type is a mojom struct has the Rust type generated
+//! for that struct. A typemap replaces it with some other Rust type, e.g. a
+//! geometry library's `Rect` rather than the generated `gfx::mojom::Rect`.
+//! That type implements `MojomTraits<T>`, where `T` is the generated type, and
+//! the generated code (via `#[mojom(typemap = T)]`, see
+//! //mojo/public/rust/mojom_derive) converts through it whenever the field is
+//! encoded or decoded, so users never see `T`.
+//!
+//! Typemaps are declared with `rust_typemaps` in a mojom() GN target, which
+//! also lists the files implementing the traits. Rust's orphan rule only
+//! allows them to be implemented in the crate defining the mapped Rust type,
+//! or in the generated crate, so they're compiled into the latter.
+
+use crate::ast::*;
+use crate::convert::{field_from_mojom_value, Mojom};
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// A Rust type which is encoded as the mojom type of `T`, by converting to
+/// and from `T`.
+pub trait MojomTraits<T: Mojom>: Sized {
+    /// Convert to the mojom type, to be encoded.
+    fn to_mojom(self) -> T;
+
+    /// Convert from a decoded value of the mojom type. Fails if `mojom` isn't
+    /// a valid value of this type, which fails decoding the whole message, like
+    /// returning false from `Read()` in C++.
+    fn from_mojom(mojom: T) -> Result<Self>;
+}
+
+impl<T: Mojom, U: MojomTraits<T>> MojomTraits<Vec<T>> for Vec<U> {
+    fn to_mojom(self) -> Vec<T> {
+        self.into_iter().map(U::to_mojom).collect()
+    }
+
+    fn from_mojom(mojom: Vec<T>) -> Result<Self> {
+        mojom
+            .into_iter()
+            .enumerate()
+            .map(|(idx, element)| {
+                U::from_mojom(element).with_context(|| format!("In array element {}", idx))
+            })
+            .collect()
+    }
+}
+
+impl<T: Mojom, U: MojomTraits<T>, const N: usize> MojomTraits<[T; N]> for [U; N] {
+    fn to_mojom(self) -> [T; N] {
+        self.map(U::to_mojom)
+    }
+
+    fn from_mojom(mojom: [T; N]) -> Result<Self> {
+        let elements = Vec::<U>::from_mojom(mojom.into())?;
+        Ok(elements.try_into().unwrap_or_else(|_| unreachable!("The lengths are the same")))
+    }
+}
+
+/// Maps whose values are typemapped. Keys can't be.
+impl<K: Mojom + Eq + Hash, T: Mojom, U: MojomTraits<T>> MojomTraits<HashMap<K, T>>
+    for HashMap<K, U>
+{
+    fn to_mojom(self) -> HashMap<K, T> {
+        self.into_iter().map(|(key, value)| (key, value.to_mojom())).collect()
+    }
+
+    fn from_mojom(mojom: HashMap<K, T>) -> Result<Self> {
+        mojom
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (key, value))| {
+                Ok((key, U::from_mojom(value).with_context(|| format!("In map value {}", idx))?))
+            })
+            .collect()
+    }
+}
+
+impl<K: Mojom + Ord, T: Mojom, U: MojomTraits<T>> MojomTraits<BTreeMap<K, T>> for BTreeMap<K, U> {
+    fn to_mojom(self) -> BTreeMap<K, T> {
+        self.into_iter().map(|(key, value)| (key, value.to_mojom())).collect()
+    }
+
+    fn from_mojom(mojom: BTreeMap<K, T>) -> Result<Self> {
+        mojom
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (key, value))| {
+                Ok((key, U::from_mojom(value).with_context(|| format!("In map value {}", idx))?))
+            })
+            .collect()
+    }
+}
+
+/******************************************************************************
+ * Helpers for typemapped fields, used by #[derive(Mojom)].
+ ******************************************************************************/
+
+/// Convert the value of a field whose Rust type `U` is mapped onto `T`.
+pub fn typemapped_into_mojom_value<T: Mojom, U: MojomTraits<T>>(value: U) -> MojomValue {
+    value.to_mojom().into_mojom_value()
+}
+
+/// Convert the value of the field `field_name` of the struct `struct_name`,
+/// whose Rust type `U` is mapped onto `T`.
+pub fn typemapped_field_from_mojom_value<T: Mojom, U: MojomTraits<T>>(
+    value: MojomValue,
+    struct_name: &str,
+    field_name: &str,
+) -> Result<U> {
+    let mojom: T = field_from_mojom_value(value, struct_name, field_name)?;
+    U::from_mojom(mojom).with_context(|| format!("In field {}.{}", struct_name, field_name))
+}
+
+/// Convert the value of a nullable field whose Rust type is `Option<U>`,
+/// where `U` is mapped onto `T`.
+pub fn nullable_typemapped_into_mojom_value<T: Mojom, U: MojomTraits<T>>(
+    value: Option<U>,
+) -> MojomValue {
+    value.map_or(MojomValue::Null, typemapped_into_mojom_value::<T, U>)
+}
+
+/// Convert the value of the nullable field `field_name` of the struct
+/// `struct_name`, whose Rust type is `Option<U>`, where `U` is mapped onto
+/// `T`.
+pub fn nullable_typemapped_field_from_mojom_value<T: Mojom, U: MojomTraits<T>>(
+    value: MojomValue,
+    struct_name: &str,
+    field_name: &str,
+) -> Result<Option<U>> {
+    match value {
+        MojomValue::Null => Ok(None),
+        value => {
+            typemapped_field_from_mojom_value::<T, U>(value, struct_name, field_name).map(Some)
+        }
+    }
+}
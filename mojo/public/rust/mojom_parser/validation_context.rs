@@ -0,0 +1,138 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Tracks which parts of an encoded message have been claimed by the objects
+//! parsed so far, like validation_context.cc in the C++ bindings.
+//!
+//! Every struct, array and union in a message must lie after everything that
+//! came before it, so a pointer may never point backwards, into the object
+//! that contains it, or past the end of the data. Nesting is also limited, so
+//! that a malicious peer can't make the parser overflow the stack by sending
+//! deeply nested data.
+//!
+//! All offsets are counted from the start of the `ParserData` being parsed,
+//! i.e. they're the value of `bytes_parsed()` at that point.
+
+use crate::parse_primitives::ParserData;
+
+use anyhow::{bail, Context, Result};
+
+/// How deeply structs, arrays and unions may be nested by default.
+pub const DEFAULT_MAX_RECURSION_DEPTH: usize = 100;
+
+/// Bounds and depth tracking for a single parse. Use a fresh context for each
+/// message.
+#[derive(Debug, Clone)]
+pub struct ValidationContext {
+    /// Where the next object may begin. Everything before this has been
+    /// claimed.
+    claimed_end: usize,
+    depth: usize,
+    max_recursion_depth: usize,
+}
+
+impl Default for ValidationContext {
+    fn default() -> Self {
+        ValidationContext::new()
+    }
+}
+
+impl ValidationContext {
+    /// Create a context which allows `DEFAULT_MAX_RECURSION_DEPTH` levels of
+    /// nesting.
+    pub fn new() -> ValidationContext {
+        ValidationContext::with_max_recursion_depth(DEFAULT_MAX_RECURSION_DEPTH)
+    }
+
+    /// Create a context which allows `max_recursion_depth` levels of nesting.
+    /// A top-level struct is at depth 1, so 0 rejects everything.
+    pub fn with_max_recursion_depth(max_recursion_depth: usize) -> ValidationContext {
+        ValidationContext { claimed_end: 0, depth: 0, max_recursion_depth }
+    }
+
+    /// Claim `size_in_bytes` bytes for an object which begins at offset
+    /// `start`. Fails if the object isn't 8-byte aligned, overlaps anything
+    /// claimed before, or runs past the end of `data`.
+    pub(crate) fn claim_memory(
+        &mut self,
+        data: &ParserData,
+        start: usize,
+        size_in_bytes: usize,
+    ) -> Result<()> {
+        let data_end = data.bytes_parsed() + data.remaining_bytes();
+        if !start.is_multiple_of(8) {
+            bail!("Object at offset {} is not 8-byte aligned", start)
+        }
+        if start < self.claimed_end {
+            bail!(
+                "Object at offset {} overlaps data which was already claimed up to offset {}",
+                start,
+                self.claimed_end
+            )
+        }
+        if size_in_bytes > data_end.saturating_sub(start) {
+            bail!(
+                "Object at offset {} claimed to have {} bytes, but the data ends at offset {}",
+                start,
+                size_in_bytes,
+                data_end
+            )
+        }
+        self.claimed_end = start + size_in_bytes;
+        Ok(())
+    }
+
+    /// Check a pointer, which was at `pointer_location` and had value
+    /// `pointer_value`, and return the offset that it points to. The offset
+    /// must be 8-byte aligned, after everything claimed so far (which includes
+    /// the object containing the pointer), and inside the data.
+    pub(crate) fn validate_pointer(
+        &self,
+        data: &ParserData,
+        pointer_location: usize,
+        pointer_value: u64,
+    ) -> Result<usize> {
+        let target = usize::try_from(pointer_value)
+            .ok()
+            .and_then(|pointer_value| pointer_location.checked_add(pointer_value))
+            .with_context(|| format!("Pointer value {} wraps around", pointer_value))?;
+        if !target.is_multiple_of(8) {
+            bail!("Pointer at offset {} points to unaligned offset {}", pointer_location, target)
+        }
+        if target < self.claimed_end {
+            bail!(
+                "Pointer at offset {} points backwards to offset {}, but everything up to \
+                 offset {} was already claimed",
+                pointer_location,
+                target,
+                self.claimed_end
+            )
+        }
+        let data_end = data.bytes_parsed() + data.remaining_bytes();
+        if target >= data_end {
+            bail!(
+                "Pointer at offset {} points to offset {}, past the end of the data at {}",
+                pointer_location,
+                target,
+                data_end
+            )
+        }
+        Ok(target)
+    }
+
+    /// Record that we're about to parse a nested object. Fails if that would
+    /// exceed the maximum depth. Call `leave_nested` once it's parsed.
+    pub(crate) fn enter_nested(&mut self) -> Result<()> {
+        if self.depth >= self.max_recursion_depth {
+            bail!("Data is nested more than {} levels deep", self.max_recursion_depth)
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Record that we've finished parsing a nested object.
+    pub(crate) fn leave_nested(&mut self) {
+        self.depth -= 1;
+    }
+}
@@ -0,0 +1,940 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines zero-copy views of encoded Mojom values.
+//!
+//! The parsers in parse_values.rs produce a `MojomValue`, which owns all of its
+//! data. This module provides an alternative: a `MojomValueRef` borrows
+//! directly from the encoded bytes, so strings and byte arrays are never
+//! copied and no per-field allocations are made.
+//!
+//! Using a view is a two-step process. First, a `StructLayout` is computed
+//! from a packed struct type. The layout records where each field lives on the
+//! wire, indexed by ordinal, and can be reused for any number of messages.
+//! Second, the encoded data is validated against the layout once, by
+//! `view_struct` or `view_message`, exactly as strictly as the parsers
+//! validate it. After that, every field can be accessed in O(1) time without
+//! any further bounds checks failing.
+
+use crate::ast::*;
+use crate::parse_messages::parse_message_header;
+use crate::parse_primitives::*;
+use crate::serialized_size::{field_min_version, struct_latest_version, struct_version_size};
+use crate::validation_context::ValidationContext;
+
+use anyhow::{bail, Context, Result};
+use std::sync::OnceLock;
+
+/// Size in bytes of the header at the start of every struct and array.
+const HEADER_SIZE: usize = 8;
+
+/// Where a single field of a struct lives on the wire.
+#[derive(Debug, Clone, PartialEq)]
+enum FieldLayout {
+    Leaf { offset: usize, leaf_type: PackedLeafType },
+    Bit { offset: usize, bit: u8 },
+    Pointer { offset: usize, nested: NestedLayout, nullable: bool },
+    Union { offset: usize, layout: UnionLayout, nullable: bool },
+}
+
+/// The layout of a struct, array or union, which appears after its enclosing
+/// struct.
+#[derive(Debug, Clone, PartialEq)]
+enum NestedLayout {
+    Struct(StructLayout),
+    Array(ArrayLayout),
+    /// A union pointed to by a field of another union.
+    Union(UnionLayout),
+    Recursive(RecursiveLayout),
+}
+
+/// The layout of a recursive type, which is only computed when it's first
+/// needed, and then kept. Otherwise, computing it would never end.
+#[derive(Debug, Clone)]
+struct RecursiveLayout {
+    ty: RecursiveType,
+    layout: OnceLock<Box<NestedLayout>>,
+}
+
+/// Layouts of the same type are equal, whether they've been computed or not.
+impl PartialEq for RecursiveLayout {
+    fn eq(&self, other: &Self) -> bool {
+        self.ty == other.ty
+    }
+}
+
+/// The layout of a single array element, or of the value of a union.
+#[derive(Debug, Clone, PartialEq)]
+enum ElementLayout {
+    Leaf(PackedLeafType),
+    /// Bool arrays are packed into bits, 8 per byte. A bool in a union is
+    /// stored in the lowest bit of its first byte.
+    Bool,
+    Pointer {
+        nested: Box<NestedLayout>,
+        nullable: bool,
+    },
+    /// Unions are stored inline in arrays, but never in other unions.
+    Union {
+        layout: UnionLayout,
+        nullable: bool,
+    },
+}
+
+/// The precomputed wire locations of every field of a packed struct, indexed
+/// by ordinal.
+///
+/// A layout only depends on the type, not on any particular value, so it
+/// should be computed once and reused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLayout {
+    /// Entry i holds the location of the field with ordinal i. Ordinals that
+    /// don't appear in the packed type are `None`.
+    fields: Vec<Option<FieldLayout>>,
+    /// Entry i holds the offset and bit of the has-value flag of the field
+    /// with ordinal i, if it's a nullable value field.
+    has_value_flags: Vec<Option<(usize, u8)>>,
+    /// The version of the struct which added each field, indexed by ordinal,
+    /// as in `PackedStructuredType::Struct`.
+    min_versions: Vec<u32>,
+    /// Entry i holds the number of bytes that version i of the struct
+    /// occupies, including the header but not including any nested data. The
+    /// last entry is for the latest version.
+    version_sizes: Vec<usize>,
+    /// The offset, size and version of each packed field, in packed order,
+    /// which is also the order of their offsets. Everything else is padding.
+    packed_fields: Vec<(usize, usize, u32)>,
+}
+
+/// The precomputed information needed to validate and index into an array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayLayout {
+    element: ElementLayout,
+    array_type: PackedArrayType,
+}
+
+/// The layout of the value of each of a union's fields, indexed by tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnionLayout {
+    variants: Vec<ElementLayout>,
+}
+
+/// Size in bytes of a union: its size and tag, followed by an 8-byte value.
+const UNION_SIZE: usize = 16;
+
+fn leaf_size(leaf_type: &PackedLeafType) -> usize {
+    match leaf_type {
+        PackedLeafType::Int8 | PackedLeafType::UInt8 => 1,
+        PackedLeafType::Int16 | PackedLeafType::UInt16 => 2,
+        PackedLeafType::Int32
+        | PackedLeafType::UInt32
+        | PackedLeafType::Float
+        | PackedLeafType::AssociatedEndpoint { .. }
+        | PackedLeafType::Handle { .. } => 4,
+        PackedLeafType::Int64
+        | PackedLeafType::UInt64
+        | PackedLeafType::Double
+        | PackedLeafType::Interface { .. }
+        | PackedLeafType::AssociatedInterface { .. } => 8,
+    }
+}
+
+/// Check that the leaf at `offset` is only a null associated endpoint,
+/// remote or handle if it's allowed to be. Other leaves are always valid.
+fn validate_leaf(bytes: &[u8], offset: usize, leaf_type: &PackedLeafType) -> Result<()> {
+    let (invalid_index, kind) = match leaf_type {
+        PackedLeafType::AssociatedEndpoint { nullable: false }
+        | PackedLeafType::AssociatedInterface { nullable: false } => {
+            (INVALID_ENDPOINT_INDEX, "associated endpoint")
+        }
+        PackedLeafType::Interface { nullable: false } => (INVALID_HANDLE_INDEX, "remote"),
+        PackedLeafType::Handle { nullable: false } => (INVALID_HANDLE_INDEX, "handle"),
+        _ => return Ok(()),
+    };
+    let mut leaf_data = ParserData::new(bytes.get(offset..).context("Leaf out of range")?);
+    if parse_u32(&mut leaf_data)? == invalid_index {
+        bail!("Non-nullable {} at {} was null", kind, offset)
+    }
+    Ok(())
+}
+
+fn align_up(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+impl NestedLayout {
+    fn new(ty: &PackedStructuredType) -> NestedLayout {
+        match ty {
+            PackedStructuredType::Struct { packed_field_types, min_versions } => {
+                NestedLayout::Struct(StructLayout::new(packed_field_types, min_versions))
+            }
+            PackedStructuredType::Array { element_type, array_type } => {
+                NestedLayout::Array(ArrayLayout::new(element_type, array_type))
+            }
+            PackedStructuredType::Union { variants } => {
+                NestedLayout::Union(UnionLayout::new(variants))
+            }
+            PackedStructuredType::Recursive(ty) => {
+                NestedLayout::Recursive(RecursiveLayout { ty: *ty, layout: OnceLock::new() })
+            }
+        }
+    }
+
+    /// If this is a recursive type's layout, compute it if it hasn't been
+    /// already, and return it. Otherwise, return the layout as is.
+    fn resolve(&self) -> &NestedLayout {
+        match self {
+            NestedLayout::Recursive(RecursiveLayout { ty, layout }) => layout.get_or_init(|| {
+                Box::new(NestedLayout::new(&PackedStructuredType::Recursive(*ty).resolve()))
+            }),
+            _ => self,
+        }
+    }
+}
+
+impl ElementLayout {
+    fn new(wire_type: &MojomWireType) -> ElementLayout {
+        match wire_type {
+            MojomWireType::Leaf { leaf_type, .. } => ElementLayout::Leaf(leaf_type.clone()),
+            MojomWireType::Bitfield { .. } => ElementLayout::Bool,
+            MojomWireType::Pointer { nested_data_type, nullable, .. } => ElementLayout::Pointer {
+                nested: Box::new(NestedLayout::new(nested_data_type)),
+                nullable: *nullable,
+            },
+            MojomWireType::Union { variants, nullable, .. } => {
+                ElementLayout::Union { layout: UnionLayout::new(variants), nullable: *nullable }
+            }
+        }
+    }
+}
+
+impl UnionLayout {
+    fn new(variants: &[MojomWireType]) -> UnionLayout {
+        UnionLayout { variants: variants.iter().map(ElementLayout::new).collect() }
+    }
+}
+
+impl StructLayout {
+    /// Compute the layout of a struct from its packed fields, which were added
+    /// in the given versions (see `PackedStructuredType::Struct`).
+    pub fn new(packed_fields: &[MojomWireType], min_versions: &[u32]) -> StructLayout {
+        let mut fields: Vec<Option<FieldLayout>> = vec![];
+        let mut has_value_flags: Vec<Option<(usize, u8)>> = vec![];
+        let mut extents: Vec<(usize, usize, u32)> = vec![];
+        let mut place = |ordinal: Ordinal, field: FieldLayout| {
+            if fields.len() <= ordinal {
+                fields.resize(ordinal + 1, None);
+            }
+            fields[ordinal] = Some(field);
+        };
+
+        // This mirrors the way parse_struct walks over the fields.
+        let mut offset = HEADER_SIZE;
+        for wire_type in packed_fields {
+            offset = align_up(offset, wire_type.alignment());
+            match wire_type {
+                MojomWireType::Leaf { ordinal, leaf_type } => {
+                    place(*ordinal, FieldLayout::Leaf { offset, leaf_type: leaf_type.clone() })
+                }
+                MojomWireType::Bitfield { ordinals, has_value_bits } => {
+                    for (bit, ordinal) in ordinals.iter().map_while(|o| *o).enumerate() {
+                        if (has_value_bits >> bit) & 1 == 1 {
+                            if has_value_flags.len() <= ordinal {
+                                has_value_flags.resize(ordinal + 1, None);
+                            }
+                            has_value_flags[ordinal] = Some((offset, bit as u8));
+                        } else {
+                            place(ordinal, FieldLayout::Bit { offset, bit: bit as u8 })
+                        }
+                    }
+                }
+                MojomWireType::Pointer { ordinal, nested_data_type, nullable } => place(
+                    *ordinal,
+                    FieldLayout::Pointer {
+                        offset,
+                        nested: NestedLayout::new(nested_data_type),
+                        nullable: *nullable,
+                    },
+                ),
+                MojomWireType::Union { ordinal, variants, nullable } => place(
+                    *ordinal,
+                    FieldLayout::Union {
+                        offset,
+                        layout: UnionLayout::new(variants),
+                        nullable: *nullable,
+                    },
+                ),
+            }
+            let min_version = field_min_version(min_versions, wire_type.first_ordinal());
+            extents.push((offset, wire_type.size(), min_version));
+            offset += wire_type.size();
+        }
+
+        let version_sizes = (0..=struct_latest_version(min_versions))
+            .map(|version| struct_version_size(packed_fields, min_versions, version))
+            .collect();
+        StructLayout {
+            fields,
+            has_value_flags,
+            min_versions: min_versions.to_vec(),
+            version_sizes,
+            packed_fields: extents,
+        }
+    }
+
+    /// Compute the layout of a struct from its wire type, which must be a
+    /// pointer to a struct (as returned by `pack_mojom_type`).
+    pub fn from_wire_type(ty: &MojomWireType) -> Result<StructLayout> {
+        match ty {
+            MojomWireType::Pointer {
+                nested_data_type: PackedStructuredType::Struct { packed_field_types, min_versions },
+                ..
+            } => Ok(StructLayout::new(packed_field_types, min_versions)),
+            _ => bail!("Expected a struct type, but got {:?}", ty),
+        }
+    }
+
+    /// The latest version of the struct that we know about.
+    fn latest_version(&self) -> u32 {
+        (self.version_sizes.len() - 1) as u32
+    }
+
+    /// Whether the field with the given ordinal is in the given version of
+    /// the struct.
+    fn has_field(&self, ordinal: Ordinal, version: u32) -> bool {
+        field_min_version(&self.min_versions, ordinal) <= version
+    }
+}
+
+impl ArrayLayout {
+    fn new(element_type: &MojomWireType, array_type: &PackedArrayType) -> ArrayLayout {
+        ArrayLayout { element: ElementLayout::new(element_type), array_type: array_type.clone() }
+    }
+
+    /// The number of bytes needed to store `num_elements` elements, including
+    /// the header.
+    fn storage_size(&self, num_elements: usize) -> Option<usize> {
+        let elements_size = match &self.element {
+            ElementLayout::Leaf(leaf_type) => num_elements.checked_mul(leaf_size(leaf_type))?,
+            ElementLayout::Bool => num_elements.div_ceil(8),
+            ElementLayout::Pointer { .. } => num_elements.checked_mul(8)?,
+            ElementLayout::Union { .. } => num_elements.checked_mul(UNION_SIZE)?,
+        };
+        elements_size.checked_add(HEADER_SIZE)
+    }
+}
+
+/******************************************************************************
+ * Validation
+ ******************************************************************************/
+
+/// Skip ahead to the given offset (from the start of `data`'s buffer).
+fn skip_to(data: &mut ParserData, offset: usize) -> Result<()> {
+    let bytes_parsed = data.bytes_parsed();
+    if offset < bytes_parsed {
+        bail!("Tried to skip back to offset {}, but we've already parsed {}", offset, bytes_parsed)
+    }
+    parse_padding(data, offset - bytes_parsed)
+}
+
+/// Check that the bytes from `start` to `end` are all zero.
+fn validate_zero_padding(bytes: &[u8], start: usize, end: usize) -> Result<()> {
+    let padding = bytes.get(start..).context("Padding out of range")?;
+    parse_zero_padding(&mut ParserData::new(padding), end - start)
+        .with_context(|| format!("In the padding at {}", start))
+}
+
+/// A pointer whose nested data still needs to be validated: the offset it
+/// points to, and the layout of the data there.
+type PendingPointer<'a> = (usize, &'a NestedLayout);
+
+/// Read the pointer stored at `ptr_offset`, and check it like `parse_struct`
+/// does. Return the offset it points to, or `None` if it's null, which is only
+/// allowed if the pointer is nullable.
+///
+/// The object containing the pointer must already have been claimed in
+/// `context`, so that pointers into it (or before it) are rejected.
+fn read_pointer(
+    bytes: &[u8],
+    data: &ParserData,
+    context: &ValidationContext,
+    ptr_offset: usize,
+    nullable: bool,
+) -> Result<Option<usize>> {
+    let mut ptr_data = ParserData::new(bytes.get(ptr_offset..).context("Pointer out of range")?);
+    let pointer_value = parse_u64(&mut ptr_data)?;
+    if pointer_value == 0 {
+        if !nullable {
+            bail!("Non-nullable pointer at {} was null", ptr_offset)
+        }
+        return Ok(None);
+    }
+    context.validate_pointer(data, ptr_offset, pointer_value).map(Some)
+}
+
+/// Validate a nested struct, array or union which must begin exactly at
+/// `expected_offset`, where a pointer said it would. The cursor must be
+/// positioned at the start of the nested data.
+fn validate_pointee(
+    bytes: &[u8],
+    data: &mut ParserData,
+    context: &mut ValidationContext,
+    (expected_offset, nested): PendingPointer,
+) -> Result<()> {
+    if expected_offset != data.bytes_parsed() {
+        bail!(
+            "Nested data was at offset {}, but a pointer expected it at {}",
+            data.bytes_parsed(),
+            expected_offset
+        );
+    }
+    match nested.resolve() {
+        NestedLayout::Struct(layout) => validate_struct(bytes, data, context, layout),
+        NestedLayout::Array(layout) => validate_array(bytes, data, context, layout),
+        NestedLayout::Union(layout) => {
+            context.enter_nested()?;
+            let start = data.bytes_parsed();
+            context.claim_memory(data, start, UNION_SIZE)?;
+            skip_to(data, start + UNION_SIZE)?;
+            // A null union is represented by a null pointer, so the union
+            // itself can't be null.
+            if let Some(pointer) =
+                validate_inline_union(bytes, data, context, start, layout, false)?
+            {
+                validate_pointee(bytes, data, context, pointer)?;
+            }
+            context.leave_nested();
+            Ok(())
+        }
+        NestedLayout::Recursive(_) => unreachable!("Resolved layouts aren't recursive"),
+    }
+}
+
+/// Validate the size and tag of the union at `offset`, which must be in
+/// bounds. If the union's value is a non-null pointer, return it so that the
+/// caller can validate the nested data once it reaches it.
+fn validate_inline_union<'a>(
+    bytes: &[u8],
+    data: &ParserData,
+    context: &ValidationContext,
+    offset: usize,
+    layout: &'a UnionLayout,
+    nullable: bool,
+) -> Result<Option<PendingPointer<'a>>> {
+    let mut union_data = ParserData::new(bytes.get(offset..).context("Union out of range")?);
+    let size_in_bytes: usize = parse_u32(&mut union_data)?.try_into()?;
+    let tag: usize = parse_u32(&mut union_data)?.try_into()?;
+    if size_in_bytes == 0 {
+        if !nullable {
+            bail!("Non-nullable union at {} was null", offset)
+        }
+        return Ok(None);
+    }
+    if size_in_bytes != UNION_SIZE {
+        bail!(
+            "Union at {} claimed to have {} bytes, but unions have {} bytes",
+            offset,
+            size_in_bytes,
+            UNION_SIZE
+        )
+    }
+    let variant = layout
+        .variants
+        .get(tag)
+        .with_context(|| format!("Union at {} had unknown tag {}", offset, tag))?;
+    Ok(match variant {
+        ElementLayout::Pointer { nested, nullable } => {
+            read_pointer(bytes, data, context, offset + 8, *nullable)?
+                .map(|target| (target, &**nested))
+        }
+        ElementLayout::Leaf(leaf_type) => {
+            validate_leaf(bytes, offset + 8, leaf_type)?;
+            None
+        }
+        _ => None,
+    })
+}
+
+/// Validate a struct starting at the current position of `data`, along with
+/// all of its nested data. On success, `data` is positioned at the end of the
+/// struct's nested data.
+///
+/// This checks everything that `parse_struct_with_context` does, with the
+/// same `context`. A struct from a version we know about must be exactly the
+/// size of that version, and all of its padding must be zero, while one from
+/// a newer version must be at least the size of the latest one. Only the
+/// fields in the struct's version are validated.
+fn validate_struct(
+    bytes: &[u8],
+    data: &mut ParserData,
+    context: &mut ValidationContext,
+    layout: &StructLayout,
+) -> Result<()> {
+    context.enter_nested()?;
+    let start = data.bytes_parsed();
+    let size_in_bytes: usize = parse_u32(data)?.try_into()?;
+    let version_number = parse_u32(data)?;
+
+    let is_known_version = version_number <= layout.latest_version();
+    let expected_size = layout.version_sizes[version_number.min(layout.latest_version()) as usize];
+    if is_known_version && size_in_bytes != expected_size {
+        bail!(
+            "Struct claimed to have {} bytes, but its fields for version {} take {} bytes",
+            size_in_bytes,
+            version_number,
+            expected_size
+        )
+    }
+    if size_in_bytes < expected_size {
+        bail!(
+            "Struct claimed to have {} bytes, but its fields for version {} need at least {} \
+             bytes",
+            size_in_bytes,
+            version_number,
+            expected_size
+        )
+    }
+    context.claim_memory(data, start, size_in_bytes)?;
+
+    // A version we know about can't have any fields we don't know about, so
+    // everything between the fields in it must be zero.
+    if is_known_version {
+        let mut fields_end = HEADER_SIZE;
+        for &(offset, size, min_version) in &layout.packed_fields {
+            if min_version <= version_number {
+                validate_zero_padding(bytes, start + fields_end, start + offset)?;
+                fields_end = offset + size;
+            }
+        }
+        validate_zero_padding(bytes, start + fields_end, start + size_in_bytes)?;
+    }
+    // Skip over the fields; we'll read them lazily once everything is valid.
+    skip_to(data, start + size_in_bytes)?;
+
+    // Nested data is required to appear in the same order as the (packed)
+    // fields of the struct, which is the same as the order of their offsets.
+    let mut pointers: Vec<(usize, PendingPointer)> = vec![];
+    let fields = layout.fields.iter().enumerate();
+    let present_fields = fields.filter(|(ordinal, _)| layout.has_field(*ordinal, version_number));
+    for field in present_fields.filter_map(|(_, field)| field.as_ref()) {
+        match field {
+            FieldLayout::Pointer { offset, nested, nullable } => {
+                if let Some(target) = read_pointer(bytes, data, context, start + offset, *nullable)?
+                {
+                    pointers.push((*offset, (target, nested)));
+                }
+            }
+            FieldLayout::Union { offset, layout, nullable } => {
+                let pointer =
+                    validate_inline_union(bytes, data, context, start + offset, layout, *nullable)?;
+                pointers.extend(pointer.map(|pointer| (*offset, pointer)));
+            }
+            FieldLayout::Leaf { offset, leaf_type } => {
+                validate_leaf(bytes, start + offset, leaf_type)?
+            }
+            FieldLayout::Bit { .. } => {}
+        }
+    }
+    pointers.sort_by_key(|(offset, _)| *offset);
+    for (_, pointer) in pointers {
+        validate_pointee(bytes, data, context, pointer)?;
+    }
+    context.leave_nested();
+    Ok(())
+}
+
+/// Validate an array starting at the current position of `data`, along with
+/// all of its nested data. On success, `data` is positioned at the end of the
+/// array's nested data.
+///
+/// Like `parse_array_with_context`, the padding after the elements must be
+/// zero.
+fn validate_array(
+    bytes: &[u8],
+    data: &mut ParserData,
+    context: &mut ValidationContext,
+    layout: &ArrayLayout,
+) -> Result<()> {
+    context.enter_nested()?;
+    let start = data.bytes_parsed();
+    let size_in_bytes: usize = parse_u32(data)?.try_into()?;
+    let num_elements: usize = parse_u32(data)?.try_into()?;
+
+    if let PackedArrayType::SizedArray(expected_elements) = layout.array_type
+        && num_elements != expected_elements
+    {
+        bail!("Expected an array of {} elements, but got {}", expected_elements, num_elements)
+    }
+    let storage_size = layout
+        .storage_size(num_elements)
+        .with_context(|| format!("Array with {} elements is too large", num_elements))?;
+    if size_in_bytes < storage_size {
+        bail!(
+            "Array claimed to have {} bytes, but its {} elements need {} bytes",
+            size_in_bytes,
+            num_elements,
+            storage_size
+        )
+    }
+    context.claim_memory(data, start, size_in_bytes)?;
+    validate_zero_padding(bytes, start + storage_size, start + size_in_bytes)?;
+    skip_to(data, start + size_in_bytes)?;
+
+    if layout.array_type == PackedArrayType::String {
+        let contents = &bytes[start + HEADER_SIZE..start + HEADER_SIZE + num_elements];
+        std::str::from_utf8(contents).context("String was not valid UTF-8")?;
+    }
+
+    // All objects start on an 8-byte boundary, so skip any padding.
+    skip_to(data, align_up(data.bytes_parsed(), 8))?;
+
+    // Check every pointer before validating any nested data, like
+    // parse_array does.
+    let mut pointers: Vec<PendingPointer> = vec![];
+    match &layout.element {
+        ElementLayout::Pointer { nested, nullable } => {
+            for idx in 0..num_elements {
+                let ptr_offset = start + HEADER_SIZE + 8 * idx;
+                if let Some(target) = read_pointer(bytes, data, context, ptr_offset, *nullable)? {
+                    pointers.push((target, nested));
+                }
+            }
+        }
+        ElementLayout::Union { layout, nullable } => {
+            for idx in 0..num_elements {
+                let union_offset = start + HEADER_SIZE + UNION_SIZE * idx;
+                pointers.extend(validate_inline_union(
+                    bytes,
+                    data,
+                    context,
+                    union_offset,
+                    layout,
+                    *nullable,
+                )?);
+            }
+        }
+        ElementLayout::Leaf(leaf_type) => {
+            let size = leaf_size(leaf_type);
+            for idx in 0..num_elements {
+                validate_leaf(bytes, start + HEADER_SIZE + size * idx, leaf_type)?;
+            }
+        }
+        ElementLayout::Bool => {}
+    }
+    for pointer in pointers {
+        validate_pointee(bytes, data, context, pointer)?;
+    }
+    context.leave_nested();
+    Ok(())
+}
+
+/// Validate an encoded struct, and return a view of it.
+///
+/// `data` must contain exactly one struct and its nested data. It's validated
+/// exactly as strictly as `parse_struct` would.
+pub fn view_struct<'a>(data: &'a [u8], layout: &'a StructLayout) -> Result<StructRef<'a>> {
+    view_struct_with_context(data, &mut ValidationContext::new(), layout)
+}
+
+/// Like `view_struct`, but validating with the given `context`, e.g. to use a
+/// different maximum recursion depth.
+pub fn view_struct_with_context<'a>(
+    data: &'a [u8],
+    context: &mut ValidationContext,
+    layout: &'a StructLayout,
+) -> Result<StructRef<'a>> {
+    let mut parser_data = ParserData::new(data);
+    validate_struct(data, &mut parser_data, context, layout)?;
+    if parser_data.remaining_bytes() != 0 {
+        bail!("There were {} trailing bytes after the struct", parser_data.remaining_bytes())
+    }
+    Ok(StructRef { data, offset: 0, layout })
+}
+
+/// Validate an entire mojom message, and return a view of its payload.
+pub fn view_message<'a>(data: &'a [u8], layout: &'a StructLayout) -> Result<StructRef<'a>> {
+    let (_, payload) = parse_message_header(data)?;
+    view_struct(payload, layout)
+}
+
+/******************************************************************************
+ * Views
+ ******************************************************************************/
+
+/// A borrowed view of a Mojom value. This is the zero-copy counterpart of
+/// `MojomValue`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MojomValueRef<'a> {
+    Bool(bool),
+    Int8(i8),
+    UInt8(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    String(&'a str),
+    Struct(StructRef<'a>),
+    Array(ArrayRef<'a>),
+    Union(UnionRef<'a>),
+    /// See `MojomValue::PendingRemote`.
+    PendingRemote {
+        index: u32,
+        version: u32,
+    },
+    /// See `MojomValue::PendingAssociatedRemote`.
+    PendingAssociatedRemote {
+        index: u32,
+        version: u32,
+    },
+    /// See `MojomValue::PendingAssociatedReceiver`.
+    PendingAssociatedReceiver {
+        index: u32,
+    },
+    /// See `MojomValue::Handle`.
+    Handle {
+        index: u32,
+    },
+    /// A null pointer, union, endpoint or handle, which can only
+    /// appear where the type is nullable.
+    Null,
+}
+
+/// A view of an encoded struct which has already been validated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StructRef<'a> {
+    /// The entire buffer the struct was validated in. Pointers are resolved
+    /// relative to this.
+    data: &'a [u8],
+    /// Offset of the struct's header within `data`.
+    offset: usize,
+    layout: &'a StructLayout,
+}
+
+/// A view of an encoded array which has already been validated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrayRef<'a> {
+    data: &'a [u8],
+    /// Offset of the array's header within `data`.
+    offset: usize,
+    len: usize,
+    layout: &'a ArrayLayout,
+}
+
+/// A view of an encoded union which has already been validated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnionRef<'a> {
+    data: &'a [u8],
+    /// Offset of the union (i.e. its size) within `data`.
+    offset: usize,
+    layout: &'a UnionLayout,
+}
+
+// The views only exist once validation has succeeded, so reading from them
+// can't fail. We still go through the safe accessors, and panic if validation
+// missed something.
+const VALIDATED: &str = "view was not validated correctly";
+
+macro_rules! read_le {
+    ($target_type:ty, $data:expr, $offset:expr) => {
+        <$target_type>::from_le_bytes(
+            $data[$offset..$offset + size_of::<$target_type>()].try_into().expect(VALIDATED),
+        )
+    };
+}
+
+fn read_leaf<'a>(data: &'a [u8], offset: usize, leaf_type: &PackedLeafType) -> MojomValueRef<'a> {
+    match leaf_type {
+        PackedLeafType::Int8 => MojomValueRef::Int8(read_le!(i8, data, offset)),
+        PackedLeafType::UInt8 => MojomValueRef::UInt8(read_le!(u8, data, offset)),
+        PackedLeafType::Int16 => MojomValueRef::Int16(read_le!(i16, data, offset)),
+        PackedLeafType::UInt16 => MojomValueRef::UInt16(read_le!(u16, data, offset)),
+        PackedLeafType::Int32 => MojomValueRef::Int32(read_le!(i32, data, offset)),
+        PackedLeafType::UInt32 => MojomValueRef::UInt32(read_le!(u32, data, offset)),
+        PackedLeafType::Int64 => MojomValueRef::Int64(read_le!(i64, data, offset)),
+        PackedLeafType::UInt64 => MojomValueRef::UInt64(read_le!(u64, data, offset)),
+        PackedLeafType::Float => MojomValueRef::Float(read_le!(f32, data, offset)),
+        PackedLeafType::Double => MojomValueRef::Double(read_le!(f64, data, offset)),
+        PackedLeafType::AssociatedEndpoint { .. } => match read_le!(u32, data, offset) {
+            INVALID_ENDPOINT_INDEX => MojomValueRef::Null,
+            index => MojomValueRef::PendingAssociatedReceiver { index },
+        },
+        PackedLeafType::Interface { .. } => match read_le!(u32, data, offset) {
+            INVALID_HANDLE_INDEX => MojomValueRef::Null,
+            index => {
+                MojomValueRef::PendingRemote { index, version: read_le!(u32, data, offset + 4) }
+            }
+        },
+        PackedLeafType::AssociatedInterface { .. } => match read_le!(u32, data, offset) {
+            INVALID_ENDPOINT_INDEX => MojomValueRef::Null,
+            index => MojomValueRef::PendingAssociatedRemote {
+                index,
+                version: read_le!(u32, data, offset + 4),
+            },
+        },
+        PackedLeafType::Handle { .. } => match read_le!(u32, data, offset) {
+            INVALID_HANDLE_INDEX => MojomValueRef::Null,
+            index => MojomValueRef::Handle { index },
+        },
+    }
+}
+
+fn read_bit(data: &[u8], offset: usize, bit: usize) -> MojomValueRef<'_> {
+    MojomValueRef::Bool((data[offset] >> bit) & 1 == 1)
+}
+
+/// Follow the pointer at `ptr_offset` and return a view of what it points to.
+fn follow_pointer<'a>(
+    data: &'a [u8],
+    ptr_offset: usize,
+    nested: &'a NestedLayout,
+) -> MojomValueRef<'a> {
+    let pointer_value = read_le!(u64, data, ptr_offset) as usize;
+    if pointer_value == 0 {
+        // Validation already checked that the pointer is nullable.
+        return MojomValueRef::Null;
+    }
+    let offset = ptr_offset + pointer_value;
+    match nested.resolve() {
+        NestedLayout::Struct(layout) => MojomValueRef::Struct(StructRef { data, offset, layout }),
+        NestedLayout::Union(layout) => MojomValueRef::Union(UnionRef { data, offset, layout }),
+        NestedLayout::Array(layout) => {
+            let len = read_le!(u32, data, offset + 4) as usize;
+            let array = ArrayRef { data, offset, len, layout };
+            match layout.array_type {
+                PackedArrayType::String => MojomValueRef::String(
+                    std::str::from_utf8(array.element_bytes()).expect(VALIDATED),
+                ),
+                _ => MojomValueRef::Array(array),
+            }
+        }
+        NestedLayout::Recursive(_) => unreachable!("Resolved layouts aren't recursive"),
+    }
+}
+
+/// Return a view of the union stored inline at `offset`.
+fn read_union<'a>(data: &'a [u8], offset: usize, layout: &'a UnionLayout) -> MojomValueRef<'a> {
+    if read_le!(u32, data, offset) == 0 {
+        // Validation already checked that the union is nullable.
+        return MojomValueRef::Null;
+    }
+    MojomValueRef::Union(UnionRef { data, offset, layout })
+}
+
+impl<'a> StructRef<'a> {
+    /// Return the field with the given ordinal, or `None` if the struct has no
+    /// such field. That includes fields which were added after the struct's
+    /// version, which the sender didn't know about.
+    pub fn field(&self, ordinal: Ordinal) -> Option<MojomValueRef<'a>> {
+        let field = self.layout.fields.get(ordinal)?.as_ref()?;
+        if !self.layout.has_field(ordinal, self.version()) {
+            return None;
+        }
+        if let Some(Some((offset, bit))) = self.layout.has_value_flags.get(ordinal)
+            && (self.data[self.offset + offset] >> bit) & 1 == 0
+        {
+            return Some(MojomValueRef::Null);
+        }
+        Some(match field {
+            FieldLayout::Leaf { offset, leaf_type } => {
+                read_leaf(self.data, self.offset + offset, leaf_type)
+            }
+            FieldLayout::Bit { offset, bit } => {
+                read_bit(self.data, self.offset + offset, *bit as usize)
+            }
+            FieldLayout::Pointer { offset, nested, .. } => {
+                follow_pointer(self.data, self.offset + offset, nested)
+            }
+            FieldLayout::Union { offset, layout, .. } => {
+                read_union(self.data, self.offset + offset, layout)
+            }
+        })
+    }
+
+    /// The number of fields in the struct's definition.
+    pub fn num_fields(&self) -> usize {
+        self.layout.fields.len()
+    }
+
+    /// The version number from the struct's header.
+    pub fn version(&self) -> u32 {
+        read_le!(u32, self.data, self.offset + 4)
+    }
+}
+
+impl<'a> ArrayRef<'a> {
+    /// The number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the element at `idx`, or `None` if it's out of bounds.
+    pub fn get(&self, idx: usize) -> Option<MojomValueRef<'a>> {
+        if idx >= self.len {
+            return None;
+        }
+        let elements_start = self.offset + HEADER_SIZE;
+        Some(match &self.layout.element {
+            ElementLayout::Leaf(leaf_type) => {
+                read_leaf(self.data, elements_start + idx * leaf_size(leaf_type), leaf_type)
+            }
+            ElementLayout::Bool => read_bit(self.data, elements_start + idx / 8, idx % 8),
+            ElementLayout::Pointer { nested, .. } => {
+                follow_pointer(self.data, elements_start + 8 * idx, nested)
+            }
+            ElementLayout::Union { layout, .. } => {
+                read_union(self.data, elements_start + UNION_SIZE * idx, layout)
+            }
+        })
+    }
+
+    /// Iterate over the elements of the array.
+    pub fn iter(&self) -> impl Iterator<Item = MojomValueRef<'a>> + '_ {
+        (0..self.len).map(|idx| self.get(idx).expect(VALIDATED))
+    }
+
+    /// Return the raw contents of an array of bytes, or `None` if the
+    /// elements aren't bytes.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self.layout.element {
+            ElementLayout::Leaf(PackedLeafType::UInt8 | PackedLeafType::Int8) => {
+                Some(self.element_bytes())
+            }
+            _ => None,
+        }
+    }
+
+    /// The bytes of the array's elements. Only meaningful for byte arrays.
+    fn element_bytes(&self) -> &'a [u8] {
+        let elements_start = self.offset + HEADER_SIZE;
+        &self.data[elements_start..elements_start + self.len]
+    }
+}
+
+impl<'a> UnionRef<'a> {
+    /// The tag of the union's active field.
+    pub fn tag(&self) -> Ordinal {
+        read_le!(u32, self.data, self.offset + 4) as Ordinal
+    }
+
+    /// The value of the union's active field.
+    pub fn value(&self) -> MojomValueRef<'a> {
+        let value_offset = self.offset + 8;
+        match &self.layout.variants[self.tag()] {
+            ElementLayout::Leaf(leaf_type) => read_leaf(self.data, value_offset, leaf_type),
+            ElementLayout::Bool => read_bit(self.data, value_offset, 0),
+            ElementLayout::Pointer { nested, .. } => {
+                follow_pointer(self.data, value_offset, nested)
+            }
+            ElementLayout::Union { .. } => unreachable!("Unions are never stored inline in unions"),
+        }
+    }
+}
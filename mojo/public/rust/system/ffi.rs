@@ -13,6 +13,7 @@ pub mod types {
 
     pub use raw_ffi::MojoAddTriggerFlags;
     pub use raw_ffi::MojoAppendMessageDataFlags;
+    pub use raw_ffi::MojoAppendMessageDataOptions;
     pub use raw_ffi::MojoArmTrapFlags;
     pub use raw_ffi::MojoBeginReadDataFlags;
     pub use raw_ffi::MojoBeginWriteDataFlags;
@@ -22,6 +23,7 @@ pub mod types {
     pub use raw_ffi::MojoCreateSharedBufferFlags;
     pub use raw_ffi::MojoCreateTrapFlags;
     pub use raw_ffi::MojoDuplicateBufferHandleFlags;
+    pub use raw_ffi::MojoDuplicateBufferHandleOptions;
     pub use raw_ffi::MojoEndReadDataFlags;
     pub use raw_ffi::MojoEndWriteDataFlags;
     pub use raw_ffi::MojoGetBufferInfoFlags;
@@ -31,10 +33,16 @@ pub mod types {
     pub use raw_ffi::MojoHandleSignalsState;
     pub use raw_ffi::MojoMapBufferFlags;
     pub use raw_ffi::MojoMessageHandle;
+    pub use raw_ffi::MojoNotifyBadMessageOptions;
+    pub use raw_ffi::MojoPlatformHandle;
+    pub use raw_ffi::MojoPlatformSharedMemoryRegionAccessMode;
     pub use raw_ffi::MojoReadDataFlags;
     pub use raw_ffi::MojoReadMessageFlags;
     pub use raw_ffi::MojoRemoveTriggerFlags;
+    pub use raw_ffi::MojoSharedBufferGuid;
+    pub use raw_ffi::MojoSharedBufferInfo;
     pub use raw_ffi::MojoTimeTicks;
+    pub use raw_ffi::MojoTrapEvent;
     pub use raw_ffi::MojoTrapEventFlags;
     pub use raw_ffi::MojoTrapEventHandler;
     pub use raw_ffi::MojoTriggerCondition;
@@ -44,3 +52,75 @@ pub mod types {
 }
 
 pub use types::MojoResultCode;
+
+pub mod result_codes {
+    //! The values of `MojoResultCode`, from mojo/public/c/system/types.h.
+    //! bindgen can't translate their definitions, which are casts.
+    use super::types::MojoResultCode;
+
+    pub const MOJO_RESULT_OK: MojoResultCode = 0;
+    pub const MOJO_RESULT_CANCELLED: MojoResultCode = 1;
+    pub const MOJO_RESULT_UNKNOWN: MojoResultCode = 2;
+    pub const MOJO_RESULT_INVALID_ARGUMENT: MojoResultCode = 3;
+    pub const MOJO_RESULT_DEADLINE_EXCEEDED: MojoResultCode = 4;
+    pub const MOJO_RESULT_NOT_FOUND: MojoResultCode = 5;
+    pub const MOJO_RESULT_ALREADY_EXISTS: MojoResultCode = 6;
+    pub const MOJO_RESULT_PERMISSION_DENIED: MojoResultCode = 7;
+    pub const MOJO_RESULT_RESOURCE_EXHAUSTED: MojoResultCode = 8;
+    pub const MOJO_RESULT_FAILED_PRECONDITION: MojoResultCode = 9;
+    pub const MOJO_RESULT_ABORTED: MojoResultCode = 10;
+    pub const MOJO_RESULT_OUT_OF_RANGE: MojoResultCode = 11;
+    pub const MOJO_RESULT_UNIMPLEMENTED: MojoResultCode = 12;
+    pub const MOJO_RESULT_INTERNAL: MojoResultCode = 13;
+    pub const MOJO_RESULT_UNAVAILABLE: MojoResultCode = 14;
+    pub const MOJO_RESULT_DATA_LOSS: MojoResultCode = 15;
+    pub const MOJO_RESULT_BUSY: MojoResultCode = 16;
+    pub const MOJO_RESULT_SHOULD_WAIT: MojoResultCode = 17;
+}
+
+pub mod signals {
+    //! The values of `MojoHandleSignals`, from mojo/public/c/system/types.h,
+    //! which bindgen can't translate either.
+    use super::types::MojoHandleSignals;
+
+    pub const MOJO_HANDLE_SIGNAL_NONE: MojoHandleSignals = 0;
+    pub const MOJO_HANDLE_SIGNAL_READABLE: MojoHandleSignals = 1 << 0;
+    pub const MOJO_HANDLE_SIGNAL_WRITABLE: MojoHandleSignals = 1 << 1;
+    pub const MOJO_HANDLE_SIGNAL_PEER_CLOSED: MojoHandleSignals = 1 << 2;
+}
+
+pub mod trigger_conditions {
+    //! The values of `MojoTriggerCondition`, from mojo/public/c/system/trap.h.
+    use super::types::MojoTriggerCondition;
+
+    pub const MOJO_TRIGGER_CONDITION_SIGNALS_UNSATISFIED: MojoTriggerCondition = 0;
+    pub const MOJO_TRIGGER_CONDITION_SIGNALS_SATISFIED: MojoTriggerCondition = 1;
+}
+
+pub mod functions {
+    //! The functions of the mojo C system API. These are all unsafe; the safe
+    //! wrappers around them are in //mojo/public/rust:mojo_rust_system_api.
+    use super::raw_ffi;
+
+    pub use raw_ffi::MojoAddTrigger;
+    pub use raw_ffi::MojoAppendMessageData;
+    pub use raw_ffi::MojoArmTrap;
+    pub use raw_ffi::MojoClose;
+    pub use raw_ffi::MojoCreateMessage;
+    pub use raw_ffi::MojoCreateMessagePipe;
+    pub use raw_ffi::MojoCreateSharedBuffer;
+    pub use raw_ffi::MojoCreateTrap;
+    pub use raw_ffi::MojoDestroyMessage;
+    pub use raw_ffi::MojoDuplicateBufferHandle;
+    pub use raw_ffi::MojoGetBufferInfo;
+    pub use raw_ffi::MojoGetMessageData;
+    pub use raw_ffi::MojoMapBuffer;
+    pub use raw_ffi::MojoNotifyBadMessage;
+    pub use raw_ffi::MojoReadMessage;
+    pub use raw_ffi::MojoUnmapBuffer;
+    pub use raw_ffi::MojoUnwrapPlatformHandle;
+    pub use raw_ffi::MojoUnwrapPlatformSharedMemoryRegion;
+    pub use raw_ffi::MojoWrapPlatformHandle;
+    pub use raw_ffi::MojoWrapPlatformSharedMemoryRegion;
+    pub use raw_ffi::MojoWriteMessage;
+}
@@ -0,0 +1,48 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Defines `UntypedHandle`, a handle of any kind, e.g. one which was read
+//! from a message before we know what kind it is. This corresponds to
+//! mojo::ScopedHandle in the C++ bindings.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_ffi";
+}
+
+use crate::mojo_types::*;
+use mojo_ffi::functions::*;
+
+/// A handle of any kind, which is closed when this is dropped.
+#[derive(Debug)]
+pub struct UntypedHandle {
+    handle: MojoHandle,
+}
+
+impl UntypedHandle {
+    /// Take ownership of a raw handle.
+    ///
+    /// # Safety
+    /// `handle` must be a valid handle which nothing else will use or close.
+    pub unsafe fn from_raw(handle: MojoHandle) -> Self {
+        UntypedHandle { handle }
+    }
+
+    pub fn raw(&self) -> MojoHandle {
+        self.handle
+    }
+
+    /// Give up ownership of the handle, without closing it.
+    pub fn into_raw(self) -> MojoHandle {
+        let handle = self.handle;
+        std::mem::forget(self);
+        handle
+    }
+}
+
+impl Drop for UntypedHandle {
+    fn drop(&mut self) {
+        // SAFETY: We own the handle, and nobody else will use it.
+        unsafe { MojoClose(self.handle) };
+    }
+}
@@ -2,4 +2,9 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+pub mod handle;
+pub mod message_pipe;
 pub mod mojo_types;
+pub mod platform_handle;
+pub mod shared_buffer;
+pub mod wait;
@@ -0,0 +1,304 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Safe wrappers around message pipes.
+//!
+//! A message pipe is a bidirectional channel between two endpoints, each of
+//! which is a handle. Every message written to one endpoint can be read, in
+//! order, from the other. Messages can also carry handles, whose ownership
+//! passes to whoever reads them.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_ffi";
+}
+
+use crate::handle::UntypedHandle;
+use crate::mojo_types::*;
+use mojo_ffi::functions::*;
+use mojo_ffi::types::MojoAppendMessageDataOptions;
+use mojo_ffi::types::MojoMessageHandle;
+use mojo_ffi::types::MojoNotifyBadMessageOptions;
+
+use std::ptr;
+
+/// The flag which tells MojoAppendMessageData that the message is complete.
+const MOJO_APPEND_MESSAGE_DATA_FLAG_COMMIT_SIZE: u32 = 1;
+
+/// One endpoint of a message pipe. The endpoint is closed when this is
+/// dropped, after which the other endpoint's peer is closed.
+#[derive(Debug)]
+pub struct MessagePipeHandle {
+    handle: MojoHandle,
+}
+
+/// Create a new message pipe, and return its two endpoints.
+pub fn create_message_pipe() -> Result<(MessagePipeHandle, MessagePipeHandle), MojoError> {
+    let mut handle0 = INVALID_HANDLE;
+    let mut handle1 = INVALID_HANDLE;
+    // SAFETY: Null options are allowed, and both out pointers are valid.
+    MojoError::check(unsafe { MojoCreateMessagePipe(ptr::null(), &mut handle0, &mut handle1) })?;
+    Ok((MessagePipeHandle { handle: handle0 }, MessagePipeHandle { handle: handle1 }))
+}
+
+/// A message object which we own, e.g. one that we read from a pipe. It's
+/// destroyed when this is dropped, unless it's been written to a pipe. Zero
+/// means that there's no message yet.
+#[derive(Debug)]
+pub struct MessageHandle(MojoMessageHandle);
+
+impl MessageHandle {
+    /// Copy out the message's bytes, and take the handles it carries, which
+    /// we then own. The handles can only be taken once, so this fails with
+    /// `Err(MojoError::NotFound)` if they already were.
+    pub fn take_data_with_handles(&self) -> Result<(Vec<u8>, Vec<UntypedHandle>), MojoError> {
+        let mut buffer: *mut std::ffi::c_void = ptr::null_mut();
+        let mut num_bytes: u32 = 0;
+        let mut num_handles: u32 = 0;
+        // SAFETY: `self` is a valid message. With no handle storage, this
+        // fails with MOJO_RESULT_RESOURCE_EXHAUSTED if the message has
+        // handles, and tells us how many.
+        let mut result = MojoError::check(unsafe {
+            MojoGetMessageData(
+                self.0,
+                ptr::null(),
+                &mut buffer,
+                &mut num_bytes,
+                ptr::null_mut(),
+                &mut num_handles,
+            )
+        });
+        let mut raw_handles = vec![];
+        if result == Err(MojoError::ResourceExhausted) {
+            raw_handles = vec![INVALID_HANDLE; num_handles as usize];
+            // SAFETY: `raw_handles` has room for `num_handles` handles.
+            result = MojoError::check(unsafe {
+                MojoGetMessageData(
+                    self.0,
+                    ptr::null(),
+                    &mut buffer,
+                    &mut num_bytes,
+                    raw_handles.as_mut_ptr(),
+                    &mut num_handles,
+                )
+            });
+        }
+        result?;
+        // SAFETY: MojoGetMessageData gave us ownership of the handles.
+        let handles = raw_handles
+            .into_iter()
+            .take(num_handles as usize)
+            .map(|handle| unsafe { UntypedHandle::from_raw(handle) })
+            .collect();
+        // SAFETY: The message's data is `num_bytes` bytes at `buffer`.
+        Ok((unsafe { copy_message_data(buffer, num_bytes) }, handles))
+    }
+
+    /// Tell the system that the message is invalid, e.g. because it failed
+    /// validation, with `error` saying why. If the message came from another
+    /// process, that process's bad message handler is told, which may kill
+    /// it.
+    pub fn notify_bad_message(&self, error: &str) -> Result<(), MojoError> {
+        let options = MojoNotifyBadMessageOptions {
+            struct_size: std::mem::size_of::<MojoNotifyBadMessageOptions>() as u32,
+            flags: 0,
+        };
+        // Errors are only for logging, so an absurdly long one can be cut
+        // short.
+        let num_bytes = u32::try_from(error.len()).unwrap_or(u32::MAX);
+        // SAFETY: `self` is a valid message, and `error` has at least
+        // `num_bytes` bytes.
+        MojoError::check(unsafe {
+            MojoNotifyBadMessage(self.0, error.as_ptr() as *const _, num_bytes, &options)
+        })
+    }
+}
+
+impl Drop for MessageHandle {
+    fn drop(&mut self) {
+        if self.0 != 0 {
+            // SAFETY: We own the message, and nobody else will use it.
+            unsafe { MojoDestroyMessage(self.0) };
+        }
+    }
+}
+
+impl MessagePipeHandle {
+    /// Take ownership of a raw message pipe handle.
+    ///
+    /// # Safety
+    /// `handle` must be a message pipe endpoint which nothing else will use or
+    /// close.
+    pub unsafe fn from_raw(handle: MojoHandle) -> Self {
+        MessagePipeHandle { handle }
+    }
+
+    pub(crate) fn raw(&self) -> MojoHandle {
+        self.handle
+    }
+
+    /// Give up ownership of the endpoint, without closing it.
+    pub fn into_raw(self) -> MojoHandle {
+        let handle = self.handle;
+        std::mem::forget(self);
+        handle
+    }
+
+    /// Write a message containing `bytes`.
+    ///
+    /// Returns `Err(MojoError::FailedPrecondition)` if the other endpoint is
+    /// known to be closed. Since closing isn't synchronous, writing may also
+    /// succeed after the other endpoint is closed, in which case the message
+    /// is dropped.
+    pub fn write(&self, bytes: &[u8]) -> Result<(), MojoError> {
+        self.write_with(bytes.len(), |buffer| buffer.copy_from_slice(bytes))
+    }
+
+    /// Write a message of `num_bytes` bytes, which `fill` writes directly into
+    /// the message's buffer. This saves encoding the message somewhere else
+    /// first, and copying it. The buffer is zeroed before `fill` is called.
+    ///
+    /// Fails like `write()`.
+    pub fn write_with(
+        &self,
+        num_bytes: usize,
+        fill: impl FnOnce(&mut [u8]),
+    ) -> Result<(), MojoError> {
+        self.write_with_handles(num_bytes, vec![], fill)
+    }
+
+    /// Write a message like `write_with()`, which also carries `handles`. The
+    /// handles are closed if writing fails.
+    pub fn write_with_handles(
+        &self,
+        num_bytes: usize,
+        handles: Vec<UntypedHandle>,
+        fill: impl FnOnce(&mut [u8]),
+    ) -> Result<(), MojoError> {
+        let num_handles: u32 =
+            handles.len().try_into().map_err(|_| MojoError::ResourceExhausted)?;
+        let raw_handles: Vec<MojoHandle> = handles.iter().map(UntypedHandle::raw).collect();
+        let num_bytes_u32: u32 = num_bytes.try_into().map_err(|_| MojoError::ResourceExhausted)?;
+        let mut message = MessageHandle(0);
+        // SAFETY: Null options are allowed, and the out pointer is valid.
+        MojoError::check(unsafe { MojoCreateMessage(ptr::null(), &mut message.0) })?;
+
+        let options = MojoAppendMessageDataOptions {
+            struct_size: std::mem::size_of::<MojoAppendMessageDataOptions>() as u32,
+            flags: MOJO_APPEND_MESSAGE_DATA_FLAG_COMMIT_SIZE,
+        };
+        let mut buffer: *mut std::ffi::c_void = ptr::null_mut();
+        let mut buffer_size: u32 = 0;
+        // SAFETY: `message` is a valid message with no context, and
+        // `raw_handles` holds `num_handles` handles, which we own.
+        MojoError::check(unsafe {
+            MojoAppendMessageData(
+                message.0,
+                num_bytes_u32,
+                raw_handles.as_ptr(),
+                num_handles,
+                &options,
+                &mut buffer,
+                &mut buffer_size,
+            )
+        })?;
+        // The message owns the handles now, and closes them if it's destroyed.
+        for handle in handles {
+            handle.into_raw();
+        }
+        if num_bytes > 0 {
+            let buffer = buffer as *mut u8;
+            // SAFETY: MojoAppendMessageData gave us a buffer with room for at
+            // least `num_bytes` bytes, which we own until the message is
+            // written. It may be uninitialized, so we zero it before we make
+            // a slice of it.
+            let buffer = unsafe {
+                ptr::write_bytes(buffer, 0, num_bytes);
+                std::slice::from_raw_parts_mut(buffer, num_bytes)
+            };
+            fill(buffer);
+        }
+
+        let message = std::mem::ManuallyDrop::new(message);
+        // SAFETY: MojoWriteMessage takes ownership of the message, whether or
+        // not it succeeds.
+        MojoError::check(unsafe { MojoWriteMessage(self.handle, message.0, ptr::null()) })
+    }
+
+    /// Read the next message, if there is one.
+    ///
+    /// Returns `Err(MojoError::ShouldWait)` if there are no messages yet, and
+    /// `Err(MojoError::FailedPrecondition)` if there are no messages and the
+    /// other endpoint is closed, so there never will be. Fails with
+    /// `Err(MojoError::ResourceExhausted)` if the message carries handles; see
+    /// `read_with_handles()`.
+    pub fn read(&self) -> Result<Vec<u8>, MojoError> {
+        let message = self.read_message()?;
+        let mut buffer: *mut std::ffi::c_void = ptr::null_mut();
+        let mut num_bytes: u32 = 0;
+        // SAFETY: `message` is a valid message. Passing no handle storage
+        // makes the call fail if the message has handles attached.
+        MojoError::check(unsafe {
+            MojoGetMessageData(
+                message.0,
+                ptr::null(),
+                &mut buffer,
+                &mut num_bytes,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        })?;
+        // SAFETY: The message's data is `num_bytes` bytes at `buffer`.
+        Ok(unsafe { copy_message_data(buffer, num_bytes) })
+    }
+
+    /// Read the next message like `read()`, along with the handles it
+    /// carries, which we then own.
+    pub fn read_with_handles(&self) -> Result<(Vec<u8>, Vec<UntypedHandle>), MojoError> {
+        self.read_message()?.take_data_with_handles()
+    }
+
+    /// Read the next message like `read()`, but return the message object
+    /// itself, e.g. to notify the system if its contents turn out to be bad.
+    pub fn read_message(&self) -> Result<MessageHandle, MojoError> {
+        let mut message = MessageHandle(0);
+        // SAFETY: Null options are allowed, and the out pointer is valid.
+        MojoError::check(unsafe { MojoReadMessage(self.handle, ptr::null(), &mut message.0) })?;
+        Ok(message)
+    }
+}
+
+/// Copy the data of a message that we read.
+///
+/// # Safety
+/// `buffer` must point to `num_bytes` bytes, unless `num_bytes` is 0.
+unsafe fn copy_message_data(buffer: *const std::ffi::c_void, num_bytes: u32) -> Vec<u8> {
+    if num_bytes == 0 {
+        return vec![];
+    }
+    // SAFETY: Guaranteed by the caller. The data stays alive until the message
+    // is destroyed.
+    unsafe { std::slice::from_raw_parts(buffer as *const u8, num_bytes as usize) }.to_vec()
+}
+
+impl From<MessagePipeHandle> for UntypedHandle {
+    fn from(pipe: MessagePipeHandle) -> Self {
+        // SAFETY: We owned the endpoint, and give it up here.
+        unsafe { UntypedHandle::from_raw(pipe.into_raw()) }
+    }
+}
+
+impl From<UntypedHandle> for MessagePipeHandle {
+    /// Treat `handle` as a message pipe endpoint. If it's some other kind of
+    /// handle, using it will fail.
+    fn from(handle: UntypedHandle) -> Self {
+        MessagePipeHandle { handle: handle.into_raw() }
+    }
+}
+
+impl Drop for MessagePipeHandle {
+    fn drop(&mut self) {
+        // SAFETY: We own the handle, and nobody else will use it.
+        unsafe { MojoClose(self.handle) };
+    }
+}
@@ -9,3 +9,70 @@ chromium::import! {
 use mojo_ffi::types;
 
 pub use types::MojoHandle;
+
+use mojo_ffi::result_codes::*;
+use mojo_ffi::MojoResultCode;
+
+/// The handle value which never refers to a valid handle.
+pub const INVALID_HANDLE: MojoHandle = 0;
+
+/// The reasons that a call to the mojo system API can fail. These correspond
+/// to the non-OK `MOJO_RESULT_*` codes; see mojo/public/c/system/types.h for
+/// what each of them means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MojoError {
+    Cancelled,
+    Unknown,
+    InvalidArgument,
+    DeadlineExceeded,
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    ResourceExhausted,
+    FailedPrecondition,
+    Aborted,
+    OutOfRange,
+    Unimplemented,
+    Internal,
+    Unavailable,
+    DataLoss,
+    Busy,
+    ShouldWait,
+}
+
+impl MojoError {
+    /// Convert the result of a mojo system call, which fails unless the code
+    /// is `MOJO_RESULT_OK`.
+    pub fn check(code: MojoResultCode) -> Result<(), MojoError> {
+        let error = match code {
+            MOJO_RESULT_OK => return Ok(()),
+            MOJO_RESULT_CANCELLED => MojoError::Cancelled,
+            MOJO_RESULT_INVALID_ARGUMENT => MojoError::InvalidArgument,
+            MOJO_RESULT_DEADLINE_EXCEEDED => MojoError::DeadlineExceeded,
+            MOJO_RESULT_NOT_FOUND => MojoError::NotFound,
+            MOJO_RESULT_ALREADY_EXISTS => MojoError::AlreadyExists,
+            MOJO_RESULT_PERMISSION_DENIED => MojoError::PermissionDenied,
+            MOJO_RESULT_RESOURCE_EXHAUSTED => MojoError::ResourceExhausted,
+            MOJO_RESULT_FAILED_PRECONDITION => MojoError::FailedPrecondition,
+            MOJO_RESULT_ABORTED => MojoError::Aborted,
+            MOJO_RESULT_OUT_OF_RANGE => MojoError::OutOfRange,
+            MOJO_RESULT_UNIMPLEMENTED => MojoError::Unimplemented,
+            MOJO_RESULT_INTERNAL => MojoError::Internal,
+            MOJO_RESULT_UNAVAILABLE => MojoError::Unavailable,
+            MOJO_RESULT_DATA_LOSS => MojoError::DataLoss,
+            MOJO_RESULT_BUSY => MojoError::Busy,
+            MOJO_RESULT_SHOULD_WAIT => MojoError::ShouldWait,
+            // MOJO_RESULT_UNKNOWN, and any codes added after this was written.
+            _ => MojoError::Unknown,
+        };
+        Err(error)
+    }
+}
+
+impl std::fmt::Display for MojoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mojo system call failed: {:?}", self)
+    }
+}
+
+impl std::error::Error for MojoError {}
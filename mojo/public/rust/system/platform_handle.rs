@@ -0,0 +1,163 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Safe wrappers around platform handles, like
+//! mojo/public/cpp/system/platform_handle.h.
+//!
+//! A platform handle is one of the OS's own handles, e.g. a file descriptor,
+//! which Mojo wraps in a Mojo handle so that it can be passed in messages.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_ffi";
+}
+
+use crate::handle::UntypedHandle;
+use crate::mojo_types::*;
+use mojo_ffi::functions::*;
+use mojo_ffi::types::MojoPlatformHandle;
+
+use std::fs::File;
+use std::ptr;
+
+/// The type of a `MojoPlatformHandle` holding a POSIX file descriptor.
+#[cfg(unix)]
+const MOJO_PLATFORM_HANDLE_TYPE_FILE_DESCRIPTOR: u32 = 1;
+
+/// The type of a `MojoPlatformHandle` holding a Windows `HANDLE`.
+#[cfg(windows)]
+const MOJO_PLATFORM_HANDLE_TYPE_WINDOWS_HANDLE: u32 = 3;
+
+/// A Mojo handle which wraps a platform handle, and closes it when this is
+/// dropped.
+#[derive(Debug)]
+pub struct PlatformHandle {
+    handle: UntypedHandle,
+}
+
+impl PlatformHandle {
+    /// Wrap a file descriptor, which Mojo takes ownership of.
+    #[cfg(unix)]
+    pub fn from_fd(fd: std::os::fd::OwnedFd) -> Result<Self, MojoError> {
+        use std::os::fd::IntoRawFd;
+        let value = fd.into_raw_fd().try_into().map_err(|_| MojoError::InvalidArgument)?;
+        PlatformHandle::wrap(MOJO_PLATFORM_HANDLE_TYPE_FILE_DESCRIPTOR, value)
+    }
+
+    /// Unwrap the file descriptor. Fails if the handle wraps some other kind
+    /// of platform handle, in which case it's closed.
+    #[cfg(unix)]
+    pub fn into_fd(self) -> Result<std::os::fd::OwnedFd, MojoError> {
+        use std::os::fd::FromRawFd;
+        let value = self.unwrap(MOJO_PLATFORM_HANDLE_TYPE_FILE_DESCRIPTOR)?;
+        let fd = value.try_into().map_err(|_| MojoError::InvalidArgument)?;
+        // SAFETY: Mojo gave us ownership of the file descriptor.
+        Ok(unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Wrap a Windows `HANDLE`, which Mojo takes ownership of.
+    #[cfg(windows)]
+    pub fn from_windows_handle(
+        handle: std::os::windows::io::OwnedHandle,
+    ) -> Result<Self, MojoError> {
+        use std::os::windows::io::IntoRawHandle;
+        let value = handle.into_raw_handle() as usize as u64;
+        PlatformHandle::wrap(MOJO_PLATFORM_HANDLE_TYPE_WINDOWS_HANDLE, value)
+    }
+
+    /// Unwrap the Windows `HANDLE`. Fails if the handle wraps some other
+    /// kind of platform handle, in which case it's closed.
+    #[cfg(windows)]
+    pub fn into_windows_handle(self) -> Result<std::os::windows::io::OwnedHandle, MojoError> {
+        use std::os::windows::io::FromRawHandle;
+        let value = self.unwrap(MOJO_PLATFORM_HANDLE_TYPE_WINDOWS_HANDLE)?;
+        let value = usize::try_from(value).map_err(|_| MojoError::InvalidArgument)?;
+        // SAFETY: Mojo gave us ownership of the handle.
+        Ok(unsafe { std::os::windows::io::OwnedHandle::from_raw_handle(value as _) })
+    }
+
+    /// Wrap a file, like `mojo::WrapPlatformFile()` in C++.
+    pub fn from_file(file: File) -> Result<Self, MojoError> {
+        #[cfg(unix)]
+        return PlatformHandle::from_fd(file.into());
+        #[cfg(windows)]
+        return PlatformHandle::from_windows_handle(file.into());
+    }
+
+    /// Unwrap a file, like `mojo::UnwrapPlatformFile()` in C++.
+    pub fn into_file(self) -> Result<File, MojoError> {
+        #[cfg(unix)]
+        return self.into_fd().map(File::from);
+        #[cfg(windows)]
+        return self.into_windows_handle().map(File::from);
+    }
+
+    /// Take ownership of a raw handle which wraps a platform handle.
+    ///
+    /// # Safety
+    /// `handle` must be a handle which nothing else will use or close.
+    pub unsafe fn from_raw(handle: MojoHandle) -> Self {
+        // SAFETY: Guaranteed by the caller.
+        PlatformHandle { handle: unsafe { UntypedHandle::from_raw(handle) } }
+    }
+
+    /// Give up ownership of the handle, without closing it.
+    pub fn into_raw(self) -> MojoHandle {
+        self.handle.into_raw()
+    }
+
+    /// Wrap the platform handle `value`, of type `handle_type`, which Mojo
+    /// takes ownership of.
+    fn wrap(handle_type: u32, value: u64) -> Result<Self, MojoError> {
+        let platform_handle = MojoPlatformHandle {
+            struct_size: std::mem::size_of::<MojoPlatformHandle>() as u32,
+            type_: handle_type,
+            value,
+        };
+        let mut handle = INVALID_HANDLE;
+        // SAFETY: `platform_handle` is initialized as the API requires, null
+        // options are allowed, and the out pointer is valid.
+        MojoError::check(unsafe {
+            MojoWrapPlatformHandle(&platform_handle, ptr::null(), &mut handle)
+        })?;
+        // SAFETY: We just created the handle, and nothing else has it.
+        Ok(PlatformHandle { handle: unsafe { UntypedHandle::from_raw(handle) } })
+    }
+
+    /// Unwrap the platform handle, which must have type `handle_type`, and
+    /// return its value, which the caller then owns.
+    fn unwrap(self, handle_type: u32) -> Result<u64, MojoError> {
+        let mut platform_handle = MojoPlatformHandle {
+            struct_size: std::mem::size_of::<MojoPlatformHandle>() as u32,
+            type_: 0,
+            value: 0,
+        };
+        // SAFETY: Null options are allowed, and `platform_handle` is
+        // initialized as the API requires. The call always closes the handle,
+        // so we give up ownership of it.
+        MojoError::check(unsafe {
+            MojoUnwrapPlatformHandle(self.into_raw(), ptr::null(), &mut platform_handle)
+        })?;
+        if platform_handle.type_ != handle_type {
+            // Wrap whatever we got again, so that it's closed rather than
+            // leaked.
+            drop(PlatformHandle::wrap(platform_handle.type_, platform_handle.value));
+            return Err(MojoError::InvalidArgument);
+        }
+        Ok(platform_handle.value)
+    }
+}
+
+impl From<PlatformHandle> for UntypedHandle {
+    fn from(platform_handle: PlatformHandle) -> Self {
+        platform_handle.handle
+    }
+}
+
+impl From<UntypedHandle> for PlatformHandle {
+    /// Treat `handle` as a platform handle. If it's some other kind of
+    /// handle, unwrapping it will fail.
+    fn from(handle: UntypedHandle) -> Self {
+        PlatformHandle { handle }
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Safe wrappers around shared buffers, like
+//! mojo/public/cpp/system/buffer.h.
+//!
+//! A shared buffer is a region of memory which every process with a handle to
+//! it can map. Handles can be duplicated, and passed to other processes in
+//! messages.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_ffi";
+}
+
+use crate::handle::UntypedHandle;
+use crate::mojo_types::*;
+use mojo_ffi::functions::*;
+use mojo_ffi::types::MojoDuplicateBufferHandleOptions;
+use mojo_ffi::types::MojoPlatformHandle;
+use mojo_ffi::types::MojoPlatformSharedMemoryRegionAccessMode;
+use mojo_ffi::types::MojoSharedBufferGuid;
+use mojo_ffi::types::MojoSharedBufferInfo;
+
+use std::ptr;
+
+/// The flag which tells MojoDuplicateBufferHandle to return a read-only
+/// handle.
+const MOJO_DUPLICATE_BUFFER_HANDLE_FLAG_READ_ONLY: u32 = 1;
+
+/// The most platform handles a shared memory region unwraps to, which is two
+/// for a writable region on POSIX.
+const MAX_PLATFORM_HANDLES_PER_REGION: usize = 2;
+
+/// How a shared buffer may be mapped, which Mojo keeps track of as handles to
+/// it are duplicated. This matches `base::subtle::PlatformSharedMemoryRegion::
+/// Mode` in C++.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedBufferAccessMode {
+    /// Every handle to the buffer can only be mapped read-only.
+    ReadOnly,
+    /// There's exactly one handle to the buffer, which can be mapped
+    /// writable.
+    Writable,
+    /// Any number of handles to the buffer can be mapped writable.
+    Unsafe,
+}
+
+impl SharedBufferAccessMode {
+    fn from_raw(mode: MojoPlatformSharedMemoryRegionAccessMode) -> Result<Self, MojoError> {
+        match mode {
+            0 => Ok(SharedBufferAccessMode::ReadOnly),
+            1 => Ok(SharedBufferAccessMode::Writable),
+            2 => Ok(SharedBufferAccessMode::Unsafe),
+            _ => Err(MojoError::Unknown),
+        }
+    }
+}
+
+/// A handle to a shared buffer, which is closed when this is dropped. The
+/// buffer itself lives until every handle to it is closed and every mapping
+/// of it is dropped.
+#[derive(Debug)]
+pub struct SharedBufferHandle {
+    handle: UntypedHandle,
+}
+
+impl SharedBufferHandle {
+    /// Create a new shared buffer of `num_bytes` bytes, which are initially
+    /// zero.
+    pub fn create(num_bytes: u64) -> Result<Self, MojoError> {
+        let mut handle = INVALID_HANDLE;
+        // SAFETY: Null options are allowed, and the out pointer is valid.
+        MojoError::check(unsafe { MojoCreateSharedBuffer(num_bytes, ptr::null(), &mut handle) })?;
+        // SAFETY: We just created the handle, and nothing else has it.
+        Ok(SharedBufferHandle { handle: unsafe { UntypedHandle::from_raw(handle) } })
+    }
+
+    /// Take ownership of a raw shared buffer handle.
+    ///
+    /// # Safety
+    /// `handle` must be a shared buffer handle which nothing else will use or
+    /// close.
+    pub unsafe fn from_raw(handle: MojoHandle) -> Self {
+        // SAFETY: Guaranteed by the caller.
+        SharedBufferHandle { handle: unsafe { UntypedHandle::from_raw(handle) } }
+    }
+
+    /// Give up ownership of the handle, without closing it.
+    pub fn into_raw(self) -> MojoHandle {
+        self.handle.into_raw()
+    }
+
+    /// Return another writable handle to the same buffer. Fails if the buffer
+    /// is read-only, and means it can never be made read-only.
+    pub fn duplicate(&self) -> Result<SharedBufferHandle, MojoError> {
+        let mut handle = INVALID_HANDLE;
+        // SAFETY: Null options are allowed, and the out pointer is valid.
+        MojoError::check(unsafe {
+            MojoDuplicateBufferHandle(self.handle.raw(), ptr::null(), &mut handle)
+        })?;
+        // SAFETY: We just created the handle, and nothing else has it.
+        Ok(SharedBufferHandle { handle: unsafe { UntypedHandle::from_raw(handle) } })
+    }
+
+    /// Return a handle to the same buffer which can only be mapped
+    /// read-only. This makes every other handle to the buffer read-only too,
+    /// though mappings which already exist stay writable. Fails if the buffer
+    /// was duplicated as writable before.
+    pub fn duplicate_read_only(&self) -> Result<SharedBufferHandle, MojoError> {
+        let options = MojoDuplicateBufferHandleOptions {
+            struct_size: std::mem::size_of::<MojoDuplicateBufferHandleOptions>() as u32,
+            flags: MOJO_DUPLICATE_BUFFER_HANDLE_FLAG_READ_ONLY,
+        };
+        let mut handle = INVALID_HANDLE;
+        // SAFETY: `options` is initialized as the API requires, and the out
+        // pointer is valid.
+        MojoError::check(unsafe {
+            MojoDuplicateBufferHandle(self.handle.raw(), &options, &mut handle)
+        })?;
+        // SAFETY: We just created the handle, and nothing else has it.
+        Ok(SharedBufferHandle { handle: unsafe { UntypedHandle::from_raw(handle) } })
+    }
+
+    /// Find out the buffer's access mode. The C API only tells us by
+    /// unwrapping the handle into the platform's shared memory region, so this
+    /// consumes the handle and returns a new one which wraps the same region
+    /// again.
+    pub fn query_access_mode(
+        self,
+    ) -> Result<(SharedBufferHandle, SharedBufferAccessMode), MojoError> {
+        let empty_handle = MojoPlatformHandle {
+            struct_size: std::mem::size_of::<MojoPlatformHandle>() as u32,
+            type_: 0,
+            value: 0,
+        };
+        let mut platform_handles = [empty_handle; MAX_PLATFORM_HANDLES_PER_REGION];
+        let mut num_platform_handles = MAX_PLATFORM_HANDLES_PER_REGION as u32;
+        let mut num_bytes = 0;
+        let mut guid = MojoSharedBufferGuid { high: 0, low: 0 };
+        let mut access_mode = 0;
+        // SAFETY: Null options are allowed, every out pointer is valid, and
+        // `platform_handles` has room for `num_platform_handles` handles,
+        // each with `struct_size` initialized. The call always closes the
+        // handle, so we give up ownership of it.
+        MojoError::check(unsafe {
+            MojoUnwrapPlatformSharedMemoryRegion(
+                self.into_raw(),
+                ptr::null(),
+                platform_handles.as_mut_ptr(),
+                &mut num_platform_handles,
+                &mut num_bytes,
+                &mut guid,
+                &mut access_mode,
+            )
+        })?;
+        let mut handle = INVALID_HANDLE;
+        // SAFETY: The platform handles, size, guid and access mode are exactly
+        // what Mojo unwrapped, so they describe a valid region which we own,
+        // and which this takes ownership of. Null options are allowed, and
+        // the out pointer is valid.
+        MojoError::check(unsafe {
+            MojoWrapPlatformSharedMemoryRegion(
+                platform_handles.as_ptr(),
+                num_platform_handles,
+                num_bytes,
+                &guid,
+                access_mode,
+                ptr::null(),
+                &mut handle,
+            )
+        })?;
+        // SAFETY: We just created the handle, and nothing else has it.
+        let buffer = SharedBufferHandle { handle: unsafe { UntypedHandle::from_raw(handle) } };
+        Ok((buffer, SharedBufferAccessMode::from_raw(access_mode)?))
+    }
+
+    /// The size of the buffer in bytes.
+    pub fn size(&self) -> Result<u64, MojoError> {
+        let mut info = MojoSharedBufferInfo {
+            struct_size: std::mem::size_of::<MojoSharedBufferInfo>() as u32,
+            size: 0,
+        };
+        // SAFETY: Null options are allowed, and `info` is initialized as the
+        // API requires.
+        MojoError::check(unsafe { MojoGetBufferInfo(self.handle.raw(), ptr::null(), &mut info) })?;
+        Ok(info.size)
+    }
+
+    /// Map `num_bytes` bytes of the buffer, starting at `offset`, into memory.
+    /// Fails with `MojoError::InvalidArgument` if they're out of range.
+    pub fn map(&self, offset: u64, num_bytes: usize) -> Result<SharedBufferMapping, MojoError> {
+        let num_bytes_u64: u64 = num_bytes.try_into().map_err(|_| MojoError::InvalidArgument)?;
+        let mut buffer: *mut std::ffi::c_void = ptr::null_mut();
+        // SAFETY: Null options are allowed, and the out pointer is valid.
+        MojoError::check(unsafe {
+            MojoMapBuffer(self.handle.raw(), offset, num_bytes_u64, ptr::null(), &mut buffer)
+        })?;
+        Ok(SharedBufferMapping { ptr: buffer as *mut u8, len: num_bytes })
+    }
+}
+
+impl From<SharedBufferHandle> for UntypedHandle {
+    fn from(buffer: SharedBufferHandle) -> Self {
+        buffer.handle
+    }
+}
+
+impl From<UntypedHandle> for SharedBufferHandle {
+    /// Treat `handle` as a shared buffer handle. If it's some other kind of
+    /// handle, using it will fail.
+    fn from(handle: UntypedHandle) -> Self {
+        SharedBufferHandle { handle }
+    }
+}
+
+/// Part of a shared buffer mapped into memory, which is unmapped when this is
+/// dropped. The mapping stays valid after the handle it was mapped from is
+/// closed.
+///
+/// Other processes with a handle to the buffer may write to it at any time,
+/// so its contents should be treated as untrusted, and may change while
+/// they're being read.
+#[derive(Debug)]
+pub struct SharedBufferMapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl SharedBufferMapping {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // SAFETY: MojoMapBuffer mapped `len` bytes at `ptr`, which stay mapped
+        // until we unmap them in `drop`.
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        // SAFETY: As for `as_slice`, and `&mut self` makes sure that nothing
+        // else in this process refers to the mapping meanwhile.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for SharedBufferMapping {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` came from MojoMapBuffer, and is only unmapped here.
+        unsafe { MojoUnmapBuffer(self.ptr as *mut std::ffi::c_void) };
+    }
+}
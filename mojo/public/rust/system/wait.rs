@@ -0,0 +1,139 @@
+// Copyright 2025 The Chromium Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Blocking until message pipes are ready to read, like
+//! mojo/public/cpp/system/wait.h.
+//!
+//! The mojo C system API has no blocking wait, so this sets a trap on the
+//! pipes, whose event handler wakes us up on whichever thread it's called on.
+
+chromium::import! {
+    "//mojo/public/rust:mojo_ffi";
+}
+
+use crate::message_pipe::MessagePipeHandle;
+use crate::mojo_types::*;
+use mojo_ffi::functions::*;
+use mojo_ffi::result_codes::*;
+use mojo_ffi::signals::*;
+use mojo_ffi::trigger_conditions::*;
+use mojo_ffi::types::{MojoHandleSignalsState, MojoTrapEvent};
+
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Shared by the waiting thread and the trap's event handler.
+#[derive(Default)]
+struct WaitState {
+    /// The index of the first pipe to become ready, once one has.
+    ready: Mutex<Option<usize>>,
+    condvar: Condvar,
+}
+
+/// The context of one trigger, which owns a reference to the wait state until
+/// the trigger is cancelled.
+struct TriggerContext {
+    index: usize,
+    state: Arc<WaitState>,
+}
+
+extern "C" fn on_trap_event(event: *const MojoTrapEvent) {
+    // SAFETY: The trap passes a valid event, whose context is a pointer made
+    // by `Box::into_raw` in `wait_for_readable`.
+    let (context, result) =
+        unsafe { ((*event).trigger_context as *mut TriggerContext, (*event).result) };
+    if result == MOJO_RESULT_CANCELLED {
+        // This is always the trigger's last event.
+        // SAFETY: Nothing else uses the context once it's cancelled.
+        drop(unsafe { Box::from_raw(context) });
+        return;
+    }
+    // SAFETY: The context lives until the trigger is cancelled.
+    let context = unsafe { &*context };
+    context.state.ready.lock().unwrap().get_or_insert(context.index);
+    context.state.condvar.notify_all();
+}
+
+/// Closes the trap, which cancels its triggers.
+struct TrapHandle(MojoHandle);
+
+impl Drop for TrapHandle {
+    fn drop(&mut self) {
+        // SAFETY: We own the trap, and nobody else will use it.
+        unsafe { MojoClose(self.0) };
+    }
+}
+
+/// Block until one of `pipes` has a message to read, or its peer has been
+/// closed so that it never will, and return that pipe's index. Returns
+/// immediately if one of them is already ready.
+///
+/// Fails with `MojoError::InvalidArgument` if `pipes` is empty.
+pub fn wait_for_readable(pipes: &[&MessagePipeHandle]) -> Result<usize, MojoError> {
+    if pipes.is_empty() {
+        return Err(MojoError::InvalidArgument);
+    }
+    let mut trap = INVALID_HANDLE;
+    // SAFETY: The handler is a valid function, null options are allowed, and
+    // the out pointer is valid.
+    MojoError::check(unsafe { MojoCreateTrap(Some(on_trap_event), ptr::null(), &mut trap) })?;
+    let trap = TrapHandle(trap);
+
+    let state = Arc::new(WaitState::default());
+    let mut contexts = Vec::with_capacity(pipes.len());
+    for (index, pipe) in pipes.iter().enumerate() {
+        let context = Box::into_raw(Box::new(TriggerContext { index, state: state.clone() }));
+        // SAFETY: If the trigger is added, the trap owns `context` until it
+        // cancels the trigger. Otherwise we still own it.
+        let result = MojoError::check(unsafe {
+            MojoAddTrigger(
+                trap.0,
+                pipe.raw(),
+                MOJO_HANDLE_SIGNAL_READABLE | MOJO_HANDLE_SIGNAL_PEER_CLOSED,
+                MOJO_TRIGGER_CONDITION_SIGNALS_SATISFIED,
+                context as usize,
+                ptr::null(),
+            )
+        });
+        if let Err(error) = result {
+            // SAFETY: The trap never took ownership of the context.
+            drop(unsafe { Box::from_raw(context) });
+            return Err(error);
+        }
+        contexts.push(context as usize);
+    }
+
+    let mut num_blocking_events = 1;
+    let mut blocking_event = MojoTrapEvent {
+        struct_size: std::mem::size_of::<MojoTrapEvent>() as u32,
+        flags: 0,
+        trigger_context: 0,
+        result: MOJO_RESULT_OK,
+        signals_state: MojoHandleSignalsState { satisfied_signals: 0, satisfiable_signals: 0 },
+    };
+    // SAFETY: Null options are allowed, and there's room for one blocking
+    // event.
+    let armed = MojoError::check(unsafe {
+        MojoArmTrap(trap.0, ptr::null(), &mut num_blocking_events, &mut blocking_event)
+    });
+    match armed {
+        Ok(()) => {}
+        // One of the pipes is already ready, so the trap can't be armed.
+        Err(MojoError::FailedPrecondition) => {
+            return contexts
+                .iter()
+                .position(|context| *context == blocking_event.trigger_context)
+                .ok_or(MojoError::Unknown);
+        }
+        Err(error) => return Err(error),
+    }
+
+    let mut ready = state.ready.lock().unwrap();
+    loop {
+        if let Some(index) = *ready {
+            return Ok(index);
+        }
+        ready = state.condvar.wait(ready).unwrap();
+    }
+}
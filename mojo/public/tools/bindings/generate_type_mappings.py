@@ -70,9 +70,9 @@ sys.path.insert(
 
 from mojom.generate.generator import WriteFile
 
-def ReadTypemap(path):
+def ReadTypemap(path, language='c++'):
   with open(path) as f:
-    return json.load(f)['c++']
+    return json.load(f).get(language, {})
 
 
 def LoadCppTypemapConfig(path):
@@ -113,6 +113,18 @@ def LoadTsTypemapConfig(path):
   return configs
 
 
+def LoadRustTypemapConfig(path):
+  configs = {}
+  with open(path) as f:
+    for config in json.load(f):
+      for entry in config['types']:
+        configs[entry['mojom']] = {
+            'typename': entry['rust'],
+            'move_only': entry.get('move_only', False),
+        }
+  return configs
+
+
 def main():
   parser = argparse.ArgumentParser(
       description=__doc__,
@@ -138,6 +150,13 @@ def main():
       dest='ts_config_path',
       help=('A path to a single JSON-formatted typemap config as emitted by'
             'GN when processing a mojom_ts_typemap build rule.'))
+  parser.add_argument(
+      '--rust-typemap-config',
+      type=str,
+      action='store',
+      dest='rust_config_path',
+      help=('A path to a single JSON-formatted typemap config as emitted by'
+            'GN when processing the rust_typemaps of a mojom target.'))
   parser.add_argument('--output',
                       type=str,
                       required=True,
@@ -151,6 +170,7 @@ def main():
       'module_typemaps': {
           'c++': [],
           'typescript': [],
+          'rust': [],
       }
   }
 
@@ -170,11 +190,21 @@ def main():
     ts_typemaps = LoadTsTypemapConfig(params.ts_config_path)
   metadata['module_typemaps']['typescript'] = list(ts_typemaps.keys())
 
+  # As for C++, the typemaps of dependencies apply to this module too, since
+  # its fields may have their mojom types.
+  rust_typemaps = {}
+  if params.rust_config_path:
+    rust_typemaps = LoadRustTypemapConfig(params.rust_config_path)
+  metadata['module_typemaps']['rust'] = list(rust_typemaps.keys())
+  for path in params.dependency:
+    rust_typemaps.update(ReadTypemap(path, 'rust'))
+
   WriteFile(
       json.dumps(
           {
               'c++': cpp_typemaps,
               'typescript': ts_typemaps,
+              'rust': rust_typemaps,
               '_metadata': metadata,
           },
           indent=2), params.output)
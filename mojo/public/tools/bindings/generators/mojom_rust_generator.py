@@ -0,0 +1,685 @@
+# Copyright 2025 The Chromium Authors
+# Use of this source code is governed by a BSD-style license that can be
+# found in the LICENSE file.
+"""Generates Rust source files from a mojom.Module.
+
+The generated types are plain Rust structs and enums which implement the
+`Mojom` trait from //mojo/public/rust/mojom_parser with `#[derive(Mojom)]`, so
+the generated code never deals with the wire format itself. Interfaces become
+Rust traits, alongside a module holding their name, version, method ordinals
+and the structs that their messages carry.
+
+mojom.gni compiles the files generated for a mojom() target into a single
+crate, named by the module metadata key `rust_crate_name`. Kinds from other
+mojom files are referred to through the crate of the file defining them.
+Kinds which mojom_parser can't handle yet get a comment instead of bindings,
+and must be listed, by qualified name, in the `rust_skipped_kinds` of their
+mojom() target, which reach the generator as --rust_skipped_kind arguments.
+
+Structs, unions and enums may be typemapped onto other Rust types by the
+`rust_typemaps` of a mojom() target, which reach the generator as the "rust"
+section of the typemap file. Each entry has the Rust type's path as
+"typename", and "move_only" if it can't be cloned and compared. The mojom
+kind's own Rust type is still generated, and fields of the kind get the mapped
+type with `#[mojom(typemap = ...)]`, which converts through `MojomTraits`.
+"""
+
+import argparse
+
+import mojom.generate.generator as generator
+import mojom.generate.module as mojom
+import mojom.generate.pack as pack
+from mojom.generate.template_expander import UseJinja
+
+# mojom_bindings_generator.py passes the generator the arguments which start
+# with --rust_.
+GENERATOR_PREFIX = "rust"
+
+_kind_to_rust_type = {
+    mojom.BOOL: "bool",
+    mojom.INT8: "i8",
+    mojom.UINT8: "u8",
+    mojom.INT16: "i16",
+    mojom.UINT16: "u16",
+    mojom.INT32: "i32",
+    mojom.UINT32: "u32",
+    mojom.INT64: "i64",
+    mojom.UINT64: "u64",
+    mojom.FLOAT: "f32",
+    mojom.DOUBLE: "f64",
+    mojom.STRING: "String",
+    mojom.NULLABLE_STRING: "String",
+}
+
+# Handles are typemapped onto mojo_bindings::EncodedHandle, since their types
+# are defined by another crate.
+_handle_kind_to_rust_type = {
+    mojom.HANDLE: "mojo_bindings::UntypedHandle",
+    mojom.NULLABLE_HANDLE: "mojo_bindings::UntypedHandle",
+    mojom.MSGPIPE: "mojo_bindings::MessagePipeHandle",
+    mojom.NULLABLE_MSGPIPE: "mojo_bindings::MessagePipeHandle",
+    mojom.SHAREDBUFFER: "mojo_bindings::SharedBufferHandle",
+    mojom.NULLABLE_SHAREDBUFFER: "mojo_bindings::SharedBufferHandle",
+    mojom.PLATFORMHANDLE: "mojo_bindings::PlatformHandle",
+    mojom.NULLABLE_PLATFORMHANDLE: "mojo_bindings::PlatformHandle",
+}
+_ENCODED_HANDLE_RUST_TYPE = "mojo_bindings::EncodedHandle"
+
+# The type of constants whose mojom kind is a string.
+_RUST_CONSTANT_STRING_TYPE = "&str"
+
+# Rust keywords, which can't be used as identifiers without a suffix.
+_rust_reserved_keywords = {
+    "abstract", "as", "async", "await", "become", "box", "break", "const",
+    "continue", "crate", "do", "dyn", "else", "enum", "extern", "false",
+    "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield"
+}
+
+_builtin_values = {
+    "double.INFINITY": "f64::INFINITY",
+    "double.NEGATIVE_INFINITY": "f64::NEG_INFINITY",
+    "double.NAN": "f64::NAN",
+    "float.INFINITY": "f32::INFINITY",
+    "float.NEGATIVE_INFINITY": "f32::NEG_INFINITY",
+    "float.NAN": "f32::NAN",
+}
+
+# The module metadata key holding the name of the crate for a mojom file.
+_CRATE_NAME_METADATA_KEY = "rust_crate_name"
+
+
+def _SanitizeIdentifier(identifier):
+  if identifier in _rust_reserved_keywords:
+    return identifier + "_"
+  return identifier
+
+
+def _GetLiteral(value, kind):
+  """Returns the Rust literal for the mojom literal |value| of kind |kind|."""
+  if ((mojom.IsFloatKind(kind) or mojom.IsDoubleKind(kind))
+      and not any(c in value for c in ".eExX")):
+    # Rust float literals need a decimal point.
+    return value + ".0"
+  return value
+
+
+def _GetUnnullableValueKind(kind):
+  """Returns the non-nullable version of |kind| if it's a nullable value kind,
+  e.g. `int32?`, whose values are wrapped in an `Option`, and |kind|
+  otherwise."""
+  if mojom.IsValueKind(kind) and mojom.IsNullableKind(kind):
+    return kind.MakeUnnullableKind()
+  return kind
+
+
+def _FirstReason(reasons):
+  """Returns the first of |reasons| which isn't None, or None if they all
+  are. |reasons| is evaluated lazily, so the rest aren't computed."""
+  return next((reason for reason in reasons if reason is not None), None)
+
+
+def _ToRustTypeName(mojom_name):
+  # Synthesized kinds have names like IFace_Method_ResponseParam_Result.
+  return generator.ToCamel(mojom_name)
+
+
+class RustStylizer(generator.Stylizer):
+  def StylizeConstant(self, mojom_name):
+    return generator.ToUpperSnakeCase(mojom_name)
+
+  def StylizeField(self, mojom_name):
+    return _SanitizeIdentifier(generator.ToLowerSnakeCase(mojom_name))
+
+  def StylizeStruct(self, mojom_name):
+    return _ToRustTypeName(mojom_name)
+
+  def StylizeUnion(self, mojom_name):
+    return _ToRustTypeName(mojom_name)
+
+  def StylizeParameter(self, mojom_name):
+    return _SanitizeIdentifier(generator.ToLowerSnakeCase(mojom_name))
+
+  def StylizeMethod(self, mojom_name):
+    return _SanitizeIdentifier(generator.ToLowerSnakeCase(mojom_name))
+
+  def StylizeInterface(self, mojom_name):
+    return _ToRustTypeName(mojom_name)
+
+  def StylizeEnumField(self, mojom_name):
+    # kFooBar and FOO_BAR both become FooBar.
+    return generator.ToCamel(generator.ToLowerSnakeCase(mojom_name))
+
+  def StylizeEnum(self, mojom_name):
+    return _ToRustTypeName(mojom_name)
+
+
+class Generator(generator.Generator):
+  def _GetParameters(self):
+    return {
+        "module": self.module,
+        "constants": self.module.constants,
+        "enums": self._GetAllEnums(),
+        "structs": self.module.structs,
+        "unions": [
+            union for union in self.module.unions
+            if self._GetResultResponse(union) is None
+        ],
+        "interfaces": self.module.interfaces,
+        "uses_derive": self._UsesDerive(),
+    }
+
+  @staticmethod
+  def GetTemplatePrefix():
+    return "rust_templates"
+
+  def GetFilters(self):
+    return {
+        "constant_type": self._GetConstantType,
+        "constant_value": self._GetConstantValue,
+        "derives": self._GetDerives,
+        "enum_variants": self._GetEnumVariants,
+        "enum_aliases": self._GetEnumAliases,
+        "enum_default": self._GetEnumDefault,
+        "field_type": self._GetFieldType,
+        "interface_mod_name": self._GetInterfaceModName,
+        "is_nullable_kind": mojom.IsNullableKind,
+        "is_supported": self._IsSupported,
+        "method_callback_type": self._GetMethodCallbackType,
+        "method_type_prefix": self._GetMethodTypePrefix,
+        "mojom_attribute": self._GetMojomAttribute,
+        "qualified_name": self._GetQualifiedName,
+        "rust_type": self._GetRustType,
+        "rust_type_name": self._GetRustTypeName,
+        "struct_fields": self._GetStructFields,
+        "sync_response_type": self._GetSyncResponseType,
+        "to_upper_snake_case": generator.ToUpperSnakeCase,
+        "union_fields": self._GetUnionFields,
+        "union_variant_name": self._GetUnionVariantName,
+        "unsupported_reason": self._GetUnsupportedReason,
+    }
+
+  @UseJinja("module.rs.tmpl")
+  def _GenerateModule(self):
+    return self._GetParameters()
+
+  def GenerateFiles(self, unparsed_args):
+    if self.variant:
+      raise Exception("Variants not supported in Rust bindings.")
+
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--rust_skipped_kind",
+                        dest="skipped_kinds",
+                        action="append",
+                        default=[])
+    args = parser.parse_args(unparsed_args)
+    self._CheckSkippedKinds(set(args.skipped_kinds))
+
+    self.module.Stylize(RustStylizer())
+    self.WriteWithComment(self._GenerateModule(), f"{self.module.path}.rs")
+
+  def _CheckSkippedKinds(self, skipped_kinds):
+    """Raises unless the kinds we can't generate bindings for are exactly the
+    ones listed in |skipped_kinds|, by their qualified names, so that kinds
+    aren't left out of the bindings without anyone noticing."""
+    for kind in (self.module.structs + self.module.unions +
+                 self._GetAllEnums() + self.module.interfaces):
+      if self._GetResultResponse(kind) is not None:
+        continue
+      reason = self._GetUnsupportedReason(kind)
+      name = kind.qualified_name
+      if reason is not None and name not in skipped_kinds:
+        raise Exception(
+            f"Can't generate Rust bindings for {name}, because mojom_parser "
+            f"doesn't support {reason} yet. Add it to the rust_skipped_kinds "
+            f"of its mojom() target to leave it out of the bindings.")
+      if reason is None and name in skipped_kinds:
+        raise Exception(
+            f"{name} is in rust_skipped_kinds, but its Rust bindings can be "
+            f"generated. Remove it from the list.")
+
+  def _GetAllEnums(self):
+    """Returns the module's enums, including the ones nested in structs and
+    interfaces, which are generated at the top level."""
+    enums = list(self.module.enums)
+    for kind in self.module.structs + self.module.interfaces:
+      enums += kind.enums
+    return enums
+
+  def _UsesDerive(self):
+    return any(
+        self._IsSupported(kind)
+        for kind in self.module.structs + self.module.unions +
+        self._GetAllEnums() + self.module.interfaces)
+
+  def _IsSupported(self, kind):
+    """Returns whether we can generate Rust bindings for |kind|, and for
+    everything it contains."""
+    return self._GetUnsupportedReason(kind) is None
+
+  def _GetUnsupportedReason(self, kind, containing_kinds=(), interfaces=()):
+    """Returns the feature that mojom_parser would need to support before we
+    can generate Rust bindings for |kind| and everything it contains, or None
+    if we already can. |containing_kinds| identifies the structs and unions
+    which contain |kind|, and |interfaces| the interfaces whose messages
+    do."""
+    # FOR_RELEASE: mojom_parser doesn't support data pipes, arrays and maps
+    # of nullable elements, nullable values in unions, unions with gaps
+    # between their tags, or result responses with nullable or typemapped
+    # kinds yet. We don't generate bindings for the kinds which
+    # contain them, and leave a comment instead.
+    result_response = self._GetResultResponse(kind)
+    if result_response is not None:
+      for result_kind in (result_response.success_kind,
+                          result_response.failure_kind):
+        if mojom.IsNullableKind(result_kind):
+          return "result responses with nullable kinds"
+        if self._IsTypemapped(result_kind):
+          return "result responses with typemapped kinds"
+      return _FirstReason(
+          self._GetUnsupportedReason(result_kind, containing_kinds, interfaces)
+          for result_kind in (result_response.success_kind,
+                              result_response.failure_kind))
+    if mojom.IsStructKind(kind) or mojom.IsUnionKind(kind):
+      # A kind and its nullable version share their definition. Recursive
+      # types are supported, so we only need to check each definition once.
+      definition = id(kind.shared_definition)
+      if definition in containing_kinds:
+        return None
+      containing_kinds += (definition, )
+      if mojom.IsStructKind(kind) and kind.native_only:
+        return "[Native] structs"
+      if mojom.IsUnionKind(kind):
+        ordinals = sorted(field.ordinal for field in kind.fields)
+        if ordinals != list(range(len(ordinals))):
+          return "unions with gaps between their tags"
+        if any(
+            mojom.IsValueKind(field.kind) and mojom.IsNullableKind(field.kind)
+            for field in kind.fields):
+          return "nullable values in unions"
+      return _FirstReason(
+          self._GetUnsupportedReason(field.kind, containing_kinds, interfaces)
+          for field in kind.fields)
+    if mojom.IsInterfaceKind(kind):
+      # Interfaces may pass remotes and receivers of their own kind, which
+      # isn't recursion, since their messages only contain an index.
+      if id(kind) in interfaces:
+        return None
+      interfaces += (id(kind), )
+      return _FirstReason(
+          self._GetUnsupportedReason(param_struct, (), interfaces)
+          for method in kind.methods
+          for param_struct in (method.param_struct,
+                               method.response_param_struct)
+          if param_struct is not None)
+    if (mojom.IsPendingRemoteKind(kind) or mojom.IsPendingReceiverKind(kind)
+        or mojom.IsAssociatedKind(kind)):
+      return self._GetUnsupportedReason(kind.kind, containing_kinds,
+                                        interfaces)
+    if mojom.IsArrayKind(kind):
+      if mojom.IsNullableKind(kind.kind):
+        return "arrays of nullable elements"
+      return self._GetUnsupportedReason(kind.kind, containing_kinds,
+                                        interfaces)
+    if mojom.IsEnumKind(kind):
+      if kind.native_only:
+        return "[Native] enums"
+      if not kind.fields:
+        return "enums without values"
+      return None
+    if mojom.IsMapKind(kind):
+      # Keys are hashed, so they can't be floats, and they're encoded as is,
+      # so they can't be typemapped.
+      key_kind = kind.key_kind
+      if not (mojom.IsStringKind(key_kind) or mojom.IsIntegralKind(key_kind)
+              or mojom.IsEnumKind(key_kind)):
+        return "maps whose keys aren't strings, integers, bools or enums"
+      if self._IsTypemapped(key_kind):
+        return "maps with typemapped keys"
+      if mojom.IsNullableKind(kind.value_kind):
+        return "maps of nullable values"
+      return _FirstReason(
+          self._GetUnsupportedReason(map_kind, containing_kinds, interfaces)
+          for map_kind in (key_kind, kind.value_kind))
+    if mojom.IsDataPipeConsumerKind(kind) or mojom.IsDataPipeProducerKind(kind):
+      return "data pipes"
+    if mojom.IsFeatureKind(kind):
+      return "features"
+    if (mojom.IsStringKind(kind) or kind in _handle_kind_to_rust_type
+        or _GetUnnullableValueKind(kind) in _kind_to_rust_type):
+      return None
+    return f"{kind.spec} kinds"
+
+  def _GetResultResponse(self, kind):
+    """Returns the mojom.Result if |kind| is the union that a method's
+    `result<T, E>` response is encoded as, or None. These are mapped onto
+    Rust's `Result<T, E>` rather than generated."""
+    if not mojom.IsUnionKind(kind):
+      return None
+    for interface in self.module.interfaces:
+      for method in interface.methods:
+        result_response = method.result_response
+        if (result_response is not None
+            and method.response_parameters[0].kind is kind):
+          return result_response
+    return None
+
+  def _GetRustTypeName(self, kind):
+    """Returns the name of a struct, union, enum or interface, relative to the
+    generated module."""
+    name = kind.name
+    if mojom.IsEnumKind(kind) and kind.parent_kind:
+      name = kind.parent_kind.name + name
+    if kind.module is None or kind.module.path == self.module.path:
+      return name
+    crate = self._GetCrateName(kind.module)
+    if crate == self._GetCrateName(self.module):
+      return f"crate::{name}"
+    return f"::{crate}::{name}"
+
+  def _GetCrateName(self, module):
+    if _CRATE_NAME_METADATA_KEY not in module.metadata:
+      raise Exception(
+          f"{module.path} has no {_CRATE_NAME_METADATA_KEY} metadata, so we "
+          "don't know which crate its Rust bindings are in.")
+    return module.metadata[_CRATE_NAME_METADATA_KEY]
+
+  def _GetTypemap(self, kind):
+    """Returns the typemap entry for |kind|, or None if it isn't
+    typemapped."""
+    if not (mojom.IsStructKind(kind) or mojom.IsUnionKind(kind)
+            or mojom.IsEnumKind(kind)):
+      return None
+    return self.typemap.get(kind.qualified_name)
+
+  def _IsTypemapped(self, kind):
+    """Returns whether |kind|, or the elements of |kind| if it's an array,
+    or the values of |kind| if it's a map, are typemapped. Handles always
+    are."""
+    if mojom.IsArrayKind(kind):
+      return self._IsTypemapped(kind.kind)
+    if mojom.IsMapKind(kind):
+      return self._IsTypemapped(kind.value_kind)
+    return (kind in _handle_kind_to_rust_type
+            or self._GetTypemap(kind) is not None)
+
+  def _GetRustType(self, kind, apply_typemaps=True):
+    """Returns the Rust type of a field of kind |kind|. For nullable kinds,
+    this is the type of the non-null values. If |apply_typemaps| is false,
+    this is the generated type, even if |kind| is typemapped."""
+    if mojom.IsArrayKind(kind):
+      element = self._GetRustType(kind.kind, apply_typemaps)
+      if kind.length is not None:
+        return f"[{element}; {kind.length}]"
+      return f"Vec<{element}>"
+    if mojom.IsMapKind(kind):
+      key = self._GetRustType(kind.key_kind, apply_typemaps)
+      value = self._GetRustType(kind.value_kind, apply_typemaps)
+      return f"std::collections::HashMap<{key}, {value}>"
+    result_response = self._GetResultResponse(kind)
+    if result_response is not None:
+      success = self._GetRustType(result_response.success_kind, apply_typemaps)
+      failure = self._GetRustType(result_response.failure_kind, apply_typemaps)
+      return f"Result<{success}, {failure}>"
+    if (mojom.IsStructKind(kind) or mojom.IsUnionKind(kind)
+        or mojom.IsEnumKind(kind)):
+      typemap = self._GetTypemap(kind)
+      if apply_typemaps and typemap is not None:
+        return typemap["typename"]
+      return self._GetRustTypeName(kind)
+    if mojom.IsPendingRemoteKind(kind):
+      interface = self._GetRustTypeName(kind.kind)
+      return f"mojo_bindings::PendingRemote<dyn {interface}>"
+    if mojom.IsPendingReceiverKind(kind):
+      interface = self._GetRustTypeName(kind.kind)
+      return f"mojo_bindings::PendingReceiver<dyn {interface}>"
+    if mojom.IsPendingAssociatedRemoteKind(kind):
+      interface = self._GetRustTypeName(kind.kind)
+      return f"mojo_bindings::PendingAssociatedRemote<dyn {interface}>"
+    if mojom.IsPendingAssociatedReceiverKind(kind):
+      interface = self._GetRustTypeName(kind.kind)
+      return f"mojo_bindings::PendingAssociatedReceiver<dyn {interface}>"
+    if kind in _handle_kind_to_rust_type:
+      if apply_typemaps:
+        return _handle_kind_to_rust_type[kind]
+      return _ENCODED_HANDLE_RUST_TYPE
+    return _kind_to_rust_type[_GetUnnullableValueKind(kind)]
+
+  def _GetDerives(self, kind):
+    """Returns the traits to derive for a struct or union. Remotes,
+    receivers, handles and move-only typemapped types can't be copied or
+    compared, so the kinds containing them only get Debug and Mojom."""
+    if self._ContainsMoveOnlyKinds(kind):
+      return "Debug, Mojom"
+    return "Debug, Clone, PartialEq, Mojom"
+
+  def _ContainsMoveOnlyKinds(self, kind, containing_kinds=()):
+    if containing_kinds:
+      # Fields of a typemapped kind have the mapped type, not the kind itself.
+      typemap = self._GetTypemap(kind)
+      if typemap is not None:
+        return typemap.get("move_only", False)
+    if mojom.IsStructKind(kind) or mojom.IsUnionKind(kind):
+      definition = id(kind.shared_definition)
+      if definition in containing_kinds:
+        return False
+      containing_kinds += (definition, )
+      return any(
+          self._ContainsMoveOnlyKinds(field.kind, containing_kinds)
+          for field in kind.fields)
+    if mojom.IsArrayKind(kind):
+      return self._ContainsMoveOnlyKinds(kind.kind, containing_kinds)
+    if mojom.IsMapKind(kind):
+      return self._ContainsMoveOnlyKinds(kind.value_kind, containing_kinds)
+    return (mojom.IsAnyInterfaceKind(kind)
+            or kind in _handle_kind_to_rust_type)
+
+  def _GetStructFields(self, struct):
+    """Returns the fields of |struct| in ordinal order, which is the order
+    #[derive(Mojom)] expects. Nullable value fields are packed as a has-value
+    flag and a value, but they're still a single field."""
+    fields = []
+    for packed_field in struct.packed.packed_fields_in_ordinal_order:
+      if not pack.IsNullableValueKindPackedField(packed_field):
+        fields.append(packed_field.field)
+      elif pack.IsPrimaryNullableValueKindPackedField(packed_field):
+        fields.append(packed_field.original_field)
+    return fields
+
+  def _GetUnionFields(self, union):
+    """Returns the fields of |union| in ordinal (i.e. tag) order."""
+    return sorted(union.fields, key=lambda field: field.ordinal)
+
+  def _GetUnionVariantName(self, field):
+    return generator.ToCamel(field.mojom_name)
+
+  def _GetFieldType(self, field, container=None):
+    """Returns the Rust type of a field of the struct or union |container|, or
+    of a parameter if there's no |container|."""
+    rust_type = self._GetRustType(field.kind)
+    if container is not None and self._NeedsBox(field, container):
+      rust_type = f"Box<{rust_type}>"
+    if mojom.IsNullableKind(field.kind):
+      return f"Option<{rust_type}>"
+    return rust_type
+
+  def _IsRecursiveField(self, field, container):
+    """Returns whether |field| of the struct or union |container| can contain
+    a |container| itself. These fields get #[mojom(recursive)], which breaks
+    the cycle in the Mojom types. Unions are inline in structs, so a struct's
+    union fields don't need it; the union's own fields break the cycle."""
+    if mojom.IsStructKind(container) and mojom.IsUnionKind(field.kind):
+      return False
+    return self._ContainsDefinition(field.kind, container.shared_definition,
+                                    inline_only=False)
+
+  def _NeedsBox(self, field, container):
+    """Returns whether |field| of the struct or union |container| needs to be
+    boxed, because the generated Rust type would otherwise contain itself.
+    `Vec`s, `HashMap`s and typemapped types are already indirect."""
+    return (self._IsRecursiveField(field, container)
+            and self._ContainsDefinition(field.kind,
+                                         container.shared_definition,
+                                         inline_only=True))
+
+  def _ContainsDefinition(self, kind, definition, inline_only, visited=()):
+    """Returns whether a value of |kind| can contain a value of the struct or
+    union whose shared definition is |definition|. If |inline_only| is set,
+    this only follows the struct and union fields that the generated Rust
+    types hold directly, and not arrays, maps or typemapped kinds."""
+    if mojom.IsArrayKind(kind) or mojom.IsMapKind(kind):
+      if inline_only:
+        return False
+      element_kind = kind.kind if mojom.IsArrayKind(kind) else kind.value_kind
+      return self._ContainsDefinition(element_kind, definition, inline_only,
+                                      visited)
+    if not (mojom.IsStructKind(kind) or mojom.IsUnionKind(kind)):
+      return False
+    if inline_only and self._IsTypemapped(kind):
+      return False
+    if kind.shared_definition is definition:
+      return True
+    if id(kind.shared_definition) in visited:
+      return False
+    visited += (id(kind.shared_definition), )
+    return any(
+        self._ContainsDefinition(field.kind, definition, inline_only, visited)
+        for field in kind.fields)
+
+  def _GetMojomAttribute(self, field, container):
+    """Returns the #[mojom(...)] attribute for a field of the struct or union
+    |container|, if it needs one."""
+    options = []
+    if mojom.IsNullableKind(field.kind):
+      options.append("nullable")
+    if self._IsRecursiveField(field, container):
+      options.append("recursive")
+    if field.min_version:
+      options.append(f"min_version = {field.min_version}")
+    if self._IsTypemapped(field.kind):
+      mojom_type = self._GetRustType(field.kind, apply_typemaps=False)
+      options.append(f"typemap = {mojom_type}")
+    default = self._GetFieldDefault(field)
+    if default is not None:
+      options.append(f"default = {default}")
+    if not options:
+      return ""
+    return f"#[mojom({', '.join(options)})]"
+
+  def _GetFieldDefault(self, field):
+    """Returns a Rust expression for the default value that a struct field
+    declares, or None if it doesn't declare one we can express. Generated
+    structs don't implement `Default`, so `default` for a struct isn't
+    supported, and neither are defaults of typemapped fields, which would need
+    converting to the mapped type."""
+    value = getattr(field, "default", None)
+    if value is None or value == "default" or self._IsTypemapped(field.kind):
+      return None
+    if isinstance(value, mojom.EnumValue):
+      variant = next(variant for variant in self._GetEnumVariants(value.enum)
+                     if variant.numeric_value == value.field.numeric_value)
+      expr = f"{self._GetRustTypeName(value.enum)}::{variant.name}"
+    elif isinstance(value, mojom.ConstantValue):
+      expr = self._GetConstantPath(value)
+      # Mojom allows integer constants of other kinds, which Rust doesn't.
+      kind = _GetUnnullableValueKind(field.kind)
+      if mojom.IsIntegralKind(kind) and value.constant.kind != kind:
+        expr = f"{expr} as {_kind_to_rust_type[kind]}"
+    elif isinstance(value, mojom.BuiltinValue):
+      expr = _builtin_values[value.value]
+    elif isinstance(value, str):
+      expr = _GetLiteral(value, field.kind)
+    else:
+      return None
+    if mojom.IsStringKind(field.kind):
+      expr = f"String::from({expr})"
+    if mojom.IsNullableKind(field.kind):
+      expr = f"Some({expr})"
+    return expr
+
+  def _GetEnumVariants(self, enum):
+    """Returns the fields of |enum| which become variants. Rust enums can't
+    have two variants with the same value, so later fields with the value of
+    an earlier one become aliases instead."""
+    seen = set()
+    variants = []
+    for field in enum.fields:
+      if field.numeric_value not in seen:
+        seen.add(field.numeric_value)
+        variants.append(field)
+    return variants
+
+  def _GetEnumAliases(self, enum):
+    """Returns (alias, variant) pairs for the fields of |enum| which don't
+    become variants."""
+    variants = self._GetEnumVariants(enum)
+    by_value = {field.numeric_value: field for field in variants}
+    return [(field, by_value[field.numeric_value]) for field in enum.fields
+            if field not in variants]
+
+  def _GetEnumDefault(self, enum):
+    """Returns the variant used by `Default`: the [Default] field if there is
+    one, or the first variant otherwise."""
+    variants = self._GetEnumVariants(enum)
+    if enum.default_field is None:
+      return variants[0]
+    return next(field for field in variants
+                if field.numeric_value == enum.default_field.numeric_value)
+
+  def _GetInterfaceModName(self, interface):
+    return generator.ToLowerSnakeCase(interface.mojom_name)
+
+  def _GetMethodTypePrefix(self, method):
+    return generator.ToCamel(method.mojom_name)
+
+  def _GetMethodCallbackType(self, method):
+    params = ", ".join(
+        self._GetFieldType(field)
+        for field in self._GetStructFields(method.response_param_struct))
+    return f"Box<dyn FnOnce({params})>"
+
+  def _GetSyncResponseType(self, method):
+    """Returns the type that the sync version of |method| returns: the type of
+    its one response parameter, or a tuple of all of them."""
+    types = [
+        self._GetFieldType(field)
+        for field in self._GetStructFields(method.response_param_struct)
+    ]
+    if len(types) == 1:
+      return types[0]
+    return "(" + ", ".join(types) + ")"
+
+  def _GetQualifiedName(self, interface):
+    return self.module.GetNamespacePrefix() + interface.mojom_name
+
+  def _GetConstantType(self, constant):
+    if mojom.IsStringKind(constant.kind):
+      return _RUST_CONSTANT_STRING_TYPE
+    return _kind_to_rust_type[constant.kind]
+
+  def _GetConstantValue(self, constant):
+    value = constant.value
+    if isinstance(value, mojom.BuiltinValue):
+      return _builtin_values[value.value]
+    if isinstance(value, mojom.ConstantValue):
+      return self._GetConstantPath(value)
+    return _GetLiteral(value, constant.kind)
+
+  def _GetConstantPath(self, value):
+    """Returns the path to the constant referred to by |value|, relative to
+    the generated module."""
+    path = value.constant.name
+    if value.parent_kind is not None:
+      if mojom.IsInterfaceKind(value.parent_kind):
+        parent = self._GetInterfaceModName(value.parent_kind)
+      else:
+        parent = value.parent_kind.name
+      path = f"{parent}::{path}"
+    if value.module is None or value.module.path == self.module.path:
+      return path
+    crate = self._GetCrateName(value.module)
+    if crate == self._GetCrateName(self.module):
+      return f"crate::{path}"
+    return f"::{crate}::{path}"